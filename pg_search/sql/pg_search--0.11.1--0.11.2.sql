@@ -26,3 +26,101 @@ DROP FUNCTION IF EXISTS more_like_this(with_document_fields text, with_min_doc_f
 CREATE OR REPLACE FUNCTION more_like_this(document_fields text, min_doc_frequency pg_catalog.int4 DEFAULT NULL, max_doc_frequency pg_catalog.int4 DEFAULT NULL, min_term_frequency pg_catalog.int4 DEFAULT NULL, max_query_terms pg_catalog.int4 DEFAULT NULL, min_word_length pg_catalog.int4 DEFAULT NULL, max_word_length pg_catalog.int4 DEFAULT NULL, boost_factor pg_catalog.float4 DEFAULT NULL, stop_words text[] DEFAULT NULL) RETURNS searchqueryinput AS 'MODULE_PATHNAME', 'more_like_this_fields_wrapper' IMMUTABLE LANGUAGE c PARALLEL SAFE;
 DROP FUNCTION IF EXISTS more_like_this(with_document_id anyelement, with_min_doc_frequency pg_catalog.int4, with_max_doc_frequency pg_catalog.int4, with_min_term_frequency pg_catalog.int4, with_max_query_terms pg_catalog.int4, with_min_word_length pg_catalog.int4, with_max_word_length pg_catalog.int4, with_boost_factor pg_catalog.float4, with_stop_words text[]);
 CREATE OR REPLACE FUNCTION more_like_this(document_id anyelement, min_doc_frequency pg_catalog.int4 DEFAULT NULL, max_doc_frequency pg_catalog.int4 DEFAULT NULL, min_term_frequency pg_catalog.int4 DEFAULT NULL, max_query_terms pg_catalog.int4 DEFAULT NULL, min_word_length pg_catalog.int4 DEFAULT NULL, max_word_length pg_catalog.int4 DEFAULT NULL, boost_factor pg_catalog.float4 DEFAULT NULL, stop_words text[] DEFAULT NULL) RETURNS searchqueryinput AS 'MODULE_PATHNAME', 'more_like_this_id_wrapper' IMMUTABLE LANGUAGE c PARALLEL SAFE;
+/* <begin connected objects> */
+-- pg_search/src/api/aggregate.rs:27
+-- pg_search::api::aggregate::aggregate
+CREATE  FUNCTION "aggregate"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"query" SearchQueryInput, /* pg_search::query::SearchQueryInput */
+	"aggs" jsonb /* pgrx::datum::json::JsonB */
+) RETURNS jsonb /* core::result::Result<pgrx::datum::json::JsonB, anyhow::Error> */
+STRICT
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'aggregate_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/synonyms.rs:23
+CREATE TABLE paradedb.synonym_sets (
+    name text PRIMARY KEY,
+    rules text[] NOT NULL
+);
+GRANT SELECT ON paradedb.synonym_sets TO PUBLIC;
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/synonyms.rs:41
+-- pg_search::api::synonyms::create_synonym_set
+CREATE  FUNCTION "create_synonym_set"(
+	"name" TEXT, /* &str */
+	"rules" TEXT[] /* alloc::vec::Vec<alloc::string::String> */
+) RETURNS void
+STRICT VOLATILE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'create_synonym_set_wrapper';
+/* </end connected objects> */
+DROP FUNCTION IF EXISTS tokenizer(name text, remove_long pg_catalog.int4, lowercase bool, min_gram pg_catalog.int4, max_gram pg_catalog.int4, prefix_only bool, language text, pattern text, stemmer text);
+CREATE OR REPLACE FUNCTION tokenizer(name text, remove_long pg_catalog.int4 DEFAULT 255, lowercase bool DEFAULT true, min_gram pg_catalog.int4 DEFAULT NULL, max_gram pg_catalog.int4 DEFAULT NULL, prefix_only bool DEFAULT NULL, language text DEFAULT NULL, pattern text DEFAULT NULL, stemmer text DEFAULT NULL, synonyms text DEFAULT NULL, stopwords_language text DEFAULT NULL, stopwords text[] DEFAULT NULL, token_chars text[] DEFAULT NULL, edge_ngram jsonb DEFAULT NULL, phonetic jsonb DEFAULT NULL) RETURNS jsonb AS 'MODULE_PATHNAME', 'tokenizer_wrapper' IMMUTABLE LANGUAGE c PARALLEL SAFE;
+/* <begin connected objects> */
+-- pg_search/src/api/explain.rs:31
+-- pg_search::api::explain::explain_score
+CREATE  FUNCTION "explain_score"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"query" SearchQueryInput, /* pg_search::query::SearchQueryInput */
+	"key" anyelement /* pgrx::datum::anyelement::AnyElement */
+) RETURNS jsonb /* core::result::Result<pgrx::datum::json::JsonB, anyhow::Error> */
+STRICT
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'explain_score_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/verify.rs:56
+-- pg_search::api::verify::verify_index
+CREATE  FUNCTION "verify_index"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"repair" bool DEFAULT false /* bool */
+) RETURNS TABLE (
+	"kind" TEXT,  /* alloc::string::String */
+	"ctid" tid,  /* core::option::Option<pgrx_pg_sys::include::pg16::ItemPointerData> */
+	"key" TEXT,  /* core::option::Option<alloc::string::String> */
+	"detail" TEXT,  /* alloc::string::String */
+	"repaired" bool  /* bool */
+)
+STRICT VOLATILE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'verify_index_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/orphans.rs:28
+-- pg_search::api::orphans::orphaned_index_directories
+CREATE  FUNCTION "orphaned_index_directories"(
+	"remove" bool DEFAULT false /* bool */
+) RETURNS TABLE (
+	"database_oid" oid,  /* pgrx_pg_sys::submodules::oids::Oid */
+	"index_oid" oid,  /* pgrx_pg_sys::submodules::oids::Oid */
+	"relfilenode" oid,  /* pgrx_pg_sys::submodules::oids::Oid */
+	"byte_size" bigint,  /* i64 */
+	"removed" bool  /* bool */
+)
+STRICT VOLATILE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'orphaned_index_directories_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/merge.rs:31
+-- pg_search::api::merge::merge_segments
+CREATE  FUNCTION "merge_segments"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"max_segments" INT DEFAULT 1, /* i32 */
+	"expunge_deletes" bool DEFAULT true /* bool */
+) RETURNS TABLE (
+	"stage" TEXT,  /* alloc::string::String */
+	"num_segments" bigint,  /* i64 */
+	"byte_size" bigint,  /* i64 */
+	"num_docs" bigint,  /* i64 */
+	"num_deleted" bigint  /* i64 */
+)
+STRICT VOLATILE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'merge_segments_wrapper';
+/* </end connected objects> */
+DROP FUNCTION IF EXISTS field(name text, indexed bool, stored bool, fast bool, fieldnorms bool, record text, expand_dots bool, tokenizer jsonb, normalizer text);
+CREATE OR REPLACE FUNCTION field(name text, indexed bool DEFAULT NULL, stored bool DEFAULT NULL, fast bool DEFAULT NULL, fieldnorms bool DEFAULT NULL, record text DEFAULT NULL, expand_dots bool DEFAULT NULL, tokenizer jsonb DEFAULT NULL, normalizer text DEFAULT NULL, fields jsonb DEFAULT NULL, search_tokenizer jsonb DEFAULT NULL) RETURNS jsonb AS 'MODULE_PATHNAME', 'field_wrapper' IMMUTABLE LANGUAGE c PARALLEL SAFE;
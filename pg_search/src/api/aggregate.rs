@@ -0,0 +1,59 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use crate::postgres::index::open_search_index;
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::SearchQueryInput;
+use anyhow::Result;
+use pgrx::{pg_extern, pg_sys, JsonB, PgRelation};
+use tantivy::aggregation::agg_req::Aggregations;
+
+/// Run tantivy aggregations (terms, range, histogram, date_histogram, avg, min, max,
+/// percentiles, ...) over the fast fields of the documents matching `query`, and return the
+/// results as JSON.  Only rows visible to the current snapshot are aggregated.
+#[pg_extern]
+pub fn aggregate(index: PgRelation, query: SearchQueryInput, aggs: JsonB) -> Result<JsonB> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are reading it.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::AccessShareLock as _) };
+    let heaprel = index
+        .heap_relation()
+        .expect("index should belong to a table");
+
+    let aggregations: Aggregations = serde_json::from_value(aggs.0)?;
+
+    let search_index = open_search_index(&index)?;
+    let search_reader = search_index.get_reader()?;
+    let query = search_index.query(&index, &query, &search_reader);
+
+    let mut visibility_checker = VisibilityChecker::with_rel_and_snap(heaprel.as_ptr(), unsafe {
+        pg_sys::GetActiveSnapshot()
+    });
+    visibility_checker.set_redirects(search_reader.redirects.clone());
+    let results = search_reader.aggregate(query.as_ref(), aggregations, |ctid| {
+        visibility_checker
+            .exec_if_visible(ctid, |_, _, _| ())
+            .is_some()
+    })?;
+
+    Ok(JsonB(serde_json::to_value(results)?))
+}
@@ -30,6 +30,8 @@ pub fn field(
     expand_dots: default!(Option<bool>, "NULL"),
     tokenizer: default!(Option<JsonB>, "NULL"),
     normalizer: default!(Option<String>, "NULL"),
+    fields: default!(Option<JsonB>, "NULL"),
+    search_tokenizer: default!(Option<JsonB>, "NULL"),
 ) -> JsonB {
     let mut config = Map::new();
 
@@ -41,6 +43,8 @@ pub fn field(
     expand_dots.map(|v| config.insert("expand_dots".to_string(), Value::Bool(v)));
     tokenizer.map(|v| config.insert("tokenizer".to_string(), v.0));
     normalizer.map(|v| config.insert("normalizer".to_string(), Value::String(v)));
+    fields.map(|v| config.insert("fields".to_string(), v.0));
+    search_tokenizer.map(|v| config.insert("search_tokenizer".to_string(), v.0));
 
     JsonB(json!({ name: config }))
 }
@@ -57,6 +61,12 @@ pub fn tokenizer(
     language: default!(Option<String>, "NULL"),
     pattern: default!(Option<String>, "NULL"),
     stemmer: default!(Option<String>, "NULL"),
+    synonyms: default!(Option<String>, "NULL"),
+    stopwords_language: default!(Option<String>, "NULL"),
+    stopwords: default!(Option<Vec<String>>, "NULL"),
+    token_chars: default!(Option<Vec<String>>, "NULL"),
+    edge_ngram: default!(Option<JsonB>, "NULL"),
+    phonetic: default!(Option<JsonB>, "NULL"),
 ) -> JsonB {
     let mut config = Map::new();
 
@@ -66,10 +76,17 @@ pub fn tokenizer(
     remove_long.map(|v| config.insert("remove_long".to_string(), Value::Number(v.into())));
     lowercase.map(|v| config.insert("lowercase".to_string(), Value::Bool(v)));
     stemmer.map(|v| config.insert("stemmer".to_string(), Value::String(v)));
+    synonyms.map(|v| config.insert("synonyms".to_string(), Value::String(v)));
+    stopwords_language.map(|v| config.insert("stopwords_language".to_string(), Value::String(v)));
+    stopwords.map(|v| config.insert("stopwords".to_string(), Value::from(v)));
+    edge_ngram.map(|v| config.insert("edge_ngram".to_string(), v.0));
+    phonetic.map(|v| config.insert("phonetic".to_string(), v.0));
     // Options for type = ngram
     min_gram.map(|v| config.insert("min_gram".to_string(), Value::Number(v.into())));
     max_gram.map(|v| config.insert("max_gram".to_string(), Value::Number(v.into())));
     prefix_only.map(|v| config.insert("prefix_only".to_string(), Value::Bool(v)));
+    // Options for type = edge_ngram, which also takes `min_gram` and `max_gram`
+    token_chars.map(|v| config.insert("token_chars".to_string(), Value::from(v)));
     // Options for type = stem
     language.map(|v| config.insert("language".to_string(), Value::String(v)));
     // Options for type = regex
@@ -115,6 +132,9 @@ mod tests {
                 None,
                 None,
                 Some("English".to_string()),
+                None,
+                None,
+                None,
             )),
             Some("lowercase".to_string()),
         );
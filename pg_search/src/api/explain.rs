@@ -0,0 +1,101 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::postgres::index::open_search_index;
+use crate::postgres::types::TantivyValue;
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::SearchQueryInput;
+use crate::schema::SearchFieldType;
+use anyhow::{anyhow, Result};
+use pgrx::{pg_extern, pg_sys, AnyElement, JsonB, PgOid, PgRelation};
+use tantivy::collector::DocSetCollector;
+use tantivy::schema::Value;
+
+/// Explain how `query` scores the document whose key field is `key`.  The result is tantivy's
+/// scoring explanation, which breaks the BM25 score down into term frequency, document
+/// frequency, field norm and boost components for each clause of the query.
+#[pg_extern]
+pub fn explain_score(index: PgRelation, query: SearchQueryInput, key: AnyElement) -> Result<JsonB> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are reading it.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::AccessShareLock as _) };
+    let heaprel = index
+        .heap_relation()
+        .expect("index should belong to a table");
+
+    let search_index = open_search_index(&index)?;
+    let search_reader = search_index.get_reader()?;
+    let schema = &search_reader.schema;
+
+    let key_field = schema.key_field();
+    let key_value =
+        unsafe { TantivyValue::try_from_datum(key.datum(), PgOid::from_untagged(key.oid())) }
+            .map_err(|err| anyhow!("could not read key value: {err}"))?
+            .0;
+    let key_query = search_index.query(
+        &index,
+        &SearchQueryInput::Term {
+            field: Some(key_field.name.0.clone()),
+            value: key_value.clone(),
+            is_datetime: matches!(key_field.type_, SearchFieldType::Date),
+        },
+        &search_reader,
+    );
+
+    // the index may still hold dead versions of the row, so find the one that's visible to us
+    let ctid_field = schema.ctid_field().id.0;
+    let mut visibility_checker = VisibilityChecker::with_rel_and_snap(heaprel.as_ptr(), unsafe {
+        pg_sys::GetActiveSnapshot()
+    });
+    visibility_checker.set_redirects(search_reader.redirects.clone());
+    let mut doc_address = None;
+    for address in search_reader
+        .searcher
+        .search(key_query.as_ref(), &DocSetCollector)?
+    {
+        let doc = search_reader.get_doc(address)?;
+        let Some(ctid) = doc.get_first(ctid_field).and_then(|ctid| ctid.as_u64()) else {
+            continue;
+        };
+        if visibility_checker
+            .exec_if_visible(ctid, |_, _, _| ())
+            .is_some()
+        {
+            doc_address = Some(address);
+            break;
+        }
+    }
+    let doc_address = doc_address.ok_or_else(|| {
+        anyhow!(
+            "no row with {} = {key_value:?} is visible in the index",
+            key_field.name
+        )
+    })?;
+
+    let query = search_index.query(&index, &query, &search_reader);
+    let explanation = query
+        .explain(&search_reader.searcher, doc_address)
+        .map_err(|err| anyhow!("could not explain the score of {key_value:?}: {err}"))?;
+
+    Ok(JsonB(serde_json::to_value(explanation)?))
+}
@@ -0,0 +1,139 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::index::{SearchIndex, WriterResources};
+use crate::postgres::index::open_search_index;
+use crate::postgres::options::SearchIndexCreateOptions;
+use anyhow::{anyhow, Result};
+use pgrx::{default, iter::TableIterator, name, pg_extern, pg_sys, PgRelation};
+use tantivy::SegmentId;
+
+/// Force-merge the segments of a bm25 index down to at most `max_segments`, for example after a
+/// bulk load.  With `expunge_deletes`, every other segment that has deleted documents is rewritten
+/// without them too.  Files that are no longer needed are then removed from the index.
+///
+/// Returns the number of segments, their total size and their number of live and deleted
+/// documents, from `before` and `after` the merge.
+#[pg_extern(volatile)]
+pub fn merge_segments(
+    index: PgRelation,
+    max_segments: default!(i32, 1),
+    expunge_deletes: default!(bool, true),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(stage, String),
+            name!(num_segments, i64),
+            name!(byte_size, i64),
+            name!(num_docs, i64),
+            name!(num_deleted, i64),
+        ),
+    >,
+> {
+    if max_segments < 1 {
+        return Err(anyhow!("max_segments must be at least 1"));
+    }
+
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped, altered,
+    // or vacuumed while we merge it.  This is the same lock VACUUM takes, so INSERT/UPDATE/COPY
+    // statements and searches can continue.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let indexrel =
+        unsafe { PgRelation::with_lock(index.oid(), pg_sys::ShareUpdateExclusiveLock as _) };
+    let options = unsafe {
+        (indexrel.rd_options as *mut SearchIndexCreateOptions)
+            .as_ref()
+            .expect("index should have options")
+    };
+
+    let search_index = open_search_index(&indexrel)?;
+
+    // waits for the writer lock held by any transaction that is merging segments of the index.
+    // Everything below reads the segments as they are once we hold it, so we never pick
+    // candidates that a concurrent merge has already replaced
+    let mut writer = search_index.get_writer(WriterResources::Vacuum, options)?;
+    let before = stats(&search_index)?;
+
+    let candidates = candidates(&search_index, max_segments as usize, expunge_deletes)?;
+    if !candidates.is_empty() {
+        for candidate in candidates {
+            writer.merge(&candidate)?;
+        }
+        search_index.vacuum(&writer)?;
+        writer.wait_merging_threads()?;
+    }
+
+    let after = stats(&open_search_index(&indexrel)?)?;
+
+    Ok(TableIterator::new(vec![
+        ("before".to_string(), before.0, before.1, before.2, before.3),
+        ("after".to_string(), after.0, after.1, after.2, after.3),
+    ]))
+}
+
+/// The groups of segments to merge:  everything but the largest `max_segments - 1` segments
+/// together, and, with `expunge_deletes`, each of those that has deleted documents on its own.
+fn candidates(
+    search_index: &SearchIndex,
+    max_segments: usize,
+    expunge_deletes: bool,
+) -> Result<Vec<Vec<SegmentId>>> {
+    // largest-to-smallest, by # of alive docs
+    let mut metas = search_index.underlying_index.searchable_segment_metas()?;
+    metas.sort_unstable_by(|a, b| a.num_docs().cmp(&b.num_docs()).reverse());
+
+    let mut candidates = vec![];
+    let keep = if metas.len() > max_segments {
+        let keep = max_segments - 1;
+        candidates.push(metas[keep..].iter().map(|meta| meta.id()).collect());
+        keep
+    } else {
+        metas.len()
+    };
+
+    if expunge_deletes {
+        candidates.extend(
+            metas[..keep]
+                .iter()
+                .filter(|meta| meta.has_deletes())
+                .map(|meta| vec![meta.id()]),
+        );
+    }
+
+    Ok(candidates)
+}
+
+/// The number of segments, total byte size, and numbers of live and deleted documents.
+fn stats(search_index: &SearchIndex) -> Result<(i64, i64, i64, i64)> {
+    let sizes = search_index.segment_sizes()?;
+    let metas = search_index.underlying_index.searchable_segment_metas()?;
+    Ok((
+        metas.len() as i64,
+        sizes.values().sum::<u64>() as i64,
+        metas.iter().map(|meta| meta.num_docs() as i64).sum(),
+        metas
+            .iter()
+            .map(|meta| meta.num_deleted_docs() as i64)
+            .sum(),
+    ))
+}
@@ -15,10 +15,16 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+pub mod aggregate;
 pub mod config;
+pub mod explain;
 pub mod index;
+pub mod merge;
 pub mod operator;
+pub mod orphans;
+pub mod synonyms;
 pub mod tokenize;
+pub mod verify;
 
 #[macro_export]
 macro_rules! nodecast {
@@ -125,6 +125,12 @@ pub(crate) fn estimate_selectivity(
     indexrel: &PgRelation,
     search_query_input: &SearchQueryInput,
 ) -> Option<f64> {
+    if unsafe { (*indexrel.rd_rel).relkind } as u8 == pg_sys::RELKIND_PARTITIONED_INDEX {
+        // the index of a partitioned table has nothing to estimate with.  Postgres estimates
+        // each of its partitions separately
+        return None;
+    }
+
     let reltuples = indexrel
         .heap_relation()
         .expect("indexrel should be an index")
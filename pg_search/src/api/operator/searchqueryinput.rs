@@ -21,8 +21,7 @@ use super::{
 use crate::api::operator::{estimate_selectivity, find_var_relation, ReturnedNodePointer};
 use crate::gucs::per_tuple_cost;
 use crate::index::fast_fields_helper::FFHelper;
-use crate::index::SearchIndex;
-use crate::postgres::index::open_search_index;
+use crate::postgres::index::{open_search_index, partition_leaf_indexes};
 use crate::postgres::types::TantivyValue;
 use crate::postgres::utils::locate_bm25_index;
 use crate::query::SearchQueryInput;
@@ -50,33 +49,46 @@ pub fn search_with_query_input(
                 _ => panic!("the SeachQueryInput must be wrapped in a WithIndex variant"),
             }
         };
-        let indexrel = unsafe {
-            &PgRelation::with_lock(index_oid, pg_sys::AccessShareLock as pg_sys::LOCKMODE)
+        // a partitioned table's index has no documents of its own, they're all in the indexes of
+        // its partitions
+        let index_oids = if unsafe { pg_sys::get_rel_relkind(index_oid) } as u8
+            == pg_sys::RELKIND_PARTITIONED_INDEX
+        {
+            partition_leaf_indexes(index_oid)
+                .expect("should be able to find the indexes of the table's partitions")
+        } else {
+            vec![index_oid]
         };
-        let search_index =
-            open_search_index(indexrel).expect("should be able to open search index");
-
-        let key_field = search_index.key_field_name();
-        let key_field_type = search_index.key_field().type_.into();
-        let search_reader = search_index.get_reader().unwrap();
-        let fast_fields = FFHelper::with_fields(
-            &search_reader,
-            &[(key_field.clone(), key_field_type).into()],
-        );
-        let top_docs = search_reader.search_via_channel(
-            query.contains_more_like_this(),
-            false,
-            SearchIndex::executor(),
-            &search_index.query(indexrel, &query, &search_reader),
-        );
+
+        let mut key_field = String::new();
         let mut hs = FxHashSet::default();
-        for (_, doc_address) in top_docs {
-            check_for_interrupts!();
-            hs.insert(
-                fast_fields
-                    .value(0, doc_address)
-                    .expect("key_field value should not be null"),
+        for index_oid in index_oids {
+            let indexrel = unsafe {
+                &PgRelation::with_lock(index_oid, pg_sys::AccessShareLock as pg_sys::LOCKMODE)
+            };
+            let search_index =
+                open_search_index(indexrel).expect("should be able to open search index");
+
+            key_field = search_index.key_field_name();
+            let key_field_type = search_index.key_field().type_.into();
+            let search_reader = search_index.get_reader().unwrap();
+            let fast_fields = FFHelper::with_fields(
+                &search_reader,
+                &[(key_field.clone(), key_field_type).into()],
             );
+            let top_docs = search_reader.search(
+                query.contains_more_like_this(),
+                false,
+                &search_index.query(indexrel, &query, &search_reader),
+            );
+            for (_, doc_address) in top_docs {
+                check_for_interrupts!();
+                hs.insert(
+                    fast_fields
+                        .value(0, doc_address)
+                        .expect("key_field value should not be null"),
+                );
+            }
         }
 
         (key_field, hs)
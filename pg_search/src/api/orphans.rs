@@ -0,0 +1,59 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::gc::sweep_orphaned_directories;
+use anyhow::{anyhow, Result};
+use pgrx::{default, iter::TableIterator, name, pg_extern, pg_sys};
+
+/// List the index directories under `$PGDATA/pg_search` that no longer belong to an index,
+/// along with their size on disk, and remove them if `remove` is true.
+///
+/// Only directories of the current database, and of databases that have been dropped, can be
+/// judged from here.  The `paradedb.orphaned_index_sweep_interval` background worker sweeps every
+/// database.
+#[pg_extern(volatile)]
+pub fn orphaned_index_directories(
+    remove: default!(bool, false),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(database_oid, pg_sys::Oid),
+            name!(index_oid, pg_sys::Oid),
+            name!(relfilenode, pg_sys::Oid),
+            name!(byte_size, i64),
+            name!(removed, bool),
+        ),
+    >,
+> {
+    if remove && unsafe { !pg_sys::superuser() } {
+        return Err(anyhow!(
+            "must be superuser to remove orphaned index directories"
+        ));
+    }
+
+    let orphans = sweep_orphaned_directories(remove)?;
+    Ok(TableIterator::new(orphans.into_iter().map(|orphan| {
+        (
+            orphan.directory.database_oid.into(),
+            orphan.directory.index_oid.into(),
+            orphan.directory.relfilenode.into(),
+            orphan.byte_size as i64,
+            orphan.removed,
+        )
+    })))
+}
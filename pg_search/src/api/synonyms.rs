@@ -0,0 +1,69 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{anyhow, Result};
+use pgrx::{extension_sql, pg_extern, IntoDatum, PgBuiltInOids, Spi};
+use tokenizers::synonyms::validate_rules;
+use tokenizers::SearchTokenizer;
+
+extension_sql!(
+    r#"
+CREATE TABLE paradedb.synonym_sets (
+    name text PRIMARY KEY,
+    rules text[] NOT NULL
+);
+GRANT SELECT ON paradedb.synonym_sets TO PUBLIC;
+"#,
+    name = "create_synonym_sets_table"
+);
+
+/// Create (or replace) a named set of synonym rules that can be referenced by the
+/// `synonyms` option of `paradedb.tokenizer()`.
+///
+/// Rules follow the Solr synonym format: `tv, television` for equivalent terms and
+/// `i-pod, i pod => ipod` for explicit mappings.  Indexes that use the set pick up
+/// changes to it after a `REINDEX`.
+#[pg_extern(volatile)]
+pub fn create_synonym_set(name: &str, rules: Vec<String>) -> Result<()> {
+    validate_rules(&rules).map_err(|e| anyhow!("invalid synonym set '{name}': {e}"))?;
+
+    Spi::run_with_args(
+        "INSERT INTO paradedb.synonym_sets (name, rules) VALUES ($1, $2)
+         ON CONFLICT (name) DO UPDATE SET rules = EXCLUDED.rules",
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), name.into_datum()),
+            (PgBuiltInOids::TEXTARRAYOID.oid(), rules.into_datum()),
+        ]),
+    )?;
+    Ok(())
+}
+
+/// Load the rules of the synonym set named by `tokenizer`'s configuration, if it has one.
+pub fn resolve_synonym_set(tokenizer: &mut SearchTokenizer) -> Result<()> {
+    let Some(set) = tokenizer.synonyms_mut() else {
+        return Ok(());
+    };
+
+    let rules = Spi::get_one_with_args::<Vec<String>>(
+        "SELECT (SELECT rules FROM paradedb.synonym_sets WHERE name = $1)",
+        vec![(PgBuiltInOids::TEXTOID.oid(), set.name.clone().into_datum())],
+    )?
+    .ok_or_else(|| anyhow!("synonym set '{}' does not exist", set.name))?;
+
+    set.rules = rules;
+    Ok(())
+}
@@ -15,6 +15,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::api::synonyms::resolve_synonym_set;
 use pgrx::{iter::TableIterator, name, pg_extern, JsonB};
 use strum::VariantNames;
 use tokenizers::SearchTokenizer;
@@ -27,8 +28,9 @@ pub fn tokenize(
 ) -> TableIterator<(name!(token, String), name!(position, i32))> {
     let tokenizer_setting = serde_json::to_value(tokenizer_setting)
         .expect("invalid tokenizer setting, expected paradedb.tokenizer()");
-    let tokenizer = SearchTokenizer::from_json_value(&tokenizer_setting)
+    let mut tokenizer = SearchTokenizer::from_json_value(&tokenizer_setting)
         .expect("invalid tokenizer setting, expected paradedb.tokenizer()");
+    resolve_synonym_set(&mut tokenizer).expect("failed to load synonym set");
 
     let mut analyzer = tokenizer
         .to_tantivy_tokenizer()
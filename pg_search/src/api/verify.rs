@@ -0,0 +1,373 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::index::reader::CACHE_NUM_BLOCKS;
+use crate::index::{SearchIndex, WriterResources};
+use crate::postgres::index::open_search_index;
+use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::types::TantivyValue;
+use crate::postgres::utils::{item_pointer_to_u64, row_to_search_document, u64_to_item_pointer};
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::schema::SearchDocument;
+use anyhow::Result;
+use pgrx::itemptr::item_pointer_get_block_number;
+use pgrx::{
+    default, iter::TableIterator, name, pg_extern, pg_guard, pg_sys, PgMemoryContexts, PgRelation,
+    PgTupleDesc,
+};
+use std::collections::HashMap;
+use tantivy::schema::{Field, OwnedValue, Value};
+use tantivy::{Directory, TantivyDocument};
+
+/// One inconsistency found by [`verify_index`].
+struct Issue {
+    kind: &'static str,
+    ctid: Option<u64>,
+    key: Option<OwnedValue>,
+    detail: String,
+    repaired: bool,
+}
+
+/// Compare the bm25 index against its heap relation and report every inconsistency:
+///
+/// - `unreadable_segment`: a segment file can't be read from the index relation
+/// - `missing_from_index`: a visible heap row has no document in the index
+/// - `key_mismatch`: the indexed key field value differs from the heap row's
+/// - `dangling_ctid`: an indexed document points at a heap tuple that doesn't exist
+/// - `dead_tuple`: an indexed document points at a heap tuple that isn't visible.  These are
+///   expected until the next `VACUUM` and are never repaired.
+///
+/// With `repair => true`, the affected rows of the first four kinds are reindexed (or removed
+/// from the index) in place.  Unreadable segments can only be fixed with a `REINDEX`.
+#[pg_extern(volatile)]
+pub fn verify_index(
+    index: PgRelation,
+    repair: default!(bool, false),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(kind, String),
+            name!(ctid, Option<pg_sys::ItemPointerData>),
+            name!(key, Option<String>),
+            name!(detail, String),
+            name!(repaired, bool),
+        ),
+    >,
+> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are reading it.  Repairing writes to the index, which needs the same
+    // lock as an INSERT.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let lockmode = if repair {
+        pg_sys::RowExclusiveLock
+    } else {
+        pg_sys::AccessShareLock
+    };
+    let indexrel = unsafe { PgRelation::with_lock(index.oid(), lockmode as _) };
+    let heaprel = indexrel
+        .heap_relation()
+        .expect("index should belong to a table");
+
+    // The heap is scanned with a snapshot taken before the index is opened.  Every row it sees
+    // was written by a transaction that had finished its statements, and so published its
+    // documents, by then, so rows inserted concurrently aren't mistaken for missing ones
+    let snapshot = unsafe { pg_sys::RegisterSnapshot(pg_sys::GetTransactionSnapshot()) };
+    let search_index = open_search_index(&indexrel)?;
+    let mut issues = unreadable_segments(&search_index)?;
+    if issues.is_empty() {
+        issues = compare_with_heap(search_index, &indexrel, &heaprel, snapshot, repair)?;
+    }
+    unsafe { pg_sys::UnregisterSnapshot(snapshot) };
+
+    Ok(TableIterator::new(issues.into_iter().map(|issue| {
+        let ctid = issue.ctid.map(|ctid| {
+            let mut ipd = pg_sys::ItemPointerData::default();
+            u64_to_item_pointer(ctid, &mut ipd);
+            ipd
+        });
+        (
+            issue.kind.to_string(),
+            ctid,
+            issue.key.map(|key| TantivyValue(key).to_string()),
+            issue.detail,
+            issue.repaired,
+        )
+    })))
+}
+
+/// Read every file of every segment back from the index relation.
+fn unreadable_segments(search_index: &SearchIndex) -> Result<Vec<Issue>> {
+    let mut issues = vec![];
+    for meta in search_index.underlying_index.searchable_segment_metas()? {
+        for path in meta.list_files() {
+            // the temporary doc store only exists while a sorted segment is being written
+            if path.extension().is_some_and(|ext| ext == "temp") {
+                continue;
+            }
+
+            let result = search_index
+                .block_directory
+                .open_read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| file.read_bytes().map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                issues.push(Issue {
+                    kind: "unreadable_segment",
+                    ctid: None,
+                    key: None,
+                    detail: format!(
+                        "segment {} file {}: {e}.  REINDEX the index to rebuild it",
+                        meta.id().short_uuid_string(),
+                        path.display()
+                    ),
+                    repaired: false,
+                });
+            }
+        }
+    }
+    Ok(issues)
+}
+
+struct HeapScanState {
+    tupdesc: PgTupleDesc<'static>,
+    search_index: SearchIndex,
+    key_field: Field,
+    memctx: PgMemoryContexts,
+    repair: bool,
+    /// ctid -> key value of every document in the index not yet matched to a heap row
+    indexed: HashMap<u64, Option<OwnedValue>>,
+    /// ctid -> key value of every later row version a document is redirected to
+    redirected: HashMap<u64, Option<OwnedValue>>,
+    issues: Vec<Issue>,
+    to_insert: Vec<SearchDocument>,
+    to_delete: Vec<u64>,
+}
+
+fn compare_with_heap(
+    search_index: SearchIndex,
+    indexrel: &PgRelation,
+    heaprel: &PgRelation,
+    snapshot: pg_sys::Snapshot,
+    repair: bool,
+) -> Result<Vec<Issue>> {
+    let ctid_field = search_index.schema.ctid_field().id.0;
+    let key_field = search_index.schema.key_field().id.0;
+
+    let mut indexed = HashMap::new();
+    let reader = search_index.get_reader()?;
+    for segment_reader in reader.searcher.segment_readers() {
+        let store_reader = segment_reader.get_store_reader(CACHE_NUM_BLOCKS)?;
+        for doc in store_reader.iter::<TantivyDocument>(segment_reader.alive_bitset()) {
+            let doc = doc?;
+            if let Some(ctid) = doc.get_first(ctid_field).and_then(|ctid| ctid.as_u64()) {
+                indexed.insert(ctid, doc.get_first(key_field).cloned());
+            }
+        }
+    }
+    let redirects = reader.redirects.clone();
+    let redirected = redirects
+        .iter()
+        .filter_map(|(from, to)| Some((to, indexed.get(&from)?.clone())))
+        .collect();
+
+    let mut state = HeapScanState {
+        tupdesc: unsafe { PgTupleDesc::from_pg_copy(indexrel.rd_att) },
+        search_index,
+        key_field,
+        memctx: PgMemoryContexts::new("verify_index"),
+        repair,
+        indexed,
+        redirected,
+        issues: vec![],
+        to_insert: vec![],
+        to_delete: vec![],
+    };
+
+    unsafe {
+        // an MVCC snapshot makes the scan return only the rows visible to us, and lets it run
+        // alongside concurrent writers
+        let index_info = pg_sys::BuildIndexInfo(indexrel.as_ptr());
+        (*index_info).ii_Concurrent = true;
+
+        let tableam = *heaprel.rd_tableam;
+        let scan_begin = tableam
+            .scan_begin
+            .expect("table access method should support sequential scans");
+        let flags = pg_sys::ScanOptions::SO_TYPE_SEQSCAN
+            | pg_sys::ScanOptions::SO_ALLOW_STRAT
+            | pg_sys::ScanOptions::SO_ALLOW_SYNC
+            | pg_sys::ScanOptions::SO_ALLOW_PAGEMODE;
+        let scan = scan_begin(
+            heaprel.as_ptr(),
+            snapshot,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            flags,
+        );
+
+        // the scan is ended by index_build_range_scan
+        let index_build_range_scan = tableam
+            .index_build_range_scan
+            .expect("table access method should support building indexes");
+        index_build_range_scan(
+            heaprel.as_ptr(),
+            indexrel.as_ptr(),
+            index_info,
+            true,
+            false,
+            false,
+            0,
+            pg_sys::InvalidBlockNumber,
+            Some(verify_callback),
+            std::ptr::addr_of_mut!(state).cast(),
+            scan,
+        );
+    }
+
+    // whatever's left in the index doesn't belong to a visible heap row
+    let mut existence_checker = VisibilityChecker::with_rel_and_snap(heaprel.as_ptr(), unsafe {
+        std::ptr::addr_of_mut!(pg_sys::SnapshotAnyData)
+    });
+    let nblocks = unsafe {
+        pg_sys::RelationGetNumberOfBlocksInFork(heaprel.as_ptr(), pg_sys::ForkNumber::MAIN_FORKNUM)
+    };
+    let mut leftovers = std::mem::take(&mut state.indexed)
+        .into_iter()
+        .collect::<Vec<_>>();
+    leftovers.sort_by_key(|(ctid, _)| *ctid);
+    for (ctid, key) in leftovers {
+        // documents are redirected from dead tuples until the next VACUUM
+        if redirects.targets(ctid).next().is_some() {
+            continue;
+        }
+
+        let mut ipd = pg_sys::ItemPointerData::default();
+        u64_to_item_pointer(ctid, &mut ipd);
+        let exists = item_pointer_get_block_number(&ipd) < nblocks
+            && existence_checker
+                .exec_if_visible(ctid, |_, _, _| ())
+                .is_some();
+
+        if exists {
+            state.issues.push(Issue {
+                kind: "dead_tuple",
+                ctid: Some(ctid),
+                key,
+                detail: "heap tuple is not visible to the current snapshot".into(),
+                repaired: false,
+            });
+        } else {
+            state.to_delete.push(ctid);
+            state.issues.push(Issue {
+                kind: "dangling_ctid",
+                ctid: Some(ctid),
+                key,
+                detail: "heap tuple does not exist".into(),
+                repaired: repair,
+            });
+        }
+    }
+
+    if repair && !(state.to_delete.is_empty() && state.to_insert.is_empty()) {
+        let options = indexrel.rd_options as *mut SearchIndexCreateOptions;
+        // deleting documents needs the writer lock, which a statement's writer doesn't take
+        let mut writer = state
+            .search_index
+            .get_writer(WriterResources::Vacuum, unsafe {
+                options.as_ref().unwrap()
+            })?;
+        writer.delete(&ctid_field, &state.to_delete)?;
+        for document in std::mem::take(&mut state.to_insert) {
+            writer.insert(document)?;
+        }
+        writer.commit()?;
+    }
+
+    Ok(state.issues)
+}
+
+#[pg_guard]
+unsafe extern "C" fn verify_callback(
+    _index: pg_sys::Relation,
+    ctid: pg_sys::ItemPointer,
+    values: *mut pg_sys::Datum,
+    isnull: *mut bool,
+    _tuple_is_alive: bool,
+    state: *mut std::os::raw::c_void,
+) {
+    pgrx::check_for_interrupts!();
+    let state = (state as *mut HeapScanState)
+        .as_mut()
+        .expect("HeapScanState pointer should not be null");
+
+    state.memctx.reset();
+    let document = state.memctx.switch_to(|_| {
+        row_to_search_document(
+            *ctid,
+            &state.tupdesc,
+            values,
+            isnull,
+            &state.search_index.schema,
+        )
+    });
+    let document = match document {
+        Ok(document) => document,
+        Err(e) => panic!("could not read heap row {ctid:?}: {e}"),
+    };
+
+    let ctid = item_pointer_to_u64(*ctid);
+    let key = document.doc.get_first(state.key_field).cloned();
+    let indexed = state
+        .indexed
+        .remove(&ctid)
+        .or_else(|| state.redirected.remove(&ctid));
+    let issue = match indexed {
+        None => Issue {
+            kind: "missing_from_index",
+            ctid: Some(ctid),
+            key,
+            detail: "visible heap row is not in the index".into(),
+            repaired: state.repair,
+        },
+        Some(indexed_key) if indexed_key != key => {
+            state.to_delete.push(ctid);
+            Issue {
+                kind: "key_mismatch",
+                ctid: Some(ctid),
+                detail: format!(
+                    "index has key {}",
+                    indexed_key.map_or("NULL".into(), |key| TantivyValue(key).to_string())
+                ),
+                key,
+                repaired: state.repair,
+            }
+        }
+        Some(_) => return,
+    };
+
+    state.issues.push(issue);
+    if state.repair {
+        state.to_insert.push(document);
+    }
+}
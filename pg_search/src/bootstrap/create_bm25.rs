@@ -21,8 +21,8 @@ use pgrx::{JsonB, PgRelation, Spi};
 use serde_json::Value;
 use std::collections::HashSet;
 
-use crate::index::{SearchFs, SearchIndex, WriterDirectory};
-use crate::postgres::index::{open_search_index, relfilenode_from_pg_relation};
+use crate::index::{SearchIndexWriter, WriterDirectory};
+use crate::postgres::index::open_search_index;
 
 #[pg_extern(
     sql = "
@@ -109,25 +109,6 @@ fn create_bm25_impl(
         );
     }
 
-    let is_partitioned_query = format!(
-        "SELECT EXISTS (SELECT 1 FROM pg_inherits WHERE inhparent = '{}.{}'::regclass)",
-        spi::quote_identifier(schema_name),
-        spi::quote_identifier(table_name),
-    );
-    let partitioned = Spi::get_one::<bool>(&is_partitioned_query)?.ok_or_else(|| {
-        anyhow::anyhow!(
-            "Could not check if {}.{} is partitioned",
-            schema_name,
-            table_name
-        )
-    })?;
-
-    if partitioned {
-        bail!(
-            "Creating BM25 indexes over partitioned tables is a ParadeDB enterprise feature. Contact support@paradedb.com for access."
-        );
-    }
-
     if text_fields == "{}"
         && numeric_fields == "{}"
         && boolean_fields == "{}"
@@ -141,6 +122,8 @@ fn create_bm25_impl(
         );
     }
 
+    // Each configured field is indexed from the table column of the same name.  Expression
+    // fields can't be expressed here, and need a `CREATE INDEX` of their own
     let mut column_names = HashSet::new();
     for fields in [
         text_fields,
@@ -248,23 +231,14 @@ unsafe fn delete_bm25_index_by_oid(index_oid: pg_sys::Oid) -> Result<()> {
     let relfile_paths = WriterDirectory::relfile_paths(database_oid, index_oid.as_u32())
         .expect("could not look up pg_search relfilenode directory");
 
+    // The index itself lives in the index relation's storage, which Postgres removes for us.
+    // All that's left is any directory left behind by an older version of pg_search, and it's
+    // queued to actually be deleted upon transaction commit.
+    if !relfile_paths.is_empty() {
+        crate::postgres::transaction::register_callback();
+    }
     for directory in relfile_paths {
-        // Drop the Tantivy data directory.
-        // It's expected that this will be queued to actually perform the delete upon
-        // transaction commit.
-        match SearchIndex::from_disk(&directory) {
-            Ok(mut search_index) => {
-                search_index.drop_index().unwrap_or_else(|err| {
-                    panic!("error dropping index with OID {index_oid:?}: {err:?}")
-                });
-            }
-            Err(e) => {
-                pgrx::warning!(
-                    "error dropping index with OID {index_oid:?} at path {}: {e:?}",
-                    directory.search_index_dir_path(false).unwrap().0.display()
-                );
-            }
-        }
+        SearchIndexWriter::mark_pending_drop(&directory);
     }
     Ok(())
 }
@@ -280,17 +254,16 @@ fn index_size(index: PgRelation) -> Result<i64> {
     // validated the existence of the relation. We are safe calling the function below as
     // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
     let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::AccessShareLock as _) };
-    let index_oid = index.oid();
-
-    let database_oid = crate::MyDatabaseId();
-    let relfilenode = relfilenode_from_pg_relation(&index);
 
-    // Create a WriterDirectory with the obtained index_oid
-    let writer_directory =
-        WriterDirectory::from_oids(database_oid, index_oid.as_u32(), relfilenode.as_u32());
-
-    // Call the total_size method to get the size in bytes
-    let total_size = writer_directory.total_size()?;
+    // The size of the index is the total size of the files stored in the index relation
+    let index = open_search_index(&index).expect("should be able to open search index");
+    let total_size = index
+        .block_directory
+        .catalog()?
+        .files
+        .values()
+        .map(|entry| entry.len)
+        .sum::<u64>();
 
     Ok(total_size as i64)
 }
@@ -321,7 +294,7 @@ fn index_info(
 
     // open the specified index
     let index = open_search_index(&index).expect("should be able to open search index");
-    let directory = index.directory.clone();
+    let catalog = index.block_directory.catalog()?;
     let data = index
         .underlying_index
         .searchable_segment_metas()?
@@ -331,19 +304,7 @@ fn index_info(
             let byte_size = meta
                 .list_files()
                 .into_iter()
-                .map(|file| {
-                    let mut full_path = directory.tantivy_dir_path(false).unwrap().0;
-                    full_path.push(file);
-
-                    if full_path.exists() {
-                        full_path
-                            .metadata()
-                            .map(|metadata| metadata.len())
-                            .unwrap_or(0)
-                    } else {
-                        0
-                    }
-                })
+                .map(|file| catalog.files.get(&file).map(|entry| entry.len).unwrap_or(0))
                 .sum::<u64>() as i64;
             let num_docs = meta.num_docs() as i64;
             let num_deleted = meta.num_deleted_docs() as i64;
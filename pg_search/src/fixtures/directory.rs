@@ -16,8 +16,7 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 pub use crate::index::SearchFs;
-use crate::index::{SearchDirectoryError, TantivyDirPath, WriterDirectory};
-use serde::{de::DeserializeOwned, Serialize};
+use crate::index::{SearchDirectoryError, WriterDirectory};
 
 pub struct MockWriterDirectory {
     pub temp_dir: tempfile::TempDir,
@@ -46,19 +45,7 @@ impl MockWriterDirectory {
 }
 
 impl SearchFs for MockWriterDirectory {
-    fn load_index<T: DeserializeOwned>(&self) -> Result<T, SearchDirectoryError> {
-        self.writer_dir.load_index()
-    }
-    fn save_index<T: Serialize>(&self, index: &T) -> Result<(), SearchDirectoryError> {
-        self.writer_dir.save_index(index)
-    }
     fn remove(&self) -> Result<(), SearchDirectoryError> {
         self.writer_dir.remove()
     }
-    fn tantivy_dir_path(
-        &self,
-        ensure_exists: bool,
-    ) -> Result<TantivyDirPath, SearchDirectoryError> {
-        self.writer_dir.tantivy_dir_path(ensure_exists)
-    }
 }
@@ -16,14 +16,12 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 mod directory;
-mod index;
 
 pub use crate::index::SearchFs;
 use crate::schema::{
     SearchDocument, SearchFieldConfig, SearchFieldName, SearchFieldType, SearchIndexSchema,
 };
 pub use directory::*;
-pub use index::*;
 pub use rstest::*;
 use serde_json::json;
 
@@ -0,0 +1,128 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{other_databases_with_directories, sweep_orphaned_directories};
+use anyhow::{anyhow, Result};
+use pgrx::bgworkers::{self, BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use pgrx::{pg_guard, pg_sys, FromDatum, IntoDatum};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+#[pg_guard]
+#[no_mangle]
+pub fn setup_orphan_sweep_background_worker() {
+    // A background worker that periodically removes index directories that no longer belong
+    // to an index, across every database in the cluster.
+    BackgroundWorkerBuilder::new("pg_search_orphan_sweep_launcher")
+        // Must be the name of a function in this file.
+        .set_function("orphan_sweep_launcher")
+        // Must be the name of the extension it will be loaded from.
+        .set_library("pg_search")
+        .enable_spi_access()
+        // Allows time for all bootstrapped tables to be created.
+        .set_start_time(bgworkers::BgWorkerStartTime::RecoveryFinished)
+        .load();
+}
+
+#[pg_guard]
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn orphan_sweep_launcher(_arg: pg_sys::Datum) {
+    // This function runs in the spawned background worker process. That means
+    // that we need to re-initialize logging.
+    crate::trace::init_ereport_logger("pg_search");
+
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM | SignalWakeFlags::SIGHUP);
+
+    // `pg_class` can only be read for the database we're connected to, so the launcher sweeps
+    // the 'postgres' database (along with the directories of databases that have been dropped)
+    // and starts a short-lived worker connected to each other database to sweep that one.
+    BackgroundWorker::connect_worker_to_spi(Some("postgres"), None);
+
+    debug!("starting pg_search orphan sweep launcher");
+    let mut last_sweep = Instant::now();
+    while BackgroundWorker::wait_latch(Some(Duration::from_secs(1))) {
+        if BackgroundWorker::sighup_received() {
+            pg_sys::ProcessConfigFile(pg_sys::GucContext::PGC_SIGHUP);
+        }
+
+        let Some(interval) = crate::gucs::orphaned_index_sweep_interval() else {
+            continue;
+        };
+        if last_sweep.elapsed() < interval {
+            continue;
+        }
+        last_sweep = Instant::now();
+
+        let database_oids = BackgroundWorker::transaction(|| {
+            sweep_orphaned_directories(true)?;
+            other_databases_with_directories()
+        });
+
+        match database_oids {
+            Ok(database_oids) => {
+                for database_oid in database_oids {
+                    if BackgroundWorker::sigterm_received() {
+                        break;
+                    }
+                    if let Err(e) = sweep_database(database_oid) {
+                        warn!("could not sweep orphaned index directories of database {database_oid}: {e}");
+                    }
+                }
+            }
+            Err(e) => warn!("could not sweep orphaned index directories: {e}"),
+        }
+    }
+    debug!("exiting pg_search orphan sweep launcher");
+}
+
+/// Start a worker connected to `database_oid` to sweep its directories, and wait for it to finish.
+fn sweep_database(database_oid: u32) -> Result<()> {
+    let worker = BackgroundWorkerBuilder::new(&format!(
+        "pg_search_orphan_sweep_worker for database {database_oid}"
+    ))
+    .set_function("orphan_sweep_worker")
+    .set_library("pg_search")
+    .set_argument(pg_sys::Oid::from(database_oid).into_datum())
+    .enable_spi_access()
+    // The launcher will try again at its next sweep.
+    .set_restart_time(None)
+    .set_notify_pid(unsafe { pg_sys::MyProcPid })
+    .load_dynamic()
+    .map_err(|_| anyhow!("could not start a background worker"))?;
+
+    worker
+        .wait_for_shutdown()
+        .map_err(|status| anyhow!("background worker did not finish: {status:?}"))
+}
+
+#[pg_guard]
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn orphan_sweep_worker(database_oid_datum: pg_sys::Datum) {
+    crate::trace::init_ereport_logger("pg_search");
+
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM);
+
+    let database_oid = pg_sys::Oid::from_datum(database_oid_datum, false)
+        .expect("database oid not passed to bgworker");
+    pg_sys::BackgroundWorkerInitializeConnectionByOid(database_oid, pg_sys::InvalidOid, 0);
+
+    if let Err(e) = BackgroundWorker::transaction(|| sweep_orphaned_directories(true)) {
+        warn!("could not sweep orphaned index directories: {e}");
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod bgworker;
+
+pub use bgworker::setup_orphan_sweep_background_worker;
+
+use crate::index::{SearchFs, WriterDirectory};
+use anyhow::Result;
+use pgrx::{pg_sys, IntoDatum, PgBuiltInOids, Spi};
+use std::collections::HashMap;
+
+/// A directory under `$data_directory/pg_search` that no longer belongs to an index.
+pub struct OrphanedDirectory {
+    pub directory: WriterDirectory,
+    pub byte_size: u64,
+    pub removed: bool,
+}
+
+/// Find the index directories under `$data_directory/pg_search` that belong to a database that
+/// no longer exists, or to an index of the connected database that no longer exists (or has since
+/// been given a new relfilenode), and optionally remove them.
+///
+/// Directories of the connected database are only judged while holding a lock on their index oid,
+/// so one whose index is being created by a transaction that hasn't committed yet is left alone.
+/// `pg_class` can only be read for the connected database, so directories belonging to any other
+/// database that still exists are skipped.
+///
+/// Must be called from within a transaction.
+pub fn sweep_orphaned_directories(remove: bool) -> Result<Vec<OrphanedDirectory>> {
+    let my_database_oid = crate::MyDatabaseId();
+    let mut database_exists = HashMap::new();
+    let mut orphans = vec![];
+
+    for directory in WriterDirectory::all_relfile_paths()? {
+        let orphaned = if directory.database_oid == my_database_oid {
+            is_orphaned_index(&directory)?
+        } else {
+            let exists = match database_exists.get(&directory.database_oid) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = database_exists_by_oid(directory.database_oid)?;
+                    database_exists.insert(directory.database_oid, exists);
+                    exists
+                }
+            };
+            !exists
+        };
+
+        if !orphaned {
+            continue;
+        }
+
+        let byte_size = directory.byte_size()?;
+        let removed = if remove {
+            directory.remove()?;
+            pgrx::log!(
+                "removed orphaned pg_search index directory for database {}, index {}, relfilenode {} ({byte_size} bytes)",
+                directory.database_oid,
+                directory.index_oid,
+                directory.relfilenode
+            );
+            true
+        } else {
+            false
+        };
+
+        orphans.push(OrphanedDirectory {
+            directory,
+            byte_size,
+            removed,
+        });
+    }
+
+    Ok(orphans)
+}
+
+/// The oids of the databases, other than the connected one, that still exist and have index
+/// directories under `$data_directory/pg_search`.
+pub fn other_databases_with_directories() -> Result<Vec<u32>> {
+    let my_database_oid = crate::MyDatabaseId();
+    let mut database_oids = WriterDirectory::all_relfile_paths()?
+        .into_iter()
+        .map(|directory| directory.database_oid)
+        .filter(|database_oid| *database_oid != my_database_oid)
+        .collect::<Vec<_>>();
+    database_oids.sort_unstable();
+    database_oids.dedup();
+
+    let mut existing = vec![];
+    for database_oid in database_oids {
+        if database_exists_by_oid(database_oid)? {
+            existing.push(database_oid);
+        }
+    }
+    Ok(existing)
+}
+
+fn is_orphaned_index(directory: &WriterDirectory) -> Result<bool> {
+    // A transaction that's creating, rebuilding or dropping the index holds an exclusive lock on
+    // it until it commits, and what we'd read of its relfilenode before then may already be out
+    // of date.  If someone holds such a lock, the directory is left for the next run.  Any lock
+    // conflicts with it, and a shared one doesn't block queries on the index while we look.
+    let index_oid = pg_sys::Oid::from(directory.index_oid);
+    if !unsafe { pg_sys::ConditionalLockRelationOid(index_oid, pg_sys::AccessShareLock as _) } {
+        return Ok(false);
+    }
+
+    match index_relfilenode(directory.index_oid)? {
+        // the index is still there, and the directory is orphaned only if it's left over from
+        // an earlier relfilenode (a REINDEX, VACUUM FULL, etc.)
+        Some(relfilenode) => Ok(relfilenode != directory.relfilenode),
+
+        // the index is gone
+        None => Ok(true),
+    }
+}
+
+fn index_relfilenode(index_oid: u32) -> Result<Option<u32>> {
+    Ok(Spi::get_one_with_args::<pg_sys::Oid>(
+        "SELECT (SELECT relfilenode FROM pg_class WHERE oid = $1)",
+        vec![(
+            PgBuiltInOids::OIDOID.oid(),
+            pg_sys::Oid::from(index_oid).into_datum(),
+        )],
+    )?
+    .map(|relfilenode| relfilenode.as_u32()))
+}
+
+fn database_exists_by_oid(database_oid: u32) -> Result<bool> {
+    Ok(Spi::get_one_with_args::<bool>(
+        "SELECT EXISTS (SELECT 1 FROM pg_database WHERE oid = $1)",
+        vec![(
+            PgBuiltInOids::OIDOID.oid(),
+            pg_sys::Oid::from(database_oid).into_datum(),
+        )],
+    )?
+    .unwrap_or(false))
+}
@@ -18,6 +18,7 @@
 use crate::index::Parallelism;
 use pgrx::{pg_sys, GucContext, GucFlags, GucRegistry, GucSetting};
 use std::num::NonZeroUsize;
+use std::time::Duration;
 
 /// Is our telemetry tracking enabled?  Default is `true`.
 static TELEMETRY: GucSetting<bool> = GucSetting::<bool>::new(true);
@@ -57,6 +58,21 @@ static STATEMENT_PARALLELISM: GucSetting<i32> = GucSetting::<i32>::new(8);
 /// thread.  So if there's 10 threads and this value is 100MB, then a total of 1GB will be allocated.
 static STATEMENT_MEMORY_BUDGET: GucSetting<i32> = GucSetting::<i32>::new(0);
 
+/// How often, in seconds, should the background worker look for and remove index directories
+/// under `$PGDATA/pg_search` that no longer belong to an index?  Zero disables the sweep.
+static ORPHANED_INDEX_SWEEP_INTERVAL: GucSetting<i32> = GucSetting::<i32>::new(3600);
+
+/// How many background workers may merge index segments at the same time?  Zero disables
+/// background merging, and merges happen during INSERT/UPDATE/COPY statements instead, according
+/// to each index's `merge_on_insert` option.
+static MERGE_WORKERS: GucSetting<i32> = GucSetting::<i32>::new(1);
+
+/// How long, in seconds, should the background merge workers wait between rounds?
+static MERGE_NAPTIME: GucSetting<i32> = GucSetting::<i32>::new(60);
+
+/// How many megabytes per second may each background merge worker merge?  Zero means unlimited.
+static MERGE_IO_LIMIT: GucSetting<i32> = GucSetting::<i32>::new(0);
+
 pub fn init() {
     // Note that Postgres is very specific about the naming convention of variables.
     // They must be namespaced... we use 'paradedb.<variable>' below.
@@ -143,6 +159,50 @@ pub fn init() {
         GucContext::Suset,
         GucFlags::UNIT_MB,
     );
+
+    GucRegistry::define_int_guc(
+        "paradedb.merge_workers",
+        "The number of background workers that merge index segments at the same time",
+        "Default is 1.  Value of zero disables background merging, and segments are merged during INSERT/UPDATE/COPY statements instead",
+        &MERGE_WORKERS,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "paradedb.merge_naptime",
+        "The time to wait between rounds of background segment merging",
+        "Default is 1 minute",
+        &MERGE_NAPTIME,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+
+    GucRegistry::define_int_guc(
+        "paradedb.merge_io_limit",
+        "The maximum rate at which each background worker merges index segments",
+        "Default is 0, which means unlimited",
+        &MERGE_IO_LIMIT,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_MB,
+    );
+
+    GucRegistry::define_int_guc(
+        "paradedb.orphaned_index_sweep_interval",
+        "How often to remove index directories that no longer belong to an index",
+        "Default is 1 hour.  Value of zero disables the sweep",
+        &ORPHANED_INDEX_SWEEP_INTERVAL,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
 }
 
 pub fn telemetry_enabled() -> bool {
@@ -179,6 +239,29 @@ pub fn statement_memory_budget() -> usize {
     adjust_budget(STATEMENT_MEMORY_BUDGET.get(), statement_parallelism())
 }
 
+pub fn merge_workers() -> usize {
+    MERGE_WORKERS.get().max(0) as usize
+}
+
+pub fn merge_naptime() -> Duration {
+    Duration::from_secs(MERGE_NAPTIME.get().max(1) as u64)
+}
+
+/// In bytes per second.
+pub fn merge_io_limit() -> Option<u64> {
+    match MERGE_IO_LIMIT.get() {
+        mb if mb <= 0 => None,
+        mb => Some(mb as u64 * 1024 * 1024),
+    }
+}
+
+pub fn orphaned_index_sweep_interval() -> Option<Duration> {
+    match ORPHANED_INDEX_SWEEP_INTERVAL.get() {
+        secs if secs <= 0 => None,
+        secs => Some(Duration::from_secs(secs as u64)),
+    }
+}
+
 fn adjust_nthreads(nthreads: i32) -> NonZeroUsize {
     let nthreads = if nthreads <= 0 {
         std::thread::available_parallelism()
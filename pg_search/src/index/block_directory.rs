@@ -0,0 +1,1007 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::index::redirects::{CtidRedirects, RedirectChanges};
+use crate::postgres::storage::{BlockStorage, BlockStorageError, FileCatalog, FileEntry};
+use crossbeam::channel::{Receiver, Sender};
+use parking_lot::{Mutex, RwLock};
+use pgrx::{pg_sys, PgRelation};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::ThreadId;
+use std::time::Duration;
+use tantivy::directory::error::{DeleteError, LockError, OpenReadError, OpenWriteError};
+use tantivy::directory::{
+    AntiCallToken, DirectoryLock, FileHandle, Lock, OwnedBytes, TerminatingWrite, WatchCallback,
+    WatchHandle, WritePtr,
+};
+use tantivy::{Directory, HasLen, SegmentId};
+
+/// Work, usually a read from the index relation, that a thread other than the backend's main
+/// thread needs done.  Only the main thread is allowed to talk to Postgres' buffer manager.
+type Request = Box<dyn FnOnce(&BlockDirectory) + Send>;
+
+/// tantivy's own bookkeeping files, which concurrent writers all update.  A detached directory
+/// never writes them to the index relation.
+pub const META_FILEPATH: &str = "meta.json";
+pub const MANAGED_FILEPATH: &str = ".managed.json";
+
+/// The segments written by prepared transactions, until it's known whether they committed.  Only
+/// pg_search reads and writes it.  See [`BlockDirectory::record_prepared`].
+pub const PREPARED_FILEPATH: &str = ".prepared.json";
+
+/// The segments each prepared transaction wrote, keyed by its full transaction id
+type PreparedSegments = BTreeMap<u64, HashSet<SegmentId>>;
+
+fn is_shared_file(path: &Path) -> bool {
+    path == Path::new(META_FILEPATH) || path == Path::new(MANAGED_FILEPATH)
+}
+
+struct BlockDirectoryInner {
+    indexrelid: pg_sys::Oid,
+    backend_thread: ThreadId,
+    /// See [`BlockDirectory::open_detached`]
+    detached: bool,
+    /// Whether we hold the index's writer lock.  See [`BlockDirectory::lock_writer`]
+    exclusive: AtomicBool,
+    /// The shared files as we last read or flushed them, which tells our own changes to them
+    /// apart from those made by concurrent writers
+    base: Mutex<HashMap<PathBuf, OwnedBytes>>,
+    /// Every file written through this directory
+    written: Mutex<HashSet<PathBuf>>,
+    /// The files written through this directory that haven't been flushed yet, and the shared
+    /// files as we last read or wrote them, keyed by path
+    files: RwLock<HashMap<PathBuf, OwnedBytes>>,
+    /// The catalog of files in the index relation, as of when we last read it, and the block its
+    /// chain of pages starts at
+    catalog: Mutex<Option<(Arc<FileCatalog>, pg_sys::BlockNumber)>>,
+    /// See [`BlockDirectory::open_at`]
+    pinned_catalog: Option<pg_sys::BlockNumber>,
+    /// Files written since the last [`BlockDirectory::flush`]
+    pending_writes: Mutex<HashSet<PathBuf>>,
+    /// Files deleted since the last [`BlockDirectory::flush`]
+    pending_deletes: Mutex<HashSet<PathBuf>>,
+    /// Changes to the index's ctid redirects since the last [`BlockDirectory::flush`]
+    pending_redirects: Mutex<RedirectChanges>,
+    requests: (Sender<Request>, Receiver<Request>),
+}
+
+/// A tantivy [`Directory`] that stores the index files in the pages of the Postgres index
+/// relation itself, so that they're covered by the buffer manager and the WAL.
+///
+/// Files are read through the buffer manager a page range at a time, as tantivy needs them, so
+/// opening an index doesn't copy it into the backend's memory.  Writes are buffered in memory
+/// and are only written to the relation, in one atomic change, by [`BlockDirectory::flush`].
+/// Because tantivy does its indexing, merging, and some of its searching in background threads,
+/// and Postgres can only be called from the backend's main thread, any reads those threads need
+/// are forwarded to the main thread and answered while it waits in
+/// [`BlockDirectory::service_reads_while`].
+#[derive(Clone)]
+pub struct BlockDirectory {
+    inner: Arc<BlockDirectoryInner>,
+}
+
+impl std::fmt::Debug for BlockDirectory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockDirectory")
+            .field("indexrelid", &self.inner.indexrelid)
+            .finish()
+    }
+}
+
+impl BlockDirectory {
+    pub fn open(indexrelid: pg_sys::Oid) -> Self {
+        Self::new(indexrelid, false, None)
+    }
+
+    /// Open the directory as of the catalog whose chain of pages starts at `catalog_start`, which
+    /// another participant of a parallel scan found with [`BlockDirectory::catalog_start`], so
+    /// that both see exactly the same files.  The directory must only be read from.
+    pub fn open_at(indexrelid: pg_sys::Oid, catalog_start: pg_sys::BlockNumber) -> Self {
+        Self::new(indexrelid, false, Some(catalog_start))
+    }
+
+    /// Open the directory for one participant of a parallel index build.
+    ///
+    /// The segment files it writes are flushed to the index relation as usual, but its
+    /// `meta.json` and `.managed.json` are kept in memory, so the index stays as it was until the
+    /// leader adopts every participant's segments.  It doesn't take the writer lock either, as
+    /// the participants of a parallel build are allowed to write to the index at the same time.
+    pub fn open_detached(indexrelid: pg_sys::Oid) -> Self {
+        Self::new(indexrelid, true, None)
+    }
+
+    fn new(
+        indexrelid: pg_sys::Oid,
+        detached: bool,
+        pinned_catalog: Option<pg_sys::BlockNumber>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(BlockDirectoryInner {
+                indexrelid,
+                backend_thread: std::thread::current().id(),
+                detached,
+                exclusive: AtomicBool::new(false),
+                base: Default::default(),
+                written: Default::default(),
+                files: Default::default(),
+                catalog: Default::default(),
+                pinned_catalog,
+                pending_writes: Default::default(),
+                pending_deletes: Default::default(),
+                pending_redirects: Default::default(),
+                requests: crossbeam::channel::unbounded(),
+            }),
+        }
+    }
+
+    /// Open the directory for a brand-new index, initializing the index relation's metapage.
+    pub fn create(indexrelid: pg_sys::Oid) -> Self {
+        let indexrel = PgRelation::open(indexrelid);
+        unsafe { BlockStorage::new(indexrel.as_ptr()) }.init_metapage();
+        Self::open(indexrelid)
+    }
+
+    /// Take the index's writer lock, which is held until the end of the transaction.
+    ///
+    /// Writers that only add new segments don't need it, and publish their segments alongside
+    /// each other.  Writers that merge segments or delete documents take it so that only one of
+    /// them rewrites the index at a time.  Readers never take it, so they're never blocked.
+    pub fn lock_writer(&self) {
+        if self.inner.detached {
+            return;
+        }
+
+        let indexrel = PgRelation::open(self.inner.indexrelid);
+        unsafe {
+            pg_sys::LockPage(
+                indexrel.as_ptr(),
+                crate::postgres::storage::METAPAGE,
+                pg_sys::ExclusiveLock as _,
+            );
+        }
+        self.inner.exclusive.store(true, Ordering::Relaxed);
+
+        // what we've read of meta.json may predate the commit of the writer we waited for
+        *self.inner.catalog.lock() = None;
+        let meta = Path::new(META_FILEPATH);
+        if !self.inner.pending_writes.lock().contains(meta) {
+            self.inner.files.write().remove(meta);
+            self.inner.base.lock().remove(meta);
+        }
+    }
+
+    /// Redirect the document indexed with ctid `from` to the ctid `to` of a later version of its
+    /// row.  See [`crate::index::redirects`].
+    pub fn add_redirect(&self, from: u64, to: u64) {
+        let mut redirects = self.inner.pending_redirects.lock();
+        redirects.removed.remove(from, to);
+        redirects.added.insert(from, to);
+        self.inner
+            .pending_writes
+            .lock()
+            .insert(PathBuf::from(META_FILEPATH));
+    }
+
+    /// Remove a redirect added by [`BlockDirectory::add_redirect`].
+    pub fn remove_redirect(&self, from: u64, to: u64) {
+        let mut redirects = self.inner.pending_redirects.lock();
+        redirects.added.remove(from, to);
+        redirects.removed.insert(from, to);
+        self.inner
+            .pending_writes
+            .lock()
+            .insert(PathBuf::from(META_FILEPATH));
+    }
+
+    /// Write every change made to this directory since the last flush to the index relation.
+    ///
+    /// Our changes to the shared files are merged with those that concurrent writers have made
+    /// since we read them, so that every writer's segments are published.
+    ///
+    /// Must be called from the backend's main thread.
+    pub fn flush(&self) -> Result<(), BlockStorageError> {
+        let mut pending_writes = self.inner.pending_writes.lock();
+        let mut pending_deletes = self.inner.pending_deletes.lock();
+        if pending_writes.is_empty() && pending_deletes.is_empty() {
+            return Ok(());
+        }
+
+        let files = self.inner.files.read();
+        let mut base = self.inner.base.lock();
+        let mut redirects = self.inner.pending_redirects.lock();
+        let writes = pending_writes
+            .iter()
+            .filter(|path| !is_shared_file(path))
+            .filter_map(|path| {
+                files
+                    .get(path)
+                    .map(|bytes| (path.as_path(), bytes.as_slice()))
+            });
+        let deletes = pending_deletes.iter().map(|path| path.as_path());
+        let merges = pending_writes
+            .iter()
+            .filter(|path| is_shared_file(path) && files.contains_key(*path))
+            .map(|path| path.as_path());
+
+        let indexrel = PgRelation::open(self.inner.indexrelid);
+        let result = unsafe { BlockStorage::new(indexrel.as_ptr()) }.apply_changes(
+            writes,
+            deletes,
+            merges,
+            |path, stored| {
+                let ours = files[path].as_slice();
+                let Some(stored) = stored else {
+                    return Ok(ours.to_vec());
+                };
+                let base = base.get(path).map(|bytes| bytes.as_slice());
+                let merged = if path == Path::new(META_FILEPATH) {
+                    merge_meta(base, ours, stored, &redirects)
+                } else {
+                    merge_managed(base, ours, stored, &pending_deletes)
+                };
+                merged.map_err(|e| BlockStorageError::Merge(path.to_path_buf(), e))
+            },
+        );
+        *self.inner.catalog.lock() = None;
+        result?;
+
+        // our next changes to the shared files are relative to what we've just written
+        for path in pending_writes.iter().filter(|path| is_shared_file(path)) {
+            if let Some(bytes) = files.get(path) {
+                base.insert(path.clone(), bytes.clone());
+            }
+        }
+
+        // the files we've flushed are read back from the index relation from now on
+        drop(files);
+        self.inner
+            .files
+            .write()
+            .retain(|path, _| is_shared_file(path) || !pending_writes.contains(path));
+
+        pending_writes.clear();
+        pending_deletes.clear();
+        *redirects = RedirectChanges::default();
+        Ok(())
+    }
+
+    /// Remove the segments with the given ids from the index.  Segments that have since been
+    /// merged away are left alone.
+    ///
+    /// This rewrites `meta.json` in the index relation directly, without involving tantivy, so
+    /// any changes pending in this directory are unaffected.  The segments' files are left for
+    /// the next `VACUUM` to collect, as readers may still be using them.
+    ///
+    /// Must be called from the backend's main thread.
+    pub fn discard_segments(
+        &self,
+        segment_ids: &HashSet<SegmentId>,
+    ) -> Result<(), BlockStorageError> {
+        let indexrel = PgRelation::open(self.inner.indexrelid);
+        let result = unsafe { BlockStorage::new(indexrel.as_ptr()) }.apply_changes(
+            std::iter::empty(),
+            std::iter::empty(),
+            [Path::new(META_FILEPATH)],
+            |path, stored| {
+                let discarded = match stored {
+                    Some(stored) => without_segments(stored, segment_ids),
+                    None => Err(serde::de::Error::custom("file does not exist")),
+                };
+                discarded.map_err(|e| BlockStorageError::Merge(path.to_path_buf(), e))
+            },
+        );
+        *self.inner.catalog.lock() = None;
+        result
+    }
+
+    /// Record that the prepared transaction with the full transaction id `xid` wrote the segments
+    /// `segment_ids`, so that whichever backend finds it rolled back, even after a restart, can
+    /// discard them.  See [`BlockDirectory::resolve_prepared`].
+    ///
+    /// Must be called from the backend's main thread.
+    pub fn record_prepared(
+        &self,
+        xid: u64,
+        segment_ids: &HashSet<SegmentId>,
+    ) -> Result<(), BlockStorageError> {
+        let indexrel = PgRelation::open(self.inner.indexrelid);
+        let result = unsafe { BlockStorage::new(indexrel.as_ptr()) }.apply_changes(
+            std::iter::empty(),
+            std::iter::empty(),
+            [Path::new(PREPARED_FILEPATH)],
+            |path, stored| {
+                let recorded = prepared_segments(stored).and_then(|mut prepared| {
+                    prepared.entry(xid).or_default().extend(segment_ids);
+                    serde_json::to_vec(&prepared)
+                });
+                recorded.map_err(|e| BlockStorageError::Merge(path.to_path_buf(), e))
+            },
+        );
+        *self.inner.catalog.lock() = None;
+        result
+    }
+
+    /// Forget the prepared transactions recorded by [`BlockDirectory::record_prepared`] that have
+    /// since committed, and remove the segments of those that were rolled back from the index.
+    /// Like in [`BlockDirectory::discard_segments`], segments that have since been merged away are
+    /// left for `VACUUM`, and their files are left for it to collect.
+    ///
+    /// Must be called from the backend's main thread.
+    pub fn resolve_prepared(&self) -> Result<(), BlockStorageError> {
+        // most indexes have no prepared transactions waiting to be resolved, and finding that out
+        // is much cheaper than rewriting the catalog
+        let path = Path::new(PREPARED_FILEPATH);
+        let Some(entry) = self.catalog()?.files.get(path).cloned() else {
+            return Ok(());
+        };
+        let indexrel = PgRelation::open(self.inner.indexrelid);
+        let storage = unsafe { BlockStorage::new(indexrel.as_ptr()) };
+        let stored = storage.read(&entry, 0..entry.len as usize)?;
+        let prepared = prepared_segments(Some(&stored))
+            .map_err(|e| BlockStorageError::Merge(path.to_path_buf(), e))?;
+        if prepared
+            .keys()
+            .all(|xid| unsafe { xact_outcome(*xid) }.is_none())
+        {
+            return Ok(());
+        }
+
+        // the prepared file is merged first, and tells us which segments to remove from meta.json
+        let mut aborted = HashSet::new();
+        let result = storage.apply_changes(
+            std::iter::empty(),
+            std::iter::empty(),
+            [path, Path::new(META_FILEPATH)],
+            |path, stored| {
+                let resolved = if path == Path::new(PREPARED_FILEPATH) {
+                    prepared_segments(stored).and_then(|mut prepared| {
+                        prepared.retain(|xid, segment_ids| match unsafe { xact_outcome(*xid) } {
+                            None => true,
+                            Some(true) => false,
+                            Some(false) => {
+                                aborted.extend(segment_ids.drain());
+                                false
+                            }
+                        });
+                        serde_json::to_vec(&prepared)
+                    })
+                } else {
+                    match stored {
+                        Some(stored) => without_segments(stored, &aborted),
+                        None => Err(serde::de::Error::custom("file does not exist")),
+                    }
+                };
+                resolved.map_err(|e| BlockStorageError::Merge(path.to_path_buf(), e))
+            },
+        );
+        *self.inner.catalog.lock() = None;
+        result
+    }
+
+    /// Returns the catalog of files stored in the index relation.
+    ///
+    /// It's read once and reused until this directory changes the index, or waits for another
+    /// writer in [`BlockDirectory::lock_writer`].  Our snapshot keeps the blocks of the files it
+    /// lists from being reused in the meantime.
+    ///
+    /// Must be called from the backend's main thread.
+    pub fn catalog(&self) -> Result<Arc<FileCatalog>, BlockStorageError> {
+        self.load_catalog().map(|(catalog, _)| catalog)
+    }
+
+    /// Returns the block that the chain of pages of [`BlockDirectory::catalog`] starts at.
+    ///
+    /// Must be called from the backend's main thread.
+    pub fn catalog_start(&self) -> Result<pg_sys::BlockNumber, BlockStorageError> {
+        self.load_catalog().map(|(_, catalog_start)| catalog_start)
+    }
+
+    fn load_catalog(&self) -> Result<(Arc<FileCatalog>, pg_sys::BlockNumber), BlockStorageError> {
+        let mut catalog = self.inner.catalog.lock();
+        if let Some(catalog) = catalog.as_ref() {
+            return Ok(catalog.clone());
+        }
+
+        let indexrel = PgRelation::open(self.inner.indexrelid);
+        let storage = unsafe { BlockStorage::new(indexrel.as_ptr()) };
+        let (loaded, catalog_start) = match self.inner.pinned_catalog {
+            Some(catalog_start) => (storage.catalog_at(catalog_start)?, catalog_start),
+            None => storage.catalog()?,
+        };
+        let loaded = (Arc::new(loaded), catalog_start);
+        *catalog = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Forget every change made to this directory since the last flush.
+    pub fn discard_pending(&self) {
+        let mut files = self.inner.files.write();
+        for path in self.inner.pending_writes.lock().drain() {
+            files.remove(&path);
+        }
+        self.inner.pending_deletes.lock().clear();
+        *self.inner.pending_redirects.lock() = RedirectChanges::default();
+    }
+
+    /// Run `f` in a separate thread and, until it finishes, answer any read requests made by
+    /// tantivy's background threads.
+    pub fn service_reads_while<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(f);
+            while !handle.is_finished() {
+                if let Ok(request) = self
+                    .inner
+                    .requests
+                    .1
+                    .recv_timeout(Duration::from_millis(10))
+                {
+                    request(self);
+                }
+            }
+            match handle.join() {
+                Ok(result) => result,
+                Err(e) => std::panic::resume_unwind(e),
+            }
+        })
+    }
+
+    /// Run `f` on the backend's main thread.  From any other thread, `f` is forwarded to the main
+    /// thread, which must be in [`BlockDirectory::service_reads_while`] to run it.
+    fn on_backend_thread<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&BlockDirectory) -> R + Send + 'static,
+    ) -> io::Result<R> {
+        if std::thread::current().id() == self.inner.backend_thread {
+            return Ok(f(self));
+        }
+
+        let (reply, response) = crossbeam::channel::bounded(1);
+        self.inner
+            .requests
+            .0
+            .send(Box::new(move |directory: &BlockDirectory| {
+                // the requesting thread may have gone away, and that's fine
+                let _ = reply.send(f(directory));
+            }))
+            .expect("read request channel should be open");
+        response
+            .recv()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    /// Find the file at `path` in the index relation.
+    fn entry(&self, path: &Path) -> Result<Arc<FileEntry>, OpenReadError> {
+        if self.inner.pending_deletes.lock().contains(path) {
+            return Err(OpenReadError::FileDoesNotExist(path.to_path_buf()));
+        }
+
+        let owned_path = path.to_path_buf();
+        let entry = self
+            .on_backend_thread(move |directory| {
+                directory
+                    .catalog()
+                    .map(|catalog| catalog.files.get(&owned_path).cloned())
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            })
+            .and_then(|entry| entry)
+            .map_err(|e| OpenReadError::wrap_io_error(e, path.to_path_buf()))?;
+        entry
+            .map(Arc::new)
+            .ok_or_else(|| OpenReadError::FileDoesNotExist(path.to_path_buf()))
+    }
+
+    /// Read the bytes in `range` of the file described by `entry` from the index relation.
+    fn read(&self, entry: &Arc<FileEntry>, range: Range<usize>) -> io::Result<OwnedBytes> {
+        let entry = entry.clone();
+        self.on_backend_thread(move |directory| {
+            let indexrel = PgRelation::open(directory.inner.indexrelid);
+            unsafe { BlockStorage::new(indexrel.as_ptr()) }
+                .read(&entry, range)
+                .map(OwnedBytes::new)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        })?
+    }
+
+    /// Read the whole file at `path`.  The shared files are kept in memory afterwards.
+    fn load(&self, path: &Path) -> Result<OwnedBytes, OpenReadError> {
+        if let Some(bytes) = self.inner.files.read().get(path) {
+            return Ok(bytes.clone());
+        }
+
+        let entry = self.entry(path)?;
+        let bytes = self
+            .read(&entry, 0..entry.len as usize)
+            .map_err(|e| OpenReadError::wrap_io_error(e, path.to_path_buf()))?;
+
+        if is_shared_file(path) {
+            self.inner
+                .base
+                .lock()
+                .insert(path.to_path_buf(), bytes.clone());
+            self.inner
+                .files
+                .write()
+                .insert(path.to_path_buf(), bytes.clone());
+        }
+        Ok(bytes)
+    }
+
+    fn store(&self, path: &Path, data: Vec<u8>) {
+        self.inner
+            .files
+            .write()
+            .insert(path.to_path_buf(), OwnedBytes::new(data));
+        self.inner.written.lock().insert(path.to_path_buf());
+        if self.inner.detached && is_shared_file(path) {
+            return;
+        }
+        self.inner.pending_deletes.lock().remove(path);
+        self.inner.pending_writes.lock().insert(path.to_path_buf());
+    }
+}
+
+/// A file stored in the index relation, whose pages are read as tantivy asks for them
+#[derive(Debug)]
+struct BlockFile {
+    directory: BlockDirectory,
+    entry: Arc<FileEntry>,
+}
+
+impl HasLen for BlockFile {
+    fn len(&self) -> usize {
+        self.entry.len as usize
+    }
+}
+
+impl FileHandle for BlockFile {
+    fn read_bytes(&self, range: Range<usize>) -> io::Result<OwnedBytes> {
+        self.directory.read(&self.entry, range)
+    }
+}
+
+/// Buffers a file being written by tantivy until it's terminated
+struct BlockFileWriter {
+    directory: BlockDirectory,
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+impl Write for BlockFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TerminatingWrite for BlockFileWriter {
+    fn terminate_ref(&mut self, _: AntiCallToken) -> io::Result<()> {
+        self.directory
+            .store(&self.path, std::mem::take(&mut self.data));
+        Ok(())
+    }
+}
+
+impl Directory for BlockDirectory {
+    fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        if let Some(bytes) = self.inner.files.read().get(path) {
+            return Ok(Arc::new(bytes.clone()));
+        }
+        if is_shared_file(path) {
+            return Ok(Arc::new(self.load(path)?));
+        }
+        Ok(Arc::new(BlockFile {
+            directory: self.clone(),
+            entry: self.entry(path)?,
+        }))
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), DeleteError> {
+        if !self.inner.exclusive.load(Ordering::Relaxed)
+            && !self.inner.written.lock().contains(path)
+        {
+            // Without the writer lock, tantivy's garbage collection could delete the files of
+            // segments that a concurrent writer has written but not yet published.  They're left
+            // for a writer that holds the lock.
+            return Ok(());
+        }
+        self.inner.files.write().remove(path);
+        self.inner.pending_writes.lock().remove(path);
+        self.inner.pending_deletes.lock().insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
+        if self.inner.files.read().contains_key(path) {
+            return Ok(true);
+        }
+        match self.entry(path) {
+            Ok(_) => Ok(true),
+            Err(OpenReadError::FileDoesNotExist(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn open_write(&self, path: &Path) -> Result<WritePtr, OpenWriteError> {
+        Ok(BufWriter::new(Box::new(BlockFileWriter {
+            directory: self.clone(),
+            path: path.to_path_buf(),
+            data: vec![],
+        })))
+    }
+
+    fn atomic_read(&self, path: &Path) -> Result<Vec<u8>, OpenReadError> {
+        Ok(self.load(path)?.to_vec())
+    }
+
+    fn atomic_write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.store(path, data.to_vec());
+        Ok(())
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        // durability is provided by the WAL when the directory is flushed
+        Ok(())
+    }
+
+    fn acquire_lock(&self, _lock: &Lock) -> Result<DirectoryLock, LockError> {
+        // tantivy's INDEX_WRITER_LOCK would only allow one writer at a time.  Writers that do need
+        // to be alone take [`BlockDirectory::lock_writer`] instead.
+        //
+        // tantivy's META_LOCK only protects files from being garbage collected while a reader
+        // opens them, which holding back the reuse of deleted files' blocks until no snapshot can
+        // see them already does for us
+        Ok(DirectoryLock::from(Box::new(())))
+    }
+
+    fn watch(&self, _watch_callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        // our readers are always reloaded manually
+        Ok(WatchHandle::empty())
+    }
+}
+
+/// Merge our changes to `meta.json`, relative to `base`, into the `stored` copy, which may have
+/// been changed by concurrent writers since we read it.
+///
+/// Segments we've added or changed are kept as we wrote them.  Those we haven't touched are taken
+/// from `stored`, which drops any that a concurrent merge has replaced, and so are the segments
+/// other writers have published.
+///
+/// The ctid redirects in the payload are likewise taken from `stored`, with our `redirects`
+/// changes applied.
+fn merge_meta(
+    base: Option<&[u8]>,
+    ours: &[u8],
+    stored: &[u8],
+    redirects: &RedirectChanges,
+) -> serde_json::Result<Vec<u8>> {
+    fn segments(meta: &Value) -> HashMap<&str, &Value> {
+        meta["segments"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|segment| (segment["segment_id"].as_str().unwrap_or_default(), segment))
+            .collect()
+    }
+
+    let base: Value = base
+        .map(serde_json::from_slice)
+        .transpose()?
+        .unwrap_or_default();
+    let mut ours: Value = serde_json::from_slice(ours)?;
+    let stored: Value = serde_json::from_slice(stored)?;
+    let base_segments = segments(&base);
+    let stored_segments = segments(&stored);
+
+    let mut merged = vec![];
+    let mut seen = HashSet::new();
+    for segment in ours["segments"].as_array().cloned().unwrap_or_default() {
+        let id = segment["segment_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match base_segments.get(id.as_str()) {
+            Some(base_segment) if **base_segment == segment => {
+                if let Some(stored_segment) = stored_segments.get(id.as_str()) {
+                    merged.push((*stored_segment).clone());
+                }
+            }
+            _ => merged.push(segment),
+        }
+        seen.insert(id);
+    }
+    for segment in stored["segments"].as_array().into_iter().flatten() {
+        let id = segment["segment_id"].as_str().unwrap_or_default();
+        if !base_segments.contains_key(id) && !seen.contains(id) {
+            merged.push(segment.clone());
+        }
+    }
+
+    let opstamp = ours["opstamp"]
+        .as_u64()
+        .max(stored["opstamp"].as_u64())
+        .unwrap_or_default();
+    ours["segments"] = Value::Array(merged);
+    ours["opstamp"] = opstamp.into();
+
+    let mut payload = CtidRedirects::from_payload(stored["payload"].as_str())?;
+    redirects.apply(&mut payload);
+    ours["payload"] = payload.to_payload()?.into();
+
+    // in the same format tantivy itself writes it
+    let mut merged = serde_json::to_vec_pretty(&ours)?;
+    merged.push(b'\n');
+    Ok(merged)
+}
+
+/// The segments of each prepared transaction, from the `stored` copy of the prepared file.
+fn prepared_segments(stored: Option<&[u8]>) -> serde_json::Result<PreparedSegments> {
+    stored
+        .map(serde_json::from_slice)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// `None` while the transaction with the full transaction id `xid` is still in progress, which a
+/// prepared transaction is until it's committed or rolled back, and otherwise whether it committed.
+/// A transaction that crashed counts as rolled back.
+unsafe fn xact_outcome(xid: u64) -> Option<bool> {
+    let xid = pg_sys::TransactionId::from_inner(xid as u32);
+    if pg_sys::TransactionIdIsInProgress(xid) {
+        None
+    } else {
+        Some(pg_sys::TransactionIdDidCommit(xid))
+    }
+}
+
+/// Remove the segments with the given ids from the `stored` copy of `meta.json`.
+fn without_segments(
+    stored: &[u8],
+    segment_ids: &HashSet<SegmentId>,
+) -> serde_json::Result<Vec<u8>> {
+    let mut meta: Value = serde_json::from_slice(stored)?;
+    if let Some(segments) = meta["segments"].as_array_mut() {
+        segments.retain(|segment| {
+            !SegmentId::deserialize(&segment["segment_id"])
+                .is_ok_and(|id| segment_ids.contains(&id))
+        });
+    }
+
+    // in the same format tantivy itself writes it
+    let mut discarded = serde_json::to_vec_pretty(&meta)?;
+    discarded.push(b'\n');
+    Ok(discarded)
+}
+
+/// Merge the files we've added to `.managed.json`, relative to `base`, into the `stored` copy, and
+/// remove those we've deleted from it.
+fn merge_managed(
+    base: Option<&[u8]>,
+    ours: &[u8],
+    stored: &[u8],
+    deleted: &HashSet<PathBuf>,
+) -> serde_json::Result<Vec<u8>> {
+    let base: HashSet<PathBuf> = base
+        .map(serde_json::from_slice)
+        .transpose()?
+        .unwrap_or_default();
+    let ours: HashSet<PathBuf> = serde_json::from_slice(ours)?;
+    let mut merged: HashSet<PathBuf> = serde_json::from_slice(stored)?;
+    merged.extend(ours.difference(&base).cloned());
+    merged.retain(|path| !deleted.contains(path));
+
+    let mut merged = serde_json::to_vec(&merged)?;
+    merged.push(b'\n');
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    fn meta(opstamp: u64, segments: &[(&str, u32)]) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "index_settings": {},
+            "segments": segments
+                .iter()
+                .map(|(id, num_deleted)| json!({
+                    "segment_id": id,
+                    "max_doc": 10,
+                    "deletes": (*num_deleted > 0)
+                        .then(|| json!({"num_deleted_docs": num_deleted, "opstamp": opstamp})),
+                }))
+                .collect::<Vec<_>>(),
+            "schema": [],
+            "opstamp": opstamp,
+        }))
+        .unwrap()
+    }
+
+    fn segments(meta: &[u8]) -> Vec<(String, u64)> {
+        let meta: Value = serde_json::from_slice(meta).unwrap();
+        meta["segments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|segment| {
+                (
+                    segment["segment_id"].as_str().unwrap().to_string(),
+                    segment["deletes"]["num_deleted_docs"]
+                        .as_u64()
+                        .unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn test_merge_meta_publishes_concurrent_segments() {
+        // we added "c" while someone else added "b"
+        let base = meta(1, &[("a", 0)]);
+        let ours = meta(2, &[("a", 0), ("c", 0)]);
+        let stored = meta(3, &[("a", 0), ("b", 0)]);
+
+        let merged = merge_meta(
+            Some(base.as_slice()),
+            &ours,
+            &stored,
+            &RedirectChanges::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            segments(&merged),
+            vec![("a".into(), 0), ("c".into(), 0), ("b".into(), 0)]
+        );
+        let merged: Value = serde_json::from_slice(&merged).unwrap();
+        assert_eq!(merged["opstamp"], 3);
+    }
+
+    #[rstest]
+    fn test_merge_meta_respects_concurrent_merges() {
+        // we added "c" while someone else merged "a" and "b" into "m" and deleted from "d"
+        let base = meta(1, &[("a", 0), ("b", 0), ("d", 0)]);
+        let ours = meta(2, &[("a", 0), ("b", 0), ("d", 0), ("c", 0)]);
+        let stored = meta(2, &[("d", 3), ("m", 0)]);
+
+        let merged = merge_meta(
+            Some(base.as_slice()),
+            &ours,
+            &stored,
+            &RedirectChanges::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            segments(&merged),
+            vec![("d".into(), 3), ("c".into(), 0), ("m".into(), 0)]
+        );
+    }
+
+    #[rstest]
+    fn test_merge_meta_keeps_our_changes() {
+        // we merged "a" and "b" into "m" and deleted from "d" while someone else added "c"
+        let base = meta(1, &[("a", 0), ("b", 0), ("d", 0)]);
+        let ours = meta(2, &[("d", 3), ("m", 0)]);
+        let stored = meta(2, &[("a", 0), ("b", 0), ("d", 0), ("c", 0)]);
+
+        let merged = merge_meta(
+            Some(base.as_slice()),
+            &ours,
+            &stored,
+            &RedirectChanges::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            segments(&merged),
+            vec![("d".into(), 3), ("m".into(), 0), ("c".into(), 0)]
+        );
+    }
+
+    #[rstest]
+    fn test_merge_meta_merges_redirects() {
+        // we redirected 1 to 3 and removed the redirect from 1 to 2 while someone else
+        // redirected 4 to 5
+        let base = meta(1, &[("a", 0)]);
+        let ours = meta(2, &[("a", 0)]);
+        let mut stored: Value = serde_json::from_slice(&meta(2, &[("a", 0)])).unwrap();
+        let mut redirects = CtidRedirects::default();
+        redirects.insert(1, 2);
+        redirects.insert(4, 5);
+        stored["payload"] = redirects.to_payload().unwrap().into();
+        let stored = serde_json::to_vec(&stored).unwrap();
+
+        let mut changes = RedirectChanges::default();
+        changes.added.insert(1, 3);
+        changes.removed.insert(1, 2);
+
+        let merged = merge_meta(Some(base.as_slice()), &ours, &stored, &changes).unwrap();
+        let merged: Value = serde_json::from_slice(&merged).unwrap();
+        let merged = CtidRedirects::from_payload(merged["payload"].as_str()).unwrap();
+        assert_eq!(merged.iter().collect::<Vec<_>>(), vec![(1, 3), (4, 5)]);
+    }
+
+    #[rstest]
+    fn test_without_segments() {
+        let ids = (0..4)
+            .map(|_| SegmentId::generate_random())
+            .collect::<Vec<_>>();
+        let names = ids
+            .iter()
+            .map(|id| {
+                serde_json::to_value(id)
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+
+        let stored = meta(
+            2,
+            &[
+                (names[0].as_str(), 0),
+                (names[1].as_str(), 1),
+                (names[2].as_str(), 0),
+            ],
+        );
+        let discarded = without_segments(&stored, &HashSet::from([ids[1], ids[3]])).unwrap();
+        assert_eq!(
+            segments(&discarded),
+            vec![(names[0].clone(), 0), (names[2].clone(), 0)]
+        );
+    }
+
+    #[rstest]
+    fn test_merge_managed() {
+        let base = br#"["a.idx", "b.idx"]"#;
+        let ours = br#"["a.idx", "c.idx"]"#;
+        let stored = br#"["a.idx", "b.idx", "d.idx"]"#;
+        let deleted = HashSet::from([PathBuf::from("b.idx")]);
+
+        let merged = merge_managed(Some(&base[..]), ours, stored, &deleted).unwrap();
+        let merged: HashSet<PathBuf> = serde_json::from_slice(&merged).unwrap();
+        assert_eq!(
+            merged,
+            HashSet::from(["a.idx".into(), "c.idx".into(), "d.idx".into()])
+        );
+    }
+
+    #[rstest]
+    fn test_prepared_segments() {
+        assert!(prepared_segments(None).unwrap().is_empty());
+
+        let ids = HashSet::from([SegmentId::generate_random(), SegmentId::generate_random()]);
+        let prepared = PreparedSegments::from([(u64::MAX, ids.clone())]);
+        let stored = serde_json::to_vec(&prepared).unwrap();
+        assert_eq!(
+            prepared_segments(Some(&stored)).unwrap(),
+            PreparedSegments::from([(u64::MAX, ids)])
+        );
+    }
+}
@@ -19,65 +19,34 @@ use crate::env;
 use anyhow::Result;
 use derive_more::AsRef;
 use fs2::FileExt;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, File},
-    io::{self, Write},
+    io,
     path::{Path, PathBuf},
 };
 use thiserror::Error;
 use walkdir::WalkDir;
 
 static SEARCH_DIR_NAME: &str = "pg_search";
-static SEARCH_INDEX_CONFIG_FILE_NAME: &str = "search-index.json";
-static TANTIVY_DIR_NAME: &str = "tantivy";
 
 /// The top-level folder name for ParadeDB extension inside the Postgres data directory.
 #[derive(AsRef)]
 #[as_ref(forward)]
 pub struct SearchIndexDirPath(pub PathBuf);
-/// The name of the index-specific configuration file, enabling loading an index across connections.
-#[derive(AsRef)]
-#[as_ref(forward)]
-pub struct SearchIndexConfigFilePath(pub PathBuf);
-/// The name of the directory where the Tantivy index will be created.
-#[derive(AsRef)]
-#[as_ref(forward)]
-pub struct TantivyDirPath(pub PathBuf);
-/// The name of the directory where pipe files will be created for transfer to the writer process.
-#[derive(AsRef)]
-#[as_ref(forward)]
-pub struct WriterTransferPipeFilePath(pub PathBuf);
 
 pub trait SearchFs {
-    /// Load a persisted index from disk, so it can be reused between connections.
-    fn load_index<T: DeserializeOwned>(&self) -> Result<T, SearchDirectoryError>;
-    /// Save a serialize index to disk, so it can be persisted between connections.
-    fn save_index<T: Serialize>(&self, index: &T) -> Result<(), SearchDirectoryError>;
     // Remove the root directory from disk, blocking while file locks are released.
     fn remove(&self) -> Result<(), SearchDirectoryError>;
-    // Return and ensure the existence of the Tantivy index path.
-    fn tantivy_dir_path(&self, ensure_exists: bool)
-        -> Result<TantivyDirPath, SearchDirectoryError>;
-    /// Get the total size in bytes of the directory.                                                      
-    fn total_size(&self) -> Result<u64> {
-        let path = self.tantivy_dir_path(false)?;
-        let mut total_size = 0;
-
-        for entry in WalkDir::new(path).into_iter().flatten() {
-            if entry.path().is_file() {
-                if let Ok(metadata) = entry.metadata() {
-                    total_size += metadata.len();
-                }
-            }
-        }
-
-        Ok(total_size)
-    }
 }
 
-/// The file location for a pg_search index is:
+/// Identifies a pg_search index by its database, index, and relfilenode oids.
+///
+/// The index itself is stored in the pages of the index relation (see [`super::BlockDirectory`]).
+/// Older versions of pg_search stored it on the filesystem, at:
 /// $data_directory/pg_search/$database_oid/$index_oid/$relfilenode
+///
+/// ...which is still where we look for leftover files to remove when an index is dropped.
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub struct WriterDirectory {
     pub database_oid: u32,
@@ -128,6 +97,43 @@ impl WriterDirectory {
         }
     }
 
+    /// Every $relfilenode directory under `$data_directory/pg_search`, across all databases
+    /// and indexes.
+    pub fn all_relfile_paths() -> Result<Vec<Self>> {
+        let search_dir_path = Self::postgres_data_dir_path().join(SEARCH_DIR_NAME);
+        let mut relfile_paths = vec![];
+        for database_oid in Self::oid_subdirs(&search_dir_path)? {
+            for index_oid in Self::oid_subdirs(&search_dir_path.join(database_oid.to_string()))? {
+                relfile_paths.extend(Self::relfile_paths(database_oid, index_oid)?);
+            }
+        }
+        Ok(relfile_paths)
+    }
+
+    /// The total size, in bytes, of the files under this directory.
+    pub fn byte_size(&self) -> Result<u64, SearchDirectoryError> {
+        let SearchIndexDirPath(index_path) = self.search_index_dir_path(false)?;
+        Ok(Self::list_files(&index_path)
+            .iter()
+            .filter_map(|path| path.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum())
+    }
+
+    /// The names of the child directories of `path` that are oids.
+    fn oid_subdirs(path: &Path) -> Result<Vec<u32>> {
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        Ok(fs::read_dir(path)
+            .map_err(|err| SearchDirectoryError::ReadDirectoryEntry(path.to_path_buf(), err))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().and_then(|s| s.parse().ok()))
+            .collect())
+    }
+
     /// Construct the directory path up to the $index_oid component.
     /// The returned path is relative to the postgres_data_dir_path.
     /// There may be multiple $relfilenode children of the $index_oid folder
@@ -165,16 +171,6 @@ impl WriterDirectory {
         Ok(SearchIndexDirPath(search_index_dir_path.to_path_buf()))
     }
 
-    fn search_index_config_file_path(
-        &self,
-        ensure_exists: bool,
-    ) -> Result<SearchIndexConfigFilePath, SearchDirectoryError> {
-        let SearchIndexDirPath(index_path) = self.search_index_dir_path(ensure_exists)?;
-        let search_index_config_file_path = index_path.join(SEARCH_INDEX_CONFIG_FILE_NAME);
-
-        Ok(SearchIndexConfigFilePath(search_index_config_file_path))
-    }
-
     fn ensure_dir(path: &Path) -> Result<(), SearchDirectoryError> {
         if !path.exists() {
             Self::create_dir_all(path)?
@@ -265,38 +261,6 @@ impl WriterDirectory {
 }
 
 impl SearchFs for WriterDirectory {
-    fn load_index<T: DeserializeOwned>(&self) -> Result<T, SearchDirectoryError> {
-        let SearchIndexConfigFilePath(config_path) = self.search_index_config_file_path(true)?;
-
-        let serialized_data = fs::read_to_string(config_path.clone())
-            .map_err(|err| SearchDirectoryError::IndexFileRead(self.clone(), config_path, err))?;
-
-        let new_self = serde_json::from_str(&serialized_data)
-            .map_err(|err| SearchDirectoryError::IndexDeserialize(self.clone(), err))?;
-        Ok(new_self)
-    }
-
-    fn save_index<T: Serialize>(&self, index: &T) -> Result<(), SearchDirectoryError> {
-        let SearchIndexConfigFilePath(config_path) = self.search_index_config_file_path(true)?;
-
-        let serialized_data = serde_json::to_string(index)
-            .map_err(|err| SearchDirectoryError::IndexSerialize(self.clone(), err))?;
-
-        let mut file = File::create(config_path)
-            .map_err(|err| SearchDirectoryError::IndexFileCreate(self.clone(), err))?;
-
-        file.write_all(serialized_data.as_bytes())
-            .map_err(|err| SearchDirectoryError::IndexFileWrite(self.clone(), err))?;
-
-        // Rust automatically flushes data to disk at the end of the scope,
-        // so this call to "flush()" isn't strictly necessary.
-        // We're doing it explicitly as a reminder in case we extend this method.
-        file.flush()
-            .map_err(|err| SearchDirectoryError::IndexFileFlush(self.clone(), err))?;
-
-        Ok(())
-    }
-
     fn remove(&self) -> Result<(), SearchDirectoryError> {
         let SearchIndexDirPath(index_path) = self.search_index_dir_path(false)?;
         if index_path.exists() {
@@ -304,17 +268,6 @@ impl SearchFs for WriterDirectory {
         }
         Ok(())
     }
-
-    fn tantivy_dir_path(
-        &self,
-        ensure_exists: bool,
-    ) -> Result<TantivyDirPath, SearchDirectoryError> {
-        let SearchIndexDirPath(index_path) = self.search_index_dir_path(ensure_exists)?;
-        let tantivy_dir_path = index_path.join(TANTIVY_DIR_NAME);
-
-        Self::ensure_dir(&tantivy_dir_path)?;
-        Ok(TantivyDirPath(tantivy_dir_path))
-    }
 }
 
 #[derive(Debug, Error)]
@@ -322,24 +275,6 @@ pub enum SearchDirectoryError {
     #[error("could not read directory entry {0:?}: {1}")]
     ReadDirectoryEntry(PathBuf, #[source] std::io::Error),
 
-    #[error("could not deserialize index at '{0:?}, {1}")]
-    IndexDeserialize(WriterDirectory, #[source] serde_json::Error),
-
-    #[error("could not read from file to load index {0:?} from {1} at {2}")]
-    IndexFileRead(WriterDirectory, PathBuf, #[source] std::io::Error),
-
-    #[error("could not serialize index '{0:?}': {1}")]
-    IndexSerialize(WriterDirectory, #[source] serde_json::Error),
-
-    #[error("could not create file to save index {0:?} at {1}")]
-    IndexFileCreate(WriterDirectory, #[source] std::io::Error),
-
-    #[error("could not write to file to save index {0:?} at {1}")]
-    IndexFileWrite(WriterDirectory, #[source] std::io::Error),
-
-    #[error("could not flush file to disk to save index {0:?} at {1}")]
-    IndexFileFlush(WriterDirectory, #[source] std::io::Error),
-
     #[error("could not create directory at {0:?}: {1}")]
     CreateDirectory(PathBuf, #[source] std::io::Error),
 
@@ -393,11 +328,11 @@ mod tests {
     fn test_remove_directory(mock_dir: MockWriterDirectory) -> Result<()> {
         let SearchIndexDirPath(root) = mock_dir.writer_dir.search_index_dir_path(true)?;
 
-        let tantivy_path = root.join(TANTIVY_DIR_NAME);
+        let tantivy_path = root.join("tantivy");
 
         std::fs::create_dir_all(&tantivy_path)?;
         File::create(tantivy_path.join("meta.json"))?;
-        File::create(root.join(SEARCH_INDEX_CONFIG_FILE_NAME))?;
+        File::create(root.join("search-index.json"))?;
 
         mock_dir.writer_dir.remove()?;
 
@@ -406,4 +341,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    fn test_directory_byte_size(mock_dir: MockWriterDirectory) -> Result<()> {
+        let SearchIndexDirPath(root) = mock_dir.writer_dir.search_index_dir_path(true)?;
+
+        let tantivy_path = root.join("tantivy");
+
+        std::fs::create_dir_all(&tantivy_path)?;
+        std::fs::write(tantivy_path.join("meta.json"), [0u8; 100])?;
+        std::fs::write(root.join("search-index.json"), [0u8; 28])?;
+
+        assert_eq!(mock_dir.writer_dir.byte_size()?, 128);
+
+        mock_dir.writer_dir.remove()?;
+        assert_eq!(mock_dir.writer_dir.byte_size()?, 0);
+
+        Ok(())
+    }
 }
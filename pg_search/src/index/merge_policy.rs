@@ -1,5 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use tantivy::indexer::{MergeCandidate, MergePolicy};
-use tantivy::SegmentMeta;
+use tantivy::{SegmentId, SegmentMeta};
+
+/// The size, in bytes, of each segment in an index, as stored in the index relation.
+pub type SegmentSizes = HashMap<SegmentId, u64>;
+
+/// A segment with at least this fraction of its documents deleted is rewritten on its own by the
+/// background merge workers, unless the index's merge policy says otherwise.
+const DEFAULT_DELETED_RATIO: f64 = 0.2;
+
+/// Which [`MergePolicy`] an index uses, along with its parameters, as set by the `merge_policy`
+/// index option.  For example:
+///
+/// {"type": "tiered", "segments_per_tier": 10, "max_segment_mb": 5120}
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MergePolicyConfig {
+    /// [`NPlusOneMergePolicy`], with N taken from the `target_segment_count` index option
+    #[default]
+    NPlusOne,
+    /// [`TieredMergePolicy`]
+    Tiered {
+        #[serde(default = "default_segments_per_tier")]
+        segments_per_tier: usize,
+        #[serde(default = "default_floor_segment_mb")]
+        floor_segment_mb: u64,
+        #[serde(default = "default_max_segment_mb")]
+        max_segment_mb: u64,
+    },
+    /// [`DeleteRatioMergePolicy`], with N taken from the `target_segment_count` index option
+    DeleteRatio {
+        #[serde(default = "default_deleted_ratio")]
+        deleted_ratio: f64,
+    },
+}
+
+fn default_segments_per_tier() -> usize {
+    10
+}
+
+fn default_floor_segment_mb() -> u64 {
+    2
+}
+
+fn default_max_segment_mb() -> u64 {
+    5 * 1024
+}
+
+fn default_deleted_ratio() -> f64 {
+    DEFAULT_DELETED_RATIO
+}
+
+impl MergePolicyConfig {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let config: Self = json5::from_str(json).map_err(|e| e.to_string())?;
+        match config {
+            Self::Tiered {
+                segments_per_tier, ..
+            } if segments_per_tier < 2 => Err("segments_per_tier must be at least 2".into()),
+            Self::Tiered {
+                floor_segment_mb,
+                max_segment_mb,
+                ..
+            } if floor_segment_mb == 0 || max_segment_mb < floor_segment_mb => {
+                Err("floor_segment_mb must be positive and no larger than max_segment_mb".into())
+            }
+            Self::DeleteRatio { deleted_ratio }
+                if !(deleted_ratio > 0.0 && deleted_ratio <= 1.0) =>
+            {
+                Err("deleted_ratio must be greater than 0 and at most 1".into())
+            }
+            config => Ok(config),
+        }
+    }
+
+    /// Only [`TieredMergePolicy`] needs to know how big the segments are.
+    pub fn needs_segment_sizes(&self) -> bool {
+        matches!(self, Self::Tiered { .. })
+    }
+
+    /// The fraction of deleted documents at which a segment should be rewritten.
+    pub fn deleted_ratio(&self) -> f64 {
+        match self {
+            Self::DeleteRatio { deleted_ratio } => *deleted_ratio,
+            _ => DEFAULT_DELETED_RATIO,
+        }
+    }
+
+    pub fn build(&self, target_segment_count: usize, sizes: SegmentSizes) -> Box<dyn MergePolicy> {
+        match self {
+            Self::NPlusOne => Box::new(NPlusOneMergePolicy(target_segment_count)),
+            Self::Tiered {
+                segments_per_tier,
+                floor_segment_mb,
+                max_segment_mb,
+            } => Box::new(TieredMergePolicy::new(
+                *segments_per_tier,
+                floor_segment_mb * 1024 * 1024,
+                max_segment_mb * 1024 * 1024,
+                sizes,
+            )),
+            Self::DeleteRatio { deleted_ratio } => Box::new(DeleteRatioMergePolicy {
+                n_plus_one: NPlusOneMergePolicy(target_segment_count),
+                deleted_ratio: *deleted_ratio,
+            }),
+        }
+    }
+}
 
 /// A tantivy [`MergePolicy`] that endeavours to keep a maximum number of segments "N", plus
 /// one extra for leftovers.
@@ -32,3 +140,213 @@ impl MergePolicy for NPlusOneMergePolicy {
         vec![candidate]
     }
 }
+
+/// A tantivy [`MergePolicy`] that groups segments into tiers by their size in bytes, where each
+/// tier holds segments up to `segments_per_tier` times larger than the one below it, and merges
+/// `segments_per_tier` segments of the same tier together.
+///
+/// Segments smaller than `floor_bytes` are treated as being that size, so that lots of tiny
+/// segments get merged quickly, and segments that are already at least half of `max_bytes` are
+/// never merged again.  This suits append-heavy indexes, where it keeps the amount of data that's
+/// rewritten by merging logarithmic in the size of the index.
+#[derive(Debug)]
+pub struct TieredMergePolicy {
+    segments_per_tier: usize,
+    floor_bytes: u64,
+    max_bytes: u64,
+    sizes: SegmentSizes,
+}
+
+impl TieredMergePolicy {
+    pub fn new(
+        segments_per_tier: usize,
+        floor_bytes: u64,
+        max_bytes: u64,
+        sizes: SegmentSizes,
+    ) -> Self {
+        Self {
+            segments_per_tier,
+            floor_bytes,
+            max_bytes,
+            sizes,
+        }
+    }
+
+    /// The size of the segment's live documents.  Segments that were created after `sizes` was
+    /// computed are estimated from their number of documents.
+    fn size_of(&self, meta: &SegmentMeta, bytes_per_doc: f64) -> u64 {
+        if meta.max_doc() == 0 {
+            return 0;
+        }
+        let bytes = match self.sizes.get(&meta.id()) {
+            Some(bytes) => *bytes as f64,
+            None => meta.max_doc() as f64 * bytes_per_doc,
+        };
+        (bytes * meta.num_docs() as f64 / meta.max_doc() as f64) as u64
+    }
+
+    fn tier_of(&self, bytes: u64) -> u32 {
+        let relative = bytes.max(self.floor_bytes) as f64 / self.floor_bytes as f64;
+        relative.log(self.segments_per_tier as f64).floor() as u32
+    }
+}
+
+impl MergePolicy for TieredMergePolicy {
+    fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
+        let (known_bytes, known_docs) = segments
+            .iter()
+            .filter_map(|meta| Some((*self.sizes.get(&meta.id())?, meta.max_doc() as u64)))
+            .fold((0, 0), |(bytes, docs), (b, d)| (bytes + b, docs + d));
+        let bytes_per_doc = if known_docs > 0 {
+            known_bytes as f64 / known_docs as f64
+        } else {
+            0.0
+        };
+
+        // segments by tier, each sorted smallest first
+        let mut tiers: BTreeMap<u32, Vec<(u64, SegmentId)>> = BTreeMap::new();
+        for meta in segments {
+            let bytes = self.size_of(meta, bytes_per_doc);
+            if bytes >= self.max_bytes / 2 {
+                continue;
+            }
+            tiers
+                .entry(self.tier_of(bytes))
+                .or_default()
+                .push((bytes, meta.id()));
+        }
+
+        let mut candidates = vec![];
+        for mut tier in tiers.into_values() {
+            tier.sort_unstable_by_key(|(bytes, _)| *bytes);
+            for group in tier.chunks_exact(self.segments_per_tier) {
+                let merged_bytes = group.iter().map(|(bytes, _)| bytes).sum::<u64>();
+                if merged_bytes > self.max_bytes {
+                    break;
+                }
+                candidates.push(MergeCandidate(group.iter().map(|(_, id)| *id).collect()));
+            }
+        }
+        candidates
+    }
+}
+
+/// A tantivy [`MergePolicy`] that works like [`NPlusOneMergePolicy`], but also merges together
+/// every segment where at least `deleted_ratio` of the documents have been deleted.  This suits
+/// update-heavy indexes, where segments otherwise fill up with deleted documents that searches
+/// have to skip over.
+#[derive(Debug)]
+pub struct DeleteRatioMergePolicy {
+    pub n_plus_one: NPlusOneMergePolicy,
+    pub deleted_ratio: f64,
+}
+
+impl MergePolicy for DeleteRatioMergePolicy {
+    fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
+        let mut candidates = self.n_plus_one.compute_merge_candidates(segments);
+
+        let deleted = segments
+            .iter()
+            .filter(|meta| !candidates.iter().any(|c| c.0.contains(&meta.id())))
+            .filter(|meta| {
+                meta.max_doc() > 0
+                    && meta.num_deleted_docs() as f64 / meta.max_doc() as f64 >= self.deleted_ratio
+            })
+            .map(|meta| meta.id())
+            .collect::<Vec<_>>();
+        if !deleted.is_empty() {
+            candidates.push(MergeCandidate(deleted));
+        }
+
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use tantivy::schema::Schema;
+    use tantivy::Index;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn segment(index: &Index, max_doc: u32, num_deleted: u32) -> SegmentMeta {
+        index
+            .new_segment_meta(SegmentId::generate_random(), max_doc)
+            .with_delete_meta(num_deleted, 0)
+    }
+
+    #[rstest]
+    fn test_merge_policy_config() {
+        assert_eq!(
+            MergePolicyConfig::from_json(r#"{"type": "tiered", "segments_per_tier": 4}"#),
+            Ok(MergePolicyConfig::Tiered {
+                segments_per_tier: 4,
+                floor_segment_mb: 2,
+                max_segment_mb: 5 * 1024
+            })
+        );
+        assert_eq!(
+            MergePolicyConfig::from_json(r#"{"type": "delete_ratio"}"#),
+            Ok(MergePolicyConfig::DeleteRatio {
+                deleted_ratio: DEFAULT_DELETED_RATIO
+            })
+        );
+        assert!(
+            MergePolicyConfig::from_json(r#"{"type": "tiered", "segments_per_tier": 1}"#).is_err()
+        );
+        assert!(
+            MergePolicyConfig::from_json(r#"{"type": "delete_ratio", "deleted_ratio": 2}"#)
+                .is_err()
+        );
+        assert!(MergePolicyConfig::from_json(r#"{"type": "n_plus_one", "n": 2}"#).is_err());
+        assert!(MergePolicyConfig::from_json(r#"{"type": "lucene"}"#).is_err());
+    }
+
+    #[rstest]
+    fn test_tiered_merge_policy() {
+        let index = Index::create_in_ram(Schema::builder().build());
+        let small = (0..5).map(|_| segment(&index, 100, 0)).collect::<Vec<_>>();
+        let large = segment(&index, 100_000, 0);
+
+        let mut sizes = small
+            .iter()
+            .map(|meta| (meta.id(), MB))
+            .collect::<SegmentSizes>();
+        sizes.insert(large.id(), 1000 * MB);
+
+        let mut segments = small.clone();
+        segments.push(large.clone());
+
+        // the five small segments share the bottom tier, and only full tiers are merged
+        let policy = TieredMergePolicy::new(4, 2 * MB, 5000 * MB, sizes.clone());
+        let candidates = policy.compute_merge_candidates(&segments);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].0.len(), 4);
+        assert!(!candidates[0].0.contains(&large.id()));
+
+        // segments at least half the max size are left alone
+        let policy = TieredMergePolicy::new(2, 2 * MB, 1500 * MB, sizes);
+        let candidates = policy.compute_merge_candidates(&[large.clone(), large]);
+        assert!(candidates.is_empty());
+    }
+
+    #[rstest]
+    fn test_delete_ratio_merge_policy() {
+        let index = Index::create_in_ram(Schema::builder().build());
+        let segments = vec![
+            segment(&index, 100, 50),
+            segment(&index, 100, 10),
+            segment(&index, 100, 30),
+        ];
+
+        let policy = DeleteRatioMergePolicy {
+            n_plus_one: NPlusOneMergePolicy(8),
+            deleted_ratio: 0.25,
+        };
+        let candidates = policy.compute_merge_candidates(&segments);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].0, vec![segments[0].id(), segments[2].id()]);
+    }
+}
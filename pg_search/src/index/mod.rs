@@ -15,13 +15,16 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+pub mod block_directory;
 pub mod directory;
 pub mod fast_fields_helper;
-mod merge_policy;
+pub mod merge_policy;
 pub mod reader;
+pub mod redirects;
 pub mod search;
 pub mod writer;
 
+pub use block_directory::*;
 pub use directory::*;
 pub use search::*;
 pub use writer::*;
@@ -16,24 +16,32 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use super::SearchIndex;
+use crate::index::block_directory::BlockDirectory;
+use crate::index::redirects::CtidRedirects;
 use crate::query::SearchQueryInput;
 use crate::schema::{SearchFieldName, SearchIndexSchema};
 use anyhow::Result;
-use pgrx::PgRelation;
+use parking_lot::Mutex;
+use pgrx::{pg_sys, PgRelation};
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
-use tantivy::collector::{Collector, TopDocs};
+use std::sync::Arc;
+use tantivy::aggregation::agg_req::Aggregations;
+use tantivy::aggregation::agg_result::AggregationResults;
+use tantivy::aggregation::AggregationCollector;
+use tantivy::collector::{Collector, Count, SegmentCollector, TopDocs};
 use tantivy::fastfield::Column;
-use tantivy::query::QueryParser;
-use tantivy::schema::{FieldType, Value};
+use tantivy::query::{Bm25StatisticsProvider, QueryParser, Weight};
+use tantivy::schema::{Field, FieldType, Schema, Value};
 use tantivy::{
-    query::Query, DocAddress, DocId, Order, Score, Searcher, SegmentOrdinal, TantivyDocument,
-    TantivyError,
+    query::Query, DocAddress, DocId, Score, Searcher, SegmentOrdinal, SegmentReader,
+    TantivyDocument, TantivyError, Term,
 };
 use tantivy::{snippet::SnippetGenerator, Executor};
 use tracing::debug;
 
-const CACHE_NUM_BLOCKS: usize = 10;
+pub const CACHE_NUM_BLOCKS: usize = 10;
 
 /// Represents a matching document from a tantivy search.  Typically it is returned as an Iterator
 /// Item alongside the originating tantivy [`DocAddress`]
@@ -67,6 +75,16 @@ pub enum SortDirection {
     Desc,
 }
 
+/// One of the keys a "top N" search is ordered by
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByInfo {
+    /// The fast field to sort by, or [`None`] to sort by score
+    pub field: Option<String>,
+    pub direction: SortDirection,
+    /// Do documents without a value for `field` sort before the ones that have one?
+    pub nulls_first: bool,
+}
+
 /// An iterator of the different styles of search results we can return
 #[derive(Default)]
 pub enum SearchResults {
@@ -77,8 +95,7 @@ pub enum SearchResults {
 
     TopNByField(usize, std::vec::IntoIter<(SearchIndexScore, DocAddress)>),
 
-    #[allow(clippy::type_complexity)]
-    Channel(std::iter::Flatten<crossbeam::channel::IntoIter<Vec<(SearchIndexScore, DocAddress)>>>),
+    AllSegments(SegmentSearch),
 
     SingleSegment(usize, std::vec::IntoIter<(SearchIndexScore, DocAddress)>),
 }
@@ -109,8 +126,8 @@ impl Debug for SearchResults {
             SearchResults::TopNByField(count, iter) => {
                 write!(f, "SearchResults::TopNByField({count}, {:?})", iter.len())
             }
-            SearchResults::Channel(iter) => {
-                write!(f, "SearchResults::FastPath(~{:?})", iter.size_hint())
+            SearchResults::AllSegments(iter) => {
+                write!(f, "SearchResults::AllSegments(~{:?})", iter.size_hint())
             }
             SearchResults::SingleSegment(count, iter) => {
                 write!(f, "SearchResults::SingleSegment({count}, {:?})", iter.len())
@@ -130,7 +147,7 @@ impl Iterator for SearchResults {
                 .next()
                 .map(|(OrderedScore { score, .. }, doc_address)| (score, doc_address)),
             SearchResults::TopNByField(_, iter) => iter.next(),
-            SearchResults::Channel(iter) => iter.next(),
+            SearchResults::AllSegments(iter) => iter.next(),
             SearchResults::SingleSegment(_, iter) => iter.next(),
         }
     }
@@ -141,7 +158,7 @@ impl Iterator for SearchResults {
             SearchResults::None => (0, Some(0)),
             SearchResults::TopNByScore(_, iter) => iter.size_hint(),
             SearchResults::TopNByField(_, iter) => iter.size_hint(),
-            SearchResults::Channel(iter) => iter.size_hint(),
+            SearchResults::AllSegments(iter) => iter.size_hint(),
             SearchResults::SingleSegment(_, iter) => iter.size_hint(),
         }
     }
@@ -155,19 +172,62 @@ impl Iterator for SearchResults {
             SearchResults::None => 0,
             SearchResults::TopNByScore(count, _) => count,
             SearchResults::TopNByField(count, _) => count,
-            SearchResults::Channel(iter) => iter.count(),
+            SearchResults::AllSegments(iter) => iter.count(),
             SearchResults::SingleSegment(count, _) => count,
         }
     }
 }
 
+/// Searches every segment of an index, one after another and on the calling thread, as the
+/// results are consumed.  A scan that stops early never searches the remaining segments.
+pub struct SegmentSearch {
+    searcher: Searcher,
+    weight: Box<dyn Weight>,
+    need_scores: bool,
+    sort_by_ctid: bool,
+    next_segment: usize,
+    current: std::vec::IntoIter<(SearchIndexScore, DocAddress)>,
+}
+
+impl Iterator for SegmentSearch {
+    type Item = (SearchIndexScore, DocAddress);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.current.next() {
+                return Some(next);
+            }
+
+            let segment_ord = self.next_segment;
+            let segment_reader = self.searcher.segment_readers().get(segment_ord)?;
+            self.next_segment += 1;
+
+            let mut results = vec_collector::VecCollector::new(self.need_scores)
+                .collect_segment(
+                    self.weight.as_ref(),
+                    segment_ord as SegmentOrdinal,
+                    segment_reader,
+                )
+                .expect("failed to search");
+            if self.sort_by_ctid {
+                results.sort_unstable_by_key(|(scored, _)| scored.ctid);
+            }
+            self.current = results.into_iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.current.len(), None)
+    }
+}
+
 impl SearchResults {
     pub fn len(&self) -> Option<usize> {
         match self {
             SearchResults::None => Some(0),
             SearchResults::TopNByScore(count, _) => Some(*count),
             SearchResults::TopNByField(count, _) => Some(*count),
-            SearchResults::Channel(_) => None,
+            SearchResults::AllSegments(_) => None,
             SearchResults::SingleSegment(count, _) => Some(*count),
         }
     }
@@ -178,6 +238,90 @@ pub struct SearchIndexReader {
     pub searcher: Searcher,
     pub schema: SearchIndexSchema,
     pub underlying_reader: tantivy::IndexReader,
+    /// The ctid redirects that belong to the segments being read
+    pub redirects: Arc<CtidRedirects>,
+    /// Where the index's files are read from
+    directory: BlockDirectory,
+    /// The statistics documents are scored with, when they aren't just those of this index
+    statistics: Option<Arc<PartitionStatistics>>,
+}
+
+/// BM25 statistics summed over the indexes of every partition of a partitioned table, so that
+/// the scores of documents found in different partitions can be compared with each other.
+///
+/// Only the segments of each index are opened, and a statistic is read from them the first time
+/// it's asked for, after which it's remembered.  Every partition scanned by the same query can
+/// then share one instance.  See [`crate::postgres::index::partition_statistics`].
+pub struct PartitionStatistics {
+    schema: Schema,
+    segments: Vec<SegmentReader>,
+    total_num_docs: u64,
+    total_num_tokens: Mutex<HashMap<Field, u64>>,
+    doc_freqs: Mutex<HashMap<Term, u64>>,
+}
+
+impl PartitionStatistics {
+    /// Open the segments of the `partitions`' indexes.  Every partition's index is built from the
+    /// same definition, but one whose schema doesn't match `schema`'s, because it was created by an
+    /// older version of pg_search, is left out.
+    pub fn open(
+        schema: &SearchIndexSchema,
+        partitions: impl IntoIterator<Item = SearchIndex>,
+    ) -> Result<Self> {
+        let mut segments = vec![];
+        for partition in partitions {
+            if partition.schema.schema != schema.schema {
+                continue;
+            }
+            for segment in partition.underlying_index.searchable_segments()? {
+                segments.push(SegmentReader::open(&segment)?);
+            }
+        }
+        let total_num_docs = segments
+            .iter()
+            .map(|segment| u64::from(segment.max_doc()))
+            .sum();
+
+        Ok(PartitionStatistics {
+            schema: schema.schema.clone(),
+            segments,
+            total_num_docs,
+            total_num_tokens: Default::default(),
+            doc_freqs: Default::default(),
+        })
+    }
+}
+
+impl Bm25StatisticsProvider for PartitionStatistics {
+    fn total_num_tokens(&self, field: Field) -> tantivy::Result<u64> {
+        if let Some(total_num_tokens) = self.total_num_tokens.lock().get(&field) {
+            return Ok(*total_num_tokens);
+        }
+
+        let mut total_num_tokens = 0;
+        for segment in &self.segments {
+            total_num_tokens += segment.inverted_index(field)?.total_num_tokens();
+        }
+        self.total_num_tokens.lock().insert(field, total_num_tokens);
+        Ok(total_num_tokens)
+    }
+
+    fn total_num_docs(&self) -> tantivy::Result<u64> {
+        Ok(self.total_num_docs)
+    }
+
+    fn doc_freq(&self, term: &Term) -> tantivy::Result<u64> {
+        if let Some(doc_freq) = self.doc_freqs.lock().get(term) {
+            return Ok(*doc_freq);
+        }
+
+        let mut doc_freq = 0;
+        for segment in &self.segments {
+            doc_freq += u64::from(segment.inverted_index(term.field())?.doc_freq(term)?);
+        }
+        self.doc_freqs.lock().insert(term.clone(), doc_freq);
+        Ok(doc_freq)
+    }
 }
 
 impl SearchIndexReader {
@@ -189,13 +333,39 @@ impl SearchIndexReader {
             .reload_policy(tantivy::ReloadPolicy::Manual)
             .try_into()?;
         let searcher = reader.searcher();
+        // the reader has just loaded meta.json, and loading it again is served from memory
+        let redirects = search_index.redirects()?;
         Ok(SearchIndexReader {
             searcher,
             schema: schema.clone(),
             underlying_reader: reader,
+            redirects: Arc::new(redirects),
+            directory: search_index.block_directory.clone(),
+            statistics: None,
         })
     }
 
+    /// Score documents with the `statistics` of every partition of the table, which include this
+    /// index's own.  They're ignored if they weren't opened for an index with our schema.
+    pub fn use_partition_statistics(&mut self, statistics: Arc<PartitionStatistics>) {
+        if statistics.schema == self.schema.schema {
+            self.statistics = Some(statistics);
+        }
+    }
+
+    /// The block that the catalog of index files this reader sees starts at.  Another participant
+    /// of a parallel scan can open the index as of the same catalog to see the same segments.
+    pub fn catalog_start(&self) -> Result<pg_sys::BlockNumber> {
+        Ok(self.directory.catalog_start()?)
+    }
+
+    fn statistics_provider(&self) -> &dyn Bm25StatisticsProvider {
+        match &self.statistics {
+            Some(statistics) => statistics.as_ref(),
+            None => &self.searcher,
+        }
+    }
+
     pub fn get_doc(&self, doc_address: DocAddress) -> tantivy::Result<TantivyDocument> {
         self.searcher.doc(doc_address)
     }
@@ -264,49 +434,42 @@ impl SearchIndexReader {
         }
     }
 
-    /// Search the Tantivy index for matching documents, in the background, streaming the matching
-    /// documents back as they're found.
+    /// Search the Tantivy index for matching documents, one segment at a time, as the results
+    /// are consumed.
     ///
-    /// The order of returned docs is unspecified.
+    /// The order of returned docs is unspecified, other than that, with `sort_segments_by_ctid`,
+    /// each segment's documents are returned in ctid order.
     ///
     /// It has no understanding of Postgres MVCC visibility.  It is the caller's responsibility to
     /// handle that, if it's necessary.
-    pub fn search_via_channel(
+    pub fn search(
         &self,
         need_scores: bool,
         sort_segments_by_ctid: bool,
-        executor: &'static Executor,
         query: &dyn Query,
     ) -> SearchResults {
-        let (sender, receiver) = crossbeam::channel::unbounded();
-        let collector =
-            collector::ChannelCollector::new(need_scores, sort_segments_by_ctid, sender);
-        let searcher = self.searcher.clone();
-        let schema = self.schema.schema.clone();
-
-        let owned_query = query.box_clone();
-        std::thread::spawn(move || {
-            searcher
-                .search_with_executor(
-                    &owned_query,
-                    &collector,
-                    executor,
-                    if need_scores {
-                        tantivy::query::EnableScoring::Enabled {
-                            searcher: &searcher,
-                            statistics_provider: &searcher,
-                        }
-                    } else {
-                        tantivy::query::EnableScoring::Disabled {
-                            schema: &schema,
-                            searcher_opt: Some(&searcher),
-                        }
-                    },
-                )
-                .expect("failed to search")
-        });
+        let weight = query
+            .weight(if need_scores {
+                tantivy::query::EnableScoring::Enabled {
+                    searcher: &self.searcher,
+                    statistics_provider: self.statistics_provider(),
+                }
+            } else {
+                tantivy::query::EnableScoring::Disabled {
+                    schema: &self.schema.schema,
+                    searcher_opt: Some(&self.searcher),
+                }
+            })
+            .expect("weight should be constructable");
 
-        SearchResults::Channel(receiver.into_iter().flatten())
+        SearchResults::AllSegments(SegmentSearch {
+            searcher: self.searcher.clone(),
+            weight,
+            need_scores,
+            sort_by_ctid: sort_segments_by_ctid,
+            next_segment: 0,
+            current: Vec::new().into_iter(),
+        })
     }
 
     /// Search a specific index segment for matching documents.
@@ -326,7 +489,7 @@ impl SearchIndexReader {
             .weight(if need_scores {
                 tantivy::query::EnableScoring::Enabled {
                     searcher: &self.searcher,
-                    statistics_provider: &self.searcher,
+                    statistics_provider: self.statistics_provider(),
                 }
             } else {
                 tantivy::query::EnableScoring::Disabled {
@@ -342,10 +505,90 @@ impl SearchIndexReader {
         SearchResults::SingleSegment(results.len(), results.into_iter())
     }
 
+    /// Run the `aggregations` over the documents matching `query`.
+    ///
+    /// Only the documents whose ctid passes `is_visible` are aggregated, which is how the caller
+    /// applies Postgres MVCC visibility rules.  As that check needs to talk to Postgres, every
+    /// segment is collected here, on the calling thread.
+    pub fn aggregate(
+        &self,
+        query: &dyn Query,
+        aggregations: Aggregations,
+        mut is_visible: impl FnMut(u64) -> bool,
+    ) -> tantivy::Result<AggregationResults> {
+        let collector = AggregationCollector::from_aggs(aggregations, Default::default());
+        let weight = query.weight(tantivy::query::EnableScoring::Disabled {
+            schema: &self.schema.schema,
+            searcher_opt: Some(&self.searcher),
+        })?;
+
+        let mut fruits = Vec::with_capacity(self.searcher.segment_readers().len());
+        for (segment_ord, segment_reader) in self.searcher.segment_readers().iter().enumerate() {
+            let segment_ord = segment_ord as SegmentOrdinal;
+            let mut matches = vec_collector::VecCollector::new(false).collect_segment(
+                weight.as_ref(),
+                segment_ord,
+                segment_reader,
+            )?;
+            matches.sort_unstable_by_key(|(_, doc_address)| doc_address.doc_id);
+
+            let mut segment_collector = collector.for_segment(segment_ord, segment_reader)?;
+            for (scored, doc_address) in matches {
+                if is_visible(scored.ctid) {
+                    segment_collector.collect(doc_address.doc_id, scored.bm25);
+                }
+            }
+            fruits.push(segment_collector.harvest());
+        }
+
+        collector.merge_fruits(fruits)
+    }
+
+    /// Count the documents matching `query` with tantivy's [`Count`] collector.
+    ///
+    /// It has no understanding of Postgres MVCC visibility.  Use [`SearchIndexReader::count_visible`]
+    /// if that's necessary.
+    pub fn count(&self, query: &dyn Query) -> tantivy::Result<usize> {
+        self.searcher.search(query, &Count)
+    }
+
+    /// Count the documents matching `query` whose ctid passes `is_visible`.
+    ///
+    /// As that check needs to talk to Postgres, every segment is collected here, on the calling
+    /// thread.
+    pub fn count_visible(
+        &self,
+        query: &dyn Query,
+        mut is_visible: impl FnMut(u64) -> bool,
+    ) -> tantivy::Result<usize> {
+        let weight = query.weight(tantivy::query::EnableScoring::Disabled {
+            schema: &self.schema.schema,
+            searcher_opt: Some(&self.searcher),
+        })?;
+
+        let mut count = 0;
+        for (segment_ord, segment_reader) in self.searcher.segment_readers().iter().enumerate() {
+            let mut matches = vec_collector::VecCollector::new(false).collect_segment(
+                weight.as_ref(),
+                segment_ord as SegmentOrdinal,
+                segment_reader,
+            )?;
+
+            // visit the heap in block order so that each page is only read once
+            matches.sort_unstable_by_key(|(scored, _)| scored.ctid);
+            count += matches
+                .into_iter()
+                .filter(|(scored, _)| is_visible(scored.ctid))
+                .count();
+        }
+
+        Ok(count)
+    }
+
     /// Search the Tantivy index for the "top N" matching documents.
     ///
-    /// The documents are returned in score order.  Most relevant first if `sortdir` is [`SortDirection::Desc`],
-    /// or least relevant first if it's [`SortDirection::Asc`].
+    /// The documents are returned in the order described by `order_by`, which is a list of fast
+    /// fields and/or the score, each with its own direction.
     ///
     /// It has no understanding of Postgres MVCC visibility.  It is the caller's responsibility to
     /// handle that, if it's necessary.
@@ -353,69 +596,50 @@ impl SearchIndexReader {
         &self,
         executor: &'static Executor,
         query: &dyn Query,
-        sort_field: Option<String>,
-        sortdir: SortDirection,
+        order_by: &[OrderByInfo],
         n: usize,
     ) -> SearchResults {
-        if let Some(sort_field) = sort_field {
-            self.top_by_field(executor, query, sort_field, sortdir, n)
-        } else {
-            self.top_by_score(executor, query, sortdir, n)
+        match order_by {
+            [OrderByInfo {
+                field: None,
+                direction,
+                ..
+            }] => self.top_by_score(executor, query, *direction, n),
+            _ => self.top_by_fields(executor, query, order_by, n),
         }
     }
 
-    fn top_by_field(
+    fn top_by_fields(
         &self,
         executor: &Executor,
         query: &dyn Query,
-        sort_field: String,
-        sortdir: SortDirection,
+        order_by: &[OrderByInfo],
         n: usize,
     ) -> SearchResults {
-        impl From<SortDirection> for tantivy::Order {
-            fn from(value: SortDirection) -> Self {
-                match value {
-                    SortDirection::Asc => Order::Asc,
-                    SortDirection::Desc => Order::Desc,
-                }
-            }
-        }
-
-        let sort_field = self
-            .schema
-            .get_search_field(&SearchFieldName(sort_field.clone()))
-            .expect("sort field should exist in index schema");
-
-        let collector =
-            TopDocs::with_limit(n).order_by_u64_field(&sort_field.name.0, sortdir.into());
+        let collector = sort_collector::SortCollector::new(order_by.to_vec(), n);
+        let need_scores = collector.requires_scoring();
+        // the executor's threads read the index through the backend's main thread
         let top_docs = self
-            .searcher
-            .search_with_executor(
-                query,
-                &collector,
-                executor,
-                tantivy::query::EnableScoring::Enabled {
-                    searcher: &self.searcher,
-                    statistics_provider: &self.searcher,
-                },
-            )
-            .expect("failed to search");
-
-        let top_docs = top_docs
-            .into_iter()
-            .map(|(_, doc_address)| {
-                let ctid = self
-                    .searcher
-                    .segment_reader(doc_address.segment_ord)
-                    .fast_fields()
-                    .u64("ctid")
-                    .expect("ctid should be a fast field");
-                (
-                    SearchIndexScore::new(&ctid, doc_address.doc_id, 1.0),
-                    doc_address,
+            .directory
+            .service_reads_while(|| {
+                self.searcher.search_with_executor(
+                    query,
+                    &collector,
+                    executor,
+                    if need_scores {
+                        tantivy::query::EnableScoring::Enabled {
+                            searcher: &self.searcher,
+                            statistics_provider: self.statistics_provider(),
+                        }
+                    } else {
+                        tantivy::query::EnableScoring::Disabled {
+                            schema: &self.schema.schema,
+                            searcher_opt: Some(&self.searcher),
+                        }
+                    },
                 )
             })
-            .collect::<Vec<_>>();
+            .expect("failed to search");
 
         SearchResults::TopNByField(top_docs.len(), top_docs.into_iter())
     }
@@ -440,17 +664,20 @@ impl SearchIndexReader {
                 }
             });
 
+        // the executor's threads read the index through the backend's main thread
         let top_docs = self
-            .searcher
-            .search_with_executor(
-                query,
-                &collector,
-                executor,
-                tantivy::query::EnableScoring::Enabled {
-                    searcher: &self.searcher,
-                    statistics_provider: &self.searcher,
-                },
-            )
+            .directory
+            .service_reads_while(|| {
+                self.searcher.search_with_executor(
+                    query,
+                    &collector,
+                    executor,
+                    tantivy::query::EnableScoring::Enabled {
+                        searcher: &self.searcher,
+                        statistics_provider: self.statistics_provider(),
+                    },
+                )
+            })
             .expect("failed to search")
             .into_iter();
 
@@ -492,7 +719,7 @@ impl SearchIndexReader {
             Err(TantivyError::InvalidArgument(_)) => query
                 .weight(tantivy::query::EnableScoring::Enabled {
                     searcher: &self.searcher,
-                    statistics_provider: &self.searcher,
+                    statistics_provider: self.statistics_provider(),
                 })
                 .expect("creating a Weight from a Query should not fail"),
 
@@ -511,91 +738,206 @@ impl SearchIndexReader {
     }
 }
 
-mod collector {
-    use crate::index::reader::SearchIndexScore;
+mod sort_collector {
+    use crate::index::reader::{OrderByInfo, SearchIndexScore, SortDirection};
+    use std::cmp::Ordering;
     use tantivy::collector::{Collector, SegmentCollector};
+    use tantivy::columnar::StrColumn;
     use tantivy::fastfield::Column;
     use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
 
-    /// A [`Collector`] that uses a crossbeam channel to stream the results directly out of
-    /// each segment, in parallel, as tantivy find each doc.
-    pub struct ChannelCollector {
-        need_scores: bool,
-        sender: crossbeam::channel::Sender<Vec<(SearchIndexScore, DocAddress)>>,
-        sort_segments_by_ctid: bool,
+    /// The value of one sort key for one document
+    #[derive(Debug, Clone)]
+    enum SortValue {
+        Null,
+        Score(Score),
+        /// Any numeric, boolean or date fast field, mapped to a u64 in an order-preserving way
+        U64(u64),
+        /// The term ordinal of a text fast field, which can only be compared within its segment
+        TermOrd(u64),
+        Str(String),
     }
 
-    impl ChannelCollector {
-        pub fn new(
-            need_scores: bool,
-            sort_segments_by_ctid: bool,
-            sender: crossbeam::channel::Sender<Vec<(SearchIndexScore, DocAddress)>>,
-        ) -> Self {
-            Self {
-                need_scores,
-                sender,
-                sort_segments_by_ctid,
-            }
+    fn cmp_values(a: &SortValue, b: &SortValue, order_by: &OrderByInfo) -> Ordering {
+        let ordering = match (a, b) {
+            (SortValue::Null, SortValue::Null) => return Ordering::Equal,
+            (SortValue::Null, _) if order_by.nulls_first => return Ordering::Less,
+            (SortValue::Null, _) => return Ordering::Greater,
+            (_, SortValue::Null) if order_by.nulls_first => return Ordering::Greater,
+            (_, SortValue::Null) => return Ordering::Less,
+            (SortValue::Score(a), SortValue::Score(b)) => a.total_cmp(b),
+            (SortValue::U64(a), SortValue::U64(b)) => a.cmp(b),
+            (SortValue::TermOrd(a), SortValue::TermOrd(b)) => a.cmp(b),
+            (SortValue::Str(a), SortValue::Str(b)) => a.cmp(b),
+            // the field has a different type in different segments, which we can't order
+            _ => Ordering::Equal,
+        };
+
+        match order_by.direction {
+            SortDirection::Asc => ordering,
+            SortDirection::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Orders two documents by their sort values, with the one that should be returned first
+    /// being [`Ordering::Less`]
+    fn cmp_keys(a: &[SortValue], b: &[SortValue], order_by: &[OrderByInfo]) -> Ordering {
+        a.iter()
+            .zip(b)
+            .zip(order_by)
+            .map(|((a, b), order_by)| cmp_values(a, b, order_by))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    type SortedDoc = (Vec<SortValue>, SearchIndexScore, DocAddress);
+
+    /// Sort `docs` and keep only the first `limit` of them
+    fn sort_and_truncate(docs: &mut Vec<SortedDoc>, order_by: &[OrderByInfo], limit: usize) {
+        docs.sort_by(|(a, ..), (b, ..)| cmp_keys(a, b, order_by));
+        docs.truncate(limit);
+    }
+
+    /// A [`Collector`] that finds the top `limit` documents when ordered by any number of
+    /// fast fields and/or the score, each in its own direction.
+    ///
+    /// Within a segment, text fields are ordered by their term ordinals, and only the segment's
+    /// top documents have their ordinals turned into strings so they can be ordered against
+    /// the other segments' documents.
+    pub struct SortCollector {
+        order_by: Vec<OrderByInfo>,
+        limit: usize,
+    }
+
+    impl SortCollector {
+        pub fn new(order_by: Vec<OrderByInfo>, limit: usize) -> Self {
+            Self { order_by, limit }
         }
     }
 
-    impl Collector for ChannelCollector {
-        type Fruit = ();
-        type Child = ChannelSegmentCollector;
+    impl Collector for SortCollector {
+        type Fruit = Vec<(SearchIndexScore, DocAddress)>;
+        type Child = SortSegmentCollector;
 
         fn for_segment(
             &self,
             segment_local_id: SegmentOrdinal,
             segment_reader: &SegmentReader,
         ) -> tantivy::Result<Self::Child> {
-            Ok(ChannelSegmentCollector {
+            let fast_fields = segment_reader.fast_fields();
+            let mut columns = Vec::with_capacity(self.order_by.len());
+            for order_by in &self.order_by {
+                columns.push(match &order_by.field {
+                    None => SortColumn::Score,
+                    Some(field) => {
+                        if let Some(column) = fast_fields.str(field)? {
+                            SortColumn::Str(column)
+                        } else if let Some((column, _)) = fast_fields.u64_lenient(field)? {
+                            SortColumn::U64(column)
+                        } else {
+                            // nothing in this segment has a value for the field
+                            SortColumn::Missing
+                        }
+                    }
+                });
+            }
+
+            Ok(SortSegmentCollector {
                 segment_ord: segment_local_id,
-                sender: self.sender.clone(),
-                fruit: Vec::new(),
-                ctid_ff: segment_reader
-                    .fast_fields()
+                order_by: self.order_by.clone(),
+                limit: self.limit,
+                columns,
+                ctid_ff: fast_fields
                     .u64("ctid")
                     .expect("ctid should be a u64 fast field"),
-                sort_by_ctid: self.sort_segments_by_ctid,
+                docs: Vec::new(),
             })
         }
 
         fn requires_scoring(&self) -> bool {
-            self.need_scores
+            self.order_by
+                .iter()
+                .any(|order_by| order_by.field.is_none())
         }
 
-        fn merge_fruits(&self, _segment_fruits: Vec<()>) -> tantivy::Result<Self::Fruit> {
-            Ok(())
+        fn merge_fruits(
+            &self,
+            segment_fruits: Vec<<Self::Child as SegmentCollector>::Fruit>,
+        ) -> tantivy::Result<Self::Fruit> {
+            let mut docs = segment_fruits.into_iter().flatten().collect::<Vec<_>>();
+            sort_and_truncate(&mut docs, &self.order_by, self.limit);
+            Ok(docs
+                .into_iter()
+                .map(|(_, scored, doc_address)| (scored, doc_address))
+                .collect())
         }
     }
 
-    pub struct ChannelSegmentCollector {
+    enum SortColumn {
+        Score,
+        U64(Column<u64>),
+        Str(StrColumn),
+        Missing,
+    }
+
+    pub struct SortSegmentCollector {
         segment_ord: SegmentOrdinal,
-        sender: crossbeam::channel::Sender<Vec<(SearchIndexScore, DocAddress)>>,
-        fruit: Vec<(SearchIndexScore, DocAddress)>,
+        order_by: Vec<OrderByInfo>,
+        limit: usize,
+        columns: Vec<SortColumn>,
         ctid_ff: Column<u64>,
-        sort_by_ctid: bool,
+        docs: Vec<SortedDoc>,
     }
 
-    impl SegmentCollector for ChannelSegmentCollector {
-        type Fruit = ();
+    impl SegmentCollector for SortSegmentCollector {
+        type Fruit = Vec<SortedDoc>;
 
         fn collect(&mut self, doc: DocId, score: Score) {
-            let doc_address = DocAddress::new(self.segment_ord, doc);
-            self.fruit.push((
+            let values = self
+                .columns
+                .iter()
+                .map(|column| match column {
+                    SortColumn::Score => SortValue::Score(score),
+                    SortColumn::U64(column) => {
+                        column.first(doc).map_or(SortValue::Null, SortValue::U64)
+                    }
+                    SortColumn::Str(column) => column
+                        .term_ords(doc)
+                        .next()
+                        .map_or(SortValue::Null, SortValue::TermOrd),
+                    SortColumn::Missing => SortValue::Null,
+                })
+                .collect();
+
+            self.docs.push((
+                values,
                 SearchIndexScore::new(&self.ctid_ff, doc, score),
-                doc_address,
-            ))
+                DocAddress::new(self.segment_ord, doc),
+            ));
+
+            // don't let the candidates grow without bound
+            if self.docs.len() >= self.limit.max(1) * 2 {
+                sort_and_truncate(&mut self.docs, &self.order_by, self.limit);
+            }
         }
 
         fn harvest(mut self) -> Self::Fruit {
-            if self.sort_by_ctid {
-                self.fruit.sort_by_key(|(scored, _)| scored.ctid);
+            sort_and_truncate(&mut self.docs, &self.order_by, self.limit);
+
+            // term ordinals mean nothing outside of this segment, so swap them for their strings
+            for (values, ..) in &mut self.docs {
+                for (value, column) in values.iter_mut().zip(&self.columns) {
+                    if let (SortValue::TermOrd(ord), SortColumn::Str(column)) = (&*value, column) {
+                        let ord = *ord;
+                        let mut s = String::new();
+                        column
+                            .ord_to_str(ord, &mut s)
+                            .expect("string should be retrievable for term ord");
+                        *value = SortValue::Str(s);
+                    }
+                }
             }
-
-            // if send fails that likely means the receiver was dropped so we have nowhere
-            // to send the result.  That's okay
-            self.sender.send(self.fruit).ok();
+            self.docs
         }
     }
 }
@@ -0,0 +1,111 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! When an UPDATE doesn't change any of an index's columns, Postgres tells `aminsert` so, and
+//! we don't index the new version of the row.  Instead, the document indexed for an earlier
+//! version of the row is redirected to the new version's ctid, and every scan that finds that
+//! document also considers the ctids it's redirected to.
+//!
+//! The redirects are stored in the payload of tantivy's `meta.json`, so that a reader always sees
+//! the redirects that belong to the segments it's reading.  `VACUUM` indexes the versions that
+//! are still redirected to when it removes the document they're redirected from.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Once an index has this many redirects, updates are indexed normally until the next `VACUUM`,
+/// which keeps the size of `meta.json` in check.
+pub const MAX_REDIRECTS: usize = 10_000;
+
+/// The ctids of an indexed document mapped to the ctids of later versions of its row.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CtidRedirects(BTreeMap<u64, BTreeSet<u64>>);
+
+impl CtidRedirects {
+    /// Read the redirects from the payload of `meta.json`.
+    pub fn from_payload(payload: Option<&str>) -> serde_json::Result<Self> {
+        match payload {
+            Some(payload) if !payload.is_empty() => serde_json::from_str(payload),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// The redirects as a `meta.json` payload, which is empty if there are no redirects.
+    pub fn to_payload(&self) -> serde_json::Result<Option<String>> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        serde_json::to_string(self).map(Some)
+    }
+
+    /// The ctids that `ctid` is redirected to.
+    pub fn targets(&self, ctid: u64) -> impl Iterator<Item = u64> + '_ {
+        self.0.get(&ctid).into_iter().flatten().copied()
+    }
+
+    /// Every redirect, as a `(from, to)` pair.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.0
+            .iter()
+            .flat_map(|(from, targets)| targets.iter().map(move |to| (*from, *to)))
+    }
+
+    pub fn insert(&mut self, from: u64, to: u64) {
+        self.0.entry(from).or_default().insert(to);
+    }
+
+    pub fn remove(&mut self, from: u64, to: u64) {
+        if let Some(targets) = self.0.get_mut(&from) {
+            targets.remove(&to);
+            if targets.is_empty() {
+                self.0.remove(&from);
+            }
+        }
+    }
+
+    /// The number of redirects.
+    pub fn len(&self) -> usize {
+        self.0.values().map(BTreeSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Redirects added and removed by one writer, which are applied to whatever `meta.json` holds
+/// when the writer flushes, so that concurrent writers don't lose each other's redirects.
+#[derive(Debug, Default)]
+pub struct RedirectChanges {
+    pub added: CtidRedirects,
+    pub removed: CtidRedirects,
+}
+
+impl RedirectChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn apply(&self, redirects: &mut CtidRedirects) {
+        for (from, to) in self.added.iter() {
+            redirects.insert(from, to);
+        }
+        for (from, to) in self.removed.iter() {
+            redirects.remove(from, to);
+        }
+    }
+}
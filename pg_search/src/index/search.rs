@@ -18,11 +18,10 @@
 use super::reader::SearchIndexReader;
 use super::IndexError;
 use crate::gucs;
-use crate::index::merge_policy::NPlusOneMergePolicy;
+use crate::index::merge_policy::SegmentSizes;
+use crate::index::redirects::CtidRedirects;
 use crate::index::SearchIndexWriter;
-use crate::index::{
-    BlockingDirectory, SearchDirectoryError, SearchFs, TantivyDirPath, WriterDirectory,
-};
+use crate::index::{BlockDirectory, SearchDirectoryError, WriterDirectory};
 use crate::postgres::options::SearchIndexCreateOptions;
 use crate::query::SearchQueryInput;
 use crate::schema::{
@@ -31,17 +30,22 @@ use crate::schema::{
 };
 use anyhow::Result;
 use once_cell::sync::Lazy;
-use pgrx::PgRelation;
-use serde::{Deserialize, Deserializer, Serialize};
+use pgrx::{pg_sys, PgRelation};
+use serde::{Deserialize, Serialize};
 use std::num::NonZeroUsize;
+use std::path::Path;
 use tantivy::indexer::NoMergePolicy;
 use tantivy::merge_policy::MergePolicy;
 use tantivy::query::Query;
-use tantivy::{query::QueryParser, Executor, Index};
+use tantivy::{query::QueryParser, Directory, Executor, Index};
 use thiserror::Error;
 use tokenizers::{create_normalizer_manager, create_tokenizer_manager};
 use tracing::trace;
 
+/// The name of the file, stored alongside the tantivy files in the index relation, that holds
+/// the serialized [`SearchIndex`], enabling loading an index across connections.
+static SEARCH_INDEX_CONFIG_FILE_NAME: &str = "search-index.json";
+
 /// PostgreSQL operates in a process-per-client model, meaning every client connection
 /// to PostgreSQL results in a new backend process being spawned on the PostgreSQL server.
 pub static mut SEARCH_EXECUTOR: Lazy<Executor> = Lazy::new(|| {
@@ -51,8 +55,11 @@ pub static mut SEARCH_EXECUTOR: Lazy<Executor> = Lazy::new(|| {
     Executor::multi_thread(num_threads, "prefix-here").expect("could not create search executor")
 });
 
+#[derive(Copy, Clone)]
 pub enum WriterResources {
     CreateIndex,
+    /// One participant of a parallel CREATE INDEX, whose segments are merged by the leader
+    ParallelCreateIndex,
     Statement,
     Vacuum,
 }
@@ -73,11 +80,19 @@ impl WriterResources {
                 index_options.target_segment_count(),
                 true, // we always want a merge on CREATE INDEX
             ),
+            WriterResources::ParallelCreateIndex => (
+                gucs::create_index_parallelism(),
+                gucs::create_index_memory_budget(),
+                index_options.target_segment_count(),
+                false, // the leader merges once every participant is done
+            ),
             WriterResources::Statement => (
                 gucs::statement_parallelism(),
                 gucs::statement_memory_budget(),
                 index_options.target_segment_count(),
-                index_options.merge_on_insert(), // user/index decides if we merge for INSERT/UPDATE statements
+                // user/index decides if we merge for INSERT/UPDATE statements, unless the
+                // background merge workers are doing it for us
+                index_options.merge_on_insert() && gucs::merge_workers() == 0,
             ),
             WriterResources::Vacuum => (
                 gucs::statement_parallelism(),
@@ -94,6 +109,8 @@ pub struct SearchIndex {
     pub schema: SearchIndexSchema,
     pub directory: WriterDirectory,
     #[serde(skip_serializing)]
+    pub block_directory: BlockDirectory,
+    #[serde(skip_serializing)]
     pub underlying_index: Index,
 }
 
@@ -105,8 +122,7 @@ impl SearchIndex {
     ) -> Result<Self, SearchIndexError> {
         SearchIndexWriter::create_index(directory.clone(), fields, key_field_index)?;
 
-        // As the new index instance was created in a background process, we need
-        // to load it from disk to use it.
+        // Load the newly-created index back from the index relation to use it.
         let new_self_ref = Self::from_disk(&directory)
             .unwrap_or_else(|err| panic!("error loading index from directory: {err}"));
 
@@ -117,9 +133,19 @@ impl SearchIndex {
         SearchIndexReader::new(self)
     }
 
-    /// Retrieve an owned writer for a given index. This will block until this process
-    /// can get an exclusive lock on the Tantivy writer. The return type needs to
-    /// be entirely owned by the new process, with no references.
+    /// The ctid redirects in the `meta.json` this index last read.  See
+    /// [`crate::index::redirects`].
+    pub fn redirects(&self) -> Result<CtidRedirects> {
+        let meta = self.underlying_index.load_metas()?;
+        Ok(CtidRedirects::from_payload(meta.payload.as_deref())?)
+    }
+
+    /// Retrieve an owned writer for a given index. The return type needs to be entirely owned
+    /// by the new process, with no references.
+    ///
+    /// A writer for a statement that doesn't merge segments only adds new ones, and doesn't wait
+    /// for any other writer.  Every other writer blocks until this process gets the index's writer
+    /// lock, which it holds until its transaction ends.
     pub fn get_writer(
         &self,
         resources: WriterResources,
@@ -127,6 +153,9 @@ impl SearchIndex {
     ) -> Result<SearchIndexWriter> {
         let (parallelism, memory_budget, target_segment_count, merge_on_insert) =
             resources.resources(index_options);
+        if !matches!(resources, WriterResources::Statement if !merge_on_insert) {
+            self.block_directory.lock_writer();
+        }
         let underlying_writer = self
             .underlying_index
             .writer_with_num_threads(parallelism.get(), memory_budget)?;
@@ -134,7 +163,13 @@ impl SearchIndex {
         let wants_merge;
         let merge_policy: Box<dyn MergePolicy> = if merge_on_insert {
             wants_merge = true;
-            Box::new(NPlusOneMergePolicy(target_segment_count))
+            let config = index_options.merge_policy();
+            let sizes = if config.needs_segment_sizes() {
+                self.segment_sizes()?
+            } else {
+                SegmentSizes::default()
+            };
+            config.build(target_segment_count, sizes)
         } else {
             wants_merge = false;
             Box::new(NoMergePolicy)
@@ -144,9 +179,29 @@ impl SearchIndex {
         Ok(SearchIndexWriter {
             underlying_writer: Some(underlying_writer),
             wants_merge,
+            directory: self.block_directory.clone(),
         })
     }
 
+    /// The size, in bytes, of each of the index's segments.
+    pub fn segment_sizes(&self) -> Result<SegmentSizes> {
+        let catalog = self.block_directory.catalog()?;
+        Ok(self
+            .underlying_index
+            .searchable_segment_metas()?
+            .into_iter()
+            .map(|meta| {
+                let bytes = meta
+                    .list_files()
+                    .iter()
+                    .filter_map(|path| catalog.files.get(path))
+                    .map(|entry| entry.len)
+                    .sum();
+                (meta.id(), bytes)
+            })
+            .collect())
+    }
+
     #[allow(static_mut_refs)]
     pub fn executor() -> &'static Executor {
         unsafe { &SEARCH_EXECUTOR }
@@ -156,15 +211,24 @@ impl SearchIndex {
         let tokenizers = schema
             .fields
             .iter()
-            .filter_map(|field| {
+            .flat_map(|field| {
                 let field_config = &field.config;
                 let field_name: &str = field.name.as_ref();
                 trace!(field_name, "attempting to create tokenizer");
-                match field_config {
-                    SearchFieldConfig::Text { tokenizer, .. }
-                    | SearchFieldConfig::Json { tokenizer, .. } => Some(tokenizer),
-                    _ => None,
-                }
+                let (tokenizer, search_tokenizer) = match field_config {
+                    SearchFieldConfig::Text {
+                        tokenizer,
+                        search_tokenizer,
+                        ..
+                    }
+                    | SearchFieldConfig::Json {
+                        tokenizer,
+                        search_tokenizer,
+                        ..
+                    } => (Some(tokenizer), search_tokenizer.as_ref()),
+                    _ => (None, None),
+                };
+                tokenizer.into_iter().chain(search_tokenizer)
             })
             .collect();
 
@@ -181,25 +245,81 @@ impl SearchIndex {
     }
 
     pub fn from_disk(directory: &WriterDirectory) -> Result<Self, SearchIndexError> {
-        let mut new_self: Self = directory.load_index()?;
+        Self::open(directory, BlockDirectory::open(directory.index_oid.into()))
+    }
 
-        // In the case of a physical replication of the database, the absolute path that is stored
-        // in the serialized WriterDirectory might refer to the source database's file system.
-        // We should overwrite it with the dynamically generated one that's been passed as an
-        // argument here.
-        new_self.directory = directory.clone();
+    /// Like [`SearchIndex::from_disk`], but as of the catalog of index files that starts at
+    /// `catalog_start`.  See [`BlockDirectory::open_at`].
+    pub fn from_disk_at(
+        directory: &WriterDirectory,
+        catalog_start: pg_sys::BlockNumber,
+    ) -> Result<Self, SearchIndexError> {
+        Self::open(
+            directory,
+            BlockDirectory::open_at(directory.index_oid.into(), catalog_start),
+        )
+    }
 
-        Ok(new_self)
+    /// Like [`SearchIndex::from_disk`], but for a participant of a parallel index build.  See
+    /// [`BlockDirectory::open_detached`].
+    pub fn from_disk_detached(directory: &WriterDirectory) -> Result<Self, SearchIndexError> {
+        Self::open(
+            directory,
+            BlockDirectory::open_detached(directory.index_oid.into()),
+        )
+    }
+
+    fn open(
+        directory: &WriterDirectory,
+        block_directory: BlockDirectory,
+    ) -> Result<Self, SearchIndexError> {
+        // A helper struct that lets us use the default deserialization for most fields.
+        #[derive(Deserialize)]
+        struct SearchIndexHelper {
+            schema: SearchIndexSchema,
+        }
+
+        let serialized = block_directory
+            .atomic_read(Path::new(SEARCH_INDEX_CONFIG_FILE_NAME))
+            .map_err(tantivy::TantivyError::from)?;
+        let SearchIndexHelper { schema } = serde_json::from_slice(&serialized)?;
+
+        let mut underlying_index = Index::open(block_directory.clone())?;
+
+        // We need to setup tokenizers again after retrieving an index from disk.
+        Self::setup_tokenizers(&mut underlying_index, &schema);
+
+        // The serialized WriterDirectory is ignored in favor of the one that's been passed as an
+        // argument here, as its relfilenode and data directory path may have changed since the
+        // index was created (after a VACUUM FULL, or on a physical replica, for example).
+        Ok(SearchIndex {
+            underlying_index,
+            directory: directory.clone(),
+            block_directory,
+            schema,
+        })
+    }
+
+    /// Serialize this [`SearchIndex`] into the index relation.
+    pub fn save(&self) -> Result<(), SearchIndexError> {
+        let serialized = serde_json::to_vec(self)?;
+        self.block_directory
+            .atomic_write(Path::new(SEARCH_INDEX_CONFIG_FILE_NAME), &serialized)?;
+        self.block_directory
+            .flush()
+            .map_err(|err| SearchIndexError::WriterIndexError(err.into()))?;
+        Ok(())
     }
 
     pub fn query_parser(&self) -> QueryParser {
-        QueryParser::for_index(
-            &self.underlying_index,
+        QueryParser::new(
+            self.schema.query_schema(),
             self.schema
                 .fields
                 .iter()
                 .map(|search_field| search_field.id.0)
                 .collect::<Vec<_>>(),
+            self.underlying_index.tokenizers().clone(),
         )
     }
 
@@ -222,9 +342,6 @@ impl SearchIndex {
         writer: &SearchIndexWriter,
         document: SearchDocument,
     ) -> Result<(), SearchIndexError> {
-        // the index is about to change, and that requires our transaction callbacks be registered
-        crate::postgres::transaction::register_callback();
-
         writer.insert(document)?;
 
         Ok(())
@@ -250,58 +367,12 @@ impl SearchIndex {
         Ok((ctids_to_delete.len() as u32, not_deleted))
     }
 
-    pub fn drop_index(&mut self) -> Result<(), SearchIndexError> {
-        // the index is about to be queued to drop and that requires our transaction callbacks be registered
-        crate::postgres::transaction::register_callback();
-
-        // Mark in our global store that this index is pending drop so it can be physically
-        // deleted on commit, or in case it needs to be rolled back on abort.
-        SearchIndexWriter::mark_pending_drop(&self.directory);
-
-        Ok(())
-    }
-
     pub fn vacuum(&self, writer: &SearchIndexWriter) -> Result<(), SearchIndexError> {
         writer.vacuum()?;
         Ok(())
     }
 }
 
-impl<'de> Deserialize<'de> for SearchIndex {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        // A helper struct that lets us use the default serialization for most fields.
-        #[derive(Deserialize)]
-        struct SearchIndexHelper {
-            schema: SearchIndexSchema,
-            directory: WriterDirectory,
-        }
-
-        // Deserialize into the struct with automatic handling for most fields
-        let SearchIndexHelper { schema, directory } = SearchIndexHelper::deserialize(deserializer)?;
-
-        let TantivyDirPath(tantivy_dir_path) = directory
-            .tantivy_dir_path(true)
-            .expect("tantivy directory path should be valid");
-
-        let tantivy_dir = BlockingDirectory::open(tantivy_dir_path)
-            .expect("need a valid path to open a tantivy index");
-        let mut underlying_index = Index::open(tantivy_dir).map_err(serde::de::Error::custom)?;
-
-        // We need to setup tokenizers again after retrieving an index from disk.
-        Self::setup_tokenizers(&mut underlying_index, &schema);
-
-        // Construct the SearchIndex.
-        Ok(SearchIndex {
-            underlying_index,
-            directory,
-            schema,
-        })
-    }
-}
-
 #[derive(Error, Debug)]
 #[allow(clippy::enum_variant_names)]
 pub enum SearchIndexError {
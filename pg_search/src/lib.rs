@@ -18,7 +18,9 @@
 mod api;
 mod bootstrap;
 mod env;
+mod gc;
 mod index;
+mod merge;
 mod postgres;
 mod query;
 mod schema;
@@ -76,13 +78,17 @@ pub unsafe extern "C" fn _PG_init() {
     gucs::init();
 
     setup_telemetry_background_worker(telemetry::ParadeExtension::PgSearch);
+    gc::setup_orphan_sweep_background_worker();
+    merge::setup_merge_background_worker();
 
     // Register our tracing / logging hook, so that we can ensure that the logger
     // is initialized for all connections.
     #[allow(static_mut_refs)]
     #[allow(deprecated)]
     pgrx::hooks::register_hook(&mut TRACE_HOOK);
+    postgres::transaction::register_utility_hook();
     customscan::register_rel_pathlist(customscan::pdbscan::PdbScan);
+    customscan::register_upper_path(customscan::countscan::CountScan);
 }
 
 /// This module is required by `cargo pgrx test` invocations.
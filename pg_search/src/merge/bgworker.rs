@@ -0,0 +1,174 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{bm25_indexes, merge_index};
+use crate::gucs;
+use anyhow::{anyhow, Result};
+use pgrx::bgworkers::{
+    self, BackgroundWorker, BackgroundWorkerBuilder, DynamicBackgroundWorker, SignalWakeFlags,
+};
+use pgrx::{pg_guard, pg_sys, FromDatum, IntoDatum};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+#[pg_guard]
+#[no_mangle]
+pub fn setup_merge_background_worker() {
+    // A background worker that periodically merges the segments of every bm25 index in the
+    // cluster, so that INSERT/UPDATE/COPY statements don't have to.
+    BackgroundWorkerBuilder::new("pg_search_merge_launcher")
+        // Must be the name of a function in this file.
+        .set_function("merge_launcher")
+        // Must be the name of the extension it will be loaded from.
+        .set_library("pg_search")
+        .enable_spi_access()
+        // Allows time for all bootstrapped tables to be created.
+        .set_start_time(bgworkers::BgWorkerStartTime::RecoveryFinished)
+        .load();
+}
+
+#[pg_guard]
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn merge_launcher(_arg: pg_sys::Datum) {
+    // This function runs in the spawned background worker process. That means
+    // that we need to re-initialize logging.
+    crate::trace::init_ereport_logger("pg_search");
+
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM | SignalWakeFlags::SIGHUP);
+
+    // Indexes can only be opened from a connection to the database they're in, so the launcher
+    // only lists the databases and starts `paradedb.merge_workers` workers at a time, each
+    // connected to one database, to do the merging.
+    BackgroundWorker::connect_worker_to_spi(Some("postgres"), None);
+
+    debug!("starting pg_search merge launcher");
+    let mut last_round = Instant::now();
+    while BackgroundWorker::wait_latch(Some(Duration::from_secs(1))) {
+        if BackgroundWorker::sighup_received() {
+            pg_sys::ProcessConfigFile(pg_sys::GucContext::PGC_SIGHUP);
+        }
+
+        let nworkers = gucs::merge_workers();
+        if nworkers == 0 || last_round.elapsed() < gucs::merge_naptime() {
+            continue;
+        }
+        last_round = Instant::now();
+
+        let database_oids = BackgroundWorker::transaction(|| {
+            pgrx::Spi::get_one::<Vec<pg_sys::Oid>>(
+                "SELECT COALESCE(array_agg(oid), '{}') FROM pg_database
+                 WHERE datallowconn AND NOT datistemplate",
+            )
+        });
+        let database_oids = match database_oids {
+            Ok(database_oids) => database_oids.unwrap_or_default(),
+            Err(e) => {
+                warn!("could not list databases to merge: {e}");
+                continue;
+            }
+        };
+
+        for chunk in database_oids.chunks(nworkers) {
+            if BackgroundWorker::sigterm_received() {
+                break;
+            }
+
+            let workers = chunk
+                .iter()
+                .filter_map(|database_oid| match start_merge_worker(*database_oid) {
+                    Ok(worker) => Some(worker),
+                    Err(e) => {
+                        warn!("could not merge indexes of database {database_oid}: {e}");
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            for worker in workers {
+                let _ = worker.wait_for_shutdown();
+            }
+        }
+    }
+    debug!("exiting pg_search merge launcher");
+}
+
+fn start_merge_worker(database_oid: pg_sys::Oid) -> Result<DynamicBackgroundWorker> {
+    BackgroundWorkerBuilder::new(&format!(
+        "pg_search_merge_worker for database {database_oid}"
+    ))
+    .set_function("merge_worker")
+    .set_library("pg_search")
+    .set_argument(database_oid.into_datum())
+    .enable_spi_access()
+    // The launcher will try again at its next round.
+    .set_restart_time(None)
+    .set_notify_pid(unsafe { pg_sys::MyProcPid })
+    .load_dynamic()
+    .map_err(|_| anyhow!("could not start a background worker"))
+}
+
+#[pg_guard]
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn merge_worker(database_oid_datum: pg_sys::Datum) {
+    crate::trace::init_ereport_logger("pg_search");
+
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM);
+
+    let database_oid = pg_sys::Oid::from_datum(database_oid_datum, false)
+        .expect("database oid not passed to bgworker");
+    pg_sys::BackgroundWorkerInitializeConnectionByOid(database_oid, pg_sys::InvalidOid, 0);
+
+    let index_oids = match BackgroundWorker::transaction(bm25_indexes) {
+        Ok(index_oids) => index_oids,
+        Err(e) => {
+            warn!("could not list bm25 indexes: {e}");
+            return;
+        }
+    };
+
+    for index_oid in index_oids {
+        if BackgroundWorker::sigterm_received() {
+            break;
+        }
+
+        // each index is merged in its own transaction, so that its writer lock is held no
+        // longer than it takes to merge it
+        let started = Instant::now();
+        match BackgroundWorker::transaction(|| merge_index(index_oid)) {
+            Ok(0) => {}
+            Ok(merged_bytes) => {
+                debug!("merged {merged_bytes} bytes of index {index_oid}");
+                throttle(merged_bytes, started.elapsed());
+            }
+            Err(e) => warn!("could not merge index {index_oid}: {e}"),
+        }
+    }
+}
+
+/// Sleep for long enough that merging `merged_bytes` in `elapsed` stays within
+/// `paradedb.merge_io_limit`.
+fn throttle(merged_bytes: u64, elapsed: Duration) {
+    let Some(bytes_per_sec) = gucs::merge_io_limit() else {
+        return;
+    };
+
+    let budget = Duration::from_secs_f64(merged_bytes as f64 / bytes_per_sec as f64);
+    if let Some(remaining) = budget.checked_sub(elapsed) {
+        BackgroundWorker::wait_latch(Some(remaining));
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod bgworker;
+
+pub use bgworker::setup_merge_background_worker;
+
+use crate::index::merge_policy::{MergePolicyConfig, SegmentSizes};
+use crate::index::WriterResources;
+use crate::postgres::index::open_search_index;
+use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::storage::METAPAGE;
+use anyhow::Result;
+use pgrx::{pg_sys, IntoDatum, PgBuiltInOids, PgRelation, Spi};
+use std::collections::HashSet;
+use tantivy::{SegmentId, SegmentMeta};
+
+/// The oids of the `bm25` indexes in the connected database.  Empty if pg_search isn't installed
+/// in it.
+///
+/// Must be called from within a transaction.
+pub fn bm25_indexes() -> Result<Vec<pg_sys::Oid>> {
+    let installed = Spi::get_one::<bool>(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_search')",
+    )?
+    .unwrap_or(false);
+    if !installed {
+        return Ok(vec![]);
+    }
+
+    Ok(Spi::get_one::<Vec<pg_sys::Oid>>(
+        "SELECT COALESCE(array_agg(c.oid), '{}') FROM pg_class c JOIN pg_am a ON c.relam = a.oid
+         WHERE a.amname = 'bm25' AND c.relkind = 'i'",
+    )?
+    .unwrap_or_default())
+}
+
+/// The groups of segments that should be merged together:  whatever the index's merge policy
+/// would merge, followed by every other segment with too many deleted documents, on its own, to
+/// reclaim the space they use and stop searches from having to skip over them.
+pub fn merge_candidates(
+    metas: &[SegmentMeta],
+    config: &MergePolicyConfig,
+    target_segment_count: usize,
+    sizes: SegmentSizes,
+) -> Vec<Vec<SegmentId>> {
+    let deleted_ratio = config.deleted_ratio();
+    let mut candidates = config
+        .build(target_segment_count, sizes)
+        .compute_merge_candidates(metas)
+        .into_iter()
+        .map(|candidate| candidate.0)
+        .collect::<Vec<_>>();
+
+    let merging = candidates.iter().flatten().copied().collect::<HashSet<_>>();
+    candidates.extend(
+        metas
+            .iter()
+            .filter(|meta| !merging.contains(&meta.id()))
+            .filter(|meta| {
+                meta.max_doc() > 0
+                    && meta.num_deleted_docs() as f64 / meta.max_doc() as f64 >= deleted_ratio
+            })
+            .map(|meta| vec![meta.id()]),
+    );
+    candidates
+}
+
+/// Merge the segments of the `bm25` index `index_oid` that need it, returning the number of
+/// bytes that were merged.
+///
+/// The index is skipped, rather than waited on, if it has been dropped, if it is being vacuumed
+/// or merged by someone else, or if a transaction that is writing to it hasn't finished yet.
+///
+/// Must be called from within a transaction, which holds the index's writer lock until it ends.
+pub fn merge_index(index_oid: pg_sys::Oid) -> Result<u64> {
+    unsafe {
+        // the same lock VACUUM takes, which lets INSERT/UPDATE/DELETE and searches continue but
+        // keeps the index from being dropped or vacuumed while we merge it
+        if !pg_sys::ConditionalLockRelationOid(index_oid, pg_sys::ShareUpdateExclusiveLock as _) {
+            return Ok(0);
+        }
+    }
+
+    let exists = Spi::get_one_with_args::<bool>(
+        "SELECT EXISTS (SELECT 1 FROM pg_class WHERE oid = $1)",
+        vec![(PgBuiltInOids::OIDOID.oid(), index_oid.into_datum())],
+    )?
+    .unwrap_or(false);
+    if !exists {
+        return Ok(0);
+    }
+
+    // SAFETY:  we locked the index above
+    let indexrel = unsafe { PgRelation::with_lock(index_oid, pg_sys::NoLock as _) };
+    let search_index = open_search_index(&indexrel)?;
+    let options = unsafe {
+        (indexrel.rd_options as *mut SearchIndexCreateOptions)
+            .as_ref()
+            .expect("index should have options")
+    };
+
+    // this is the writer lock that `get_writer` takes, which it would otherwise wait for
+    unsafe {
+        if !pg_sys::ConditionalLockPage(indexrel.as_ptr(), METAPAGE, pg_sys::ExclusiveLock as _) {
+            return Ok(0);
+        }
+    }
+
+    // the candidates have to come from the segments as they are under the writer lock -- a
+    // concurrent merge could have already replaced anything we looked at before taking it
+    let mut writer = search_index.get_writer(WriterResources::Vacuum, options)?;
+    let metas = search_index.underlying_index.searchable_segment_metas()?;
+    let sizes = search_index.segment_sizes()?;
+    let candidates = merge_candidates(
+        &metas,
+        &options.merge_policy(),
+        options.target_segment_count(),
+        sizes.clone(),
+    );
+    if candidates.is_empty() {
+        return Ok(0);
+    }
+
+    let merged_bytes = candidates
+        .iter()
+        .flatten()
+        .filter_map(|id| sizes.get(id))
+        .sum();
+
+    for candidate in candidates {
+        writer.merge(&candidate)?;
+    }
+    search_index.vacuum(&writer)?;
+
+    Ok(merged_bytes)
+}
@@ -15,20 +15,47 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::api::synonyms::resolve_synonym_set;
 use crate::index::WriterDirectory;
 use crate::index::{SearchIndex, WriterResources};
 use crate::postgres::index::relfilenode_from_pg_relation;
-use crate::postgres::insert::init_insert_state;
+use crate::postgres::insert::{init_insert_state, InsertState};
 use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::storage::BlockStorage;
 use crate::postgres::utils::row_to_search_document;
 use crate::schema::{IndexRecordOption, SearchFieldConfig, SearchFieldName, SearchFieldType};
 use pgrx::*;
 use std::collections::HashMap;
 use std::ffi::CStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 use tokenizers::manager::SearchTokenizerFilters;
 use tokenizers::{SearchNormalizer, SearchTokenizer};
 
+mod parallel;
+
+/// Values from Postgres' `commands/progress.h`, for reporting our progress through
+/// `pg_stat_progress_create_index`.
+mod progress {
+    pub const SUBPHASE: i32 = 10;
+    pub const TUPLES_TOTAL: i32 = 11;
+    pub const TUPLES_DONE: i32 = 12;
+
+    // our own subphases, named by [`super::ambuildphasename`].  1 is "initializing"
+    pub const SUBPHASE_SCAN: i64 = 2;
+    pub const SUBPHASE_COMMIT: i64 = 3;
+    pub const SUBPHASE_MERGE: i64 = 4;
+}
+
+/// How many rows a build participant indexes between updates of `TUPLES_DONE`
+const PROGRESS_INTERVAL: usize = 1_000;
+
+fn report_progress(param: i32, value: i64) {
+    unsafe {
+        pg_sys::pgstat_progress_update_param(param, value);
+    }
+}
+
 // For now just pass the count on the build callback state
 struct BuildState {
     count: usize,
@@ -36,18 +63,45 @@ struct BuildState {
     index_info: *mut pg_sys::IndexInfo,
     tupdesc: PgTupleDesc<'static>,
     start: Instant,
+    writer_resources: WriterResources,
+    /// The number of rows indexed by every participant of a parallel build, in shared memory
+    shared_count: Option<*const AtomicU64>,
+    /// How many of our `count` rows have been reported
+    reported: usize,
 }
 
 impl BuildState {
-    fn new(indexrel: &PgRelation, index_info: *mut pg_sys::IndexInfo) -> Self {
+    fn new(
+        indexrel: &PgRelation,
+        index_info: *mut pg_sys::IndexInfo,
+        writer_resources: WriterResources,
+    ) -> Self {
         BuildState {
             count: 0,
             memctx: PgMemoryContexts::new("pg_search_index_build"),
             index_info,
             tupdesc: unsafe { PgTupleDesc::from_pg_copy(indexrel.rd_att) },
             start: Instant::now(),
+            writer_resources,
+            shared_count: None,
+            reported: 0,
         }
     }
+
+    /// Report the rows indexed so far, by us or by every participant of a parallel build, to
+    /// `pg_stat_progress_create_index`, and add the ones we've indexed since the last report to
+    /// the shared count
+    fn report_tuples_done(&mut self) {
+        let unreported = (self.count - self.reported) as u64;
+        self.reported = self.count;
+        let total = match self.shared_count {
+            Some(shared_count) => unsafe {
+                (*shared_count).fetch_add(unreported, Ordering::Relaxed) + unreported
+            },
+            None => self.count as u64,
+        };
+        report_progress(progress::TUPLES_DONE, total as i64);
+    }
 }
 
 #[pg_guard]
@@ -82,9 +136,18 @@ pub extern "C" fn ambuild(
         ops.into_pg_boxed()
     };
 
+    // Expressions are indexed as fields named after the index's columns, which are the same as
+    // the table's for plain columns, so the index's first column must be a plain one for the
+    // `@@@` operator to find the table it belongs to
+    let indkey = unsafe { &(*index_relation.rd_index).indkey };
+    let indkey = unsafe { indkey.values.as_slice(indkey.dim1 as usize) };
+    if indkey.first() == Some(&0) {
+        panic!("the first column of a `USING bm25` index cannot be an expression");
+    }
+
     // Create a map from column name to column type. We'll use this to verify that index
     // configurations passed by the user reference the correct types for each column.
-    let name_type_map: HashMap<SearchFieldName, SearchFieldType> = heap_relation
+    let name_type_map: HashMap<SearchFieldName, SearchFieldType> = index_relation
         .tuple_desc()
         .into_iter()
         .filter_map(|attribute| {
@@ -161,6 +224,14 @@ pub extern "C" fn ambuild(
         Some(field_type) => field_type,
         None => panic!("key field does not exist"),
     };
+    let key_field_is_expression = index_relation
+        .tuple_desc()
+        .iter()
+        .zip(indkey)
+        .any(|(attribute, attno)| attribute.name() == key_field.0 && *attno == 0);
+    if key_field_is_expression {
+        panic!("key field '{key_field}' must be a column of the table, not an expression");
+    }
     let key_config = match key_field_type {
         SearchFieldType::I64 | SearchFieldType::U64 | SearchFieldType::F64 => {
             SearchFieldConfig::Numeric {
@@ -175,8 +246,10 @@ pub extern "C" fn ambuild(
             stored: true,
             fieldnorms: false,
             tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
+            search_tokenizer: None,
             record: IndexRecordOption::Basic,
             normalizer: SearchNormalizer::Raw,
+            fields: Default::default(),
         },
         SearchFieldType::Json => SearchFieldConfig::Json {
             indexed: true,
@@ -184,6 +257,7 @@ pub extern "C" fn ambuild(
             stored: true,
             expand_dots: false,
             tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
+            search_tokenizer: None,
             record: IndexRecordOption::Basic,
             normalizer: SearchNormalizer::Raw,
         },
@@ -201,7 +275,7 @@ pub extern "C" fn ambuild(
     };
 
     // Concatenate the separate lists of fields.
-    let fields: Vec<_> = text_fields
+    let mut fields: Vec<_> = text_fields
         .chain(numeric_fields)
         .chain(boolean_fields)
         .chain(json_fields)
@@ -232,29 +306,110 @@ pub extern "C" fn ambuild(
         panic!("no fields specified")
     }
 
+    // Tokenizers only name their synonym set.  Its rules are resolved here so they're saved
+    // with the index schema.
+    for (name, config, _) in fields.iter_mut() {
+        resolve_synonym_sets(name, config);
+        if let SearchFieldConfig::Text { fields, .. } = config {
+            for (suffix, config) in fields.iter_mut() {
+                let multi_field = SearchFieldName::multi_field(name, suffix);
+                if name_type_map.contains_key(&multi_field) {
+                    panic!("multi-field '{multi_field}' has the same name as a column");
+                }
+                resolve_synonym_sets(&multi_field, config);
+            }
+        }
+    }
+
     let directory =
         WriterDirectory::from_oids(database_oid, index_oid.as_u32(), relfilenode.as_u32());
 
     SearchIndex::create_index(directory, fields, key_field_index)
         .expect("error creating new index instance");
 
-    let state = do_heap_scan(index_info, &heap_relation, &index_relation);
+    report_progress(progress::SUBPHASE, progress::SUBPHASE_SCAN);
+    if let Some(reltuples) = heap_relation
+        .reltuples()
+        .filter(|reltuples| *reltuples > 0.0)
+    {
+        report_progress(progress::TUPLES_TOTAL, reltuples as i64);
+    }
+    let count = match unsafe { parallel::build(&heap_relation, &index_relation, index_info) } {
+        Some(count) => count,
+        None => do_heap_scan(index_info, &heap_relation, &index_relation).count,
+    };
+
+    // commit the index and wait for its segments to be merged here, rather than when the
+    // IndexInfo's memory context is deleted, so that the time they take shows up in our progress
+    unsafe {
+        let insert_state = (*index_info).ii_AmCache as *mut InsertState;
+        if let Some(insert_state) = insert_state.as_mut() {
+            report_progress(progress::SUBPHASE, progress::SUBPHASE_COMMIT);
+            insert_state
+                .writer
+                .commit()
+                .expect("tantivy index commit should succeed");
+
+            report_progress(progress::SUBPHASE, progress::SUBPHASE_MERGE);
+            insert_state
+                .writer
+                .wait_merging_threads()
+                .expect("wait_merging_threads() should succeed");
+        }
+    }
+
     let mut result = unsafe { PgBox::<pg_sys::IndexBuildResult>::alloc0() };
-    result.heap_tuples = state.count as f64;
-    result.index_tuples = state.count as f64;
+    result.heap_tuples = count as f64;
+    result.index_tuples = count as f64;
 
     result.into_pg()
 }
 
+fn resolve_synonym_sets(name: &SearchFieldName, config: &mut SearchFieldConfig) {
+    if let SearchFieldConfig::Text {
+        tokenizer,
+        search_tokenizer,
+        ..
+    }
+    | SearchFieldConfig::Json {
+        tokenizer,
+        search_tokenizer,
+        ..
+    } = config
+    {
+        resolve_synonym_set(tokenizer)
+            .unwrap_or_else(|e| panic!("field '{name}' has an invalid tokenizer: {e}"));
+        if let Some(search_tokenizer) = search_tokenizer {
+            resolve_synonym_set(search_tokenizer)
+                .unwrap_or_else(|e| panic!("field '{name}' has an invalid search_tokenizer: {e}"));
+        }
+    }
+}
+
+/// Names the subphases we report in `pg_stat_progress_create_index.phase`, which Postgres shows
+/// as "building index: <name>".
 #[pg_guard]
-pub extern "C" fn ambuildempty(_index_relation: pg_sys::Relation) {}
+pub extern "C" fn ambuildphasename(phasenum: i64) -> *mut std::os::raw::c_char {
+    match phasenum {
+        progress::SUBPHASE_SCAN => "scanning table".as_pg_cstr(),
+        progress::SUBPHASE_COMMIT => "committing index".as_pg_cstr(),
+        progress::SUBPHASE_MERGE => "merging segments".as_pg_cstr(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+#[pg_guard]
+pub extern "C" fn ambuildempty(index_relation: pg_sys::Relation) {
+    // an unlogged index is reset to its init fork after a crash, so it needs a valid metapage
+    unsafe { BlockStorage::new(index_relation) }.init_empty_metapage();
+}
 
 fn do_heap_scan<'a>(
     index_info: *mut pg_sys::IndexInfo,
     heap_relation: &'a PgRelation,
     index_relation: &'a PgRelation,
 ) -> BuildState {
-    let mut state = BuildState::new(index_relation, index_info);
+    let mut state = BuildState::new(index_relation, index_info, WriterResources::CreateIndex);
     unsafe {
         pg_sys::IndexBuildHeapScan(
             heap_relation.as_ptr(),
@@ -264,6 +419,7 @@ fn do_heap_scan<'a>(
             &mut state,
         );
     }
+    state.report_tuples_done();
     state
 }
 
@@ -296,7 +452,7 @@ unsafe fn build_callback_internal(
     let insert_state = init_insert_state(
         indexrel,
         build_state.index_info,
-        WriterResources::CreateIndex,
+        build_state.writer_resources,
     );
     let search_index = &(*insert_state).index;
     let writer = &(*insert_state).writer;
@@ -333,6 +489,9 @@ unsafe fn build_callback_internal(
         // important to count the number of items we've indexed for proper statistics updates,
         // especially after CREATE INDEX has finished
         build_state.count += 1;
+        if build_state.count % PROGRESS_INTERVAL == 0 {
+            build_state.report_tuples_done();
+        }
 
         if crate::gucs::log_create_index_progress() && build_state.count % 100_000 == 0 {
             let secs = build_state.start.elapsed().as_secs_f64();
@@ -0,0 +1,311 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Parallel CREATE INDEX.
+//!
+//! Every participant, the leader included, scans its share of the heap through a parallel table
+//! scan and writes its own segments to the index relation through a detached
+//! [`crate::index::BlockDirectory`].  Once they're all done the leader adds their segments to the
+//! index's `meta.json`, and its own writer merges them as it would for a serial build.
+
+use super::{build_callback, progress, report_progress, BuildState};
+use crate::index::{WriterResources, MANAGED_FILEPATH, META_FILEPATH};
+use crate::postgres::index::open_search_index;
+use crate::postgres::insert::{init_insert_state, InsertState};
+use anyhow::Result;
+use pgrx::{pg_guard, pg_sys, AsPgCStr, PgRelation};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tantivy::directory::error::OpenReadError;
+use tantivy::{Directory, SegmentId};
+
+const PARALLEL_KEY_BUILD_SHARED: u64 = 0xB325_0000_0000_0001;
+const PARALLEL_KEY_TABLE_SCAN: u64 = 0xB325_0000_0000_0002;
+
+/// `ALIGNOF_BUFFER` from Postgres' `pg_config_manual.h`, which `shm_toc_estimate_chunk()` rounds
+/// every chunk up to
+const ALIGNOF_BUFFER: usize = 32;
+
+/// State the leader shares with its workers.  The parallel table scan has its own chunk.
+#[repr(C)]
+struct ParallelBuildShared {
+    heaprelid: pg_sys::Oid,
+    indexrelid: pg_sys::Oid,
+    isconcurrent: bool,
+    /// The number of rows indexed so far, by every participant
+    indexed: AtomicU64,
+}
+
+/// The segments written by one participant, which it leaves in the index relation for the leader
+#[derive(Serialize, Deserialize)]
+struct ParticipantSegments {
+    /// The id and `max_doc` of each segment
+    segments: Vec<(String, u32)>,
+    files: Vec<PathBuf>,
+}
+
+/// The leader is participant 0, and workers are numbered from 1
+fn participant_file(participant: i32) -> PathBuf {
+    PathBuf::from(format!(".parallel_build.{participant}.json"))
+}
+
+fn buffer_align(len: usize) -> usize {
+    (len + ALIGNOF_BUFFER - 1) & !(ALIGNOF_BUFFER - 1)
+}
+
+/// Build the index with the parallel workers Postgres planned for this CREATE INDEX, if any.
+///
+/// Returns the number of rows indexed, or `None` if the caller needs to build the index by itself.
+pub(super) unsafe fn build(
+    heaprel: &PgRelation,
+    indexrel: &PgRelation,
+    index_info: *mut pg_sys::IndexInfo,
+) -> Option<usize> {
+    let nworkers = (*index_info).ii_ParallelWorkers;
+    if nworkers <= 0 {
+        return None;
+    }
+    let isconcurrent = (*index_info).ii_Concurrent;
+
+    pg_sys::EnterParallelMode();
+    let pcxt = pg_sys::CreateParallelContext(
+        "pg_search".as_pg_cstr(),
+        "pg_search_parallel_build_main".as_pg_cstr(),
+        nworkers,
+    );
+
+    // like nbtree, a plain CREATE INDEX indexes every tuple that isn't dead to everyone, while a
+    // concurrent one only indexes what's visible to its snapshot
+    let snapshot = if isconcurrent {
+        pg_sys::RegisterSnapshot(pg_sys::GetTransactionSnapshot())
+    } else {
+        std::ptr::addr_of_mut!(pg_sys::SnapshotAnyData)
+    };
+
+    let scan_size = pg_sys::table_parallelscan_estimate(heaprel.as_ptr(), snapshot);
+    let estimator = &mut (*pcxt).estimator;
+    estimator.space_for_chunks +=
+        buffer_align(std::mem::size_of::<ParallelBuildShared>()) + buffer_align(scan_size);
+    estimator.number_of_keys += 2;
+
+    pg_sys::InitializeParallelDSM(pcxt);
+    if (*pcxt).seg.is_null() {
+        // there's no dynamic shared memory to be had, so build the index serially
+        if isconcurrent {
+            pg_sys::UnregisterSnapshot(snapshot);
+        }
+        pg_sys::DestroyParallelContext(pcxt);
+        pg_sys::ExitParallelMode();
+        return None;
+    }
+
+    let toc = (*pcxt).toc;
+    let shared = pg_sys::shm_toc_allocate(toc, std::mem::size_of::<ParallelBuildShared>())
+        as *mut ParallelBuildShared;
+    shared.write(ParallelBuildShared {
+        heaprelid: heaprel.oid(),
+        indexrelid: indexrel.oid(),
+        isconcurrent,
+        indexed: AtomicU64::new(0),
+    });
+    let pscan = pg_sys::shm_toc_allocate(toc, scan_size) as pg_sys::ParallelTableScanDesc;
+    pg_sys::table_parallelscan_initialize(heaprel.as_ptr(), pscan, snapshot);
+    pg_sys::shm_toc_insert(toc, PARALLEL_KEY_BUILD_SHARED, shared.cast());
+    pg_sys::shm_toc_insert(toc, PARALLEL_KEY_TABLE_SCAN, pscan.cast());
+
+    pg_sys::LaunchParallelWorkers(pcxt);
+
+    // the leader indexes its share of the heap too, which also keeps the build going if no
+    // workers could be launched
+    participate(&*shared, pscan, heaprel.as_ptr(), indexrel.as_ptr());
+    pg_sys::WaitForParallelWorkersToFinish(pcxt);
+
+    let nparticipants = (*pcxt).nworkers_launched + 1;
+    let indexed = (*shared).indexed.load(Ordering::Relaxed) as usize;
+    report_progress(progress::TUPLES_DONE, indexed as i64);
+
+    if isconcurrent {
+        pg_sys::UnregisterSnapshot(snapshot);
+    }
+    pg_sys::DestroyParallelContext(pcxt);
+    pg_sys::ExitParallelMode();
+
+    adopt_participant_segments(indexrel, nparticipants)
+        .expect("should be able to add the segments written by parallel workers to the index");
+
+    // the leader's own writer, which `ambuild` commits, merges the adopted segments
+    init_insert_state(indexrel.as_ptr(), index_info, WriterResources::CreateIndex);
+
+    Some(indexed)
+}
+
+/// The entry point of a parallel worker, as named to `CreateParallelContext()`
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn pg_search_parallel_build_main(
+    _seg: *mut pg_sys::dsm_segment,
+    toc: *mut pg_sys::shm_toc,
+) {
+    unsafe {
+        let shared = &*(pg_sys::shm_toc_lookup(toc, PARALLEL_KEY_BUILD_SHARED, false)
+            as *const ParallelBuildShared);
+        let pscan = pg_sys::shm_toc_lookup(toc, PARALLEL_KEY_TABLE_SCAN, false)
+            as pg_sys::ParallelTableScanDesc;
+
+        // the same locks as the leader's, which won't conflict because we're in its lock group
+        let (heap_lockmode, index_lockmode) = if shared.isconcurrent {
+            (pg_sys::ShareUpdateExclusiveLock, pg_sys::RowExclusiveLock)
+        } else {
+            (pg_sys::ShareLock, pg_sys::AccessExclusiveLock)
+        };
+        let heaprel = pg_sys::table_open(shared.heaprelid, heap_lockmode as _);
+        let indexrel = pg_sys::index_open(shared.indexrelid, index_lockmode as _);
+
+        participate(shared, pscan, heaprel, indexrel);
+
+        pg_sys::index_close(indexrel, index_lockmode as _);
+        pg_sys::table_close(heaprel, heap_lockmode as _);
+    }
+}
+
+/// Index this participant's share of the heap into segments of its own, and leave a list of them
+/// in the index relation for the leader.
+unsafe fn participate(
+    shared: &ParallelBuildShared,
+    pscan: pg_sys::ParallelTableScanDesc,
+    heaprel: pg_sys::Relation,
+    indexrel: pg_sys::Relation,
+) {
+    let index_info = pg_sys::BuildIndexInfo(indexrel);
+    (*index_info).ii_Concurrent = shared.isconcurrent;
+
+    let index_relation = PgRelation::from_pg(indexrel);
+    let mut state = BuildState::new(
+        &index_relation,
+        index_info,
+        WriterResources::ParallelCreateIndex,
+    );
+    state.shared_count = Some(std::ptr::addr_of!(shared.indexed));
+
+    let is_leader = pg_sys::ParallelWorkerNumber < 0;
+    let scan = pg_sys::table_beginscan_parallel(heaprel, pscan);
+    let index_build_range_scan = (*(*heaprel).rd_tableam)
+        .index_build_range_scan
+        .expect("table access method should support building indexes");
+    index_build_range_scan(
+        heaprel,
+        indexrel,
+        index_info,
+        true,
+        false,
+        // only the leader reports the scan's progress in pg_stat_progress_create_index
+        is_leader,
+        0,
+        pg_sys::InvalidBlockNumber,
+        Some(build_callback),
+        std::ptr::addr_of_mut!(state).cast(),
+        scan,
+    );
+    // which also adds our last rows to the shared count
+    state.report_tuples_done();
+
+    // a participant that didn't index any rows never opened a writer
+    let insert_state = (*index_info).ii_AmCache as *mut InsertState;
+    if let Some(insert_state) = insert_state.as_mut() {
+        insert_state
+            .writer
+            .commit()
+            .expect("tantivy index commit should succeed");
+
+        let metas = insert_state
+            .index
+            .underlying_index
+            .searchable_segment_metas()
+            .expect("should be able to read the participant's segments");
+        let participant_segments = ParticipantSegments {
+            segments: metas
+                .iter()
+                .map(|meta| (meta.id().uuid_string(), meta.max_doc()))
+                .collect(),
+            files: metas.iter().flat_map(|meta| meta.list_files()).collect(),
+        };
+        let directory = &insert_state.writer.directory;
+        directory
+            .atomic_write(
+                &participant_file(pg_sys::ParallelWorkerNumber + 1),
+                &serde_json::to_vec(&participant_segments)
+                    .expect("participant segments should serialize"),
+            )
+            .expect("should be able to write the participant's segment list");
+        directory
+            .flush()
+            .expect("should be able to write the participant's segment list");
+
+        // there's nothing to wait for, but it leaves nothing for the writer to do when it's dropped
+        insert_state
+            .writer
+            .wait_merging_threads()
+            .expect("wait_merging_threads() should succeed");
+    }
+}
+
+/// Add the segments written by every participant to the index's `meta.json`, and their files to
+/// its `.managed.json` so they're garbage collected once they're merged away.
+unsafe fn adopt_participant_segments(indexrel: &PgRelation, nparticipants: i32) -> Result<()> {
+    let index = open_search_index(indexrel)?;
+    let directory = &index.block_directory;
+    // which lets us delete the files the participants wrote
+    directory.lock_writer();
+
+    let mut metas = index.underlying_index.load_metas()?;
+    let mut managed: HashSet<PathBuf> = match directory.atomic_read(Path::new(MANAGED_FILEPATH)) {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(OpenReadError::FileDoesNotExist(_)) => HashSet::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    for participant in 0..nparticipants {
+        let path = participant_file(participant);
+        let data = match directory.atomic_read(&path) {
+            Ok(data) => data,
+            Err(OpenReadError::FileDoesNotExist(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let participant_segments: ParticipantSegments = serde_json::from_slice(&data)?;
+        for (segment_id, max_doc) in participant_segments.segments {
+            let segment_id = SegmentId::from_uuid_string(&segment_id)?;
+            metas
+                .segments
+                .push(index.underlying_index.new_segment_meta(segment_id, max_doc));
+        }
+        managed.extend(participant_segments.files);
+        directory.delete(&path)?;
+    }
+
+    // in the same format tantivy itself writes them
+    let mut meta_json = serde_json::to_vec_pretty(&metas)?;
+    meta_json.push(b'\n');
+    directory.atomic_write(Path::new(META_FILEPATH), &meta_json)?;
+    let mut managed_json = serde_json::to_vec(&managed)?;
+    managed_json.push(b'\n');
+    directory.atomic_write(Path::new(MANAGED_FILEPATH), &managed_json)?;
+
+    directory.flush()?;
+    Ok(())
+}
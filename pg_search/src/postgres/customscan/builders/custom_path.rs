@@ -69,6 +69,15 @@ impl From<SortDirection> for crate::index::reader::SortDirection {
     }
 }
 
+impl From<crate::index::reader::SortDirection> for SortDirection {
+    fn from(value: crate::index::reader::SortDirection) -> Self {
+        match value {
+            crate::index::reader::SortDirection::Asc => SortDirection::Asc,
+            crate::index::reader::SortDirection::Desc => SortDirection::Desc,
+        }
+    }
+}
+
 impl From<SortDirection> for u32 {
     fn from(value: SortDirection) -> Self {
         value as _
@@ -96,6 +105,22 @@ impl OrderByStyle {
             (*self.pathkey()).pk_strategy.into()
         }
     }
+
+    pub fn nulls_first(&self) -> bool {
+        unsafe {
+            let pathkey = self.pathkey();
+            assert!(!pathkey.is_null());
+
+            (*self.pathkey()).pk_nulls_first
+        }
+    }
+
+    pub fn field_name(&self) -> Option<&str> {
+        match self {
+            OrderByStyle::Score(_) => None,
+            OrderByStyle::Field(_, name) => Some(name),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -181,6 +206,23 @@ impl<P: Into<*mut pg_sys::List> + Default> CustomPathBuilder<P> {
         }
     }
 
+    /// Create a builder for a path that computes the upper relation `output_rel` directly from
+    /// the base relation `input_rel`, such as an aggregate that can be answered by the index.
+    pub fn new_upper<CS: CustomScan>(
+        root: *mut pg_sys::PlannerInfo,
+        input_rel: *mut pg_sys::RelOptInfo,
+        output_rel: *mut pg_sys::RelOptInfo,
+    ) -> CustomPathBuilder<P> {
+        unsafe {
+            let rti = (*input_rel).relid;
+            let rte = *(*root).simple_rte_array.add(rti as usize);
+            let mut builder = Self::new::<CS>(root, input_rel, rti, rte);
+            builder.custom_path_node.path.parent = output_rel;
+            builder.custom_path_node.path.pathtarget = (*output_rel).reltarget;
+            builder
+        }
+    }
+
     pub fn args(&self) -> &Args {
         &self.args
     }
@@ -204,9 +246,8 @@ impl<P: Into<*mut pg_sys::List> + Default> CustomPathBuilder<P> {
         }
     }
 
-    #[allow(dead_code)]
     pub fn path_target(&self) -> *mut pg_sys::PathTarget {
-        self.args.rel().reltarget
+        self.custom_path_node.path.pathtarget
     }
 
     #[allow(dead_code)]
@@ -254,15 +295,23 @@ impl<P: Into<*mut pg_sys::List> + Default> CustomPathBuilder<P> {
         self
     }
 
-    pub fn add_path_key(mut self, pathkey: &Option<OrderByStyle>) -> Self {
+    /// Mark the path as one that can be split across `nworkers` parallel workers.  Along with the
+    /// regular path, a partial copy of it will be offered to the planner for use under a Gather.
+    pub fn set_parallel(mut self, nworkers: usize) -> Self {
+        self.custom_path_node.path.parallel_aware = true;
+        self.custom_path_node.path.parallel_safe = true;
+        self.custom_path_node.path.parallel_workers =
+            nworkers.try_into().expect("nworkers should be a valid i32");
+        self
+    }
+
+    pub fn add_path_key(mut self, style: &OrderByStyle) -> Self {
         unsafe {
-            if let Some(style) = pathkey {
-                let mut pklist =
-                    PgList::<pg_sys::PathKey>::from_pg(self.custom_path_node.path.pathkeys);
-                pklist.push(style.pathkey());
+            let mut pklist =
+                PgList::<pg_sys::PathKey>::from_pg(self.custom_path_node.path.pathkeys);
+            pklist.push(style.pathkey());
 
-                self.custom_path_node.path.pathkeys = pklist.into_pg();
-            }
+            self.custom_path_node.path.pathkeys = pklist.into_pg();
             self
         }
     }
@@ -107,6 +107,13 @@ impl<P: Into<*mut pg_sys::List> + From<*mut pg_sys::List> + Default> CustomScanB
         &mut self.custom_private
     }
 
+    /// Describe the scan tuples this node produces.  This is required when the node doesn't scan
+    /// a single base relation, in which case `scanrelid` is zero.
+    pub fn set_custom_scan_tlist(mut self, tlist: *mut pg_sys::List) -> Self {
+        self.custom_scan_node.custom_scan_tlist = tlist;
+        self
+    }
+
     pub fn build(self) -> pg_sys::CustomScan {
         let mut node = self.custom_scan_node;
         node.custom_private = self.custom_private.into();
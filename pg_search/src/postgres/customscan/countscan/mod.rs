@@ -0,0 +1,289 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Answers `SELECT count(*) FROM t WHERE ... @@@ ...` by counting the matches in the index,
+//! instead of scanning the matching tuples and aggregating them.
+
+mod privdat;
+mod scan_state;
+
+use crate::api::operator::anyelement_query_input_opoid;
+use crate::nodecast;
+use crate::postgres::customscan::builders::custom_path::CustomPathBuilder;
+use crate::postgres::customscan::builders::custom_scan::CustomScanBuilder;
+use crate::postgres::customscan::builders::custom_state::{
+    CustomScanStateBuilder, CustomScanStateWrapper,
+};
+use crate::postgres::customscan::countscan::privdat::PrivateData;
+use crate::postgres::customscan::countscan::scan_state::CountScanState;
+use crate::postgres::customscan::explainer::Explainer;
+use crate::postgres::customscan::pdbscan::qual_inspect::extract_quals;
+use crate::postgres::customscan::{CustomScan, ExecMethod, PlainExecCapable};
+use crate::postgres::rel_get_bm25_index;
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::SearchQueryInput;
+use crate::DEFAULT_STARTUP_COST;
+use pgrx::pg_sys::CustomExecMethods;
+use pgrx::{direct_function_call, pg_sys, IntoDatum, PgList};
+use std::ffi::CStr;
+
+#[derive(Default)]
+pub struct CountScan;
+
+impl ExecMethod for CountScan {
+    fn exec_methods() -> *const CustomExecMethods {
+        <CountScan as PlainExecCapable>::exec_methods()
+    }
+}
+
+impl PlainExecCapable for CountScan {}
+
+impl CustomScan for CountScan {
+    const NAME: &'static CStr = c"ParadeDB Count Scan";
+
+    type State = CountScanState;
+    type PrivateData = PrivateData;
+
+    fn callback(mut builder: CustomPathBuilder<Self::PrivateData>) -> Option<pg_sys::CustomPath> {
+        unsafe {
+            // we can only replace an aggregation of every matching row
+            let parse = (*builder.args().root).parse;
+            if !(*parse).groupClause.is_null()
+                || !(*parse).groupingSets.is_null()
+                || !(*parse).havingQual.is_null()
+            {
+                return None;
+            }
+
+            // and the only thing it can compute is `count(*)`
+            let exprs = PgList::<pg_sys::Expr>::from_pg((*builder.path_target()).exprs);
+            let count_star_funcoid = count_star_funcoid();
+            if exprs.is_empty()
+                || !exprs
+                    .iter_ptr()
+                    .all(|expr| is_count_star(expr.cast(), count_star_funcoid))
+            {
+                return None;
+            }
+
+            // over a plain relation with a `USING bm25` index
+            let rte = builder.args().rte();
+            if rte.rtekind != pg_sys::RTEKind::RTE_RELATION || rte.inh {
+                return None;
+            }
+            let relkind = pg_sys::get_rel_relkind(rte.relid) as u8;
+            if relkind != pg_sys::RELKIND_RELATION && relkind != pg_sys::RELKIND_MATVIEW {
+                return None;
+            }
+            let (table, bm25_index) = rel_get_bm25_index(rte.relid)?;
+
+            // every one of the quals must be answered by the index, otherwise we'd count rows
+            // that the quals we can't handle would have filtered out
+            let restrict_info = builder.restrict_info();
+            if restrict_info.is_empty() {
+                return None;
+            }
+            let rti = builder.args().rti;
+            extract_quals(
+                rti,
+                restrict_info.as_ptr().cast(),
+                anyelement_query_input_opoid(),
+            )?;
+
+            builder.custom_private().set_heaprelid(table.oid());
+            builder.custom_private().set_indexrelid(bm25_index.oid());
+            builder.custom_private().set_range_table_index(rti);
+            builder.custom_private().set_quals(restrict_info);
+
+            // we only need to look at the matching ctids, not the tuples themselves
+            let matches = builder.args().rel().rows;
+            let startup_cost = DEFAULT_STARTUP_COST;
+            let total_cost = startup_cost + matches * pg_sys::cpu_index_tuple_cost;
+
+            Some(
+                builder
+                    .set_rows(1.0)
+                    .set_startup_cost(startup_cost)
+                    .set_total_cost(total_cost)
+                    .build(),
+            )
+        }
+    }
+
+    fn plan_custom_path(builder: CustomScanBuilder<Self::PrivateData>) -> pg_sys::CustomScan {
+        unsafe {
+            // we don't scan a base relation, so the scan tuple is the `count(*)` aggregates
+            // themselves, which the targetlist will refer to once it's been through setrefs.c
+            let tlist = builder.args().tlist.as_ptr();
+            let scan_tlist = pg_sys::copyObjectImpl(tlist.cast()).cast::<pg_sys::List>();
+            builder.set_custom_scan_tlist(scan_tlist).build()
+        }
+    }
+
+    fn create_custom_scan_state(
+        mut builder: CustomScanStateBuilder<Self, Self::PrivateData>,
+    ) -> *mut CustomScanStateWrapper<Self> {
+        builder.custom_state().heaprelid = builder
+            .custom_private()
+            .heaprelid()
+            .expect("heaprelid should have a value");
+        builder.custom_state().indexrelid = builder
+            .custom_private()
+            .indexrelid()
+            .expect("indexrelid should have a value");
+
+        let quals = builder
+            .custom_private()
+            .quals()
+            .expect("should have a Qual structure");
+        builder.custom_state().search_query_input = SearchQueryInput::from(quals);
+
+        builder.build()
+    }
+
+    fn explain_custom_scan(
+        state: &CustomScanStateWrapper<Self>,
+        ancestors: *mut pg_sys::List,
+        explainer: &mut Explainer,
+    ) {
+        explainer.add_text("Table", state.custom_state().heaprelname());
+        explainer.add_text("Index", state.custom_state().indexrelname());
+        explainer.add_text("Aggregate", "count(*)");
+
+        if explainer.is_analyze() && explainer.is_verbose() {
+            explainer.add_unsigned_integer(
+                "All-Visible Tuples",
+                state.custom_state().all_visible_tuple_count as u64,
+                None,
+            );
+            explainer.add_unsigned_integer(
+                "Heap-checked Tuples",
+                state.custom_state().heap_tuple_check_count as u64,
+                None,
+            );
+            explainer.add_unsigned_integer(
+                "Invisible Tuples",
+                state.custom_state().invisible_tuple_count as u64,
+                None,
+            );
+        }
+
+        let query = &state.custom_state().search_query_input;
+        let pretty_json = if explainer.is_verbose() {
+            serde_json::to_string_pretty(&query)
+        } else {
+            serde_json::to_string(&query)
+        }
+        .expect("query should serialize to json");
+        explainer.add_text("Tantivy Query", &pretty_json);
+    }
+
+    fn begin_custom_scan(
+        state: &mut CustomScanStateWrapper<Self>,
+        estate: *mut pg_sys::EState,
+        eflags: i32,
+    ) {
+        unsafe {
+            // an aggregate is only ever read, so this is the lock the planner already took
+            let lockmode = pg_sys::AccessShareLock as pg_sys::LOCKMODE;
+            let heaprel = pg_sys::relation_open(state.custom_state().heaprelid, lockmode);
+            let indexrel = pg_sys::relation_open(state.custom_state().indexrelid, lockmode);
+            state.custom_state_mut().heaprel = Some(heaprel);
+            state.custom_state_mut().indexrel = Some(indexrel);
+
+            state.custom_state_mut().visibility_checker = Some(
+                VisibilityChecker::with_rel_and_snap(heaprel, pg_sys::GetActiveSnapshot()),
+            );
+        }
+    }
+
+    fn rescan_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
+        state.custom_state_mut().done = false;
+    }
+
+    fn exec_custom_scan(state: &mut CustomScanStateWrapper<Self>) -> *mut pg_sys::TupleTableSlot {
+        if state.custom_state().done {
+            return std::ptr::null_mut();
+        }
+        state.custom_state_mut().done = true;
+
+        let count = state.custom_state_mut().count();
+
+        unsafe {
+            // every column of our scan tuple is a `count(*)`
+            let slot = state.scanslot();
+            (*(*slot).tts_ops)
+                .clear
+                .expect("slot should have a `clear` callback")(slot);
+            let natts = (*(*slot).tts_tupleDescriptor).natts as usize;
+            for i in 0..natts {
+                *(*slot).tts_values.add(i) = count.into_datum().unwrap();
+                *(*slot).tts_isnull.add(i) = false;
+            }
+            pg_sys::ExecStoreVirtualTuple(slot);
+
+            let projection_info = state.projection_info();
+            if projection_info.is_null() {
+                return slot;
+            }
+            (*(*projection_info).pi_exprContext).ecxt_scantuple = slot;
+            pg_sys::ExecProject(projection_info)
+        }
+    }
+
+    fn shutdown_custom_scan(state: &mut CustomScanStateWrapper<Self>) {}
+
+    fn end_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
+        drop(state.custom_state_mut().visibility_checker.take());
+
+        let lockmode = pg_sys::AccessShareLock as pg_sys::LOCKMODE;
+        if let Some(heaprel) = state.custom_state_mut().heaprel.take() {
+            unsafe {
+                pg_sys::relation_close(heaprel, lockmode);
+            }
+        }
+        if let Some(indexrel) = state.custom_state_mut().indexrel.take() {
+            unsafe {
+                pg_sys::relation_close(indexrel, lockmode);
+            }
+        }
+    }
+}
+
+/// Is `node` a plain `count(*)` aggregate?
+unsafe fn is_count_star(node: *mut pg_sys::Node, count_star_funcoid: pg_sys::Oid) -> bool {
+    match nodecast!(Aggref, T_Aggref, node) {
+        Some(aggref) => {
+            (*aggref).aggfnoid == count_star_funcoid
+                && (*aggref).aggstar
+                && (*aggref).agglevelsup == 0
+                && (*aggref).aggfilter.is_null()
+                && (*aggref).aggdistinct.is_null()
+        }
+        None => false,
+    }
+}
+
+fn count_star_funcoid() -> pg_sys::Oid {
+    unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::regprocedurein,
+            &[c"pg_catalog.count()".into_datum()],
+        )
+        .expect("the `pg_catalog.count()` function should exist")
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::api::operator::anyelement_query_input_opoid;
+use crate::api::AsInt;
+use crate::nodecast;
+use crate::postgres::customscan::pdbscan::qual_inspect::{extract_quals, Qual};
+use pgrx::{pg_sys, PgList};
+
+#[derive(Default, Debug)]
+pub struct PrivateData {
+    heaprelid: Option<pg_sys::Oid>,
+    indexrelid: Option<pg_sys::Oid>,
+    range_table_index: Option<pg_sys::Index>,
+    clauses: Option<*mut pg_sys::List>,
+}
+
+impl From<*mut pg_sys::List> for PrivateData {
+    fn from(list: *mut pg_sys::List) -> Self {
+        unsafe {
+            let list = PgList::<pg_sys::Node>::from_pg(list);
+            let decode_integer = |n: *mut pg_sys::Node| n.as_int().map(|i| i as u32);
+            PrivateData {
+                heaprelid: list.get_ptr(0).and_then(decode_integer).map(Into::into),
+                indexrelid: list.get_ptr(1).and_then(decode_integer).map(Into::into),
+                range_table_index: list.get_ptr(2).and_then(decode_integer),
+                clauses: list.get_ptr(3).and_then(|n| nodecast!(List, T_List, n)),
+            }
+        }
+    }
+}
+
+impl From<PrivateData> for *mut pg_sys::List {
+    fn from(value: PrivateData) -> Self {
+        unsafe {
+            let make_integer = |i: Option<u32>| {
+                pg_sys::makeInteger(i.expect("private data should be fully set") as _)
+                    .cast::<pg_sys::Node>()
+            };
+
+            let mut ser = PgList::<pg_sys::Node>::new();
+            ser.push(make_integer(value.heaprelid.map(u32::from)));
+            ser.push(make_integer(value.indexrelid.map(u32::from)));
+            ser.push(make_integer(value.range_table_index));
+            ser.push(
+                value
+                    .clauses
+                    .expect("private data should have its clauses")
+                    .cast(),
+            );
+            ser.into_pg()
+        }
+    }
+}
+
+impl PrivateData {
+    pub fn set_heaprelid(&mut self, oid: pg_sys::Oid) {
+        self.heaprelid = Some(oid);
+    }
+
+    pub fn set_indexrelid(&mut self, oid: pg_sys::Oid) {
+        self.indexrelid = Some(oid);
+    }
+
+    pub fn set_range_table_index(&mut self, rti: pg_sys::Index) {
+        self.range_table_index = Some(rti);
+    }
+
+    /// Store the bare clauses wrapped by the `quals`, as a `RestrictInfo` can't be represented
+    /// by `nodeToString()`
+    pub fn set_quals(&mut self, quals: PgList<pg_sys::RestrictInfo>) {
+        let mut clauses = PgList::<pg_sys::Expr>::new();
+        for ri in quals.iter_ptr() {
+            unsafe {
+                clauses.push((*ri).clause);
+            }
+        }
+        self.clauses = Some(clauses.into_pg());
+    }
+
+    pub fn heaprelid(&self) -> Option<pg_sys::Oid> {
+        self.heaprelid
+    }
+
+    pub fn indexrelid(&self) -> Option<pg_sys::Oid> {
+        self.indexrelid
+    }
+
+    pub fn quals(&self) -> Option<Qual> {
+        unsafe {
+            self.clauses.and_then(|clauses| {
+                extract_quals(
+                    self.range_table_index
+                        .expect("rti should be set to get a Qual"),
+                    clauses.cast(),
+                    anyelement_query_input_opoid(),
+                )
+            })
+        }
+    }
+}
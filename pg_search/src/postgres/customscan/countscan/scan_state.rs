@@ -0,0 +1,126 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::postgres::customscan::CustomScanState;
+use crate::postgres::index::open_search_index;
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::SearchQueryInput;
+use pgrx::{name_data_to_str, pg_sys, PgRelation};
+
+#[derive(Default)]
+pub struct CountScanState {
+    pub search_query_input: SearchQueryInput,
+
+    pub heaprelid: pg_sys::Oid,
+    pub heaprel: Option<pg_sys::Relation>,
+    pub indexrelid: pg_sys::Oid,
+    pub indexrel: Option<pg_sys::Relation>,
+
+    pub visibility_checker: Option<VisibilityChecker>,
+
+    /// Have we already returned the count?
+    pub done: bool,
+    pub all_visible_tuple_count: usize,
+    pub heap_tuple_check_count: usize,
+    pub invisible_tuple_count: usize,
+}
+
+impl CustomScanState for CountScanState {
+    fn init_exec_method(&mut self, _cstate: *mut pg_sys::CustomScanState) {
+        // there's only one way to count
+    }
+}
+
+impl CountScanState {
+    #[track_caller]
+    #[inline(always)]
+    pub fn heaprel(&self) -> pg_sys::Relation {
+        self.heaprel.unwrap()
+    }
+
+    #[track_caller]
+    #[inline(always)]
+    pub fn indexrel(&self) -> pg_sys::Relation {
+        self.indexrel.unwrap()
+    }
+
+    #[inline(always)]
+    pub fn heaprelname(&self) -> &str {
+        unsafe { name_data_to_str(&(*(*self.heaprel()).rd_rel).relname) }
+    }
+
+    #[inline(always)]
+    pub fn indexrelname(&self) -> &str {
+        unsafe { name_data_to_str(&(*(*self.indexrel()).rd_rel).relname) }
+    }
+
+    /// Count the documents matching our query that are visible to the active snapshot.
+    ///
+    /// Matches on heap pages the visibility map says are all-visible are counted without
+    /// reading the heap.  If that's every page of the heap, tantivy counts them by itself.
+    pub fn count(&mut self) -> i64 {
+        let indexrel = unsafe { PgRelation::from_pg(self.indexrel()) };
+        let search_index =
+            open_search_index(&indexrel).expect("should be able to open search index");
+        let search_reader = search_index
+            .get_reader()
+            .expect("search index reader should have been constructed correctly");
+        let query = search_index.query(&indexrel, &self.search_query_input, &search_reader);
+
+        let visibility_checker = self
+            .visibility_checker
+            .as_mut()
+            .expect("visibility checker should have been created");
+        visibility_checker.set_redirects(search_reader.redirects.clone());
+
+        let count = if visibility_checker.is_relation_all_visible() {
+            let count = search_reader
+                .count(&query)
+                .expect("counting matching documents should not fail");
+            self.all_visible_tuple_count += count;
+            count
+        } else {
+            let mut all_visible = 0;
+            let mut heap_checked = 0;
+            let mut invisible = 0;
+            let count = search_reader
+                .count_visible(&query, |ctid| {
+                    if visibility_checker.is_all_visible(ctid) {
+                        all_visible += 1;
+                        return true;
+                    }
+
+                    heap_checked += 1;
+                    let is_visible = visibility_checker
+                        .exec_if_visible(ctid, |_, _, _| ())
+                        .is_some();
+                    if !is_visible {
+                        invisible += 1;
+                    }
+                    is_visible
+                })
+                .expect("counting visible matching documents should not fail");
+
+            self.all_visible_tuple_count += all_visible;
+            self.heap_tuple_check_count += heap_checked;
+            self.invisible_tuple_count += invisible;
+            count
+        };
+
+        count.try_into().expect("count should fit in a bigint")
+    }
+}
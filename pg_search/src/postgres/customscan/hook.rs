@@ -54,6 +54,38 @@ pub fn register_rel_pathlist<CS: CustomScan + 'static>(_: CS) {
     }
 }
 
+pub fn register_upper_path<CS: CustomScan + 'static>(_: CS) {
+    unsafe {
+        static mut PREV_HOOKS: Lazy<
+            FxHashMap<std::any::TypeId, pg_sys::create_upper_paths_hook_type>,
+        > = Lazy::new(Default::default);
+
+        #[pg_guard]
+        extern "C" fn __priv_callback<CS: CustomScan + 'static>(
+            root: *mut pg_sys::PlannerInfo,
+            stage: pg_sys::UpperRelationKind::Type,
+            input_rel: *mut pg_sys::RelOptInfo,
+            output_rel: *mut pg_sys::RelOptInfo,
+            extra: *mut std::os::raw::c_void,
+        ) {
+            unsafe {
+                if let Some(Some(prev_hook)) = PREV_HOOKS.get(&std::any::TypeId::of::<CS>()) {
+                    (*prev_hook)(root, stage, input_rel, output_rel, extra);
+                }
+
+                paradedb_upper_paths_callback::<CS>(root, stage, input_rel, output_rel, extra);
+            }
+        }
+
+        match PREV_HOOKS.entry(std::any::TypeId::of::<CS>()) {
+            Entry::Occupied(_) => panic!("{} is already registered", std::any::type_name::<CS>()),
+            Entry::Vacant(entry) => entry.insert(pg_sys::create_upper_paths_hook),
+        };
+
+        pg_sys::create_upper_paths_hook = Some(__priv_callback::<CS>);
+    }
+}
+
 /// Although this hook function can be used to examine, modify, or remove paths generated by the
 /// core system, a custom scan provider will typically confine itself to generating CustomPath
 /// objects and adding them to rel using add_path. The custom scan provider is responsible for
@@ -71,9 +103,71 @@ pub extern "C" fn paradedb_rel_pathlist_callback<CS: CustomScan>(
         }
 
         if let Some(mut path) = CS::callback(CustomPathBuilder::new::<CS>(root, rel, rti, rte)) {
+            if path.path.parallel_aware {
+                // each participant only does its share of the work, so scale the partial path's
+                // estimates the same way Postgres does for its own parallel scans
+                let mut partial = path;
+                let divisor = parallel_divisor(partial.path.parallel_workers);
+                partial.path.rows /= divisor;
+                partial.path.total_cost = partial.path.startup_cost
+                    + (partial.path.total_cost - partial.path.startup_cost) / divisor;
+
+                let partial = PgMemoryContexts::CurrentMemoryContext
+                    .copy_ptr_into(&mut partial, std::mem::size_of_val(&partial));
+                pg_sys::add_partial_path(rel, partial.cast());
+
+                // and the regular path scans everything by itself
+                path.path.parallel_aware = false;
+                path.path.parallel_workers = 0;
+            }
+
             let path = PgMemoryContexts::CurrentMemoryContext
                 .copy_ptr_into(&mut path, std::mem::size_of_val(&path));
             pg_sys::add_path(rel, path.cast());
         }
     }
 }
+
+/// Mirrors Postgres' own `get_parallel_divisor()`, which isn't exported
+unsafe fn parallel_divisor(parallel_workers: i32) -> f64 {
+    let mut divisor = parallel_workers as f64;
+    if pg_sys::parallel_leader_participation {
+        let leader_contribution = 1.0 - (0.3 * parallel_workers as f64);
+        if leader_contribution > 0.0 {
+            divisor += leader_contribution;
+        }
+    }
+    divisor
+}
+
+/// Postgres calls this hook for every upper relation it plans (grouping, window functions,
+/// ordering, etc).  We only offer paths that replace the aggregation of a single base relation,
+/// which the custom scan computes from its index instead of from the relation's tuples.
+#[pg_guard]
+pub extern "C" fn paradedb_upper_paths_callback<CS: CustomScan>(
+    root: *mut pg_sys::PlannerInfo,
+    stage: pg_sys::UpperRelationKind::Type,
+    input_rel: *mut pg_sys::RelOptInfo,
+    output_rel: *mut pg_sys::RelOptInfo,
+    extra: *mut std::os::raw::c_void,
+) {
+    unsafe {
+        if !gucs::enable_custom_scan() {
+            return;
+        }
+
+        if stage != pg_sys::UpperRelationKind::UPPERREL_GROUP_AGG
+            || (*input_rel).reloptkind != pg_sys::RelOptKind::RELOPT_BASEREL
+        {
+            return;
+        }
+
+        if let Some(mut path) = CS::callback(CustomPathBuilder::new_upper::<CS>(
+            root, input_rel, output_rel,
+        )) {
+            let path = PgMemoryContexts::CurrentMemoryContext
+                .copy_ptr_into(&mut path, std::mem::size_of_val(&path));
+            pg_sys::add_path(output_rel, path.cast());
+        }
+    }
+}
@@ -20,8 +20,12 @@
 #![allow(unused_variables)]
 #![allow(clippy::tabs_in_doc_comments)]
 
+use once_cell::sync::Lazy;
 use pgrx::{pg_sys, PgMemoryContexts};
+use rustc_hash::FxHashMap;
+use std::any::TypeId;
 use std::ffi::CStr;
+use std::ptr::addr_of_mut;
 
 mod builders;
 mod dsm;
@@ -30,6 +34,7 @@ mod hook;
 mod path;
 mod scan;
 
+pub mod countscan;
 mod explainer;
 pub mod pdbscan;
 
@@ -50,7 +55,7 @@ use crate::postgres::customscan::builders::custom_state::{
 use crate::postgres::customscan::explainer::Explainer;
 use crate::postgres::customscan::path::{plan_custom_path, reparameterize_custom_path_by_child};
 use crate::postgres::customscan::scan::create_custom_scan_state;
-pub use hook::register_rel_pathlist;
+pub use hook::{register_rel_pathlist, register_upper_path};
 use std::ptr::NonNull;
 
 pub trait CustomScanState: Default {
@@ -61,7 +66,7 @@ pub trait CustomScanState: Default {
     }
 }
 
-pub trait CustomScan: ExecMethod + Default + Sized {
+pub trait CustomScan: ExecMethod + Default + Sized + 'static {
     const NAME: &'static CStr;
     type State: CustomScanState;
     type PrivateData: From<*mut pg_sys::List> + Into<*mut pg_sys::List> + Default;
@@ -70,28 +75,23 @@ pub trait CustomScan: ExecMethod + Default + Sized {
     // SAFETY:  We need to allocate the struct to define the functions once, however
     // all the methods are generic over this trait ([`CustomScan]).  Because Rust
     // monomorphizes these functions, they're actually at different addresses per CustomScan
-    // impl.  As such, we allocate them once, in Postgres "TopMemoryContext", which is **never**
-    // freed.  This ensures we don't waste any more memory than we need and more importantly,
-    // ensures the returned pointer holding the function pointers lives for the life of the
-    // process, which Postgres requires of these.
+    // impl, but a `static` declared inside them is not.  As such, we allocate them once per
+    // impl (see [`leak_once`]), in Postgres "TopMemoryContext", which is **never** freed.  This
+    // ensures we don't waste any more memory than we need and more importantly, ensures the
+    // returned pointer holding the function pointers lives for the life of the process, which
+    // Postgres requires of these.
     //
 
     fn custom_path_methods() -> *const pg_sys::CustomPathMethods {
         unsafe {
-            static mut METHODS: *mut pg_sys::CustomPathMethods = std::ptr::null_mut();
-
-            if METHODS.is_null() {
-                METHODS = PgMemoryContexts::TopMemoryContext.leak_and_drop_on_delete(
-                    pg_sys::CustomPathMethods {
-                        CustomName: Self::NAME.as_ptr(),
-                        PlanCustomPath: Some(plan_custom_path::<Self>),
-                        ReparameterizeCustomPathByChild: Some(
-                            reparameterize_custom_path_by_child::<Self>,
-                        ),
-                    },
-                );
-            }
-            METHODS
+            static mut METHODS: Lazy<FxHashMap<TypeId, *mut pg_sys::CustomPathMethods>> =
+                Lazy::new(Default::default);
+
+            leak_once::<Self, _>(&mut *addr_of_mut!(METHODS), || pg_sys::CustomPathMethods {
+                CustomName: Self::NAME.as_ptr(),
+                PlanCustomPath: Some(plan_custom_path::<Self>),
+                ReparameterizeCustomPathByChild: Some(reparameterize_custom_path_by_child::<Self>),
+            })
         }
     }
 
@@ -148,28 +148,24 @@ where
 {
     fn exec_methods() -> *const pg_sys::CustomExecMethods {
         unsafe {
-            static mut METHODS: *mut pg_sys::CustomExecMethods = std::ptr::null_mut();
-
-            if METHODS.is_null() {
-                METHODS = PgMemoryContexts::TopMemoryContext.leak_and_drop_on_delete(
-                    pg_sys::CustomExecMethods {
-                        CustomName: Self::NAME.as_ptr(),
-                        BeginCustomScan: Some(begin_custom_scan::<Self>),
-                        ExecCustomScan: Some(exec_custom_scan::<Self>),
-                        EndCustomScan: Some(end_custom_scan::<Self>),
-                        ReScanCustomScan: Some(rescan_custom_scan::<Self>),
-                        MarkPosCustomScan: None,
-                        RestrPosCustomScan: None,
-                        EstimateDSMCustomScan: None,
-                        InitializeDSMCustomScan: None,
-                        ReInitializeDSMCustomScan: None,
-                        InitializeWorkerCustomScan: None,
-                        ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
-                        ExplainCustomScan: Some(explain_custom_scan::<Self>),
-                    },
-                );
-            }
-            METHODS
+            static mut METHODS: Lazy<FxHashMap<TypeId, *mut pg_sys::CustomExecMethods>> =
+                Lazy::new(Default::default);
+
+            leak_once::<Self, _>(&mut *addr_of_mut!(METHODS), || pg_sys::CustomExecMethods {
+                CustomName: Self::NAME.as_ptr(),
+                BeginCustomScan: Some(begin_custom_scan::<Self>),
+                ExecCustomScan: Some(exec_custom_scan::<Self>),
+                EndCustomScan: Some(end_custom_scan::<Self>),
+                ReScanCustomScan: Some(rescan_custom_scan::<Self>),
+                MarkPosCustomScan: None,
+                RestrPosCustomScan: None,
+                EstimateDSMCustomScan: None,
+                InitializeDSMCustomScan: None,
+                ReInitializeDSMCustomScan: None,
+                InitializeWorkerCustomScan: None,
+                ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
+                ExplainCustomScan: Some(explain_custom_scan::<Self>),
+            })
         }
     }
 }
@@ -181,28 +177,24 @@ where
 {
     fn exec_methods() -> *const pg_sys::CustomExecMethods {
         unsafe {
-            static mut METHODS: *mut pg_sys::CustomExecMethods = std::ptr::null_mut();
-
-            if METHODS.is_null() {
-                METHODS = PgMemoryContexts::TopMemoryContext.leak_and_drop_on_delete(
-                    pg_sys::CustomExecMethods {
-                        CustomName: Self::NAME.as_ptr(),
-                        BeginCustomScan: Some(begin_custom_scan::<Self>),
-                        ExecCustomScan: Some(exec_custom_scan::<Self>),
-                        EndCustomScan: Some(end_custom_scan::<Self>),
-                        ReScanCustomScan: Some(rescan_custom_scan::<Self>),
-                        MarkPosCustomScan: Some(mark_pos_custom_scan::<Self>),
-                        RestrPosCustomScan: Some(restr_pos_custom_scan::<Self>),
-                        EstimateDSMCustomScan: None,
-                        InitializeDSMCustomScan: None,
-                        ReInitializeDSMCustomScan: None,
-                        InitializeWorkerCustomScan: None,
-                        ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
-                        ExplainCustomScan: Some(explain_custom_scan::<Self>),
-                    },
-                );
-            }
-            METHODS
+            static mut METHODS: Lazy<FxHashMap<TypeId, *mut pg_sys::CustomExecMethods>> =
+                Lazy::new(Default::default);
+
+            leak_once::<Self, _>(&mut *addr_of_mut!(METHODS), || pg_sys::CustomExecMethods {
+                CustomName: Self::NAME.as_ptr(),
+                BeginCustomScan: Some(begin_custom_scan::<Self>),
+                ExecCustomScan: Some(exec_custom_scan::<Self>),
+                EndCustomScan: Some(end_custom_scan::<Self>),
+                ReScanCustomScan: Some(rescan_custom_scan::<Self>),
+                MarkPosCustomScan: Some(mark_pos_custom_scan::<Self>),
+                RestrPosCustomScan: Some(restr_pos_custom_scan::<Self>),
+                EstimateDSMCustomScan: None,
+                InitializeDSMCustomScan: None,
+                ReInitializeDSMCustomScan: None,
+                InitializeWorkerCustomScan: None,
+                ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
+                ExplainCustomScan: Some(explain_custom_scan::<Self>),
+            })
         }
     }
 
@@ -218,28 +210,24 @@ where
 {
     fn exec_methods() -> *const pg_sys::CustomExecMethods {
         unsafe {
-            static mut METHODS: *mut pg_sys::CustomExecMethods = std::ptr::null_mut();
-
-            if METHODS.is_null() {
-                METHODS = PgMemoryContexts::TopMemoryContext.leak_and_drop_on_delete(
-                    pg_sys::CustomExecMethods {
-                        CustomName: Self::NAME.as_ptr(),
-                        BeginCustomScan: Some(begin_custom_scan::<Self>),
-                        ExecCustomScan: Some(exec_custom_scan::<Self>),
-                        EndCustomScan: Some(end_custom_scan::<Self>),
-                        ReScanCustomScan: Some(rescan_custom_scan::<Self>),
-                        MarkPosCustomScan: None,
-                        RestrPosCustomScan: None,
-                        EstimateDSMCustomScan: Some(estimate_dsm_custom_scan::<Self>),
-                        InitializeDSMCustomScan: Some(initialize_dsm_custom_scan::<Self>),
-                        ReInitializeDSMCustomScan: Some(reinitialize_dsm_custom_scan::<Self>),
-                        InitializeWorkerCustomScan: Some(initialize_worker_custom_scan::<Self>),
-                        ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
-                        ExplainCustomScan: Some(explain_custom_scan::<Self>),
-                    },
-                );
-            }
-            METHODS
+            static mut METHODS: Lazy<FxHashMap<TypeId, *mut pg_sys::CustomExecMethods>> =
+                Lazy::new(Default::default);
+
+            leak_once::<Self, _>(&mut *addr_of_mut!(METHODS), || pg_sys::CustomExecMethods {
+                CustomName: Self::NAME.as_ptr(),
+                BeginCustomScan: Some(begin_custom_scan::<Self>),
+                ExecCustomScan: Some(exec_custom_scan::<Self>),
+                EndCustomScan: Some(end_custom_scan::<Self>),
+                ReScanCustomScan: Some(rescan_custom_scan::<Self>),
+                MarkPosCustomScan: None,
+                RestrPosCustomScan: None,
+                EstimateDSMCustomScan: Some(estimate_dsm_custom_scan::<Self>),
+                InitializeDSMCustomScan: Some(initialize_dsm_custom_scan::<Self>),
+                ReInitializeDSMCustomScan: Some(reinitialize_dsm_custom_scan::<Self>),
+                InitializeWorkerCustomScan: Some(initialize_worker_custom_scan::<Self>),
+                ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
+                ExplainCustomScan: Some(explain_custom_scan::<Self>),
+            })
         }
     }
 
@@ -274,32 +262,42 @@ where
 {
     fn exec_methods() -> *const pg_sys::CustomExecMethods {
         unsafe {
-            static mut METHODS: *mut pg_sys::CustomExecMethods = std::ptr::null_mut();
-
-            if METHODS.is_null() {
-                METHODS = PgMemoryContexts::TopMemoryContext.leak_and_drop_on_delete(
-                    pg_sys::CustomExecMethods {
-                        CustomName: Self::NAME.as_ptr(),
-                        BeginCustomScan: Some(begin_custom_scan::<Self>),
-                        ExecCustomScan: Some(exec_custom_scan::<Self>),
-                        EndCustomScan: Some(end_custom_scan::<Self>),
-                        ReScanCustomScan: Some(rescan_custom_scan::<Self>),
-                        MarkPosCustomScan: Some(mark_pos_custom_scan::<Self>),
-                        RestrPosCustomScan: Some(restr_pos_custom_scan::<Self>),
-                        EstimateDSMCustomScan: Some(estimate_dsm_custom_scan::<Self>),
-                        InitializeDSMCustomScan: Some(initialize_dsm_custom_scan::<Self>),
-                        ReInitializeDSMCustomScan: Some(reinitialize_dsm_custom_scan::<Self>),
-                        InitializeWorkerCustomScan: Some(initialize_worker_custom_scan::<Self>),
-                        ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
-                        ExplainCustomScan: Some(explain_custom_scan::<Self>),
-                    },
-                );
-            }
-            METHODS
+            static mut METHODS: Lazy<FxHashMap<TypeId, *mut pg_sys::CustomExecMethods>> =
+                Lazy::new(Default::default);
+
+            leak_once::<Self, _>(&mut *addr_of_mut!(METHODS), || pg_sys::CustomExecMethods {
+                CustomName: Self::NAME.as_ptr(),
+                BeginCustomScan: Some(begin_custom_scan::<Self>),
+                ExecCustomScan: Some(exec_custom_scan::<Self>),
+                EndCustomScan: Some(end_custom_scan::<Self>),
+                ReScanCustomScan: Some(rescan_custom_scan::<Self>),
+                MarkPosCustomScan: Some(mark_pos_custom_scan::<Self>),
+                RestrPosCustomScan: Some(restr_pos_custom_scan::<Self>),
+                EstimateDSMCustomScan: Some(estimate_dsm_custom_scan::<Self>),
+                InitializeDSMCustomScan: Some(initialize_dsm_custom_scan::<Self>),
+                ReInitializeDSMCustomScan: Some(reinitialize_dsm_custom_scan::<Self>),
+                InitializeWorkerCustomScan: Some(initialize_worker_custom_scan::<Self>),
+                ShutdownCustomScan: Some(shutdown_custom_scan::<Self>),
+                ExplainCustomScan: Some(explain_custom_scan::<Self>),
+            })
         }
     }
 }
 
+/// Return the `T` that belongs to the [`CustomScan`] `CS`, leaking it into Postgres'
+/// "TopMemoryContext" the first time it's asked for.
+///
+/// A `static` declared inside a generic function is shared by every monomorphized copy of that
+/// function, so the values are kept in a map keyed by the [`CustomScan`]'s type.
+unsafe fn leak_once<CS: CustomScan, T>(
+    values: &mut FxHashMap<TypeId, *mut T>,
+    init: impl FnOnce() -> T,
+) -> *mut T {
+    *values
+        .entry(TypeId::of::<CS>())
+        .or_insert_with(|| PgMemoryContexts::TopMemoryContext.leak_and_drop_on_delete(init()))
+}
+
 /// Helper function for wrapping a raw [`pg_sys::CustomScanState`] pointer with something more
 /// usable by implementers
 fn wrap_custom_scan_state<CS: CustomScan>(
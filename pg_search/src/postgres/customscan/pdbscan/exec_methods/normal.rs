@@ -15,37 +15,73 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::index::reader::SearchResults;
-use crate::index::SearchIndex;
+use crate::index::reader::{SearchIndexReader, SearchResults};
 use crate::postgres::customscan::pdbscan::exec_methods::{ExecMethod, ExecState};
 use crate::postgres::customscan::pdbscan::scan_state::PdbScanState;
+use crate::postgres::parallel::Bm25ParallelScanState;
 use pgrx::pg_sys;
+use tantivy::query::{Query, QueryClone};
 
 #[derive(Default)]
 pub struct NormalScanExecState {
+    need_scores: bool,
+    search_reader: Option<SearchIndexReader>,
+    query: Option<Box<dyn Query>>,
+    parallel_state: Option<*mut Bm25ParallelScanState>,
     search_results: SearchResults,
 }
 
+impl NormalScanExecState {
+    /// Claim the next segment nobody else in the parallel scan has searched yet, and search it
+    fn claim_segment(&mut self) -> bool {
+        let Some(parallel_state) = self.parallel_state else {
+            return false;
+        };
+
+        match unsafe { (*parallel_state).claim_segment() } {
+            None => false,
+            Some(segment_ord) => {
+                self.search_results = self.search_reader.as_ref().unwrap().search_segment(
+                    self.need_scores,
+                    segment_ord,
+                    self.query.as_ref().unwrap(),
+                );
+                true
+            }
+        }
+    }
+}
+
 impl ExecMethod for NormalScanExecState {
     fn init(&mut self, state: &PdbScanState, _cstate: *mut pg_sys::CustomScanState) {
         let search_reader = state.search_reader.as_ref().unwrap();
         let query = state.query.as_ref().unwrap();
-        self.search_results = search_reader.search_via_channel(
-            state.need_scores,
-            false,
-            SearchIndex::executor(),
-            query,
-        );
+
+        self.need_scores = state.need_scores;
+        self.parallel_state = state.parallel_state;
+        if self.parallel_state.is_some() {
+            // we're one participant in a parallel scan, so we only search the segments we claim
+            self.search_reader = Some(search_reader.clone());
+            self.query = Some(query.box_clone());
+            self.search_results = SearchResults::None;
+        } else {
+            self.search_results = search_reader.search(state.need_scores, false, query);
+        }
     }
 
     fn next(&mut self) -> ExecState {
-        match self.search_results.next() {
-            None => ExecState::Eof,
-            Some((scored, doc_address)) => ExecState::RequiresVisibilityCheck {
-                ctid: scored.ctid,
-                score: scored.bm25,
-                doc_address,
-            },
+        loop {
+            match self.search_results.next() {
+                None if self.claim_segment() => continue,
+                None => return ExecState::Eof,
+                Some((scored, doc_address)) => {
+                    return ExecState::RequiresVisibilityCheck {
+                        ctid: scored.ctid,
+                        score: scored.bm25,
+                        doc_address,
+                    }
+                }
+            }
         }
     }
 }
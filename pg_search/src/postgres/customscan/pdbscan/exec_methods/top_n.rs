@@ -15,9 +15,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::index::reader::{SearchIndexReader, SearchResults};
+use crate::index::reader::{OrderByInfo, SearchIndexReader, SearchResults};
 use crate::index::SearchIndex;
-use crate::postgres::customscan::builders::custom_path::SortDirection;
 use crate::postgres::customscan::pdbscan::exec_methods::{ExecMethod, ExecState};
 use crate::postgres::customscan::pdbscan::scan_state::PdbScanState;
 use pgrx::{direct_function_call, pg_sys, IntoDatum};
@@ -32,13 +31,12 @@ pub struct TopNScanExecState {
     // required
     heaprelid: pg_sys::Oid,
     limit: usize,
-    sort_direction: SortDirection,
 
     // set during init
     have_less: bool,
     query: Option<Box<dyn Query>>,
     search_reader: Option<SearchIndexReader>,
-    sort_field: Option<String>,
+    order_by: Vec<OrderByInfo>,
     search_results: SearchResults,
 
     // state tracking
@@ -49,11 +47,10 @@ pub struct TopNScanExecState {
 }
 
 impl TopNScanExecState {
-    pub fn new(heaprelid: pg_sys::Oid, limit: usize, sort_direction: SortDirection) -> Self {
+    pub fn new(heaprelid: pg_sys::Oid, limit: usize) -> Self {
         Self {
             heaprelid,
             limit,
-            sort_direction,
             ..Default::default()
         }
     }
@@ -61,17 +58,16 @@ impl TopNScanExecState {
 
 impl ExecMethod for TopNScanExecState {
     fn init(&mut self, state: &PdbScanState, _cstate: *mut pg_sys::CustomScanState) {
-        let sort_field = state.sort_field.clone();
+        let order_by = state.order_by.clone();
         let search_reader = state.search_reader.as_ref().unwrap();
         let query = state.query.as_ref().map(|q| q.box_clone());
 
         self.query = query;
-        self.sort_field = sort_field;
+        self.order_by = order_by;
         self.search_results = search_reader.search_top_n(
             SearchIndex::executor(),
             self.query.as_ref().unwrap(),
-            self.sort_field.clone(),
-            self.sort_direction.into(),
+            &self.order_by,
             self.limit,
         );
 
@@ -138,8 +134,7 @@ impl ExecMethod for TopNScanExecState {
                 let mut results = self.search_reader.as_ref().unwrap().search_top_n(
                     SearchIndex::executor(),
                     self.query.as_ref().unwrap(),
-                    self.sort_field.clone(),
-                    self.sort_direction.into(),
+                    &self.order_by,
                     self.chunk_size,
                 );
 
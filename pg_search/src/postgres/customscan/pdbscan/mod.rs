@@ -19,7 +19,7 @@
 mod exec_methods;
 mod privdat;
 mod projections;
-mod qual_inspect;
+pub(crate) mod qual_inspect;
 mod scan_state;
 
 use crate::api::operator::{
@@ -27,7 +27,9 @@ use crate::api::operator::{
 };
 use crate::api::{AsCStr, AsInt, Cardinality};
 use crate::index::SearchIndex;
-use crate::postgres::customscan::builders::custom_path::{CustomPathBuilder, Flags, OrderByStyle};
+use crate::postgres::customscan::builders::custom_path::{
+    CustomPathBuilder, Flags, OrderByStyle, SortDirection,
+};
 use crate::postgres::customscan::builders::custom_scan::CustomScanBuilder;
 use crate::postgres::customscan::builders::custom_state::{
     CustomScanStateBuilder, CustomScanStateWrapper,
@@ -45,8 +47,9 @@ use crate::postgres::customscan::pdbscan::projections::{
 };
 use crate::postgres::customscan::pdbscan::qual_inspect::extract_quals;
 use crate::postgres::customscan::pdbscan::scan_state::PdbScanState;
-use crate::postgres::customscan::{CustomScan, CustomScanState, ExecMethod, PlainExecCapable};
-use crate::postgres::index::open_search_index;
+use crate::postgres::customscan::{CustomScan, CustomScanState, ExecMethod, ParallelQueryCapable};
+use crate::postgres::index::{open_search_index, open_search_index_at, partition_statistics};
+use crate::postgres::parallel::Bm25ParallelScanState;
 use crate::postgres::rel_get_bm25_index;
 use crate::postgres::visibility_checker::VisibilityChecker;
 use crate::query::SearchQueryInput;
@@ -67,11 +70,58 @@ pub struct PdbScan;
 
 impl ExecMethod for PdbScan {
     fn exec_methods() -> *const CustomExecMethods {
-        <PdbScan as PlainExecCapable>::exec_methods()
+        <PdbScan as ParallelQueryCapable>::exec_methods()
     }
 }
 
-impl PlainExecCapable for PdbScan {}
+impl ParallelQueryCapable for PdbScan {
+    fn estimate_dsm_custom_scan(
+        state: &mut CustomScanStateWrapper<Self>,
+        pcxt: *mut pg_sys::ParallelContext,
+    ) -> pg_sys::Size {
+        size_of::<Bm25ParallelScanState>()
+    }
+
+    fn initialize_dsm_custom_scan(
+        state: &mut CustomScanStateWrapper<Self>,
+        pcxt: *mut pg_sys::ParallelContext,
+        coordinate: *mut std::os::raw::c_void,
+    ) {
+        unsafe {
+            let parallel_state = coordinate.cast::<Bm25ParallelScanState>();
+            Bm25ParallelScanState::init(parallel_state);
+            state.custom_state_mut().parallel_state = Some(parallel_state);
+
+            // now that we know the scan is shared, we can start it and make all of the index's
+            // segments, as we've opened it, available to be claimed.  This happens before any
+            // workers are launched, and they open the index exactly as we have
+            PdbScan::rescan_custom_scan(state);
+            (*parallel_state).publish(state.custom_state().search_reader.as_ref().unwrap());
+        }
+    }
+
+    fn reinitialize_dsm_custom_scan(
+        state: &mut CustomScanStateWrapper<Self>,
+        pcxt: *mut pg_sys::ParallelContext,
+        coordinate: *mut std::os::raw::c_void,
+    ) {
+        // make every segment claimable again.  This only ever happens in the leader, before the
+        // workers are relaunched, so a segment can never be handed out twice during one scan
+        unsafe {
+            let parallel_state = coordinate.cast::<Bm25ParallelScanState>();
+            (*parallel_state).publish(state.custom_state().search_reader.as_ref().unwrap());
+        }
+    }
+
+    fn initialize_worker_custom_scan(
+        state: &mut CustomScanStateWrapper<Self>,
+        toc: *mut pg_sys::shm_toc,
+        coordinate: *mut std::os::raw::c_void,
+    ) {
+        state.custom_state_mut().parallel_state = Some(coordinate.cast());
+        PdbScan::rescan_custom_scan(state);
+    }
+}
 
 impl CustomScan for PdbScan {
     const NAME: &'static CStr = c"ParadeDB Scan";
@@ -108,7 +158,7 @@ impl CustomScan for PdbScan {
             let root = builder.args().root;
             let search_index =
                 open_search_index(&bm25_index).expect("should be able to open search index");
-            let pathkey = pullup_orderby_pathkey(&mut builder, rti, &search_index, root);
+            let pathkeys = pullup_orderby_pathkeys(&mut builder, rti, &search_index, root);
 
             #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
             let baserels = (*builder.args().root).all_baserels;
@@ -116,8 +166,8 @@ impl CustomScan for PdbScan {
             let baserels = (*builder.args().root).all_query_rels;
 
             let limit = if
-            // we can only use the limit if we have an orderby score pathkey
-            pathkey.is_some()
+            // we can only use the limit if every orderby pathkey is one we can sort by
+            pathkeys.is_some()
                 && (*builder.args().root).limit_tuples > -1.0
 
                 // and if the path is for sole base relation
@@ -159,19 +209,20 @@ impl CustomScan for PdbScan {
                 builder.custom_private().set_range_table_index(rti);
                 builder.custom_private().set_quals(restrict_info);
 
-                if limit.is_some() && pathkey.is_some() {
+                if let (Some(_), Some(pathkeys)) = (limit, &pathkeys) {
                     // sorting by a field only works if we're not doing const projections
                     //
                     // and sorting by score always works
                     if !(maybe_needs_const_projections
-                        && matches!(&pathkey, Some(OrderByStyle::Field(..))))
+                        && pathkeys
+                            .iter()
+                            .any(|style| matches!(style, OrderByStyle::Field(..))))
                     {
-                        builder = builder.add_path_key(&pathkey);
-                        builder.custom_private().set_sort_field(&pathkey);
+                        for style in pathkeys {
+                            builder = builder.add_path_key(style);
+                        }
+                        builder.custom_private().set_order_by(pathkeys);
                         builder.custom_private().set_limit(limit);
-                        builder
-                            .custom_private()
-                            .set_sort_direction(pathkey.map(|style| style.direction()));
                     }
                 }
 
@@ -206,6 +257,20 @@ impl CustomScan for PdbScan {
                 builder = builder.set_total_cost(total_cost + cpu_run_cost);
                 builder = builder.set_flag(Flags::Projection);
 
+                // a large enough table can have its index segments split across parallel
+                // workers, but a Top-N scan needs to see every segment to find the best N
+                if builder.args().rel().consider_parallel && !is_join && limit.is_none() {
+                    let nworkers = pg_sys::compute_parallel_worker(
+                        builder.args().rel,
+                        builder.args().rel().pages as f64,
+                        -1.0,
+                        pg_sys::max_parallel_workers_per_gather,
+                    );
+                    if nworkers > 0 {
+                        builder = builder.set_parallel(nworkers as usize);
+                    }
+                }
+
                 return Some(builder.build());
             }
         }
@@ -263,6 +328,7 @@ impl CustomScan for PdbScan {
             builder
                 .custom_private_mut()
                 .set_var_attname_lookup(attname_lookup.into_pg());
+            builder.custom_private_mut().strip_restrict_info();
             builder.build()
         }
     }
@@ -298,8 +364,7 @@ impl CustomScan for PdbScan {
 
             // information about if we're sorted by score and our limit
             builder.custom_state().limit = builder.custom_private().limit();
-            builder.custom_state().sort_field = builder.custom_private().sort_field();
-            builder.custom_state().sort_direction = builder.custom_private().sort_direction();
+            builder.custom_state().order_by = builder.custom_private().order_by();
 
             // store our query quals into our custom state too
             let quals = builder
@@ -367,13 +432,13 @@ impl CustomScan for PdbScan {
                     .collect();
 
             let need_snippets = builder.custom_state().need_snippets();
-            if let Some((limit, sort_direction)) = builder.custom_state().is_top_n_capable() {
+            if let Some((limit, _)) = builder.custom_state().is_top_n_capable() {
                 // having a valid limit and sort direction means we can do a TopN query
                 // and TopN can do snippets
                 let heaprelid = builder.custom_state().heaprelid;
                 builder
                     .custom_state()
-                    .assign_exec_method(TopNScanExecState::new(heaprelid, limit, sort_direction));
+                    .assign_exec_method(TopNScanExecState::new(heaprelid, limit));
             } else if need_snippets {
                 // if snippets are required then the query goes through a normal scan
                 builder
@@ -416,16 +481,29 @@ impl CustomScan for PdbScan {
         }
 
         explainer.add_bool("Scores", state.custom_state().need_scores());
-        if let (Some(sort_direction), Some(limit)) = (
-            state.custom_state().sort_direction,
+        if let (Some(limit), false) = (
             state.custom_state().limit,
+            state.custom_state().order_by.is_empty(),
         ) {
-            if let Some(sort_field) = &state.custom_state().sort_field {
-                explainer.add_text("   Sort Field", sort_field);
-            } else {
-                explainer.add_text("   Sort Field", "paradedb.score()");
-            }
-            explainer.add_text("   Sort Direction", sort_direction);
+            let order_by = &state.custom_state().order_by;
+            let sort_fields = order_by
+                .iter()
+                .map(|info| info.field.as_deref().unwrap_or("paradedb.score()"))
+                .collect::<Vec<_>>();
+            let sort_directions = order_by
+                .iter()
+                .map(|info| {
+                    let direction = SortDirection::from(info.direction);
+                    // only mention the nulls ordering when it's not Postgres' default
+                    match (direction, info.nulls_first) {
+                        (SortDirection::Asc, true) => format!("{direction} nulls first"),
+                        (SortDirection::Desc, false) => format!("{direction} nulls last"),
+                        _ => direction.to_string(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            explainer.add_text("   Sort Field", sort_fields.join(", "));
+            explainer.add_text("   Sort Direction", sort_directions.join(", "));
             explainer.add_unsigned_integer("   Top N Limit", limit as u64, None);
             if explainer.is_analyze() && state.custom_state().retry_count > 0 {
                 explainer.add_unsigned_integer(
@@ -487,6 +565,13 @@ impl CustomScan for PdbScan {
             return;
         }
 
+        if unsafe { (*(*state.planstate()).plan).parallel_aware } {
+            // the scan is started by `initialize_dsm_custom_scan()` or `initialize_worker_custom_scan()`
+            // once we know if it's shared with other parallel workers, or lazily by
+            // `exec_custom_scan()` if it turns out not to be
+            return;
+        }
+
         PdbScan::rescan_custom_scan(state)
     }
 
@@ -501,17 +586,45 @@ impl CustomScan for PdbScan {
             .map(|indexrel| unsafe { PgRelation::from_pg(*indexrel) })
             .expect("custom_state.indexrel should already be open");
 
-        let search_index =
-            open_search_index(&indexrel).expect("should be able to open search index");
-        let search_reader = search_index
+        // every participant of a parallel scan reads the index as the leader opened it, so that
+        // the segments they claim are the same ones
+        let published_catalog = state
+            .custom_state()
+            .parallel_state
+            .and_then(|parallel_state| unsafe { (*parallel_state).published_catalog() });
+        let search_index = match published_catalog {
+            Some(catalog_start) => open_search_index_at(&indexrel, catalog_start),
+            None => open_search_index(&indexrel),
+        }
+        .expect("should be able to open search index");
+        let mut search_reader = search_index
             .get_reader()
             .expect("search index reader should have been constructed correctly");
+        if let (Some(parallel_state), Some(_)) =
+            (state.custom_state().parallel_state, published_catalog)
+        {
+            unsafe { (*parallel_state).assert_segment_count(&search_reader) };
+        }
+        if state.custom_state().scores_documents() {
+            // if we're scanning one partition of a partitioned table, the scores of the documents
+            // each partition finds are only comparable if they're computed from the same statistics
+            if let Some(statistics) =
+                partition_statistics(&indexrel, &search_index.schema, state.csstate.ss.ps.state)
+                    .expect("should be able to open the indexes of the other partitions")
+            {
+                search_reader.use_partition_statistics(statistics);
+            }
+        }
         let query = search_index.query(
             &indexrel,
             &state.custom_state().search_query_input,
             &search_reader,
         );
 
+        state
+            .custom_state_mut()
+            .visibility_checker()
+            .set_redirects(search_reader.redirects.clone());
         state.custom_state_mut().search_reader = Some(search_reader);
         state.custom_state_mut().query = Some(query);
 
@@ -542,6 +655,11 @@ impl CustomScan for PdbScan {
 
     #[allow(clippy::blocks_in_conditions)]
     fn exec_custom_scan(state: &mut CustomScanStateWrapper<Self>) -> *mut pg_sys::TupleTableSlot {
+        if state.custom_state().search_reader.is_none() {
+            // we're parallel-aware, but Postgres decided not to run us in parallel after all
+            PdbScan::rescan_custom_scan(state);
+        }
+
         loop {
             // get the next matching document from our search results and look for it in the heap
             match state.custom_state_mut().exec_method().next() {
@@ -702,56 +820,68 @@ unsafe fn maybe_rebuild_projinfo_for_const_projection(
     )
 }
 
-unsafe fn pullup_orderby_pathkey<P: Into<*mut pg_sys::List> + Default>(
+/// Find how to sort by every one of the query's orderby pathkeys.  If any of them can't be
+/// sorted by using the index, then none of them can.
+unsafe fn pullup_orderby_pathkeys<P: Into<*mut pg_sys::List> + Default>(
     builder: &mut CustomPathBuilder<P>,
     rti: pg_sys::Index,
     search_index: &SearchIndex,
     root: *mut pg_sys::PlannerInfo,
-) -> Option<OrderByStyle> {
+) -> Option<Vec<OrderByStyle>> {
     let pathkeys = PgList::<pg_sys::PathKey>::from_pg((*builder.args().root).query_pathkeys);
+    if pathkeys.is_empty() {
+        return None;
+    }
 
-    if let Some(first_pathkey) = pathkeys.get_ptr(0) {
-        let equivclass = (*first_pathkey).pk_eclass;
-        let members = PgList::<pg_sys::EquivalenceMember>::from_pg((*equivclass).ec_members);
-
-        for member in members.iter_ptr() {
-            let expr = (*member).em_expr;
+    pathkeys
+        .iter_ptr()
+        .map(|pathkey| pullup_orderby_pathkey(pathkey, rti, search_index, root))
+        .collect()
+}
 
-            if is_score_func(expr.cast(), rti as _) {
-                return Some(OrderByStyle::Score(first_pathkey));
-            } else if let Some(var) = is_lower_func(expr.cast(), rti as _) {
-                let (heaprelid, attno, _) = find_var_relation(var, root);
-                let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
-                let tupdesc = heaprel.tuple_desc();
-                if let Some(att) = tupdesc.get(attno as usize - 1) {
-                    if search_index.schema.is_field_lower_sortable(att.name()) {
-                        return Some(OrderByStyle::Field(first_pathkey, att.name().to_string()));
-                    }
-                }
-            } else if let Some(relabel) = nodecast!(RelabelType, T_RelabelType, expr) {
-                if let Some(var) = nodecast!(Var, T_Var, (*relabel).arg) {
-                    let (heaprelid, attno, _) = find_var_relation(var, root);
-                    let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
-                    let tupdesc = heaprel.tuple_desc();
-                    if let Some(att) = tupdesc.get(attno as usize - 1) {
-                        if search_index.schema.is_field_raw_sortable(att.name()) {
-                            return Some(OrderByStyle::Field(
-                                first_pathkey,
-                                att.name().to_string(),
-                            ));
-                        }
-                    }
+unsafe fn pullup_orderby_pathkey(
+    pathkey: *mut pg_sys::PathKey,
+    rti: pg_sys::Index,
+    search_index: &SearchIndex,
+    root: *mut pg_sys::PlannerInfo,
+) -> Option<OrderByStyle> {
+    let equivclass = (*pathkey).pk_eclass;
+    let members = PgList::<pg_sys::EquivalenceMember>::from_pg((*equivclass).ec_members);
+
+    for member in members.iter_ptr() {
+        let expr = (*member).em_expr;
+
+        if is_score_func(expr.cast(), rti as _) {
+            return Some(OrderByStyle::Score(pathkey));
+        } else if let Some(var) = is_lower_func(expr.cast(), rti as _) {
+            let (heaprelid, attno, _) = find_var_relation(var, root);
+            let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
+            let tupdesc = heaprel.tuple_desc();
+            if let Some(att) = tupdesc.get(attno as usize - 1) {
+                if search_index.schema.is_field_lower_sortable(att.name()) {
+                    return Some(OrderByStyle::Field(pathkey, att.name().to_string()));
                 }
-            } else if let Some(var) = nodecast!(Var, T_Var, expr) {
+            }
+        } else if let Some(relabel) = nodecast!(RelabelType, T_RelabelType, expr) {
+            if let Some(var) = nodecast!(Var, T_Var, (*relabel).arg) {
                 let (heaprelid, attno, _) = find_var_relation(var, root);
                 let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
                 let tupdesc = heaprel.tuple_desc();
                 if let Some(att) = tupdesc.get(attno as usize - 1) {
                     if search_index.schema.is_field_raw_sortable(att.name()) {
-                        return Some(OrderByStyle::Field(first_pathkey, att.name().to_string()));
+                        return Some(OrderByStyle::Field(pathkey, att.name().to_string()));
                     }
                 }
             }
+        } else if let Some(var) = nodecast!(Var, T_Var, expr) {
+            let (heaprelid, attno, _) = find_var_relation(var, root);
+            let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
+            let tupdesc = heaprel.tuple_desc();
+            if let Some(att) = tupdesc.get(attno as usize - 1) {
+                if search_index.schema.is_field_raw_sortable(att.name()) {
+                    return Some(OrderByStyle::Field(pathkey, att.name().to_string()));
+                }
+            }
         }
     }
     None
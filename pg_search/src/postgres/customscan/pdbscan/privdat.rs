@@ -17,8 +17,8 @@
 
 use crate::api::operator::anyelement_query_input_opoid;
 use crate::api::Cardinality;
+use crate::index::reader::OrderByInfo;
 use crate::postgres::customscan::builders::custom_path::OrderByStyle;
-use crate::postgres::customscan::builders::custom_path::SortDirection;
 use crate::postgres::customscan::pdbscan::qual_inspect::{extract_quals, Qual};
 use pgrx::{pg_sys, PgList};
 
@@ -29,8 +29,7 @@ pub struct PrivateData {
     range_table_index: Option<pg_sys::Index>,
     restrict_info: Option<*mut pg_sys::List>,
     limit: Option<usize>,
-    sort_field: Option<String>,
-    sort_direction: Option<SortDirection>,
+    order_by: Vec<OrderByInfo>,
     var_attname_lookup: Option<*mut pg_sys::List>,
     maybe_ff: bool,
 }
@@ -68,21 +67,34 @@ impl PrivateData {
         self.restrict_info = Some(quals.into_pg())
     }
 
-    pub fn set_limit(&mut self, limit: Option<Cardinality>) {
-        self.limit = limit.map(|l| l.round() as usize);
-    }
-
-    pub fn set_sort_field(&mut self, pathkey: &Option<OrderByStyle>) {
-        if let Some(style) = pathkey {
-            match style {
-                OrderByStyle::Score(_) => {}
-                OrderByStyle::Field(_, name) => self.sort_field = Some(name.clone()),
+    /// Replace the quals' `RestrictInfo` nodes with the bare clauses they wrap.  The plan is copied
+    /// to parallel workers with `nodeToString()`, which can't represent a `RestrictInfo`.
+    pub fn strip_restrict_info(&mut self) {
+        if let Some(restrict_info) = self.restrict_info {
+            unsafe {
+                let restrict_info = PgList::<pg_sys::RestrictInfo>::from_pg(restrict_info);
+                let mut clauses = PgList::<pg_sys::Expr>::new();
+                for ri in restrict_info.iter_ptr() {
+                    clauses.push((*ri).clause);
+                }
+                self.restrict_info = Some(clauses.into_pg());
             }
         }
     }
 
-    pub fn set_sort_direction(&mut self, direction: Option<SortDirection>) {
-        self.sort_direction = direction;
+    pub fn set_limit(&mut self, limit: Option<Cardinality>) {
+        self.limit = limit.map(|l| l.round() as usize);
+    }
+
+    pub fn set_order_by(&mut self, pathkeys: &[OrderByStyle]) {
+        self.order_by = pathkeys
+            .iter()
+            .map(|style| OrderByInfo {
+                field: style.field_name().map(String::from),
+                direction: style.direction().into(),
+                nulls_first: style.nulls_first(),
+            })
+            .collect();
     }
 
     pub fn set_var_attname_lookup(&mut self, var_attname_lookup: *mut pg_sys::List) {
@@ -128,12 +140,8 @@ impl PrivateData {
         self.limit
     }
 
-    pub fn sort_field(&self) -> Option<String> {
-        self.sort_field.clone()
-    }
-
-    pub fn sort_direction(&self) -> Option<SortDirection> {
-        self.sort_direction
+    pub fn order_by(&self) -> Vec<OrderByInfo> {
+        self.order_by.clone()
     }
 
     pub fn var_attname_lookup(&self) -> Option<PgList<pg_sys::Node>> {
@@ -148,6 +156,7 @@ impl PrivateData {
 
 #[allow(non_snake_case)]
 mod serialize {
+    use crate::postgres::customscan::builders::custom_path::SortDirection;
     use crate::postgres::customscan::pdbscan::privdat::PrivateData;
     use pgrx::pg_sys::AsPgCStr;
     use pgrx::{pg_sys, PgList};
@@ -189,20 +198,27 @@ mod serialize {
     pub unsafe fn serialize(privdat: PrivateData) -> PgList<pg_sys::Node> {
         let mut ser = PgList::new();
 
-        if privdat.sort_direction.is_none() {
+        if privdat.order_by.is_empty() {
             assert!(
                 privdat.limit.is_none(),
-                "internal error:  cannot have a limit without also sorting by score"
+                "internal error:  cannot have a limit without also sorting"
             );
         }
 
+        // each sort key is a triplet of (field name, direction, nulls first) List entries
+        let mut order_by = PgList::<pg_sys::Node>::new();
+        for info in &privdat.order_by {
+            order_by.push(makeString(info.field.as_ref()));
+            order_by.push(makeInteger(Some(SortDirection::from(info.direction))));
+            order_by.push(makeBoolean(Some(info.nulls_first)));
+        }
+
         ser.push(makeInteger(privdat.heaprelid));
         ser.push(makeInteger(privdat.indexrelid));
         ser.push(makeInteger(privdat.range_table_index));
         ser.push(unwrapOrNull(privdat.restrict_info.map(|l| l.cast())));
         ser.push(makeString(privdat.limit));
-        ser.push(makeString(privdat.sort_field));
-        ser.push(makeInteger(privdat.sort_direction));
+        ser.push(order_by.into_pg().cast());
         ser.push(unwrapOrNull(
             privdat.var_attname_lookup.map(|v| v.cast::<pg_sys::Node>()),
         ));
@@ -215,7 +231,9 @@ mod serialize {
 #[allow(non_snake_case)]
 mod deserialize {
     use crate::api::{AsBool, AsCStr, AsInt};
+    use crate::index::reader::OrderByInfo;
     use crate::nodecast;
+    use crate::postgres::customscan::builders::custom_path::SortDirection;
     use crate::postgres::customscan::pdbscan::privdat::PrivateData;
     use pgrx::{pg_sys, PgList};
     use std::str::FromStr;
@@ -238,6 +256,24 @@ mod deserialize {
         node.as_bool().map(|b| b.into())
     }
 
+    unsafe fn decodeOrderBy(list: *mut pg_sys::List) -> Vec<OrderByInfo> {
+        let list = PgList::<pg_sys::Node>::from_pg(list);
+        let mut iter = list.iter_ptr();
+        let mut order_by = Vec::new();
+        while let (Some(field), Some(direction), Some(nulls_first)) =
+            (iter.next(), iter.next(), iter.next())
+        {
+            order_by.push(OrderByInfo {
+                field: decodeString(field),
+                direction: decodeInteger::<SortDirection>(direction)
+                    .expect("sort direction should be set")
+                    .into(),
+                nulls_first: decodeBoolean(nulls_first).unwrap_or_default(),
+            });
+        }
+        order_by
+    }
+
     pub unsafe fn deserialize(input: *mut pg_sys::List) -> PrivateData {
         let input = PgList::<pg_sys::Node>::from_pg(input);
         PrivateData {
@@ -246,13 +282,16 @@ mod deserialize {
             range_table_index: input.get_ptr(2).and_then(|n| decodeInteger(n)),
             restrict_info: input.get_ptr(3).and_then(|n| nodecast!(List, T_List, n)),
             limit: input.get_ptr(4).and_then(|n| decodeString(n)),
-            sort_field: input.get_ptr(5).and_then(|n| decodeString(n)),
-            sort_direction: input.get_ptr(6).and_then(|n| decodeInteger(n)),
+            order_by: input
+                .get_ptr(5)
+                .and_then(|n| nodecast!(List, T_List, n, true))
+                .map(|list| decodeOrderBy(list))
+                .unwrap_or_default(),
             var_attname_lookup: input
-                .get_ptr(7)
+                .get_ptr(6)
                 .and_then(|n| nodecast!(List, T_List, n, true)),
             maybe_ff: input
-                .get_ptr(8)
+                .get_ptr(7)
                 .and_then(|n| decodeBoolean(n))
                 .unwrap_or_default(),
         }
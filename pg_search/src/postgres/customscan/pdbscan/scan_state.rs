@@ -16,12 +16,13 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use crate::index::fast_fields_helper::WhichFastField;
-use crate::index::reader::{SearchIndexReader, SearchResults};
+use crate::index::reader::{OrderByInfo, SearchIndexReader, SearchResults};
 use crate::postgres::customscan::builders::custom_path::SortDirection;
 use crate::postgres::customscan::pdbscan::exec_methods::ExecMethod;
 use crate::postgres::customscan::pdbscan::projections::snippet::SnippetInfo;
 use crate::postgres::customscan::CustomScanState;
 use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::parallel::Bm25ParallelScanState;
 use crate::postgres::visibility_checker::VisibilityChecker;
 use crate::query::SearchQueryInput;
 use pgrx::{name_data_to_str, pg_sys, PgRelation};
@@ -42,8 +43,7 @@ pub struct PdbScanState {
     pub which_fast_fields: Option<Vec<WhichFastField>>,
 
     pub limit: Option<usize>,
-    pub sort_field: Option<String>,
-    pub sort_direction: Option<SortDirection>,
+    pub order_by: Vec<OrderByInfo>,
     pub retry_count: usize,
     pub heap_tuple_check_count: usize,
     pub virtual_tuple_count: usize,
@@ -56,6 +56,7 @@ pub struct PdbScanState {
     pub lockmode: pg_sys::LOCKMODE,
 
     pub visibility_checker: Option<VisibilityChecker>,
+    pub parallel_state: Option<*mut Bm25ParallelScanState>,
 
     pub need_scores: bool,
     pub snippet_generators: HashMap<SnippetInfo, Option<SnippetGenerator>>,
@@ -73,8 +74,8 @@ impl CustomScanState for PdbScanState {
     }
 
     fn is_top_n_capable(&self) -> Option<(usize, SortDirection)> {
-        match (self.limit, self.sort_direction) {
-            (Some(limit), Some(sort_direction)) => Some((limit, sort_direction)),
+        match (self.limit, self.order_by.first()) {
+            (Some(limit), Some(first)) => Some((limit, first.direction.into())),
             _ => None,
         }
     }
@@ -100,6 +101,16 @@ impl PdbScanState {
         self.need_scores
     }
 
+    /// Are documents scored, either to return their scores or to order them by?
+    #[inline(always)]
+    pub fn scores_documents(&self) -> bool {
+        self.need_scores
+            || self
+                .order_by
+                .iter()
+                .any(|order_by| order_by.field.is_none())
+    }
+
     #[inline(always)]
     pub fn determine_key_field(&self) -> String {
         unsafe {
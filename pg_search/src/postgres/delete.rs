@@ -15,10 +15,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::index::WriterResources;
+use crate::index::{SearchIndex, SearchIndexWriter, WriterResources};
 use crate::postgres::index::open_search_index;
 use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::utils::{row_to_search_document, u64_to_item_pointer};
 use pgrx::{pg_sys::ItemPointerData, *};
+use std::collections::BTreeSet;
 
 #[pg_guard]
 pub extern "C" fn ambulkdelete(
@@ -57,6 +59,25 @@ pub extern "C" fn ambulkdelete(
             crate::postgres::utils::u64_to_item_pointer(ctid_val, &mut ctid);
             actual_callback(&mut ctid, callback_state)
         };
+
+        // the documents redirected from dead tuples are about to go away, so the later versions
+        // of their rows that are still alive need documents of their own.  Once a row version
+        // has a document, nothing needs to be redirected to it
+        let redirects = search_index
+            .redirects()
+            .unwrap_or_else(|err| panic!("error loading ctid redirects in bulkdelete: {err}"));
+        let rehome = redirects
+            .iter()
+            .filter(|(from, to)| should_delete(*from) && !should_delete(*to))
+            .map(|(_, to)| to)
+            .collect::<BTreeSet<_>>();
+        for (from, to) in redirects.iter() {
+            if should_delete(from) || should_delete(to) || rehome.contains(&to) {
+                writer.directory.remove_redirect(from, to);
+            }
+        }
+        unsafe { index_heap_tuples(&index_relation, &search_index, &writer, rehome) };
+
         match search_index.delete(&reader, &writer, should_delete) {
             Ok((deleted, not_deleted)) => {
                 stats.pages_deleted += deleted;
@@ -74,3 +95,77 @@ pub extern "C" fn ambulkdelete(
 
     stats.into_pg()
 }
+
+/// Index the heap tuples at `ctids`, reading their values from the heap like `CREATE INDEX`
+/// does.  Tuples that no longer exist are skipped.
+unsafe fn index_heap_tuples(
+    index_relation: &PgRelation,
+    search_index: &SearchIndex,
+    writer: &SearchIndexWriter,
+    ctids: impl IntoIterator<Item = u64>,
+) {
+    let heaprel = index_relation
+        .heap_relation()
+        .expect("index should belong to a table");
+    let tableam = heaprel.rd_tableam;
+    let index_info = pg_sys::BuildIndexInfo(index_relation.as_ptr());
+    let estate = pg_sys::CreateExecutorState();
+    let econtext = pg_sys::MakePerTupleExprContext(estate);
+    let slot = pg_sys::table_slot_create(heaprel.as_ptr(), std::ptr::null_mut());
+    (*econtext).ecxt_scantuple = slot;
+    let fetch = (*tableam)
+        .index_fetch_begin
+        .expect("table AM should fetch by ctid")(heaprel.as_ptr());
+    let index_fetch_tuple = (*tableam)
+        .index_fetch_tuple
+        .expect("table AM should fetch by ctid");
+
+    let tupdesc = PgTupleDesc::from_pg_unchecked(index_relation.rd_att);
+    let mut values = [pg_sys::Datum::null(); pg_sys::INDEX_MAX_KEYS as usize];
+    let mut isnull = [false; pg_sys::INDEX_MAX_KEYS as usize];
+    for ctid in ctids {
+        let mut root = ItemPointerData::default();
+        u64_to_item_pointer(ctid, &mut root);
+
+        // any member of the tuple's HOT chain has the same indexed values
+        let mut tid = root;
+        let mut call_again = false;
+        let mut all_dead = false;
+        if !index_fetch_tuple(
+            fetch,
+            &mut tid,
+            std::ptr::addr_of_mut!(pg_sys::SnapshotAnyData),
+            slot,
+            &mut call_again,
+            &mut all_dead,
+        ) {
+            continue;
+        }
+
+        pg_sys::FormIndexDatum(
+            index_info,
+            slot,
+            estate,
+            values.as_mut_ptr(),
+            isnull.as_mut_ptr(),
+        );
+        let document = row_to_search_document(
+            root,
+            &tupdesc,
+            values.as_mut_ptr(),
+            isnull.as_mut_ptr(),
+            &search_index.schema,
+        )
+        .unwrap_or_else(|err| panic!("error creating index entries in bulkdelete: {err}"));
+        search_index
+            .insert(writer, document)
+            .unwrap_or_else(|err| panic!("error inserting into index in bulkdelete: {err}"));
+        pg_sys::MemoryContextReset((*econtext).ecxt_per_tuple_memory);
+    }
+
+    (*tableam)
+        .index_fetch_end
+        .expect("table AM should fetch by ctid")(fetch);
+    pg_sys::ExecDropSingleTupleTableSlot(slot);
+    pg_sys::FreeExecutorState(estate);
+}
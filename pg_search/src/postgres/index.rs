@@ -15,22 +15,123 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::index::reader::PartitionStatistics;
 use crate::index::{SearchIndex, SearchIndexError, WriterDirectory};
-use pgrx::{pg_sys, PgRelation};
+use crate::postgres::transaction::discard_rolled_back_segments;
+use crate::schema::SearchIndexSchema;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use pgrx::{pg_sys, IntoDatum, PgBuiltInOids, PgMemoryContexts, PgRelation, Spi};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Open the underlying [`SearchIndex`] for the specified Postgres index relation
 pub fn open_search_index(
     index_relation: &PgRelation,
 ) -> anyhow::Result<SearchIndex, SearchIndexError> {
+    // documents of rolled back subtransactions mustn't be seen, even by the rest of the transaction
+    discard_rolled_back_segments();
+    SearchIndex::from_disk(&writer_directory(index_relation))
+}
+
+/// Open the underlying [`SearchIndex`] for the specified Postgres index relation, as of the
+/// catalog of index files that starts at `catalog_start`.  See [`SearchIndex::from_disk_at`].
+pub fn open_search_index_at(
+    index_relation: &PgRelation,
+    catalog_start: pg_sys::BlockNumber,
+) -> anyhow::Result<SearchIndex, SearchIndexError> {
+    SearchIndex::from_disk_at(&writer_directory(index_relation), catalog_start)
+}
+
+/// Open the underlying [`SearchIndex`] for one participant of a parallel build of the specified
+/// Postgres index relation
+pub fn open_search_index_detached(
+    index_relation: &PgRelation,
+) -> anyhow::Result<SearchIndex, SearchIndexError> {
+    SearchIndex::from_disk_detached(&writer_directory(index_relation))
+}
+
+/// Returns the leaf indexes of the partition tree the specified index belongs to, including
+/// itself if it's a leaf, or none if it doesn't belong to one
+pub fn partition_leaf_indexes(index_oid: pg_sys::Oid) -> anyhow::Result<Vec<pg_sys::Oid>> {
+    Ok(Spi::get_one_with_args::<Vec<pg_sys::Oid>>(
+        "SELECT array_agg(relid::oid) FROM pg_partition_tree(pg_partition_root($1::regclass)) WHERE isleaf",
+        vec![(PgBuiltInOids::OIDOID.oid(), index_oid.into_datum())],
+    )?
+    .unwrap_or_default())
+}
+
+/// The [`PartitionStatistics`] of the running queries, keyed by each query's `EState` and the
+/// index of every partition of the table they were opened for
+static PARTITION_STATISTICS: Lazy<Mutex<HashMap<(usize, pg_sys::Oid), Arc<PartitionStatistics>>>> =
+    Lazy::new(Default::default);
+
+/// Forgets a query's [`PartitionStatistics`] once the query's memory is freed
+struct PartitionStatisticsGuard(Vec<(usize, pg_sys::Oid)>);
+
+impl Drop for PartitionStatisticsGuard {
+    fn drop(&mut self) {
+        let mut statistics = PARTITION_STATISTICS.lock();
+        for key in &self.0 {
+            statistics.remove(key);
+        }
+    }
+}
+
+/// The statistics of the indexes of every partition of the partitioned table that the specified
+/// index's table belongs to, so that its documents can be scored with the statistics of the whole
+/// table.  See [`crate::index::reader::SearchIndexReader::use_partition_statistics`].
+///
+/// They're opened once per query, by whichever of the table's partitions the query `estate`
+/// scans first, and shared by the rest.  Returns `None` if the table isn't a partition.
+pub fn partition_statistics(
+    index_relation: &PgRelation,
+    schema: &SearchIndexSchema,
+    estate: *mut pg_sys::EState,
+) -> anyhow::Result<Option<Arc<PartitionStatistics>>> {
+    if unsafe { !(*index_relation.rd_rel).relispartition } {
+        return Ok(None);
+    }
+
+    let key = (estate as usize, index_relation.oid());
+    if let Some(statistics) = PARTITION_STATISTICS.lock().get(&key) {
+        return Ok(Some(statistics.clone()));
+    }
+
+    let index_oids = partition_leaf_indexes(index_relation.oid())?;
+    let mut partitions = vec![];
+    for index_oid in &index_oids {
+        let partition = unsafe { PgRelation::with_lock(*index_oid, pg_sys::AccessShareLock as _) };
+        partitions.push(open_search_index(&partition)?);
+    }
+    let statistics = Arc::new(PartitionStatistics::open(schema, partitions)?);
+
+    let keys = index_oids
+        .into_iter()
+        .map(|index_oid| (estate as usize, index_oid))
+        .collect::<Vec<_>>();
+    PARTITION_STATISTICS
+        .lock()
+        .extend(keys.iter().map(|key| (*key, statistics.clone())));
+    // SAFETY: `leak_and_drop_on_delete` palloc's memory in CurrentMemoryContext, but the guard
+    // has to live exactly as long as the query's own memory context
+    unsafe {
+        PgMemoryContexts::For((*estate).es_query_cxt)
+            .switch_to(|mcxt| mcxt.leak_and_drop_on_delete(PartitionStatisticsGuard(keys)));
+    }
+
+    Ok(Some(statistics))
+}
+
+fn writer_directory(index_relation: &PgRelation) -> WriterDirectory {
     let database_oid = unsafe { pg_sys::MyDatabaseId };
     let index_oid = index_relation.oid();
     let relfilenode = relfilenode_from_pg_relation(index_relation);
-    let directory = WriterDirectory::from_oids(
+    WriterDirectory::from_oids(
         database_oid.as_u32(),
         index_oid.as_u32(),
         relfilenode.as_u32(),
-    );
-    SearchIndex::from_disk(&directory)
+    )
 }
 
 /// Retrieves the `relfilenode` from a `PgRelation`, handling PostgreSQL version differences.
@@ -15,33 +15,64 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::index::reader::SearchIndexReader;
+use crate::index::redirects::{CtidRedirects, MAX_REDIRECTS};
 use crate::index::SearchIndexWriter;
 use crate::index::{SearchIndex, WriterResources};
-use crate::postgres::index::open_search_index;
+use crate::postgres::index::{open_search_index, open_search_index_detached};
 use crate::postgres::options::SearchIndexCreateOptions;
-use crate::postgres::utils::row_to_search_document;
+use crate::postgres::transaction::track_segments;
+use crate::postgres::utils::{item_pointer_to_u64, row_to_search_document};
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::value_to_term;
+use crate::schema::SearchDocument;
 use pgrx::{pg_guard, pg_sys, pgrx_extern_c_guard, PgMemoryContexts, PgRelation, PgTupleDesc};
+use std::collections::HashSet;
 use std::ffi::CStr;
 use std::panic::{catch_unwind, resume_unwind};
+use tantivy::collector::DocSetCollector;
+use tantivy::query::TermQuery;
+use tantivy::schema::{IndexRecordOption, Value};
+use tantivy::SegmentId;
 
 pub struct InsertState {
     pub index: SearchIndex,
     pub writer: SearchIndexWriter,
     abort_on_drop: bool,
+    /// Used to find the documents of earlier versions of updated rows, and created the first
+    /// time an update doesn't change the indexed values
+    lookup: Option<RedirectLookup>,
+}
+
+/// What [`InsertState::redirect_unchanged`] needs to find the document of an earlier version of
+/// a row
+struct RedirectLookup {
+    reader: SearchIndexReader,
+    /// The index's redirects, including those added by this statement
+    redirects: CtidRedirects,
+    /// Follows HOT chains with `SnapshotAny`
+    chains: VisibilityChecker,
 }
 
 impl Drop for InsertState {
     /// When [`InsertState`] is dropped we'll either commit the underlying tantivy index changes
     /// or abort.
     fn drop(&mut self) {
+        if self.writer.underlying_writer.is_none() {
+            // CREATE INDEX already committed and waited for merges
+            return;
+        }
+
         unsafe {
             pgrx_extern_c_guard(|| {
-                if !pg_sys::IsAbortedTransactionBlockState() && !self.abort_on_drop {
-                    self.writer
-                        .commit()
-                        .expect("tantivy index commit should succeed");
+                // we can be dropped while a (sub)transaction is being aborted, and nothing of
+                // its changes should be written then
+                let in_progress =
+                    pg_sys::IsTransactionState() && !pg_sys::IsAbortedTransactionBlockState();
+                if in_progress && !self.abort_on_drop {
+                    self.commit();
                 } else if let Err(e) = self.writer.abort() {
-                    if pg_sys::IsAbortedTransactionBlockState() {
+                    if !in_progress {
                         // we're in an aborted state, so the best we can do is warn that our
                         // attempt to abort the tantivy changes failed
                         pgrx::warning!("failed to abort tantivy index changes: {}", e);
@@ -56,19 +87,130 @@ impl Drop for InsertState {
 }
 
 impl InsertState {
+    /// Commit our changes, and remember the segments we wrote so they can be discarded should our
+    /// subtransaction, or our prepared transaction, be rolled back.
+    unsafe fn commit(&mut self) {
+        let before = self.segment_ids();
+        self.writer
+            .commit()
+            .expect("tantivy index commit should succeed");
+
+        let after = self.segment_ids();
+        if !before.is_subset(&after) {
+            // our segments were merged with ones we didn't write, and the transaction can't
+            // take those with it.  Should it be rolled back, its documents are left for VACUUM to
+            // remove, and until then they count towards scores and statistics
+            pgrx::debug1!(
+                "segments written to index {} were merged, and can't be discarded if the transaction is rolled back",
+                self.index.directory.index_oid
+            );
+            return;
+        }
+        track_segments(
+            self.index.directory.index_oid.into(),
+            after.difference(&before).copied().collect(),
+        );
+    }
+
+    fn segment_ids(&self) -> HashSet<SegmentId> {
+        self.index
+            .underlying_index
+            .searchable_segment_ids()
+            .expect("tantivy index should be readable")
+            .into_iter()
+            .collect()
+    }
+
     unsafe fn new(
         indexrel: &PgRelation,
         writer_resources: WriterResources,
     ) -> anyhow::Result<Self> {
-        let index = open_search_index(indexrel)?;
+        let index = match writer_resources {
+            WriterResources::ParallelCreateIndex => open_search_index_detached(indexrel)?,
+            _ => open_search_index(indexrel)?,
+        };
         let options = indexrel.rd_options as *mut SearchIndexCreateOptions;
         let writer = index.get_writer(writer_resources, options.as_ref().unwrap())?;
         Ok(Self {
             index,
             writer,
             abort_on_drop: false,
+            lookup: None,
         })
     }
+
+    /// The indexed values of the row version at `ctid` are the same as those of the version it
+    /// was updated from.  If the document of an earlier version of the row can be found,
+    /// redirect it to `ctid` instead of indexing `document` again.  Returns whether it was.
+    ///
+    /// The earlier version is found by looking up the row's key, and is the one whose HOT chain,
+    /// or that of a ctid it's already redirected to, was updated to `ctid` by this transaction.
+    unsafe fn redirect_unchanged(
+        &mut self,
+        heaprel: pg_sys::Relation,
+        document: &SearchDocument,
+        ctid: u64,
+    ) -> anyhow::Result<bool> {
+        if self.lookup.is_none() {
+            let reader = self.index.get_reader()?;
+            let redirects = CtidRedirects::clone(&reader.redirects);
+            let chains = VisibilityChecker::with_rel_and_snap(
+                heaprel,
+                std::ptr::addr_of_mut!(pg_sys::SnapshotAnyData),
+            );
+            self.lookup = Some(RedirectLookup {
+                reader,
+                redirects,
+                chains,
+            });
+        }
+        let lookup = self.lookup.as_mut().unwrap();
+        if lookup.redirects.len() >= MAX_REDIRECTS {
+            return Ok(false);
+        }
+
+        let key_field = self.index.key_field().id.0;
+        let Some(key) = document.doc.get_first(key_field) else {
+            return Ok(false);
+        };
+        let field_type = self
+            .index
+            .schema
+            .schema
+            .get_field_entry(key_field)
+            .field_type();
+        let Ok(term) = value_to_term(key_field, key, field_type, None, false) else {
+            return Ok(false);
+        };
+        let query = TermQuery::new(term, IndexRecordOption::Basic);
+
+        let ctid_field = self.index.schema.ctid_field().id.0;
+        for address in lookup.reader.searcher.search(&query, &DocSetCollector)? {
+            let doc = lookup.reader.get_doc(address)?;
+            let Some(indexed) = doc.get_first(ctid_field).and_then(|ctid| ctid.as_u64()) else {
+                continue;
+            };
+
+            let candidates = std::iter::once(indexed)
+                .chain(lookup.redirects.targets(indexed))
+                .collect::<Vec<_>>();
+            let Some(predecessor) = candidates
+                .into_iter()
+                .find(|candidate| lookup.chains.is_updated_to(*candidate, ctid))
+            else {
+                continue;
+            };
+
+            // if VACUUM gives the predecessor a document of its own, that one must lead here too
+            for from in [indexed, predecessor] {
+                self.writer.directory.add_redirect(from, ctid);
+                lookup.redirects.insert(from, ctid);
+            }
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
 }
 
 pub unsafe fn init_insert_state(
@@ -105,12 +247,20 @@ pub unsafe extern "C" fn aminsert(
     values: *mut pg_sys::Datum,
     isnull: *mut bool,
     heap_tid: pg_sys::ItemPointer,
-    _heap_relation: pg_sys::Relation,
+    heap_relation: pg_sys::Relation,
     _check_unique: pg_sys::IndexUniqueCheck::Type,
-    _index_unchanged: bool,
+    index_unchanged: bool,
     index_info: *mut pg_sys::IndexInfo,
 ) -> bool {
-    aminsert_internal(index_relation, values, isnull, heap_tid, index_info)
+    aminsert_internal(
+        index_relation,
+        values,
+        isnull,
+        heap_tid,
+        heap_relation,
+        index_unchanged,
+        index_info,
+    )
 }
 
 #[cfg(feature = "pg13")]
@@ -120,11 +270,20 @@ pub unsafe extern "C" fn aminsert(
     values: *mut pg_sys::Datum,
     isnull: *mut bool,
     heap_tid: pg_sys::ItemPointer,
-    _heap_relation: pg_sys::Relation,
+    heap_relation: pg_sys::Relation,
     _check_unique: pg_sys::IndexUniqueCheck::Type,
     index_info: *mut pg_sys::IndexInfo,
 ) -> bool {
-    aminsert_internal(index_relation, values, isnull, heap_tid, index_info)
+    // Postgres 13 doesn't tell us when an update leaves the indexed values unchanged
+    aminsert_internal(
+        index_relation,
+        values,
+        isnull,
+        heap_tid,
+        heap_relation,
+        false,
+        index_info,
+    )
 }
 
 #[inline(always)]
@@ -133,15 +292,15 @@ unsafe fn aminsert_internal(
     values: *mut pg_sys::Datum,
     isnull: *mut bool,
     ctid: pg_sys::ItemPointer,
+    heap_relation: pg_sys::Relation,
+    index_unchanged: bool,
     index_info: *mut pg_sys::IndexInfo,
 ) -> bool {
     let result = catch_unwind(|| {
-        let state = &*init_insert_state(index_relation, index_info, WriterResources::Statement);
+        let state = &mut *init_insert_state(index_relation, index_info, WriterResources::Statement);
         let tupdesc = PgTupleDesc::from_pg_unchecked((*index_relation).rd_att);
-        let search_index = &state.index;
-        let writer = &state.writer;
         let search_document =
-            row_to_search_document(*ctid, &tupdesc, values, isnull, &search_index.schema)
+            row_to_search_document(*ctid, &tupdesc, values, isnull, &state.index.schema)
                 .unwrap_or_else(|err| {
                     panic!(
                         "error creating index entries for index '{}': {err}",
@@ -149,8 +308,18 @@ unsafe fn aminsert_internal(
                             .to_string_lossy()
                     );
                 });
-        search_index
-            .insert(writer, search_document)
+
+        if index_unchanged
+            && state
+                .redirect_unchanged(heap_relation, &search_document, item_pointer_to_u64(*ctid))
+                .expect("looking up the earlier version of an updated row should succeed")
+        {
+            return true;
+        }
+
+        state
+            .index
+            .insert(&state.writer, search_document)
             .expect("insertion into index should succeed");
         true
     });
@@ -31,6 +31,7 @@ pub mod customscan;
 pub mod datetime;
 pub mod index;
 mod parallel;
+pub mod storage;
 pub mod transaction;
 pub mod types;
 pub mod utils;
@@ -76,6 +77,7 @@ fn bm25_handler(_fcinfo: pg_sys::FunctionCallInfo) -> PgBox<pg_sys::IndexAmRouti
     amroutine.amvalidate = Some(validate::amvalidate);
     amroutine.ambuild = Some(build::ambuild);
     amroutine.ambuildempty = Some(build::ambuildempty);
+    amroutine.ambuildphasename = Some(build::ambuildphasename);
     amroutine.aminsert = Some(insert::aminsert);
     amroutine.ambulkdelete = Some(delete::ambulkdelete);
     amroutine.amvacuumcleanup = Some(vacuum::amvacuumcleanup);
@@ -93,6 +95,12 @@ fn bm25_handler(_fcinfo: pg_sys::FunctionCallInfo) -> PgBox<pg_sys::IndexAmRouti
     amroutine.amestimateparallelscan = Some(parallel::amestimateparallelscan);
     amroutine.amparallelrescan = Some(parallel::amparallelrescan);
 
+    // Postgres only plans parallel workers for CREATE INDEX on other access methods from 17 on
+    #[cfg(feature = "pg17")]
+    {
+        amroutine.amcanbuildparallel = true;
+    }
+
     amroutine.into_pg_boxed()
 }
 
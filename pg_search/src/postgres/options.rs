@@ -22,6 +22,7 @@ use pgrx::*;
 use std::collections::HashMap;
 use std::ffi::CStr;
 
+use crate::index::merge_policy::MergePolicyConfig;
 use crate::schema::{SearchFieldConfig, SearchFieldName};
 
 /* ADDING OPTIONS
@@ -55,6 +56,7 @@ pub struct SearchIndexCreateOptions {
     key_field_offset: i32,
     target_segment_count: i32,
     merge_on_insert: bool,
+    merge_policy_offset: i32,
 }
 
 #[pg_guard]
@@ -129,6 +131,17 @@ extern "C" fn validate_datetime_fields(value: *const std::os::raw::c_char) {
     );
 }
 
+#[pg_guard]
+extern "C" fn validate_merge_policy(value: *const std::os::raw::c_char) {
+    let json_str = cstr_to_rust_str(value);
+    if json_str.is_empty() {
+        return;
+    }
+    if let Err(err) = MergePolicyConfig::from_json(&json_str) {
+        panic!("invalid merge_policy: {err}");
+    }
+}
+
 #[pg_guard]
 extern "C" fn validate_key_field(value: *const std::os::raw::c_char) {
     cstr_to_rust_str(value);
@@ -146,7 +159,7 @@ fn cstr_to_rust_str(value: *const std::os::raw::c_char) -> String {
         .to_string()
 }
 
-const NUM_REL_OPTS: usize = 9;
+const NUM_REL_OPTS: usize = 10;
 #[pg_guard]
 pub unsafe extern "C" fn amoptions(
     reloptions: pg_sys::Datum,
@@ -198,6 +211,11 @@ pub unsafe extern "C" fn amoptions(
             opttype: pg_sys::relopt_type::RELOPT_TYPE_BOOL,
             offset: offset_of!(SearchIndexCreateOptions, merge_on_insert) as i32,
         },
+        pg_sys::relopt_parse_elt {
+            optname: "merge_policy".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_STRING,
+            offset: offset_of!(SearchIndexCreateOptions, merge_policy_offset) as i32,
+        },
     ];
     build_relopts(reloptions, validate, options)
 }
@@ -311,6 +329,15 @@ impl SearchIndexCreateOptions {
         self.merge_on_insert
     }
 
+    pub fn merge_policy(&self) -> MergePolicyConfig {
+        let config = self.get_str(self.merge_policy_offset, "".to_string());
+        if config.is_empty() {
+            return MergePolicyConfig::default();
+        }
+        MergePolicyConfig::from_json(&config)
+            .unwrap_or_else(|err| panic!("invalid merge_policy: {err}"))
+    }
+
     fn get_str(&self, offset: i32, default: String) -> String {
         if offset == 0 {
             default
@@ -407,4 +434,12 @@ pub unsafe fn init() {
         true,
         pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
     );
+    pg_sys::add_string_reloption(
+        RELOPT_KIND_PDB,
+        "merge_policy".as_pg_cstr(),
+        "JSON string specifying how index segments should be merged".as_pg_cstr(),
+        std::ptr::null(),
+        Some(validate_merge_policy),
+        pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
+    );
 }
@@ -15,6 +15,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::index::reader::SearchIndexReader;
 use pgrx::{pg_guard, pg_sys};
 use std::ptr::addr_of_mut;
 
@@ -51,24 +52,115 @@ impl Drop for AcquiredSpinLock {
     }
 }
 
+/// The state shared by the participants of a parallel scan of a `bm25` index.
+///
+/// Segment ordinals only mean the same thing to every participant if they all read the same
+/// segments, in the same order, so one participant publishes the catalog of index files it opened
+/// the index as of, and the others open the index as of that same catalog.  See
+/// [`crate::index::BlockDirectory::open_at`].
 #[derive(Debug)]
 #[repr(C)]
 pub struct Bm25ParallelScanState {
     mutex: Spinlock,
+    published: bool,
+    /// The block the published catalog's chain of pages starts at
+    catalog_start: pg_sys::BlockNumber,
+    /// The number of segments in the index as of the published catalog
+    nsegments: u32,
     remaining_segments: u32,
 }
 
 impl Bm25ParallelScanState {
+    /// Initialize the shared state in a freshly allocated piece of shared memory.
+    pub unsafe fn init(state: *mut Self) {
+        pg_sys::SpinLockInit(addr_of_mut!((*state).mutex.0));
+        (*state).published = false;
+        (*state).catalog_start = pg_sys::InvalidBlockNumber;
+        (*state).nsegments = 0;
+        (*state).remaining_segments = 0;
+    }
+
     #[inline(always)]
     pub fn lock(&mut self) -> impl Drop {
         self.mutex.acquire()
     }
+
+    /// Publish the catalog of index files that `reader` opened the index as of, and make every
+    /// one of its segments available to be claimed.
+    pub fn publish(&mut self, reader: &SearchIndexReader) {
+        let (catalog_start, nsegments) = describe(reader);
+        let _mutex = self.lock();
+        self.set_published(catalog_start, nsegments);
+    }
+
+    /// Like [`Bm25ParallelScanState::publish`], unless another participant has already published
+    /// a catalog.  Returns whether the published catalog is the one `reader` sees, which is what
+    /// makes the segments it claims the ones it reads.
+    pub fn publish_or_check(&mut self, reader: &SearchIndexReader) -> bool {
+        let (catalog_start, nsegments) = describe(reader);
+        let _mutex = self.lock();
+        if self.published {
+            return self.catalog_start == catalog_start;
+        }
+        self.set_published(catalog_start, nsegments);
+        true
+    }
+
+    fn set_published(&mut self, catalog_start: pg_sys::BlockNumber, nsegments: u32) {
+        self.published = true;
+        self.catalog_start = catalog_start;
+        self.nsegments = nsegments;
+        self.remaining_segments = nsegments;
+    }
+
+    /// The block the published catalog's chain of pages starts at, if one has been published.
+    pub fn published_catalog(&mut self) -> Option<pg_sys::BlockNumber> {
+        let _mutex = self.lock();
+        self.published.then_some(self.catalog_start)
+    }
+
+    /// Check that `reader`, which was opened as of the published catalog, sees as many segments
+    /// as the participant that published it.
+    pub fn assert_segment_count(&mut self, reader: &SearchIndexReader) {
+        let (_, nsegments) = describe(reader);
+        let _mutex = self.lock();
+        assert_eq!(
+            self.nsegments, nsegments,
+            "every participant of a parallel scan should see the same index segments"
+        );
+    }
+
+    /// Claim the next unclaimed segment, if there are any left.
+    pub fn claim_segment(&mut self) -> Option<tantivy::SegmentOrdinal> {
+        let _mutex = self.lock();
+        if self.remaining_segments == 0 {
+            // no more to claim
+            None
+        } else {
+            // claim the next one
+            self.remaining_segments -= 1;
+            Some(self.remaining_segments)
+        }
+    }
+}
+
+/// The catalog of index files that `reader` opened the index as of, and how many segments it has
+fn describe(reader: &SearchIndexReader) -> (pg_sys::BlockNumber, u32) {
+    let catalog_start = reader
+        .catalog_start()
+        .expect("should be able to read the index's catalog");
+    let nsegments = reader
+        .searcher
+        .segment_readers()
+        .len()
+        .try_into()
+        .expect("should not have more than u32 index segments");
+    (catalog_start, nsegments)
 }
 
 #[pg_guard]
 pub unsafe extern "C" fn aminitparallelscan(target: *mut ::core::ffi::c_void) {
-    let state = target.cast::<Bm25ParallelScanState>();
-    pg_sys::SpinLockInit(addr_of_mut!((*state).mutex.0));
+    Bm25ParallelScanState::init(target.cast());
 }
 
 #[pg_guard]
@@ -100,40 +192,33 @@ unsafe fn bm25_shared_state(
     }
 }
 
-pub fn maybe_init_parallel_scan(
-    scan: pg_sys::IndexScanDesc,
-    searcher: &tantivy::Searcher,
-) -> Option<i32> {
-    let state = get_bm25_scan_state(&scan)?;
-
-    let worker_number = unsafe { pg_sys::ParallelWorkerNumber };
-    let _mutex = state.lock();
-    if worker_number == -1 {
-        // ParallelWorkerNumber -1 is the main backend, which is where we'll set up
-        // our shared memory information
-        state.remaining_segments = searcher
-            .segment_readers()
-            .len()
-            .try_into()
-            .expect("should not have more than u32 index segments");
-    }
-    Some(worker_number)
+/// Is the scan shared with the other participants of a parallel query?
+pub fn is_parallel_scan(scan: pg_sys::IndexScanDesc) -> bool {
+    get_bm25_scan_state(&scan).is_some()
 }
 
-pub fn maybe_claim_segment(scan: pg_sys::IndexScanDesc) -> Option<tantivy::SegmentOrdinal> {
-    let state = get_bm25_scan_state(&scan)?;
+/// The catalog of index files that another participant of a parallel scan has opened the index
+/// as of, if one has.  See [`Bm25ParallelScanState`].
+pub fn published_catalog(scan: pg_sys::IndexScanDesc) -> Option<pg_sys::BlockNumber> {
+    get_bm25_scan_state(&scan)?.published_catalog()
+}
 
-    let _mutex = state.lock();
-    if state.remaining_segments == 0 {
-        // no more to claim
-        None
-    } else {
-        // claim the next one
-        state.remaining_segments -= 1;
-        Some(state.remaining_segments)
+/// Whichever participant of a parallel scan gets here first publishes the catalog of index
+/// files that `reader` opened the index as of.  Returns false if `reader` doesn't see the
+/// published catalog, in which case it must be reopened as of [`published_catalog`].
+///
+/// A scan that isn't parallel needs nothing.
+pub fn maybe_init_parallel_scan(scan: pg_sys::IndexScanDesc, reader: &SearchIndexReader) -> bool {
+    match get_bm25_scan_state(&scan) {
+        Some(state) => state.publish_or_check(reader),
+        None => true,
     }
 }
 
+pub fn maybe_claim_segment(scan: pg_sys::IndexScanDesc) -> Option<tantivy::SegmentOrdinal> {
+    get_bm25_scan_state(&scan)?.claim_segment()
+}
+
 fn get_bm25_scan_state(scan: &pg_sys::IndexScanDesc) -> Option<&mut Bm25ParallelScanState> {
     unsafe {
         assert!(!scan.is_null());
@@ -17,8 +17,7 @@
 
 use crate::index::fast_fields_helper::FFHelper;
 use crate::index::reader::{SearchIndexReader, SearchResults};
-use crate::index::SearchIndex;
-use crate::postgres::index::open_search_index;
+use crate::postgres::index::{open_search_index, open_search_index_at};
 use crate::postgres::options::SearchIndexCreateOptions;
 use crate::postgres::{parallel, ScanStrategy};
 use crate::query::SearchQueryInput;
@@ -34,6 +33,8 @@ pub struct Bm25ScanState {
     results: SearchResults,
     itup: (Vec<pg_sys::Datum>, Vec<bool>),
     key_field_oid: PgOid,
+    /// ctids the last returned document is redirected to, which are returned next
+    redirected: Vec<u64>,
 }
 
 #[pg_guard]
@@ -105,15 +106,23 @@ pub extern "C" fn amrescan(
         };
     }
 
-    // Create the index and scan state
-    let search_index = open_search_index(&indexrel).expect("should be able to open search index");
-    let search_reader = search_index
-        .get_reader()
-        .expect("SearchState should construct cleanly");
+    // Create the index and scan state.  Every participant of a parallel scan reads the index as
+    // the first one to get here opened it, so that the segments they claim are the same ones
+    let (search_index, search_reader) = loop {
+        let search_index = match parallel::published_catalog(scan) {
+            Some(catalog_start) => open_search_index_at(&indexrel, catalog_start),
+            None => open_search_index(&indexrel),
+        }
+        .expect("should be able to open search index");
+        let search_reader = search_index
+            .get_reader()
+            .expect("SearchState should construct cleanly");
+        if parallel::maybe_init_parallel_scan(scan, &search_reader) {
+            break (search_index, search_reader);
+        }
+    };
 
     unsafe {
-        parallel::maybe_init_parallel_scan(scan, &search_reader.searcher);
-
         let options = (*(*scan).indexRelation).rd_options as *mut SearchIndexCreateOptions;
         let key_field = (*options)
             .get_key_field()
@@ -123,17 +132,12 @@ pub extern "C" fn amrescan(
 
         let need_scores = search_query_input.contains_more_like_this();
         let query = search_index.query(&indexrel, &search_query_input, &search_reader);
-        let results = if let Some(segment_number) = parallel::maybe_claim_segment(scan) {
+        let results = if !parallel::is_parallel_scan(scan) {
+            search_reader.search(need_scores, !(*scan).xs_want_itup, &query)
+        } else if let Some(segment_number) = parallel::maybe_claim_segment(scan) {
             search_reader.search_segment(need_scores, segment_number, &query)
-        } else if pg_sys::ParallelWorkerNumber > -1 {
-            SearchResults::None
         } else {
-            search_reader.search_via_channel(
-                need_scores,
-                !(*scan).xs_want_itup,
-                SearchIndex::executor(),
-                &query,
-            )
+            SearchResults::None
         };
 
         let natts = (*(*scan).xs_hitupdesc).natts as usize;
@@ -151,6 +155,7 @@ pub extern "C" fn amrescan(
                 key_field_oid: PgOid::from(
                     (*(*scan).xs_hitupdesc).attrs.as_slice(natts)[0].atttypid,
                 ),
+                redirected: vec![],
             }
         } else {
             Bm25ScanState {
@@ -161,6 +166,7 @@ pub extern "C" fn amrescan(
                 results,
                 itup: (vec![], vec![]),
                 key_field_oid: PgOid::Invalid,
+                redirected: vec![],
             }
         };
 
@@ -189,6 +195,14 @@ pub extern "C" fn amgettuple(
         (*scan).xs_recheck = false;
     }
 
+    // the later versions of the row we last returned share its key, and `xs_hitup` with it
+    if let Some(ctid) = state.redirected.pop() {
+        unsafe {
+            crate::postgres::utils::u64_to_item_pointer(ctid, &mut (*scan).xs_heaptid);
+        }
+        return true;
+    }
+
     loop {
         match state.results.next() {
             Some((scored, doc_address)) => unsafe {
@@ -259,6 +273,9 @@ pub extern "C" fn amgettuple(
                     }
                 }
 
+                state
+                    .redirected
+                    .extend(state.reader.redirects.targets(scored.ctid));
                 return true;
             },
             None => {
@@ -289,16 +306,19 @@ pub extern "C" fn amgetbitmap(scan: pg_sys::IndexScanDesc, tbm: *mut pg_sys::TID
     let mut cnt = 0i64;
     loop {
         for (scored, _) in state.results.by_ref() {
-            let mut ipd = pg_sys::ItemPointerData::default();
-            crate::postgres::utils::u64_to_item_pointer(scored.ctid, &mut ipd);
+            let redirects = state.reader.redirects.targets(scored.ctid);
+            for ctid in std::iter::once(scored.ctid).chain(redirects) {
+                let mut ipd = pg_sys::ItemPointerData::default();
+                crate::postgres::utils::u64_to_item_pointer(ctid, &mut ipd);
+
+                unsafe {
+                    // SAFETY:  `tbm` has been asserted to be non-null and our `&mut tid` has been
+                    // initialized as a stack-allocated ItemPointerData
+                    pg_sys::tbm_add_tuples(tbm, &mut ipd, 1, false);
+                }
 
-            unsafe {
-                // SAFETY:  `tbm` has been asserted to be non-null and our `&mut tid` has been
-                // initialized as a stack-allocated ItemPointerData
-                pg_sys::tbm_add_tuples(tbm, &mut ipd, 1, false);
+                cnt += 1;
             }
-
-            cnt += 1;
         }
 
         // check if the bitmap scan needs to claim another individual segment
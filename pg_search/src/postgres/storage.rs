@@ -0,0 +1,687 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Page-level storage for the files of a `bm25` index.
+//!
+//! Every file is stored as a singly-linked chain of pages in the index relation's main fork.  A
+//! "catalog" that maps file paths to the blocks of their chain is itself stored as a chain of
+//! pages, and the metapage (block 0) points to the current catalog.
+//!
+//! Changes are copy-on-write:  new file contents and a new catalog are written to unused blocks
+//! and only then is the metapage updated to point to the new catalog.  Every page is written
+//! through Postgres' generic WAL machinery, which means the index is crash-safe and is
+//! replicated to physical standbys like any other relation.
+//!
+//! Readers only lock the metapage while they find the catalog, and read the pages of the catalog
+//! and of the files it lists afterwards, as they need them.  That's safe because the blocks of a
+//! replaced catalog or a deleted file aren't reused until no snapshot that could have seen them
+//! remains.  Hot standbys can't hold that up, so before blocks are reused a WAL record is logged
+//! whose replay cancels the standby queries that could still see them.  It also lets the
+//! participants of a parallel scan all read the index as of the same catalog.
+
+use pgrx::pg_sys;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The block number of the index metapage
+pub const METAPAGE: pg_sys::BlockNumber = 0;
+
+/// Identifies a relation as a `bm25` index with block storage:  the bytes "BM25"
+const BM25_MAGIC: u32 = 0x424D3235;
+const BM25_STORAGE_VERSION: u32 = 1;
+
+/// Stored at the start of the metapage's contents
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct BM25MetaPageData {
+    magic: u32,
+    version: u32,
+    catalog_start: pg_sys::BlockNumber,
+}
+
+/// `XLOG_BTREE_REUSE_PAGE`, the nbtree WAL record that's logged before a deleted page is reused.
+/// Its replay does nothing but resolve hot standby conflicts, which our generic WAL records can't.
+const XLOG_BTREE_REUSE_PAGE: u8 = 0xD0;
+
+/// The layout of nbtree's `xl_btree_reuse_page`
+#[cfg(feature = "pg13")]
+#[repr(C)]
+struct ReusePageRecord {
+    node: pg_sys::RelFileNode,
+    block: pg_sys::BlockNumber,
+    latest_removed_xid: pg_sys::TransactionId,
+}
+
+/// The layout of nbtree's `xl_btree_reuse_page`
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+#[repr(C)]
+struct ReusePageRecord {
+    node: pg_sys::RelFileNode,
+    block: pg_sys::BlockNumber,
+    latest_removed_full_xid: pg_sys::FullTransactionId,
+}
+
+/// The layout of nbtree's `xl_btree_reuse_page`
+#[cfg(any(feature = "pg16", feature = "pg17"))]
+#[repr(C)]
+struct ReusePageRecord {
+    locator: pg_sys::RelFileLocator,
+    block: pg_sys::BlockNumber,
+    snapshot_conflict_horizon: pg_sys::FullTransactionId,
+    is_catalog_rel: bool,
+}
+
+impl ReusePageRecord {
+    /// Conflicts with every standby snapshot that could see what was deleted as of the full
+    /// transaction id `deleted_at`
+    #[cfg(feature = "pg13")]
+    unsafe fn new(relation: pg_sys::Relation, deleted_at: u64) -> Self {
+        Self {
+            node: (*relation).rd_node,
+            block: pg_sys::InvalidBlockNumber,
+            latest_removed_xid: pg_sys::TransactionId::from_inner(deleted_at as u32),
+        }
+    }
+
+    /// Conflicts with every standby snapshot that could see what was deleted as of the full
+    /// transaction id `deleted_at`
+    #[cfg(any(feature = "pg14", feature = "pg15"))]
+    unsafe fn new(relation: pg_sys::Relation, deleted_at: u64) -> Self {
+        Self {
+            node: (*relation).rd_node,
+            block: pg_sys::InvalidBlockNumber,
+            latest_removed_full_xid: pg_sys::FullTransactionId { value: deleted_at },
+        }
+    }
+
+    /// Conflicts with every standby snapshot that could see what was deleted as of the full
+    /// transaction id `deleted_at`
+    #[cfg(any(feature = "pg16", feature = "pg17"))]
+    unsafe fn new(relation: pg_sys::Relation, deleted_at: u64) -> Self {
+        Self {
+            locator: (*relation).rd_locator,
+            block: pg_sys::InvalidBlockNumber,
+            snapshot_conflict_horizon: pg_sys::FullTransactionId { value: deleted_at },
+            is_catalog_rel: false,
+        }
+    }
+}
+
+/// Stored in the special space of every file (and catalog) page
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct BM25PageSpecialData {
+    next_blockno: pg_sys::BlockNumber,
+}
+
+/// Runs of consecutive block numbers `(first, count)`
+pub type BlockRuns = Vec<(pg_sys::BlockNumber, u32)>;
+
+/// Collapse `blocks` into runs of consecutive block numbers, in the same order.
+fn block_runs(blocks: impl IntoIterator<Item = pg_sys::BlockNumber>) -> BlockRuns {
+    let mut runs: BlockRuns = vec![];
+    for blockno in blocks {
+        match runs.last_mut() {
+            Some((first, count)) if *first + *count == blockno => *count += 1,
+            _ => runs.push((blockno, 1)),
+        }
+    }
+    runs
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// The blocks of the file's page chain, in order, as runs of consecutive block numbers
+    /// `(first, count)`.  Empty if the file is.
+    pub blocks: BlockRuns,
+    /// The length of the file, in bytes
+    pub len: u64,
+}
+
+impl FileEntry {
+    fn new(blocks: &[pg_sys::BlockNumber], len: u64) -> Self {
+        Self {
+            blocks: block_runs(blocks.iter().copied()),
+            len,
+        }
+    }
+
+    /// The `n`th block of the file
+    fn block(&self, mut n: u32) -> Option<pg_sys::BlockNumber> {
+        for &(first, count) in &self.blocks {
+            if n < count {
+                return Some(first + n);
+            }
+            n -= count;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FileCatalog {
+    pub files: HashMap<PathBuf, FileEntry>,
+    /// Blocks that are no longer referenced by any file and can be reused, in descending order
+    pub free_blocks: BlockRuns,
+    /// Blocks of deleted files that readers may still be reading, along with the next full
+    /// transaction id as of their deletion.  They become free once every snapshot is newer.
+    #[serde(default)]
+    pub deleted_blocks: Vec<(u64, BlockRuns)>,
+}
+
+/// Provides access to the pages of an open `bm25` index relation.
+///
+/// The caller is responsible for ensuring the relation is open, and suitably locked, for the
+/// lifetime of this struct.
+pub struct BlockStorage {
+    relation: pg_sys::Relation,
+}
+
+impl BlockStorage {
+    /// # Safety
+    ///
+    /// `relation` must be a valid, open index relation.
+    pub unsafe fn new(relation: pg_sys::Relation) -> Self {
+        Self { relation }
+    }
+
+    /// Initialize the metapage of a brand-new, empty, index relation.  This is a no-op if the
+    /// relation already has a metapage.
+    pub fn init_metapage(&self) {
+        unsafe {
+            if pg_sys::RelationGetNumberOfBlocksInFork(
+                self.relation,
+                pg_sys::ForkNumber::MAIN_FORKNUM,
+            ) > 0
+            {
+                return;
+            }
+
+            let buffer = self.new_buffer(pg_sys::ForkNumber::MAIN_FORKNUM);
+            assert_eq!(
+                pg_sys::BufferGetBlockNumber(buffer),
+                METAPAGE,
+                "the metapage must be the first block of the index"
+            );
+            self.write_metapage(buffer, pg_sys::InvalidBlockNumber);
+            pg_sys::UnlockReleaseBuffer(buffer);
+        }
+    }
+
+    /// Write an empty metapage to the init fork of an unlogged index.
+    pub fn init_empty_metapage(&self) {
+        unsafe {
+            let buffer = self.new_buffer(pg_sys::ForkNumber::INIT_FORKNUM);
+            self.write_metapage(buffer, pg_sys::InvalidBlockNumber);
+            pg_sys::UnlockReleaseBuffer(buffer);
+        }
+    }
+
+    /// Returns the current [`FileCatalog`], along with the block its chain of pages starts at.
+    pub fn catalog(&self) -> Result<(FileCatalog, pg_sys::BlockNumber), BlockStorageError> {
+        let catalog_start = unsafe {
+            let metabuf = self.lock_metapage(pg_sys::BUFFER_LOCK_SHARE);
+            let metadata = page_contents(pg_sys::BufferGetPage(metabuf)) as *const BM25MetaPageData;
+            let catalog_start = (*metadata).catalog_start;
+            pg_sys::UnlockReleaseBuffer(metabuf);
+            catalog_start
+        };
+        Ok((self.catalog_at(catalog_start)?, catalog_start))
+    }
+
+    /// Returns the [`FileCatalog`] whose chain of pages starts at `catalog_start`, which the
+    /// caller, or another participant of its parallel query, found with [`BlockStorage::catalog`]
+    /// while holding a snapshot that's still held.
+    pub fn catalog_at(
+        &self,
+        catalog_start: pg_sys::BlockNumber,
+    ) -> Result<FileCatalog, BlockStorageError> {
+        if catalog_start == pg_sys::InvalidBlockNumber {
+            return Ok(FileCatalog::default());
+        }
+        let (serialized, _) = unsafe { self.read_chain(catalog_start)? };
+        Ok(serde_json::from_slice(&serialized)?)
+    }
+
+    /// Read the bytes in `range` of the file described by `entry`, which the caller found in a
+    /// catalog it read while holding a snapshot that it still holds.
+    ///
+    /// Only the pages that hold `range` are read, and the metapage isn't locked, so large files
+    /// can be read a piece at a time without holding up writers.
+    pub fn read(
+        &self,
+        entry: &FileEntry,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, BlockStorageError> {
+        let capacity = page_capacity();
+        let first = entry.block(0).unwrap_or(pg_sys::InvalidBlockNumber);
+        let end = range.end.min(entry.len as usize);
+        let mut data = Vec::with_capacity(end.saturating_sub(range.start));
+        let mut offset = range.start;
+        while offset < end {
+            let blockno =
+                entry
+                    .block((offset / capacity) as u32)
+                    .ok_or(BlockStorageError::CorruptChain(
+                        first,
+                        pg_sys::InvalidBlockNumber,
+                    ))?;
+            unsafe {
+                let buffer = pg_sys::ReadBuffer(self.relation, blockno);
+                pg_sys::LockBuffer(buffer, pg_sys::BUFFER_LOCK_SHARE as i32);
+                let page = pg_sys::BufferGetPage(buffer);
+                let header = page as *const pg_sys::PageHeaderData;
+                let len = (*header).pd_lower as usize - contents_offset();
+                let start = offset % capacity;
+                let stop = len.min(start + (end - offset));
+                if start >= stop {
+                    pg_sys::UnlockReleaseBuffer(buffer);
+                    return Err(BlockStorageError::CorruptChain(first, blockno));
+                }
+                data.extend_from_slice(std::slice::from_raw_parts(
+                    page_contents(page).add(start),
+                    stop - start,
+                ));
+                pg_sys::UnlockReleaseBuffer(buffer);
+                offset += stop - start;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Atomically apply a set of file writes and deletes.
+    ///
+    /// Files in `writes` replace any existing file with the same path.  Deleting a file that
+    /// doesn't exist is not an error.
+    ///
+    /// The new contents of each file in `merges` are computed by `merge` from its current
+    /// contents, if it exists, while other writers are locked out.  This lets writers that update
+    /// the same file at the same time combine their changes instead of overwriting each other's.
+    pub fn apply_changes<'a, M>(
+        &self,
+        writes: impl IntoIterator<Item = (&'a Path, &'a [u8])>,
+        deletes: impl IntoIterator<Item = &'a Path>,
+        merges: impl IntoIterator<Item = &'a Path>,
+        merge: M,
+    ) -> Result<(), BlockStorageError>
+    where
+        M: FnMut(&Path, Option<&[u8]>) -> Result<Vec<u8>, BlockStorageError>,
+    {
+        unsafe {
+            // the exclusive lock on the metapage serializes writers and blocks readers from
+            // following a catalog whose blocks we might be about to reuse
+            let metabuf = self.lock_metapage(pg_sys::BUFFER_LOCK_EXCLUSIVE);
+            let result = self.apply_changes_locked(metabuf, writes, deletes, merges, merge);
+            pg_sys::UnlockReleaseBuffer(metabuf);
+            result
+        }
+    }
+
+    unsafe fn apply_changes_locked<'a, M>(
+        &self,
+        metabuf: pg_sys::Buffer,
+        writes: impl IntoIterator<Item = (&'a Path, &'a [u8])>,
+        deletes: impl IntoIterator<Item = &'a Path>,
+        merges: impl IntoIterator<Item = &'a Path>,
+        mut merge: M,
+    ) -> Result<(), BlockStorageError>
+    where
+        M: FnMut(&Path, Option<&[u8]>) -> Result<Vec<u8>, BlockStorageError>,
+    {
+        let (mut catalog, old_catalog_blocks) = self.read_catalog(metabuf)?;
+
+        let mut merged = vec![];
+        for path in merges {
+            let current = match catalog.files.get(path) {
+                Some(entry) => Some(self.read(entry, 0..entry.len as usize)?),
+                None => None,
+            };
+            merged.push((path, merge(path, current.as_deref())?));
+        }
+
+        self.recycle(&mut catalog);
+
+        // the blocks of the current catalog, and of the files we delete or replace, may still be
+        // read by anyone who's found the current catalog, so they're set aside until no snapshot
+        // that could have is left
+        let mut deleted = block_runs(old_catalog_blocks);
+        for path in deletes {
+            if let Some(entry) = catalog.files.remove(path) {
+                deleted.extend(entry.blocks);
+            }
+        }
+
+        let mut writes: Vec<(&Path, &[u8])> = writes.into_iter().collect();
+        writes.extend(merged.iter().map(|(path, data)| (*path, data.as_slice())));
+        for (path, data) in writes {
+            if let Some(entry) = catalog.files.remove(path) {
+                deleted.extend(entry.blocks);
+            }
+            let blocks = self.write_chain(data, &mut catalog.free_blocks);
+            catalog.files.insert(
+                path.to_path_buf(),
+                FileEntry::new(&blocks, data.len() as u64),
+            );
+        }
+        if !deleted.is_empty() {
+            let next_xid = pg_sys::ReadNextFullTransactionId().value;
+            catalog.deleted_blocks.push((next_xid, deleted));
+        }
+
+        // nothing reads the free blocks, so the new catalog can go into them as well, and the old
+        // catalog and everything it references remains intact until the metapage is updated.
+        // Taking blocks off the free list changes the catalog, and possibly how many blocks it
+        // needs, so we take more until it fits.  Any it doesn't need are left empty
+        let mut catalog_blocks = vec![];
+        let serialized = loop {
+            let serialized = serde_json::to_vec(&catalog)?;
+            let needed = serialized.len().div_ceil(page_capacity());
+            if catalog_blocks.len() >= needed {
+                break serialized;
+            }
+            catalog_blocks
+                .extend(self.allocate(needed - catalog_blocks.len(), &mut catalog.free_blocks));
+        };
+        self.write_pages(&catalog_blocks, &serialized);
+        self.write_metapage(metabuf, catalog_blocks[0]);
+        Ok(())
+    }
+
+    /// Free the deleted blocks of `catalog` that no snapshot can still see the files of, and
+    /// order the free blocks so that new files are written to consecutive blocks where possible.
+    unsafe fn recycle(&self, catalog: &mut FileCatalog) {
+        let (recyclable, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut catalog.deleted_blocks)
+            .into_iter()
+            .partition(|(deleted_at, _)| self.is_recyclable(*deleted_at));
+        catalog.deleted_blocks = pending;
+        if let Some(horizon) = recyclable.iter().map(|(deleted_at, _)| *deleted_at).max() {
+            self.log_reuse(horizon);
+        }
+
+        let mut runs = std::mem::take(&mut catalog.free_blocks);
+        runs.extend(recyclable.into_iter().flat_map(|(_, blocks)| blocks));
+        runs.sort_unstable();
+        let mut merged: BlockRuns = vec![];
+        for (first, count) in runs {
+            match merged.last_mut() {
+                Some((last, last_count)) if *last + *last_count >= first => {
+                    *last_count = (*last_count).max(first + count - *last);
+                }
+                _ => merged.push((first, count)),
+            }
+        }
+
+        // blocks are taken from the end
+        merged.reverse();
+        catalog.free_blocks = merged;
+    }
+
+    /// Before blocks deleted as of the full transaction id `deleted_at` are reused, make hot
+    /// standbys cancel the queries whose snapshots could still see them, the same way nbtree does
+    /// before it reuses a deleted page.  The primary's snapshots are checked by `is_recyclable`,
+    /// but a standby's aren't visible to it unless it sends feedback.
+    unsafe fn log_reuse(&self, deleted_at: u64) {
+        let relpersistence = (*(*self.relation).rd_rel).relpersistence;
+        if pg_sys::wal_level < pg_sys::WalLevel::WAL_LEVEL_REPLICA as i32
+            || relpersistence != pg_sys::RELPERSISTENCE_PERMANENT as std::ffi::c_char
+        {
+            return;
+        }
+
+        let record = ReusePageRecord::new(self.relation, deleted_at);
+        pg_sys::XLogBeginInsert();
+        pg_sys::XLogRegisterData(
+            &record as *const ReusePageRecord as _,
+            std::mem::size_of::<ReusePageRecord>() as _,
+        );
+        pg_sys::XLogInsert(pg_sys::RmgrIds::RM_BTREE_ID as _, XLOG_BTREE_REUSE_PAGE);
+    }
+
+    /// Is every snapshot newer than the full transaction id `deleted_at`?
+    #[cfg(feature = "pg13")]
+    unsafe fn is_recyclable(&self, deleted_at: u64) -> bool {
+        pg_sys::TransactionIdPrecedes(
+            pg_sys::TransactionId::from_inner(deleted_at as u32),
+            pg_sys::RecentGlobalXmin,
+        )
+    }
+
+    /// Is every snapshot newer than the full transaction id `deleted_at`?
+    #[cfg(not(feature = "pg13"))]
+    unsafe fn is_recyclable(&self, deleted_at: u64) -> bool {
+        pg_sys::GlobalVisCheckRemovableFullXid(
+            self.relation,
+            pg_sys::FullTransactionId { value: deleted_at },
+        )
+    }
+
+    /// Returns a pinned and locked buffer for `METAPAGE`, after validating it.
+    unsafe fn lock_metapage(&self, mode: u32) -> pg_sys::Buffer {
+        let buffer = pg_sys::ReadBuffer(self.relation, METAPAGE);
+        pg_sys::LockBuffer(buffer, mode as i32);
+
+        let metadata = page_contents(pg_sys::BufferGetPage(buffer)) as *const BM25MetaPageData;
+        if (*metadata).magic != BM25_MAGIC {
+            pg_sys::UnlockReleaseBuffer(buffer);
+            panic!(
+                "index \"{}\" is not a bm25 index with block storage.  Use REINDEX to rebuild it",
+                relation_name(self.relation)
+            );
+        }
+        buffer
+    }
+
+    unsafe fn read_catalog(
+        &self,
+        metabuf: pg_sys::Buffer,
+    ) -> Result<(FileCatalog, Vec<pg_sys::BlockNumber>), BlockStorageError> {
+        let metadata = page_contents(pg_sys::BufferGetPage(metabuf)) as *const BM25MetaPageData;
+        let catalog_start = (*metadata).catalog_start;
+        if catalog_start == pg_sys::InvalidBlockNumber {
+            return Ok((FileCatalog::default(), vec![]));
+        }
+
+        let (serialized, blocks) = self.read_chain(catalog_start)?;
+        Ok((serde_json::from_slice(&serialized)?, blocks))
+    }
+
+    /// Read a chain of pages, returning their concatenated contents and their block numbers.
+    unsafe fn read_chain(
+        &self,
+        start: pg_sys::BlockNumber,
+    ) -> Result<(Vec<u8>, Vec<pg_sys::BlockNumber>), BlockStorageError> {
+        let nblocks = pg_sys::RelationGetNumberOfBlocksInFork(
+            self.relation,
+            pg_sys::ForkNumber::MAIN_FORKNUM,
+        );
+        let mut data = vec![];
+        let mut blocks = vec![];
+        let mut blockno = start;
+        while blockno != pg_sys::InvalidBlockNumber {
+            if blockno == METAPAGE || blockno >= nblocks || blocks.len() > nblocks as usize {
+                return Err(BlockStorageError::CorruptChain(start, blockno));
+            }
+
+            let buffer = pg_sys::ReadBuffer(self.relation, blockno);
+            pg_sys::LockBuffer(buffer, pg_sys::BUFFER_LOCK_SHARE as i32);
+            let page = pg_sys::BufferGetPage(buffer);
+            let header = page as *const pg_sys::PageHeaderData;
+            let len = (*header).pd_lower as usize - contents_offset();
+            data.extend_from_slice(std::slice::from_raw_parts(page_contents(page), len));
+            let special = page_special(page);
+            blocks.push(blockno);
+            blockno = (*special).next_blockno;
+            pg_sys::UnlockReleaseBuffer(buffer);
+        }
+        Ok((data, blocks))
+    }
+
+    /// Write `data` into a new chain of pages, reusing blocks from `free_blocks` before extending
+    /// the relation.  Returns the blocks of the chain, in order.
+    unsafe fn write_chain(
+        &self,
+        data: &[u8],
+        free_blocks: &mut BlockRuns,
+    ) -> Vec<pg_sys::BlockNumber> {
+        let blocks = self.allocate(data.len().div_ceil(page_capacity()), free_blocks);
+        self.write_pages(&blocks, data);
+        blocks
+    }
+
+    /// Take `n` blocks from `free_blocks`, lowest first, and extend the relation for any more.
+    unsafe fn allocate(&self, n: usize, free_blocks: &mut BlockRuns) -> Vec<pg_sys::BlockNumber> {
+        (0..n)
+            .map(|_| match free_blocks.last_mut() {
+                Some((first, count)) => {
+                    let blockno = *first;
+                    *first += 1;
+                    *count -= 1;
+                    if *count == 0 {
+                        free_blocks.pop();
+                    }
+                    blockno
+                }
+                None => {
+                    // the new page is initialized when it's written, and nothing reads it until
+                    let buffer = self.new_buffer(pg_sys::ForkNumber::MAIN_FORKNUM);
+                    let blockno = pg_sys::BufferGetBlockNumber(buffer);
+                    pg_sys::UnlockReleaseBuffer(buffer);
+                    blockno
+                }
+            })
+            .collect()
+    }
+
+    /// Write `data` into a chain of pages at `blocks`, which were all picked beforehand so that
+    /// each page's successor is known when it's written.  Pages past the end of `data` are left
+    /// empty.
+    unsafe fn write_pages(&self, blocks: &[pg_sys::BlockNumber], data: &[u8]) {
+        let mut chunks = data.chunks(page_capacity());
+        for i in 0..blocks.len() {
+            let chunk = chunks.next().unwrap_or_default();
+            let next_blockno = blocks
+                .get(i + 1)
+                .copied()
+                .unwrap_or(pg_sys::InvalidBlockNumber);
+            let buffer = pg_sys::ReadBuffer(self.relation, blocks[i]);
+            pg_sys::LockBuffer(buffer, pg_sys::BUFFER_LOCK_EXCLUSIVE as i32);
+
+            let state = pg_sys::GenericXLogStart(self.relation);
+            let page = pg_sys::GenericXLogRegisterBuffer(
+                state,
+                buffer,
+                pg_sys::GENERIC_XLOG_FULL_IMAGE as _,
+            );
+            pg_sys::PageInit(
+                page,
+                pg_sys::BLCKSZ as usize,
+                std::mem::size_of::<BM25PageSpecialData>(),
+            );
+            (*page_special(page)).next_blockno = next_blockno;
+            std::ptr::copy_nonoverlapping(chunk.as_ptr(), page_contents(page), chunk.len());
+            // generic WAL only logs the region outside of `pd_lower..pd_upper`, so `pd_lower` must
+            // cover everything we've written
+            (*(page as *mut pg_sys::PageHeaderData)).pd_lower =
+                (contents_offset() + chunk.len()) as _;
+            pg_sys::GenericXLogFinish(state);
+            pg_sys::UnlockReleaseBuffer(buffer);
+        }
+    }
+
+    unsafe fn write_metapage(&self, buffer: pg_sys::Buffer, catalog_start: pg_sys::BlockNumber) {
+        let state = pg_sys::GenericXLogStart(self.relation);
+        let page =
+            pg_sys::GenericXLogRegisterBuffer(state, buffer, pg_sys::GENERIC_XLOG_FULL_IMAGE as _);
+        pg_sys::PageInit(page, pg_sys::BLCKSZ as usize, 0);
+        let metadata = page_contents(page) as *mut BM25MetaPageData;
+        *metadata = BM25MetaPageData {
+            magic: BM25_MAGIC,
+            version: BM25_STORAGE_VERSION,
+            catalog_start,
+        };
+        (*(page as *mut pg_sys::PageHeaderData)).pd_lower =
+            (contents_offset() + std::mem::size_of::<BM25MetaPageData>()) as _;
+        pg_sys::GenericXLogFinish(state);
+    }
+
+    /// Extend the relation by one block, returning the new buffer pinned and exclusively locked.
+    unsafe fn new_buffer(&self, forknum: pg_sys::ForkNumber::Type) -> pg_sys::Buffer {
+        pg_sys::LockRelationForExtension(self.relation, pg_sys::ExclusiveLock as _);
+        let buffer = pg_sys::ReadBufferExtended(
+            self.relation,
+            forknum,
+            pg_sys::InvalidBlockNumber, // P_NEW
+            pg_sys::ReadBufferMode::RBM_NORMAL,
+            std::ptr::null_mut(),
+        );
+        pg_sys::LockBuffer(buffer, pg_sys::BUFFER_LOCK_EXCLUSIVE as i32);
+        pg_sys::UnlockRelationForExtension(self.relation, pg_sys::ExclusiveLock as _);
+        buffer
+    }
+}
+
+/// The offset of a page's contents, `MAXALIGN(SizeOfPageHeaderData)`.
+#[inline(always)]
+fn contents_offset() -> usize {
+    maxalign(std::mem::size_of::<pg_sys::PageHeaderData>())
+}
+
+/// How many bytes of file data fit on a single page
+#[inline(always)]
+fn page_capacity() -> usize {
+    pg_sys::BLCKSZ as usize
+        - contents_offset()
+        - maxalign(std::mem::size_of::<BM25PageSpecialData>())
+}
+
+#[inline(always)]
+fn maxalign(len: usize) -> usize {
+    let align = pg_sys::MAXIMUM_ALIGNOF as usize;
+    (len + align - 1) & !(align - 1)
+}
+
+#[inline(always)]
+unsafe fn page_contents(page: pg_sys::Page) -> *mut u8 {
+    (page as *mut u8).add(contents_offset())
+}
+
+#[inline(always)]
+unsafe fn page_special(page: pg_sys::Page) -> *mut BM25PageSpecialData {
+    let header = page as *const pg_sys::PageHeaderData;
+    (page as *mut u8).add((*header).pd_special as usize) as *mut BM25PageSpecialData
+}
+
+unsafe fn relation_name(relation: pg_sys::Relation) -> String {
+    std::ffi::CStr::from_ptr((*(*relation).rd_rel).relname.data.as_ptr())
+        .to_string_lossy()
+        .to_string()
+}
+
+#[derive(Debug, Error)]
+pub enum BlockStorageError {
+    #[error("page chain starting at block {0} is corrupt at block {1}")]
+    CorruptChain(pg_sys::BlockNumber, pg_sys::BlockNumber),
+
+    #[error("could not (de)serialize the file catalog: {0}")]
+    Catalog(#[from] serde_json::Error),
+
+    #[error("could not merge concurrent changes to {0}: {1}")]
+    Merge(PathBuf, serde_json::Error),
+}
@@ -15,13 +15,153 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::index::BlockDirectory;
 use crate::index::SearchFs;
 use crate::index::SearchIndexWriter;
-use pgrx::{pg_guard, pg_sys};
+use crate::nodecast;
+use pgrx::{pg_guard, pg_sys, Spi};
+use std::collections::{HashMap, HashSet};
+use tantivy::SegmentId;
 use tracing::warn;
 
-/// Initialize a transaction callback that pg_search uses to commit or abort pending tantivy
-/// index changes.
+/// The segments that were written to an index by a (sub)transaction.
+struct SubxactSegments {
+    subxid: pg_sys::SubTransactionId,
+    indexrelid: pg_sys::Oid,
+    segment_ids: HashSet<SegmentId>,
+}
+
+/// Segments written by the transaction, keyed by the subtransaction that wrote them, or the
+/// parent that it has committed into.
+static mut SUBXACT_SEGMENTS: Vec<SubxactSegments> = Vec::new();
+
+/// Segments written by subtransactions that have been rolled back, waiting to be removed from
+/// their index.  That can't happen while the subtransaction is aborting, so it's done the next
+/// time any index is opened, see [`discard_rolled_back_segments`], or when the transaction
+/// commits.
+static mut DISCARDED_SEGMENTS: Vec<SubxactSegments> = Vec::new();
+
+/// Remember that the current (sub)transaction wrote `segment_ids` to the index `indexrelid`, so
+/// they can be removed from the index if it's rolled back.
+pub fn track_segments(indexrelid: pg_sys::Oid, segment_ids: HashSet<SegmentId>) {
+    if segment_ids.is_empty() {
+        return;
+    }
+
+    register_callback();
+    unsafe {
+        SUBXACT_SEGMENTS.push(SubxactSegments {
+            subxid: pg_sys::GetCurrentSubTransactionId(),
+            indexrelid,
+            segment_ids,
+        });
+    }
+}
+
+/// Remove the segments of subtransactions that have been rolled back from their indexes, if the
+/// transaction is in a state to write to them.  This is done whenever an index is opened, so that
+/// searches don't see their documents, which would otherwise count towards scores and statistics.
+pub fn discard_rolled_back_segments() {
+    unsafe {
+        if !DISCARDED_SEGMENTS.is_empty() && pg_sys::IsTransactionState() {
+            discard_segments();
+        }
+    }
+}
+
+/// Remove the segments of rolled back subtransactions from their indexes.
+unsafe fn discard_segments() {
+    for discarded in std::mem::take(&mut DISCARDED_SEGMENTS) {
+        // the index itself may have been created by the subtransaction, in which case it's gone
+        let indexrel =
+            pg_sys::try_relation_open(discarded.indexrelid, pg_sys::RowExclusiveLock as _);
+        if indexrel.is_null() {
+            continue;
+        }
+
+        BlockDirectory::open(discarded.indexrelid)
+            .discard_segments(&discarded.segment_ids)
+            .unwrap_or_else(|err| {
+                warn!(
+                    "unexpected error discarding segments of a rolled back subtransaction: {:?}",
+                    err
+                )
+            });
+        pg_sys::relation_close(indexrel, pg_sys::RowExclusiveLock as _);
+    }
+}
+
+/// Record the segments the transaction wrote in their indexes, along with its transaction id,
+/// before it's prepared.  See [`resolve_prepared_segments`].
+unsafe fn record_prepared_segments() {
+    let xid = pg_sys::GetTopFullTransactionIdIfAny();
+    let mut written: HashMap<pg_sys::Oid, HashSet<SegmentId>> = HashMap::new();
+    for segments in SUBXACT_SEGMENTS.iter() {
+        written
+            .entry(segments.indexrelid)
+            .or_default()
+            .extend(&segments.segment_ids);
+    }
+
+    for (indexrelid, segment_ids) in written {
+        // the index itself may have been created by the transaction, and then it goes away if the
+        // transaction is rolled back
+        let indexrel = pg_sys::try_relation_open(indexrelid, pg_sys::RowExclusiveLock as _);
+        if indexrel.is_null() {
+            continue;
+        }
+
+        // without a record, the segments couldn't be removed if the transaction is rolled back,
+        // so it's better for PREPARE TRANSACTION to fail
+        BlockDirectory::open(indexrelid)
+            .record_prepared(xid.value, &segment_ids)
+            .unwrap_or_else(|err| {
+                panic!("could not record the segments of the prepared transaction: {err}")
+            });
+        pg_sys::relation_close(indexrel, pg_sys::RowExclusiveLock as _);
+    }
+}
+
+/// Forget the prepared transactions that have been committed, and remove the segments of those
+/// that have been rolled back from their indexes, in every bm25 index of the current database.
+///
+/// This is done after every COMMIT PREPARED and ROLLBACK PREPARED, and by `VACUUM` for each index
+/// it vacuums, in case a transaction was rolled back while pg_search wasn't loaded.
+pub fn resolve_prepared_segments() {
+    let indexrelids = Spi::get_one::<Vec<pg_sys::Oid>>(
+        "SELECT array_agg(c.oid) FROM pg_class c JOIN pg_am a ON c.relam = a.oid WHERE a.amname = 'bm25' AND c.relkind = 'i'",
+    )
+    .expect("should be able to find the bm25 indexes")
+    .unwrap_or_default();
+
+    for indexrelid in indexrelids {
+        unsafe {
+            // the index may have been dropped since we looked it up
+            let indexrel = pg_sys::try_relation_open(indexrelid, pg_sys::RowExclusiveLock as _);
+            if indexrel.is_null() {
+                continue;
+            }
+
+            BlockDirectory::open(indexrelid)
+                .resolve_prepared()
+                .unwrap_or_else(|err| {
+                    warn!(
+                        "unexpected error resolving the segments of prepared transactions: {:?}",
+                        err
+                    )
+                });
+            pg_sys::relation_close(indexrel, pg_sys::RowExclusiveLock as _);
+        }
+    }
+}
+
+unsafe fn clear_subxact_segments() {
+    SUBXACT_SEGMENTS.clear();
+    DISCARDED_SEGMENTS.clear();
+}
+
+/// Initialize the transaction callbacks that pg_search uses to remove the directories of dropped
+/// indexes, and the segments written by rolled back subtransactions.
 ///
 /// This callback must be initialized **once per backend connection**, rather than once when
 /// `pg_search.so` is loaded.  As such calling this function from `_PG_init()` does not work.
@@ -35,6 +175,7 @@ pub fn register_callback() {
             // register a XactCallback, once, for this backend connection where we'll decide to
             // commit or abort pending index changes
             pg_sys::RegisterXactCallback(Some(pg_search_xact_callback), std::ptr::null_mut());
+            pg_sys::RegisterSubXactCallback(Some(pg_search_subxact_callback), std::ptr::null_mut());
             INITIALIZED = true;
         }
     }
@@ -47,7 +188,11 @@ unsafe extern "C" fn pg_search_xact_callback(
 ) {
     match event {
         pg_sys::XactEvent::XACT_EVENT_PRE_COMMIT => {
-            // first, indexes in our cache that are pending a DROP need to be dropped
+            // the segments of subtransactions that were rolled back mustn't be published
+            discard_segments();
+            clear_subxact_segments();
+
+            // then, indexes in our cache that are pending a DROP need to be dropped
             for directory in SearchIndexWriter::pending_drops() {
                 directory.remove().unwrap_or_else(|err| {
                     warn!(
@@ -57,25 +202,64 @@ unsafe extern "C" fn pg_search_xact_callback(
                 });
             }
 
-            // finally, any indexes that are marked as pending create are now created because the
-            // transaction is committed
             SearchIndexWriter::clear_pending_drops();
-            SearchIndexWriter::clear_pending_creates();
+        }
+
+        pg_sys::XactEvent::XACT_EVENT_PRE_PREPARE => {
+            discard_segments();
+
+            // Every statement has already written its segments to the index relation, and their
+            // documents become visible along with the transaction's heap tuples if it's committed
+            // with COMMIT PREPARED, which may happen in another backend or after a restart.  If
+            // it's rolled back instead, the segments are removed by whoever finds out, for which
+            // they're recorded in the index relation.
+            record_prepared_segments();
+            clear_subxact_segments();
+
+            // The directories it was going to remove are left for the orphaned index sweep, which
+            // removes them once the transaction has committed and their index no longer exists.
+            SearchIndexWriter::clear_pending_drops();
         }
 
         pg_sys::XactEvent::XACT_EVENT_ABORT => {
-            // first, indexes in our cache that are pending a CREATE need to be dropped
-            for directory in SearchIndexWriter::pending_creates() {
-                directory.remove().unwrap_or_else(|err| {
-                    warn!(
-                        "unexpected error removing index directory during abort: {:?}; {:?}",
-                        directory, err
-                    )
-                });
+            // the segments our statements wrote were already published to the index relation
+            // when each statement finished, and aborting doesn't remove them.  Their documents
+            // point at heap tuples that are now dead, so searches skip them and VACUUM removes
+            // them.  All we need to do is forget about any leftover directories we were going to
+            // remove
+            SearchIndexWriter::clear_pending_drops();
+            clear_subxact_segments();
+        }
+
+        _ => {
+            // not an event we care about
+        }
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn pg_search_subxact_callback(
+    event: pg_sys::SubXactEvent::Type,
+    my_subid: pg_sys::SubTransactionId,
+    parent_subid: pg_sys::SubTransactionId,
+    _arg: *mut std::ffi::c_void,
+) {
+    match event {
+        pg_sys::SubXactEvent::SUBXACT_EVENT_COMMIT_SUB => {
+            // the parent is now responsible for the segments
+            for segments in SUBXACT_SEGMENTS.iter_mut().filter(|s| s.subxid == my_subid) {
+                segments.subxid = parent_subid;
             }
+        }
 
-            SearchIndexWriter::clear_pending_drops();
-            SearchIndexWriter::clear_pending_creates();
+        pg_sys::SubXactEvent::SUBXACT_EVENT_ABORT_SUB => {
+            // the subtransaction's rows are dead, so its documents should go too.  We're in the
+            // middle of aborting and can't write to the index relation now
+            let (aborted, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut SUBXACT_SEGMENTS)
+                .into_iter()
+                .partition(|s| s.subxid == my_subid);
+            SUBXACT_SEGMENTS = kept;
+            DISCARDED_SEGMENTS.extend(aborted);
         }
 
         _ => {
@@ -83,3 +267,99 @@ unsafe extern "C" fn pg_search_xact_callback(
         }
     }
 }
+
+static mut PREV_PROCESS_UTILITY_HOOK: pg_sys::ProcessUtility_hook_type = None;
+
+/// Install a `ProcessUtility` hook that resolves the segments of prepared transactions as soon as
+/// they're committed or rolled back.  See [`resolve_prepared_segments`].
+///
+/// Unlike [`register_callback`], this must be called from `_PG_init()`.
+pub fn register_utility_hook() {
+    unsafe {
+        PREV_PROCESS_UTILITY_HOOK = pg_sys::ProcessUtility_hook;
+        pg_sys::ProcessUtility_hook = Some(pg_search_process_utility);
+    }
+}
+
+#[cfg(feature = "pg13")]
+#[pg_guard]
+unsafe extern "C" fn pg_search_process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const std::ffi::c_char,
+    context: pg_sys::ProcessUtilityContext::Type,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    qc: *mut pg_sys::QueryCompletion,
+) {
+    match PREV_PROCESS_UTILITY_HOOK {
+        Some(prev_hook) => prev_hook(pstmt, query_string, context, params, query_env, dest, qc),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            context,
+            params,
+            query_env,
+            dest,
+            qc,
+        ),
+    }
+
+    if finishes_prepared_transaction(pstmt) {
+        resolve_prepared_segments();
+    }
+}
+
+#[cfg(not(feature = "pg13"))]
+#[allow(clippy::too_many_arguments)]
+#[pg_guard]
+unsafe extern "C" fn pg_search_process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const std::ffi::c_char,
+    read_only_tree: bool,
+    context: pg_sys::ProcessUtilityContext::Type,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    qc: *mut pg_sys::QueryCompletion,
+) {
+    match PREV_PROCESS_UTILITY_HOOK {
+        Some(prev_hook) => prev_hook(
+            pstmt,
+            query_string,
+            read_only_tree,
+            context,
+            params,
+            query_env,
+            dest,
+            qc,
+        ),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            read_only_tree,
+            context,
+            params,
+            query_env,
+            dest,
+            qc,
+        ),
+    }
+
+    if finishes_prepared_transaction(pstmt) {
+        resolve_prepared_segments();
+    }
+}
+
+/// Is `pstmt` a COMMIT PREPARED or ROLLBACK PREPARED?  Either runs in a transaction of its own,
+/// which is still in progress when the statement returns, so we can write to indexes then.
+unsafe fn finishes_prepared_transaction(pstmt: *mut pg_sys::PlannedStmt) -> bool {
+    let Some(stmt) = nodecast!(TransactionStmt, T_TransactionStmt, (*pstmt).utilityStmt) else {
+        return false;
+    };
+    matches!(
+        (*stmt).kind,
+        pg_sys::TransactionStmtKind::TRANS_STMT_COMMIT_PREPARED
+            | pg_sys::TransactionStmtKind::TRANS_STMT_ROLLBACK_PREPARED
+    )
+}
@@ -116,19 +116,19 @@ pub unsafe fn row_to_search_document(
             continue;
         }
 
-        if is_array {
-            for value in TantivyValue::try_from_datum_array(datum, base_oid)? {
-                document.insert(search_field.id, value.tantivy_schema_value());
-            }
+        let values = if is_array {
+            TantivyValue::try_from_datum_array(datum, base_oid)?
         } else if is_json {
-            for value in TantivyValue::try_from_datum_json(datum, base_oid)? {
-                document.insert(search_field.id, value.tantivy_schema_value());
-            }
+            TantivyValue::try_from_datum_json(datum, base_oid)?
         } else {
-            document.insert(
-                search_field.id,
-                TantivyValue::try_from_datum(datum, base_oid)?.tantivy_schema_value(),
-            );
+            vec![TantivyValue::try_from_datum(datum, base_oid)?]
+        };
+
+        // the column's multi-fields index the same values under their own configurations
+        for field in std::iter::once(search_field).chain(schema.multi_fields(search_field)) {
+            for value in &values {
+                document.insert(field.id, value.tantivy_schema_value());
+            }
         }
     }
 
@@ -43,6 +43,16 @@ pub extern "C" fn amvacuumcleanup(
     let search_index =
         open_search_index(&index_relation).expect("should be able to open search index");
     let options = index_relation.rd_options as *mut SearchIndexCreateOptions;
+
+    // normally done as soon as they're committed or rolled back, but VACUUM makes sure no
+    // prepared transaction's segments are left behind
+    search_index
+        .block_directory
+        .resolve_prepared()
+        .unwrap_or_else(|err| {
+            panic!("error resolving prepared transactions of index {index_name}: {err:?}")
+        });
+
     let mut writer = search_index
         .get_writer(WriterResources::Vacuum, unsafe {
             options.as_ref().unwrap()
@@ -57,13 +67,9 @@ pub extern "C" fn amvacuumcleanup(
     // we also need to make sure segments get merged.
     //
     // we can force this by doing a .commit(), even tho we don't have changes
-    // then directly taking control of the underlying_writer and waiting for the merge threads
-    // to complete
+    // then waiting for the merge threads to complete and write their results to the index
     writer.commit().expect("commit should succeed");
     writer
-        .underlying_writer
-        .take()
-        .unwrap()
         .wait_merging_threads()
         .expect("wait_merging_threads() should succeed");
 
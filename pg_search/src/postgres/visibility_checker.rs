@@ -15,10 +15,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::index::redirects::CtidRedirects;
 use crate::postgres::utils;
 use pgrx::itemptr::item_pointer_get_block_number;
 use pgrx::pg_sys;
 use pgrx::pg_sys::Buffer;
+use std::sync::Arc;
 
 //
 // we redeclare these functions so we can use the directly without pgrx' "#[pg_guard]" overhead.
@@ -45,8 +47,28 @@ extern "C" {
         all_dead: *mut bool,
         first_call: bool,
     ) -> bool;
+
+    fn visibilitymap_get_status(
+        rel: pg_sys::Relation,
+        heapBlk: pg_sys::BlockNumber,
+        vmbuf: *mut Buffer,
+    ) -> u8;
+    fn visibilitymap_count(
+        rel: pg_sys::Relation,
+        all_visible: *mut pg_sys::BlockNumber,
+        all_frozen: *mut pg_sys::BlockNumber,
+    );
+    fn HeapTupleGetUpdateXid(tuple: pg_sys::HeapTupleHeader) -> pg_sys::TransactionId;
+    fn TransactionIdIsCurrentTransactionId(xid: pg_sys::TransactionId) -> bool;
+    fn RelationGetNumberOfBlocksInFork(
+        relation: pg_sys::Relation,
+        forkNum: pg_sys::ForkNumber::Type,
+    ) -> pg_sys::BlockNumber;
 }
 
+/// #define VISIBILITYMAP_ALL_VISIBLE	0x01
+const VISIBILITYMAP_ALL_VISIBLE: u8 = 0x01;
+
 /// Helper to manage the information necessary to validate that a "ctid" is currently visible to
 /// a snapshot
 pub struct VisibilityChecker {
@@ -54,7 +76,9 @@ pub struct VisibilityChecker {
     need_close: bool,
     snapshot: pg_sys::Snapshot,
     last_buffer: pg_sys::Buffer,
+    vm_buffer: pg_sys::Buffer,
     ipd: pg_sys::ItemPointerData,
+    redirects: Arc<CtidRedirects>,
 }
 
 impl Drop for VisibilityChecker {
@@ -68,6 +92,9 @@ impl Drop for VisibilityChecker {
             if self.last_buffer != pg_sys::InvalidBuffer as pg_sys::Buffer {
                 pg_sys::ReleaseBuffer(self.last_buffer);
             }
+            if self.vm_buffer != pg_sys::InvalidBuffer as pg_sys::Buffer {
+                pg_sys::ReleaseBuffer(self.vm_buffer);
+            }
 
             if self.need_close {
                 // SAFETY:  `self.relation` is always a valid, open relation, created via `pg_sys::RelationGetRelation`
@@ -86,16 +113,43 @@ impl VisibilityChecker {
             need_close: false,
             snapshot,
             last_buffer: pg_sys::InvalidBuffer as pg_sys::Buffer,
+            vm_buffer: pg_sys::InvalidBuffer as pg_sys::Buffer,
             ipd: pg_sys::ItemPointerData::default(),
+            redirects: Default::default(),
         }
     }
 
-    /// If the specified `ctid` is visible in the heap, run the provided closure and return its
-    /// result as `Some(T)`.  If it's not visible, return `None` without running the provided closure
+    /// Also look for the later versions of a row that an indexed ctid is redirected to.  These
+    /// are the [`crate::index::reader::SearchIndexReader::redirects`] of the reader whose ctids
+    /// are being checked.
+    pub fn set_redirects(&mut self, redirects: Arc<CtidRedirects>) {
+        self.redirects = redirects;
+    }
+
+    /// If the specified `ctid`, or a ctid it's redirected to, is visible in the heap, run the
+    /// provided closure and return its result as `Some(T)`.  If it's not visible, return `None`
+    /// without running the provided closure
     pub fn exec_if_visible<T, F: FnMut(pg_sys::Oid, pg_sys::HeapTupleData, pg_sys::Buffer) -> T>(
         &mut self,
         ctid: u64,
         mut func: F,
+    ) -> Option<T> {
+        if let Some(result) = self.exec_if_visible_at(ctid, &mut func) {
+            return Some(result);
+        }
+
+        // at most one version of a row is visible to a snapshot
+        let redirects = self.redirects.clone();
+        let result = redirects
+            .targets(ctid)
+            .find_map(|target| self.exec_if_visible_at(target, &mut func));
+        result
+    }
+
+    fn exec_if_visible_at<T, F: FnMut(pg_sys::Oid, pg_sys::HeapTupleData, pg_sys::Buffer) -> T>(
+        &mut self,
+        ctid: u64,
+        func: &mut F,
     ) -> Option<T> {
         unsafe {
             // Using ctid, get itempointer => buffer => page => heaptuple
@@ -122,6 +176,92 @@ impl VisibilityChecker {
         }
     }
 
+    /// Is the heap page holding the specified `ctid` marked all-visible in the visibility map?
+    /// If it is, the tuple is visible to every MVCC snapshot and the heap doesn't need to be read.
+    pub fn is_all_visible(&mut self, ctid: u64) -> bool {
+        unsafe {
+            utils::u64_to_item_pointer(ctid, &mut self.ipd);
+            let blockno = item_pointer_get_block_number(&self.ipd);
+
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                visibilitymap_get_status(self.relation, blockno, &mut self.vm_buffer)
+                    & VISIBILITYMAP_ALL_VISIBLE
+                    != 0
+            })
+        }
+    }
+
+    /// Is every page of the heap marked all-visible in the visibility map?
+    pub fn is_relation_all_visible(&self) -> bool {
+        unsafe {
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                let mut all_visible = 0;
+                let mut all_frozen = 0;
+                visibilitymap_count(self.relation, &mut all_visible, &mut all_frozen);
+                all_visible
+                    == RelationGetNumberOfBlocksInFork(
+                        self.relation,
+                        pg_sys::ForkNumber::MAIN_FORKNUM,
+                    )
+            })
+        }
+    }
+
+    /// Was a version of the row in the HOT chain rooted at `ctid` updated, by the current
+    /// transaction, to the tuple at `successor`?  The checker's snapshot must be `SnapshotAny`,
+    /// so that every member of the chain is considered.
+    pub fn is_updated_to(&mut self, ctid: u64, successor: u64) -> bool {
+        unsafe {
+            utils::u64_to_item_pointer(ctid, &mut self.ipd);
+            let blockno = item_pointer_get_block_number(&self.ipd);
+
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                if blockno
+                    >= RelationGetNumberOfBlocksInFork(
+                        self.relation,
+                        pg_sys::ForkNumber::MAIN_FORKNUM,
+                    )
+                {
+                    return false;
+                }
+
+                self.last_buffer = ReleaseAndReadBuffer(self.last_buffer, self.relation, blockno);
+                LockBuffer(self.last_buffer, pg_sys::BUFFER_LOCK_SHARE as _);
+
+                let mut found = false;
+                let mut first_call = true;
+                let mut heap_tuple = pg_sys::HeapTupleData::default();
+                // with a non-MVCC snapshot, every call returns the next member of the chain
+                while heap_hot_search_buffer(
+                    &mut self.ipd,
+                    self.relation,
+                    self.last_buffer,
+                    self.snapshot,
+                    &mut heap_tuple,
+                    std::ptr::null_mut(),
+                    first_call,
+                ) {
+                    first_call = false;
+                    let header = heap_tuple.t_data;
+                    if utils::item_pointer_to_u64((*header).t_ctid) != successor {
+                        continue;
+                    }
+
+                    let updater = if (*header).t_infomask & pg_sys::HEAP_XMAX_IS_MULTI as u16 != 0 {
+                        HeapTupleGetUpdateXid(header)
+                    } else {
+                        (*header).t_choice.t_heap.t_xmax
+                    };
+                    found = TransactionIdIsCurrentTransactionId(updater);
+                    break;
+                }
+
+                LockBuffer(self.last_buffer, pg_sys::BUFFER_LOCK_UNLOCK as _);
+                found
+            })
+        }
+    }
+
     unsafe fn check_page_vis(&mut self, buffer: pg_sys::Buffer) -> (bool, pg_sys::HeapTupleData) {
         unsafe {
             let mut heap_tuple = pg_sys::HeapTupleData::default();
@@ -37,7 +37,8 @@ use tantivy::{
     },
     query_grammar::Occur,
     schema::{Field, FieldType, OwnedValue, DATE_TIME_PRECISION_INDEXED},
-    Searcher, Term,
+    tokenizer::TextAnalyzer,
+    Searcher, TantivyError, Term,
 };
 use thiserror::Error;
 
@@ -581,7 +582,7 @@ impl SearchQueryInput {
                 transposition_cost_one,
                 prefix,
             } => {
-                let (field, path) = split_field_and_path(&field);
+                let (field, path) = split_field_and_path(field_lookup, &field);
                 let (field_type, _, field) = field_lookup
                     .as_field_type(&field)
                     .ok_or_else(|| QueryError::NonIndexedField(field))?;
@@ -616,7 +617,7 @@ impl SearchQueryInput {
                 prefix,
                 match_all_terms,
             } => {
-                let (field, path) = split_field_and_path(&field);
+                let (field, path) = split_field_and_path(field_lookup, &field);
                 let distance = distance.unwrap_or(2);
                 let transposition_cost_one = transposition_cost_one.unwrap_or(true);
                 let match_all_terms = match_all_terms.unwrap_or(false);
@@ -626,7 +627,7 @@ impl SearchQueryInput {
                     .as_field_type(&field)
                     .ok_or_else(|| QueryError::NonIndexedField(field))?;
 
-                let mut analyzer = searcher.index().tokenizer_for_field(field)?;
+                let mut analyzer = search_analyzer(searcher, &field, &field_type)?;
                 let mut stream = analyzer.token_stream(&value);
                 let mut terms = Vec::new();
 
@@ -746,7 +747,7 @@ impl SearchQueryInput {
                 phrases,
                 max_expansions,
             } => {
-                let (field, path) = split_field_and_path(&field);
+                let (field, path) = split_field_and_path(field_lookup, &field);
                 let (field_type, _, field) = field_lookup
                     .as_field_type(&field)
                     .ok_or_else(|| QueryError::NonIndexedField(field))?;
@@ -806,7 +807,7 @@ impl SearchQueryInput {
                 phrases,
                 slop,
             } => {
-                let (field, path) = split_field_and_path(&field);
+                let (field, path) = split_field_and_path(field_lookup, &field);
                 let (field_type, _, field) = field_lookup
                     .as_field_type(&field)
                     .ok_or_else(|| QueryError::NonIndexedField(field))?;
@@ -832,7 +833,7 @@ impl SearchQueryInput {
                 upper_bound,
                 is_datetime,
             } => {
-                let (field, path) = split_field_and_path(&field);
+                let (field, path) = split_field_and_path(field_lookup, &field);
                 let field_name = field;
                 let (field_type, typeoid, field) = field_lookup
                     .as_field_type(&field_name)
@@ -1504,7 +1505,7 @@ impl SearchQueryInput {
             } => {
                 let record_option = IndexRecordOption::WithFreqsAndPositions;
                 if let Some(field) = field {
-                    let (field, path) = split_field_and_path(&field);
+                    let (field, path) = split_field_and_path(field_lookup, &field);
                     let (field_type, typeoid, field) = field_lookup
                         .as_field_type(&field)
                         .ok_or_else(|| QueryError::NonIndexedField(field))?;
@@ -1537,7 +1538,7 @@ impl SearchQueryInput {
                     is_datetime,
                 } in fields
                 {
-                    let (_, path) = split_field_and_path(&field);
+                    let (_, path) = split_field_and_path(field_lookup, &field);
                     let (field_type, typeoid, field) = field_lookup
                         .as_field_type(&field)
                         .ok_or_else(|| QueryError::NonIndexedField(field))?;
@@ -1684,13 +1685,53 @@ impl TryFrom<&str> for TantivyDateTime {
     }
 }
 
-pub fn split_field_and_path(field: &str) -> (String, Option<String>) {
+/// The analyzer that query text for `field` is tokenized with, as named by its query-time
+/// `field_type`.
+fn search_analyzer(
+    searcher: &Searcher,
+    field: &Field,
+    field_type: &FieldType,
+) -> tantivy::Result<TextAnalyzer> {
+    let indexing_options = match field_type {
+        FieldType::JsonObject(options) => options.get_text_indexing_options(),
+        FieldType::Str(options) => options.get_indexing_options(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        TantivyError::InvalidArgument(format!("field {field:?} is not a tokenized text field"))
+    })?;
+
+    searcher
+        .index()
+        .tokenizers()
+        .get(indexing_options.tokenizer())
+        .ok_or_else(|| {
+            TantivyError::InvalidArgument(format!(
+                "no tokenizer '{}' found for field {field:?}",
+                indexing_options.tokenizer()
+            ))
+        })
+}
+
+pub fn split_field_and_path(
+    field_lookup: &impl AsFieldType<String>,
+    field: &str,
+) -> (String, Option<String>) {
+    // a field's name can itself contain dots, like those of multi-fields, so like tantivy's
+    // `Schema::find_field` we look for the whole name first, and otherwise only treat what follows
+    // the longest prefix that names a JSON field as a path within it
+    if field_lookup.as_field_type(&field.to_string()).is_some() {
+        return (field.to_string(), None);
+    }
+
     let json_path = split_json_path(field);
-    if json_path.len() == 1 {
-        (field.to_string(), None)
-    } else {
-        (json_path[0].clone(), Some(json_path[1..].join(".")))
+    for split in (1..json_path.len()).rev() {
+        let name = json_path[..split].join(".");
+        if let Some((FieldType::JsonObject(_), _, _)) = field_lookup.as_field_type(&name) {
+            return (name, Some(json_path[split..].join(".")));
+        }
     }
+    (field.to_string(), None)
 }
 
 #[allow(dead_code)]
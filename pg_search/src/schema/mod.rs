@@ -24,11 +24,11 @@ pub use document::*;
 use pgrx::{PgBuiltInOids, PgOid, PgRelation};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use tantivy::schema::{
-    DateOptions, Field, JsonObjectOptions, NumericOptions, Schema, TextFieldIndexing, TextOptions,
-    FAST, INDEXED, STORED,
+    DateOptions, Field, FieldEntry, FieldType, JsonObjectOptions, NumericOptions, Schema,
+    TextFieldIndexing, TextOptions, FAST, INDEXED, STORED,
 };
 use thiserror::Error;
 use tokenizers::{SearchNormalizer, SearchTokenizer};
@@ -40,6 +40,13 @@ use crate::query::AsFieldType;
 #[from(forward)]
 pub struct SearchFieldName(pub String);
 
+impl SearchFieldName {
+    /// The name of the `suffix` multi-field of the `column` field.
+    pub fn multi_field(column: &SearchFieldName, suffix: &str) -> Self {
+        Self(format!("{}.{suffix}", column.0))
+    }
+}
+
 /// The name of a field, as it appears to Postgres.
 #[derive(Debug, Copy, Clone, From, PartialEq, Eq, Serialize, Deserialize)]
 #[from(forward)]
@@ -109,10 +116,17 @@ pub enum SearchFieldConfig {
         fieldnorms: bool,
         #[serde(default)]
         tokenizer: SearchTokenizer,
+        /// Analyzes query text instead of `tokenizer`, which only analyzes indexed values.
+        #[serde(default)]
+        search_tokenizer: Option<SearchTokenizer>,
         #[serde(default = "default_as_freqs_and_positions")]
         record: IndexRecordOption,
         #[serde(default)]
         normalizer: SearchNormalizer,
+        /// Multi-fields that index the same column under other configurations, keyed by the
+        /// suffix of their field name: a `ngram` entry on `title` is searched as `title.ngram`.
+        #[serde(default)]
+        fields: BTreeMap<String, SearchFieldConfig>,
     },
     Json {
         #[serde(default = "default_as_true")]
@@ -125,6 +139,9 @@ pub enum SearchFieldConfig {
         expand_dots: bool,
         #[serde(default)]
         tokenizer: SearchTokenizer,
+        /// Analyzes query text instead of `tokenizer`, which only analyzes indexed values.
+        #[serde(default)]
+        search_tokenizer: Option<SearchTokenizer>,
         #[serde(default = "default_as_freqs_and_positions")]
         record: IndexRecordOption,
         #[serde(default)]
@@ -200,6 +217,11 @@ impl SearchFieldConfig {
             None => Ok(SearchTokenizer::default()),
         }?;
 
+        let search_tokenizer = match obj.get("search_tokenizer") {
+            Some(v) => SearchTokenizer::from_json_value(v).map(Some),
+            None => Ok(None),
+        }?;
+
         let record = match obj.get("record") {
             Some(v) => serde_json::from_value(v.clone()),
             None => Ok(default_as_freqs_and_positions()),
@@ -210,14 +232,33 @@ impl SearchFieldConfig {
             None => Ok(SearchNormalizer::Raw),
         }?;
 
+        let fields = match obj.get("fields") {
+            Some(v) => v
+                .as_object()
+                .ok_or_else(|| anyhow::anyhow!("'fields' field should be an object"))?
+                .iter()
+                .map(|(suffix, config)| {
+                    let config = Self::text_from_json(config.clone())?;
+                    if matches!(&config, SearchFieldConfig::Text { fields, .. } if !fields.is_empty())
+                    {
+                        anyhow::bail!("multi-field '{suffix}' cannot have 'fields' of its own");
+                    }
+                    Ok((suffix.clone(), config))
+                })
+                .collect::<Result<_>>(),
+            None => Ok(BTreeMap::new()),
+        }?;
+
         Ok(SearchFieldConfig::Text {
             indexed,
             fast,
             stored,
             fieldnorms,
             tokenizer,
+            search_tokenizer,
             record,
             normalizer,
+            fields,
         })
     }
 
@@ -259,6 +300,11 @@ impl SearchFieldConfig {
             None => Ok(SearchTokenizer::default()),
         }?;
 
+        let search_tokenizer = match obj.get("search_tokenizer") {
+            Some(v) => SearchTokenizer::from_json_value(v).map(Some),
+            None => Ok(None),
+        }?;
+
         let record = match obj.get("record") {
             Some(v) => serde_json::from_value(v.clone()),
             None => Ok(default_as_freqs_and_positions()),
@@ -275,6 +321,7 @@ impl SearchFieldConfig {
             stored,
             expand_dots,
             tokenizer,
+            search_tokenizer,
             record,
             normalizer,
         })
@@ -434,6 +481,7 @@ impl From<SearchFieldConfig> for TextOptions {
                 tokenizer,
                 record,
                 normalizer,
+                ..
             } => {
                 if stored {
                     text_options = text_options.set_stored();
@@ -499,6 +547,7 @@ impl From<SearchFieldConfig> for JsonObjectOptions {
                 tokenizer,
                 record,
                 normalizer,
+                ..
             } => {
                 if stored {
                     json_options = json_options.set_stored();
@@ -604,8 +653,29 @@ impl SearchIndexSchema {
         let mut builder = Schema::builder();
         let mut search_fields = vec![];
 
+        // Multi-fields go after every other field, so that `key_index` and the ctid's position
+        // still point at the fields they were given for.
+        let multi_fields: Vec<_> = fields
+            .iter()
+            .flat_map(|(name, config, field_type)| match config {
+                SearchFieldConfig::Text { fields, .. } => fields
+                    .iter()
+                    .map(|(suffix, config)| {
+                        (
+                            SearchFieldName::multi_field(name, suffix),
+                            config.clone(),
+                            *field_type,
+                        )
+                    })
+                    .collect(),
+                _ => vec![],
+            })
+            .collect();
+
         let mut ctid_index = 0;
-        for (index, (name, config, field_type)) in fields.into_iter().enumerate() {
+        for (index, (name, config, field_type)) in
+            fields.into_iter().chain(multi_fields).enumerate()
+        {
             if config == SearchFieldConfig::Ctid {
                 ctid_index = index
             }
@@ -686,6 +756,87 @@ impl SearchIndexSchema {
         }
     }
 
+    /// The tantivy type of `search_field` as queries see it, which analyzes query text with the
+    /// field's `search_tokenizer` rather than its `tokenizer` when it has one.
+    pub fn query_field_type(&self, search_field: &SearchField) -> FieldType {
+        let field_type = self
+            .schema
+            .get_field_entry(search_field.id.0)
+            .field_type()
+            .clone();
+        let search_tokenizer = match &search_field.config {
+            SearchFieldConfig::Text {
+                search_tokenizer: Some(search_tokenizer),
+                ..
+            }
+            | SearchFieldConfig::Json {
+                search_tokenizer: Some(search_tokenizer),
+                ..
+            } => search_tokenizer.name(),
+            _ => return field_type,
+        };
+
+        match field_type {
+            FieldType::Str(options) => match options.get_indexing_options().cloned() {
+                Some(indexing) => FieldType::Str(
+                    options.set_indexing_options(indexing.set_tokenizer(&search_tokenizer)),
+                ),
+                None => FieldType::Str(options),
+            },
+            FieldType::JsonObject(options) => match options.get_text_indexing_options().cloned() {
+                Some(indexing) => FieldType::JsonObject(
+                    options.set_indexing_options(indexing.set_tokenizer(&search_tokenizer)),
+                ),
+                None => FieldType::JsonObject(options),
+            },
+            field_type => field_type,
+        }
+    }
+
+    /// The tantivy schema that query strings are parsed against, whose fields have the types
+    /// given by [`Self::query_field_type`].
+    pub fn query_schema(&self) -> Schema {
+        let mut builder = Schema::builder();
+        for (field, entry) in self.schema.fields() {
+            let field_type = match self
+                .fields
+                .iter()
+                .find(|search_field| search_field.id.0 == field)
+            {
+                Some(search_field) => self.query_field_type(search_field),
+                None => entry.field_type().clone(),
+            };
+            builder.add_field(FieldEntry::new(entry.name().to_string(), field_type));
+        }
+        builder.build()
+    }
+
+    /// The multi-fields that index `search_field`'s column under other configurations.
+    pub fn multi_fields<'a>(
+        &'a self,
+        search_field: &'a SearchField,
+    ) -> impl Iterator<Item = &'a SearchField> + 'a {
+        let suffixes = match &search_field.config {
+            SearchFieldConfig::Text { fields, .. } => Some(fields.keys()),
+            _ => None,
+        };
+        suffixes.into_iter().flatten().filter_map(move |suffix| {
+            self.get_search_field(&SearchFieldName::multi_field(&search_field.name, suffix))
+        })
+    }
+
+    /// The field whose column `search_field` is read from, which is `search_field` itself
+    /// unless it's a multi-field.
+    pub fn column_field<'a>(&'a self, search_field: &'a SearchField) -> &'a SearchField {
+        self.fields
+            .iter()
+            .find(|field| {
+                self.multi_fields(field)
+                    .any(|multi_field| multi_field.id == search_field.id)
+            })
+            .unwrap_or(search_field)
+    }
+
     pub fn get_search_field(&self, name: &SearchFieldName) -> Option<&SearchField> {
         if let Some(lookup) = &self.lookup {
             lookup.get(name).and_then(|idx| self.fields.get(*idx))
@@ -815,6 +966,7 @@ impl AsTypeOid for (&PgRelation, &SearchIndexSchema) {
         if search_field.name.0 == "ctid" {
             return PgOid::BuiltIn(pgrx::pg_sys::BuiltinOid::TIDOID);
         }
+        let search_field = self.1.column_field(search_field);
         let indexrel = self.0;
         for attribute in indexrel.tuple_desc().iter() {
             let attname = attribute.name().to_string();
@@ -847,7 +999,7 @@ impl AsFieldType<String> for (&PgRelation, &SearchIndexSchema) {
     fn key_field(&self) -> (tantivy::schema::FieldType, PgOid, Field) {
         let search_field = self.1.key_field();
         let field = search_field.id.0;
-        let field_type = self.1.schema.get_field_entry(field).field_type().clone();
+        let field_type = self.1.query_field_type(&search_field);
         (field_type, self.typeoid(&search_field), field)
     }
 
@@ -863,8 +1015,9 @@ impl AsFieldType<String> for (&PgRelation, &SearchIndexSchema) {
             .iter()
             .map(|search_field| {
                 let field = search_field.id.0;
-                let field_type = self.1.schema.get_field_entry(field).field_type().clone();
-                (field_type, typeoid_lookup.typeoid(search_field), field)
+                let field_type = self.1.query_field_type(search_field);
+                let column_field = self.1.column_field(search_field);
+                (field_type, typeoid_lookup.typeoid(column_field), field)
             })
             .collect()
     }
@@ -873,7 +1026,7 @@ impl AsFieldType<String> for (&PgRelation, &SearchIndexSchema) {
             .get_search_field(&SearchFieldName(from.into()))
             .map(|search_field| {
                 let field = search_field.id.0;
-                let field_type = self.1.schema.get_field_entry(field).field_type().clone();
+                let field_type = self.1.query_field_type(search_field);
                 (field_type, self.typeoid(search_field), field)
             })
     }
@@ -0,0 +1,113 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+
+fn setup(conn: &mut PgConnection) {
+    r#"
+    CREATE TABLE agg_items (
+        id SERIAL PRIMARY KEY,
+        description TEXT,
+        category TEXT,
+        rating INTEGER
+    );
+
+    INSERT INTO agg_items (description, category, rating) VALUES
+        ('red shoes', 'footwear', 1),
+        ('blue shoes', 'footwear', 2),
+        ('green shoes', 'footwear', 3),
+        ('running shoes', 'sports', 4),
+        ('winter hat', 'clothing', 5);
+
+    CALL paradedb.create_bm25(
+        table_name => 'agg_items',
+        index_name => 'agg_idx',
+        key_field => 'id',
+        text_fields => paradedb.field('description') || paradedb.field('category', fast => true, tokenizer => paradedb.tokenizer('raw')),
+        numeric_fields => paradedb.field('rating')
+    );
+    "#
+    .execute(conn);
+}
+
+#[rstest]
+fn aggregate_stats(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    let (result,) = r#"
+    SELECT paradedb.aggregate(
+        'agg_idx',
+        paradedb.term('description', 'shoes'),
+        '{"rating_stats": {"stats": {"field": "rating"}}}'
+    )"#
+    .fetch_one::<(Value,)>(&mut conn);
+    assert_eq!(
+        result["rating_stats"],
+        json!({"count": 4, "sum": 10.0, "min": 1.0, "max": 4.0, "avg": 2.5})
+    );
+}
+
+#[rstest]
+fn aggregate_terms(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    let (result,) = r#"
+    SELECT paradedb.aggregate(
+        'agg_idx',
+        paradedb.all(),
+        '{"categories": {"terms": {"field": "category"}}}'
+    )"#
+    .fetch_one::<(Value,)>(&mut conn);
+    let buckets = result["categories"]["buckets"].as_array().unwrap();
+    assert_eq!(buckets.len(), 3);
+    assert_eq!(buckets[0], json!({"key": "footwear", "doc_count": 3}));
+}
+
+#[rstest]
+fn aggregate_respects_visibility(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    "DELETE FROM agg_items WHERE rating = 4; UPDATE agg_items SET rating = 10 WHERE rating = 1;"
+        .execute(&mut conn);
+
+    let (result,) = r#"
+    SELECT paradedb.aggregate(
+        'agg_idx',
+        paradedb.term('description', 'shoes'),
+        '{"rating_stats": {"stats": {"field": "rating"}}}'
+    )"#
+    .fetch_one::<(Value,)>(&mut conn);
+    assert_eq!(
+        result["rating_stats"],
+        json!({"count": 3, "sum": 15.0, "min": 2.0, "max": 10.0, "avg": 5.0})
+    );
+}
+
+#[rstest]
+fn aggregate_invalid_request(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    let result = r#"
+    SELECT paradedb.aggregate('agg_idx', paradedb.all(), '{"bad": {"nonsense": {}}}')"#
+        .execute_result(&mut conn);
+    assert!(result.is_err());
+}
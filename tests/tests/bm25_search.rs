@@ -17,7 +17,6 @@
 
 mod fixtures;
 
-use crate::fixtures::utils::pg_search_index_directory_path;
 use anyhow::Result;
 use approx::assert_relative_eq;
 use core::panic;
@@ -27,7 +26,6 @@ use pretty_assertions::assert_eq;
 use rstest::*;
 use sqlx::{types::BigDecimal, PgConnection};
 use std::str::FromStr;
-use tantivy::Index;
 
 #[rstest]
 async fn basic_search_query(mut conn: PgConnection) -> Result<(), sqlx::Error> {
@@ -492,53 +490,27 @@ fn update_non_indexed_column(mut conn: PgConnection) -> Result<()> {
     )"
     .execute(&mut conn);
 
-    let index_dir_path = pg_search_index_directory_path(&mut conn, "search_idx");
-    assert!(index_dir_path.exists());
-
-    // Get the index metadata
-    let index = Index::open_in_dir(index_dir_path.join("tantivy"))?;
-    let reader = index.reader()?;
-    let searcher = reader.searcher();
-    let total_docs = searcher
-        .segment_readers()
-        .iter()
-        .map(|segment_reader| segment_reader.num_docs())
-        .reduce(|acc, count| acc + count)
-        .unwrap_or(0);
+    fn total_docs(conn: &mut PgConnection) -> i64 {
+        "SELECT SUM(num_docs)::int8 FROM paradedb.index_info('search_idx')"
+            .fetch_one::<(i64,)>(conn)
+            .0
+    }
 
-    assert_eq!(total_docs, 41);
+    assert_eq!(total_docs(&mut conn), 41);
 
     // Update an indexed column.
     "UPDATE mock_items set description = 'Organic blue tea' WHERE description = 'Organic green tea'"
         .execute(&mut conn);
 
-    reader.reload()?;
-
-    let searcher = reader.searcher();
-    let total_docs = searcher
-        .segment_readers()
-        .iter()
-        .map(|segment_reader| segment_reader.num_docs())
-        .reduce(|acc, count| acc + count)
-        .unwrap_or(0);
-
     // The total document should be higher, as a new document was created for the updated row.
-    assert_eq!(total_docs, 42);
+    assert_eq!(total_docs(&mut conn), 42);
 
     // Update a non-indexed column.
     "UPDATE mock_items set category = 'Books' WHERE description = 'Sleek running shoes'"
         .execute(&mut conn);
 
-    let searcher = reader.searcher();
-    let total_docs = searcher
-        .segment_readers()
-        .iter()
-        .map(|segment_reader| segment_reader.num_docs())
-        .reduce(|acc, count| acc + count)
-        .unwrap_or(0);
-
     // The total document count should not have changed when updating a non-indexed column.
-    assert_eq!(total_docs, 42);
+    assert_eq!(total_docs(&mut conn), 42);
 
     Ok(())
 }
@@ -984,13 +956,6 @@ fn high_limit_rows(mut conn: PgConnection) {
 fn index_size(mut conn: PgConnection) {
     SimpleProductsTable::setup().execute(&mut conn);
 
-    let index_dir = pg_search_index_directory_path(&mut conn, "bm25_search_bm25_index");
-    assert!(
-        index_dir.exists(),
-        "expected index directory to exist at: {:?}",
-        index_dir
-    );
-
     // Calculate the index size using the new method
     let size: i64 = "SELECT paradedb.index_size('paradedb.bm25_search_bm25_index')"
         .fetch_one::<(i64,)>(&mut conn)
@@ -0,0 +1,99 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use tokio::join;
+
+/// A build's progress can be watched from another connection through
+/// `pg_stat_progress_create_index`, and `tuples_done` is only updated every 1,000 rows.
+#[rstest]
+#[tokio::test]
+async fn test_create_index_progress(database: Db) -> Result<()> {
+    let mut conn = database.connection().await;
+    let mut watcher = database.connection().await;
+
+    // indexing the 2,500th row takes long enough for the watcher to see the build's progress
+    r#"
+    CREATE EXTENSION pg_search;
+    CREATE TABLE progress_test (id SERIAL PRIMARY KEY, body TEXT);
+    INSERT INTO progress_test (body) SELECT 'row ' || i FROM generate_series(1, 5000) i;
+    ANALYZE progress_test;
+    CREATE FUNCTION slow_body(body TEXT) RETURNS TEXT IMMUTABLE LANGUAGE plpgsql AS $$
+    BEGIN
+        IF body = 'row 2500' THEN
+            PERFORM pg_sleep(3);
+        END IF;
+        RETURN body;
+    END
+    $$;
+    "#
+    .execute(&mut conn);
+
+    let build = async move {
+        "SET max_parallel_maintenance_workers = 0;
+        CREATE INDEX progress_test_idx ON progress_test
+        USING bm25 (id, slow_body(body))
+        WITH (key_field = 'id', text_fields = '{\"slow_body\": {}}');"
+            .execute_async(&mut conn)
+            .await;
+        conn
+    };
+
+    let watch = async move {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let progress: Option<(String, i64, i64)> = sqlx::query_as(
+                "SELECT phase, tuples_done, tuples_total FROM pg_stat_progress_create_index WHERE datname = current_database()",
+            )
+            .fetch_optional(&mut watcher)
+            .await
+            .expect("should be able to read pg_stat_progress_create_index");
+
+            if let Some((phase, tuples_done, tuples_total)) = progress {
+                assert_eq!(tuples_done % 1000, 0);
+                if tuples_done >= 2000 || Instant::now() > deadline {
+                    return (phase, tuples_done, tuples_total);
+                }
+            } else {
+                assert!(Instant::now() < deadline, "the build never started");
+            }
+            async_std::task::sleep(Duration::from_millis(50)).await;
+        }
+    };
+
+    let (mut conn, progress) = join!(build, watch);
+    assert_eq!(
+        progress,
+        ("building index: scanning table".to_string(), 2000, 5000)
+    );
+
+    let (count,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM progress_test WHERE progress_test @@@ 'slow_body:row'",
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    assert_eq!(count, 5000);
+
+    Ok(())
+}
@@ -412,3 +412,118 @@ fn leaky_file_handles(mut conn: PgConnection) {
         assert!(!tantivy_files_still_open(pid));
     }
 }
+
+#[rstest]
+fn parallel_custom_scan(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    r#"
+    CREATE TABLE parallel_items (id SERIAL8 NOT NULL PRIMARY KEY, description TEXT);
+    CALL paradedb.create_bm25(
+        index_name => 'parallel_items_idx',
+        table_name => 'parallel_items',
+        key_field => 'id',
+        text_fields => paradedb.field('description')
+    );
+    "#
+    .execute(&mut conn);
+
+    // every statement commits a new segment
+    for _ in 0..5 {
+        "INSERT INTO parallel_items (description) SELECT 'keyboard ' || x FROM generate_series(1, 1000) x;"
+            .execute(&mut conn);
+    }
+    "ANALYZE parallel_items;".execute(&mut conn);
+
+    "SET max_parallel_workers_per_gather = 2;
+     SET parallel_setup_cost = 0;
+     SET parallel_tuple_cost = 0;
+     SET min_parallel_table_scan_size = 0;
+     SET enable_indexscan TO off;"
+        .execute(&mut conn);
+
+    let (plan,) =
+        "EXPLAIN (FORMAT JSON) SELECT * FROM parallel_items WHERE description @@@ 'keyboard'"
+            .fetch_one::<(Value,)>(&mut conn);
+    let plan = plan.pointer("/0/Plan").unwrap();
+    eprintln!("{plan:#?}");
+    assert_eq!(plan.get("Node Type"), Some(&Value::from("Gather")));
+    let scan = plan.pointer("/Plans/0").unwrap();
+    assert_eq!(
+        scan.get("Custom Plan Provider"),
+        Some(&Value::from("ParadeDB Scan"))
+    );
+    assert_eq!(scan.get("Parallel Aware"), Some(&Value::from(true)));
+
+    // the workers and the leader, between them, should return every matching row exactly once
+    let (count, distinct) =
+        "SELECT COUNT(*), COUNT(DISTINCT id) FROM parallel_items WHERE description @@@ 'keyboard'"
+            .fetch_one::<(i64, i64)>(&mut conn);
+    assert_eq!(count, 5000);
+    assert_eq!(distinct, 5000);
+}
+
+#[rstest]
+fn count_star_pushdown(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    let (plan,) = "EXPLAIN (FORMAT JSON) SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes'"
+        .fetch_one::<(Value,)>(&mut conn);
+    let plan = plan.pointer("/0/Plan").unwrap();
+    eprintln!("{plan:#?}");
+    assert_eq!(
+        plan.get("Custom Plan Provider"),
+        Some(&Value::from("ParadeDB Count Scan"))
+    );
+    assert_eq!(plan.get("Aggregate"), Some(&Value::from("count(*)")));
+
+    let (count,) = "SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes'"
+        .fetch_one::<(i64,)>(&mut conn);
+
+    "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+    let (expected,) = "SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, expected);
+}
+
+#[rstest]
+fn count_star_pushdown_respects_visibility(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    // every page is all-visible, so tantivy counts the matches by itself
+    "VACUUM paradedb.bm25_search".execute(&mut conn);
+    let (count,) = "SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes'"
+        .fetch_one::<(i64,)>(&mut conn);
+
+    // the deleted rows are still in the index until the next vacuum, but aren't visible
+    "DELETE FROM paradedb.bm25_search WHERE id IN (SELECT id FROM paradedb.bm25_search WHERE description @@@ 'shoes')"
+        .execute(&mut conn);
+    let (deleted,) = "SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert!(deleted < count);
+
+    let (keyboards,) =
+        "SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard'"
+            .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(deleted, keyboards);
+}
+
+#[rstest]
+fn count_star_not_pushed_down(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    // grouping, other aggregates and quals the index can't answer all need the tuples
+    for query in [
+        "SELECT category, count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes' GROUP BY category",
+        "SELECT count(*), max(rating) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes'",
+        "SELECT count(*) FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes' AND rating > 2",
+    ] {
+        let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+        eprintln!("{plan:#?}");
+        assert!(!plan.to_string().contains("ParadeDB Count Scan"));
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use serde_json::Value;
+use sqlx::PgConnection;
+
+#[rstest]
+fn explain_score_matches_score(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    let (score,) = "
+    SELECT paradedb.score(id) FROM paradedb.bm25_search
+    WHERE bm25_search @@@ 'description:keyboard' AND id = 2"
+        .fetch_one::<(f32,)>(&mut conn);
+
+    let (explanation,) = "
+    SELECT paradedb.explain_score(
+        'paradedb.bm25_search_bm25_index',
+        paradedb.parse('description:keyboard'),
+        2
+    )"
+    .fetch_one::<(Value,)>(&mut conn);
+
+    assert_eq!(explanation["value"].as_f64().unwrap() as f32, score);
+    let text = explanation.to_string();
+    assert!(text.contains("freq, occurrences of term within document"));
+    assert!(text.contains("avgdl, average length of field"));
+}
+
+#[rstest]
+fn explain_score_boolean_clauses(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    let (explanation,) = "
+    SELECT paradedb.explain_score(
+        'paradedb.bm25_search_bm25_index',
+        paradedb.boolean(should => ARRAY[
+            paradedb.boost(2, paradedb.term('description', 'keyboard')),
+            paradedb.term('category', 'electronics')
+        ]),
+        1
+    )"
+    .fetch_one::<(Value,)>(&mut conn);
+
+    // one detail per matching clause
+    let details = explanation["details"].as_array().unwrap();
+    assert_eq!(details.len(), 2);
+    assert!(explanation.to_string().contains("Boost"));
+}
+
+#[rstest]
+fn explain_score_errors(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    // the row doesn't match the query
+    let res = "
+    SELECT paradedb.explain_score(
+        'paradedb.bm25_search_bm25_index',
+        paradedb.parse('description:keyboard'),
+        3
+    )"
+    .execute_result(&mut conn);
+    assert!(res.is_err());
+
+    // the row is no longer visible
+    "DELETE FROM paradedb.bm25_search WHERE id = 2".execute(&mut conn);
+    let res = "
+    SELECT paradedb.explain_score(
+        'paradedb.bm25_search_bm25_index',
+        paradedb.parse('description:keyboard'),
+        2
+    )"
+    .execute_result(&mut conn);
+    assert!(res.is_err());
+}
@@ -20,7 +20,6 @@ mod fixtures;
 
 use std::path::PathBuf;
 
-use fixtures::utils::pg_search_index_directory_path;
 use fixtures::*;
 use pretty_assertions::assert_eq;
 use rstest::*;
@@ -607,6 +606,227 @@ fn multi_index_insert_in_transaction(mut conn: PgConnection) {
     assert_eq!(rows.len(), 2);
 }
 
+#[rstest]
+fn expression_fields(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE expressions (id SERIAL PRIMARY KEY, title TEXT, body TEXT, metadata JSONB);
+        INSERT INTO expressions (title, body, metadata) VALUES
+        ('Running Shoes', 'light and fast', '{"color": "red"}'),
+        ('Keyboard', 'mechanical switches', '{"color": "black"}');
+
+        CREATE INDEX expressions_idx ON expressions
+        USING bm25 (id, lower(title), (title || ' ' || body), (metadata ->> 'color'))
+        WITH (key_field = 'id', text_fields = '{"lower": {}, "expr": {}, "expr1": {}}');
+    "#
+    .execute(&mut conn);
+
+    // Postgres names the index columns of expressions, and each is a field of its own
+    let columns: Vec<(String,)> = r#"
+        SELECT attname::text FROM pg_attribute
+        WHERE attrelid = 'expressions_idx'::regclass ORDER BY attnum
+    "#
+    .fetch(&mut conn);
+    assert_eq!(
+        columns,
+        vec![
+            ("id".into(),),
+            ("lower".into(),),
+            ("expr".into(),),
+            ("expr1".into(),)
+        ]
+    );
+
+    // rows indexed by CREATE INDEX
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM expressions WHERE expressions @@@ 'lower:shoes'".fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM expressions WHERE expressions @@@ 'expr:(keyboard AND switches)'"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,)]);
+
+    // and by inserts
+    r#"INSERT INTO expressions (title, body, metadata) VALUES ('Trail Shoes', 'grippy', '{"color": "black"}')"#
+        .execute(&mut conn);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM expressions WHERE expressions @@@ 'expr1:black' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,), (3,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM expressions WHERE expressions @@@ 'expr:(trail AND grippy)'"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(3,)]);
+
+    // the key field can't be an expression
+    "DROP INDEX expressions_idx".execute(&mut conn);
+    let result = r#"
+        CREATE INDEX expressions_idx ON expressions
+        USING bm25 (id, title, (id + 1))
+        WITH (key_field = 'expr', text_fields = '{"title": {}}')
+    "#
+    .execute_result(&mut conn);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("must be a column of the table, not an expression"));
+}
+
+#[rstest]
+fn multi_fields(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE multi_fields (id SERIAL PRIMARY KEY, title TEXT);
+        INSERT INTO multi_fields (title) VALUES ('Running Shoes'), ('Keyboard Runner');
+
+        CALL paradedb.create_bm25(
+            index_name => 'multi_fields_bm25_index',
+            table_name => 'multi_fields',
+            key_field => 'id',
+            text_fields => paradedb.field(
+                'title',
+                tokenizer => paradedb.tokenizer('en_stem'),
+                fields => paradedb.field('ngram', stored => false, tokenizer => paradedb.tokenizer('ngram', min_gram => 3, max_gram => 3, prefix_only => true)) ||
+                          paradedb.field('raw', stored => false, tokenizer => paradedb.tokenizer('raw', lowercase => false))
+            )
+        );
+    "#
+    .execute(&mut conn);
+
+    // every sub-field is a field of its own
+    let fields: Vec<(String,)> = r#"
+        SELECT name FROM paradedb.schema('multi_fields_bm25_index') ORDER BY name
+    "#
+    .fetch(&mut conn);
+    assert_eq!(
+        fields,
+        vec![
+            ("ctid".into(),),
+            ("id".into(),),
+            ("title".into(),),
+            ("title.ngram".into(),),
+            ("title.raw".into(),)
+        ]
+    );
+
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM multi_fields WHERE multi_fields @@@ 'title:run' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM multi_fields WHERE id @@@ paradedb.parse('title.ngram:run') ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM multi_fields WHERE id @@@ paradedb.term('title.raw', 'Keyboard Runner')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM multi_fields WHERE id @@@ paradedb.term_set(ARRAY[paradedb.term('title.raw', 'Keyboard Runner')])"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,)]);
+
+    // inserted rows are indexed into every sub-field
+    "INSERT INTO multi_fields (title) VALUES ('Runway Lights')".execute(&mut conn);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM multi_fields WHERE id @@@ paradedb.parse('title.ngram:run') ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (3,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM multi_fields WHERE id @@@ paradedb.term('title.raw', 'Runway Lights')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(3,)]);
+
+    // sub-fields can't have sub-fields of their own
+    "DROP INDEX multi_fields_bm25_index".execute(&mut conn);
+    let result = r#"
+        CREATE INDEX multi_fields_nested_idx ON multi_fields
+        USING bm25 (id, title)
+        WITH (key_field = 'id', text_fields = '{"title": {"fields": {"raw": {"fields": {"lower": {}}}}}}')
+    "#
+    .execute_result(&mut conn);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("cannot have 'fields' of its own"));
+}
+
+#[rstest]
+fn search_tokenizer(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE search_tokenizer (id SERIAL PRIMARY KEY, title TEXT);
+        INSERT INTO search_tokenizer (title) VALUES ('Keyboard'), ('Keys and locks');
+
+        CALL paradedb.create_bm25(
+            index_name => 'search_tokenizer_bm25_index',
+            table_name => 'search_tokenizer',
+            key_field => 'id',
+            text_fields => paradedb.field(
+                'title',
+                tokenizer => paradedb.tokenizer('ngram', min_gram => 2, max_gram => 4, prefix_only => true),
+                search_tokenizer => paradedb.tokenizer('default')
+            )
+        );
+    "#
+    .execute(&mut conn);
+
+    // queries are looked up as whole words among the indexed ngrams, rather than being split
+    // into ngrams of their own, which would match any title starting with "ke"
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM search_tokenizer WHERE search_tokenizer @@@ 'title:key' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,)]);
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM search_tokenizer WHERE search_tokenizer @@@ 'title:keyb' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+    let rows: Vec<(i32,)> = r#"
+        SELECT id FROM search_tokenizer
+        WHERE id @@@ paradedb.fuzzy_phrase(field => 'title', value => 'keyb', distance => 0)
+        ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+}
+
+#[rstest]
+fn phonetic_tokenizer(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE customers (id SERIAL PRIMARY KEY, name TEXT);
+        INSERT INTO customers (name) VALUES ('John Smith'), ('Jon Smyth'), ('Jane Doe');
+
+        CALL paradedb.create_bm25(
+            index_name => 'customers_bm25_index',
+            table_name => 'customers',
+            key_field => 'id',
+            text_fields => paradedb.field(
+                'name',
+                tokenizer => paradedb.tokenizer(
+                    'default',
+                    phonetic => '{"algorithm": "double_metaphone", "preserve_original": true}'
+                ),
+                search_tokenizer => paradedb.tokenizer(
+                    'default',
+                    phonetic => '{"algorithm": "double_metaphone"}'
+                )
+            )
+        );
+    "#
+    .execute(&mut conn);
+
+    // queries are only encoded, since an original spelling would have to match as well
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM customers WHERE customers @@@ 'name:smith' ORDER BY id".fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,)]);
+
+    // the original tokens are kept, so exact spellings can still be told apart
+    let rows: Vec<(i32,)> = r#"
+        SELECT id FROM customers
+        WHERE id @@@ paradedb.term(field => 'name', value => 'smyth')
+        ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,)]);
+}
+
 #[rstest]
 fn partitioned_index(mut conn: PgConnection) {
     r#"
@@ -637,7 +857,7 @@ fn partitioned_index(mut conn: PgConnection) {
     "#
     .execute(&mut conn);
 
-    match r#"
+    r#"
         CALL paradedb.create_bm25(
             index_name => 'sales_index',
             table_name => 'sales',
@@ -647,10 +867,46 @@ fn partitioned_index(mut conn: PgConnection) {
             datetime_fields => paradedb.field('sale_date'),
             numeric_fields => paradedb.field('amount')
         )
-    "#.execute_result(&mut conn) {
-        Ok(_) => panic!("should fail with partitioned table"),
-        Err(err) => assert_eq!(err.to_string(), "error returned from database: Creating BM25 indexes over partitioned tables is a ParadeDB enterprise feature. Contact support@paradedb.com for access."),
-    };
+    "#
+    .execute(&mut conn);
+
+    // each partition gets an index of its own
+    let (count,) = r#"
+        SELECT COUNT(*) FROM pg_inherits
+        WHERE inhparent = 'sales_index'::regclass
+    "#
+    .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 2);
+
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM sales WHERE description @@@ 'keyboard OR wall' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,), (6,), (7,)]);
+
+    // documents are scored as if the table weren't partitioned
+    r#"
+        CREATE TABLE sales_unpartitioned AS SELECT * FROM sales;
+        ALTER TABLE sales_unpartitioned ADD PRIMARY KEY (id);
+        CALL paradedb.create_bm25(
+            index_name => 'sales_unpartitioned_index',
+            table_name => 'sales_unpartitioned',
+            schema_name => 'public',
+            key_field => 'id',
+            text_fields => paradedb.field('description')
+        )
+    "#
+    .execute(&mut conn);
+    let partitioned: Vec<(i32, f32)> = r#"
+        SELECT id, paradedb.score(id) FROM sales
+        WHERE description @@@ 'keyboard OR wall' ORDER BY id
+    "#
+    .fetch(&mut conn);
+    let unpartitioned: Vec<(i32, f32)> = r#"
+        SELECT id, paradedb.score(id) FROM sales_unpartitioned
+        WHERE description @@@ 'keyboard OR wall' ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(partitioned, unpartitioned);
 }
 
 #[rstest]
@@ -668,27 +924,20 @@ fn delete_index_deletes_tantivy_files(mut conn: PgConnection) {
     )"
     .execute(&mut conn);
 
-    // Ensure the expected directory exists.
-    let index_dir = pg_search_index_directory_path(&mut conn, "index_config_index");
-    assert!(
-        index_dir.exists(),
-        "expected index directory to exist at: {:?}",
-        index_dir
-    );
+    // The index is stored in the index relation's own storage.
+    let index_size = "SELECT paradedb.index_size('index_config_index')"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert!(index_size > 0, "expected the index to have been written");
 
     // Delete the index.
     "DROP INDEX index_config_index CASCADE".execute(&mut conn);
 
     // Ensure deletion has worked as expected.
-    // Tantivy is a little stubborn about deletion. While the contents of the index
-    // will indeed be cleaned up, lingering Readers cached in connections seem to re-create
-    // certain files if they are found to be deleted. This makes it difficult to completely
-    // clean up the folder, so we will just test if our configuration JSON has been deleted.
-    assert!(
-        !index_dir.join("search-index.json").exists(),
-        "expected index directory to have been deleted at: {:?}",
-        index_dir
-    );
+    let index_count = "SELECT COUNT(*) FROM pg_class WHERE relname = 'index_config_index'"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(index_count, 0, "expected index to have been deleted");
 }
 
 #[rstest]
@@ -706,14 +955,6 @@ fn delete_index_aborted_maintains_tantivy_files(mut conn: PgConnection) {
     )"
     .execute(&mut conn);
 
-    // Ensure the expected directory exists.
-    let index_dir = pg_search_index_directory_path(&mut conn, "index_config_index");
-    assert!(
-        index_dir.exists(),
-        "expected index directory to exist at: {:?}",
-        index_dir
-    );
-
     // Delete the index.
     "DO $$ 
     BEGIN
@@ -723,10 +964,66 @@ fn delete_index_aborted_maintains_tantivy_files(mut conn: PgConnection) {
         .execute_result(&mut conn)
         .ok();
 
-    // Ensure index files still exist.
+    // Ensure the index is still intact and searchable.
+    let rows: Vec<(i32,)> =
+        "SELECT id FROM index_config WHERE index_config @@@ 'description:shoes' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(3,), (4,), (5,)]);
+}
+
+#[rstest]
+fn merge_policy_option(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE merge_policy_test (id SERIAL PRIMARY KEY, body TEXT);
+    CALL paradedb.create_bm25(
+        index_name => 'merge_policy_test_bm25_index',
+        table_name => 'merge_policy_test',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );
+    "#
+    .execute(&mut conn);
+
+    match r#"ALTER INDEX merge_policy_test_bm25_index SET (merge_policy = '{"type": "tiered", "segments_per_tier": 1}')"#
+        .execute_result(&mut conn)
+    {
+        Ok(_) => panic!("should fail with invalid segments_per_tier"),
+        Err(err) => assert!(
+            err.to_string().contains("invalid merge_policy"),
+            "{}",
+            fmt_err(err)
+        ),
+    };
+
+    match r#"ALTER INDEX merge_policy_test_bm25_index SET (merge_policy = '{"type": "lucene"}')"#
+        .execute_result(&mut conn)
+    {
+        Ok(_) => panic!("should fail with unknown policy"),
+        Err(err) => assert!(
+            err.to_string().contains("invalid merge_policy"),
+            "{}",
+            fmt_err(err)
+        ),
+    };
+
+    r#"ALTER INDEX merge_policy_test_bm25_index SET (merge_policy = '{"type": "delete_ratio", "deleted_ratio": 0.5}')"#
+        .execute(&mut conn);
+    r#"ALTER INDEX merge_policy_test_bm25_index SET (merge_policy = '{"type": "tiered", "segments_per_tier": 2}')"#
+        .execute(&mut conn);
+
+    for i in 0..4 {
+        format!("INSERT INTO merge_policy_test (body) VALUES ('row {i}')").execute(&mut conn);
+    }
+    "VACUUM merge_policy_test".execute(&mut conn);
+
+    let (segments,) = "SELECT COUNT(*) FROM paradedb.index_info('merge_policy_test_bm25_index')"
+        .fetch_one::<(i64,)>(&mut conn);
     assert!(
-        index_dir.join("search-index.json").exists(),
-        "expected index directory to have been not been deleted at: {:?}",
-        index_dir
+        segments <= 2,
+        "tiered merge policy left {segments} segments"
     );
+
+    let (count,) = "SELECT COUNT(*) FROM merge_policy_test WHERE merge_policy_test @@@ 'body:row'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 4);
 }
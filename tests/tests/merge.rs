@@ -0,0 +1,66 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use rstest::*;
+use sqlx::PgConnection;
+use std::time::{Duration, Instant};
+
+#[rstest]
+fn background_merge(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE merge_test (id SERIAL PRIMARY KEY, body TEXT);
+    CALL paradedb.create_bm25(
+        index_name => 'merge_test_bm25_index',
+        table_name => 'merge_test',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );
+    ALTER INDEX merge_test_bm25_index SET (target_segment_count = 2);
+    "#
+    .execute(&mut conn);
+
+    // every statement writes a new segment, and none of them merge
+    for i in 0..10 {
+        format!("INSERT INTO merge_test (body) VALUES ('row {i}')").execute(&mut conn);
+    }
+    "DELETE FROM merge_test WHERE id <= 5".execute(&mut conn);
+
+    "ALTER SYSTEM SET paradedb.merge_naptime = 1".execute(&mut conn);
+    "SELECT pg_reload_conf()".execute(&mut conn);
+
+    let started = Instant::now();
+    let segments = loop {
+        let (segments,) = "SELECT COUNT(*) FROM paradedb.index_info('merge_test_bm25_index')"
+            .fetch_one::<(i64,)>(&mut conn);
+        if segments <= 3 || started.elapsed() > Duration::from_secs(30) {
+            break segments;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    };
+
+    "ALTER SYSTEM RESET paradedb.merge_naptime".execute(&mut conn);
+    "SELECT pg_reload_conf()".execute(&mut conn);
+
+    assert!(segments <= 3, "background merge left {segments} segments");
+
+    let (count,) = "SELECT COUNT(*) FROM merge_test WHERE merge_test @@@ 'body:row'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 5);
+}
@@ -0,0 +1,70 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use sqlx::PgConnection;
+
+#[rstest]
+fn merge_segments_to_one(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE merge_segments_test (id SERIAL PRIMARY KEY, body TEXT);
+    CALL paradedb.create_bm25(
+        index_name => 'merge_segments_test_bm25_index',
+        table_name => 'merge_segments_test',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );
+    ALTER INDEX merge_segments_test_bm25_index SET (merge_on_insert = false);
+    "#
+    .execute(&mut conn);
+
+    for i in 0..6 {
+        format!("INSERT INTO merge_segments_test (body) VALUES ('row {i}')").execute(&mut conn);
+    }
+    "DELETE FROM merge_segments_test WHERE id = 1".execute(&mut conn);
+
+    let rows: Vec<(String, i64, i64, i64)> = "SELECT stage, num_segments, num_docs, num_deleted FROM paradedb.merge_segments('merge_segments_test_bm25_index')"
+        .fetch(&mut conn);
+    assert_eq!(rows[0].0, "before");
+    assert!(rows[0].1 >= 1);
+    assert_eq!(rows[1], ("after".into(), 1, 5, 0));
+
+    let (segments,) = "SELECT COUNT(*) FROM paradedb.index_info('merge_segments_test_bm25_index')"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(segments, 1);
+
+    let (count,) =
+        "SELECT COUNT(*) FROM merge_segments_test WHERE merge_segments_test @@@ 'body:row'"
+            .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 5);
+}
+
+#[rstest]
+fn merge_segments_invalid_max_segments(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    match "SELECT * FROM paradedb.merge_segments('paradedb.bm25_search_bm25_index', max_segments => 0)"
+        .execute_result(&mut conn)
+    {
+        Ok(_) => panic!("should fail with max_segments = 0"),
+        Err(err) => assert!(err.to_string().contains("max_segments must be at least 1")),
+    }
+}
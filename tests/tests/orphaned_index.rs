@@ -0,0 +1,60 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use sqlx::PgConnection;
+use std::path::PathBuf;
+
+#[rstest]
+fn remove_orphaned_index_directories(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    let (data_directory,) = "SHOW data_directory".fetch_one::<(String,)>(&mut conn);
+    let (database_oid,) = "SELECT oid::int8 FROM pg_database WHERE datname = current_database()"
+        .fetch_one::<(i64,)>(&mut conn);
+
+    // a directory left behind by an index that no longer exists
+    let index_oid: i64 = 4_000_000_000;
+    let orphan = PathBuf::from(data_directory)
+        .join("pg_search")
+        .join(database_oid.to_string())
+        .join(index_oid.to_string())
+        .join("1");
+    std::fs::create_dir_all(orphan.join("tantivy")).unwrap();
+    std::fs::write(orphan.join("tantivy").join("meta.json"), [0u8; 100]).unwrap();
+    std::fs::write(orphan.join("search-index.json"), [0u8; 28]).unwrap();
+
+    let rows: Vec<(i64, i64, i64, bool)> =
+        "SELECT index_oid::int8, relfilenode::int8, byte_size, removed FROM paradedb.orphaned_index_directories()"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(index_oid, 1, 128, false)]);
+    assert!(orphan.exists());
+
+    let rows: Vec<(i64, bool)> =
+        "SELECT index_oid::int8, removed FROM paradedb.orphaned_index_directories(remove => true)"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(index_oid, true)]);
+    assert!(!orphan.exists());
+
+    let rows: Vec<(i64,)> =
+        "SELECT index_oid::int8 FROM paradedb.orphaned_index_directories()".fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+}
@@ -177,3 +177,44 @@ async fn test_statement_level_locking(database: Db) -> Result<()> {
 
     Ok(())
 }
+
+/// Transactions that only insert into the same index don't wait for each other's writer lock.
+#[rstest]
+#[tokio::test]
+async fn test_concurrent_writers_do_not_block(database: Db) -> Result<()> {
+    let mut conn1 = database.connection().await;
+    let mut conn2 = database.connection().await;
+
+    "CREATE EXTENSION pg_search;
+
+    CREATE TABLE concurrent_writers (id SERIAL PRIMARY KEY, body TEXT);
+
+    CALL paradedb.create_bm25(
+        table_name => 'concurrent_writers',
+        index_name => 'concurrent_writers',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );"
+    .execute(&mut conn1);
+
+    "BEGIN; INSERT INTO concurrent_writers (body) VALUES ('first writer');".execute(&mut conn1);
+
+    // this would time out waiting for the first transaction to end if writers were serialized
+    "SET statement_timeout = '5s';
+    INSERT INTO concurrent_writers (body) VALUES ('second writer');"
+        .execute(&mut conn2);
+
+    "COMMIT".execute(&mut conn1);
+
+    let (count,) =
+        "SELECT COUNT(*) FROM concurrent_writers WHERE concurrent_writers @@@ 'body:writer'"
+            .fetch_one::<(i64,)>(&mut conn1);
+    assert_eq!(count, 2);
+
+    let (num_docs,) =
+        "SELECT SUM(num_docs)::int8 FROM paradedb.index_info('concurrent_writers_bm25_index')"
+            .fetch_one::<(i64,)>(&mut conn2);
+    assert_eq!(num_docs, 2);
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// parallel builds of bm25 indexes need `amcanbuildparallel`, which was added in Postgres 17
+#![cfg(feature = "pg17")]
+
+mod fixtures;
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use sqlx::PgConnection;
+use tokio::join;
+
+#[rstest]
+fn parallel_create_index(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE parallel_build (id SERIAL PRIMARY KEY, body TEXT);
+    ALTER TABLE parallel_build SET (parallel_workers = 2);
+    INSERT INTO parallel_build (body)
+    SELECT CASE WHEN i % 2 = 0 THEN 'even row ' || i ELSE 'odd row ' || i END
+    FROM generate_series(1, 50000) i;
+    SET max_parallel_maintenance_workers = 2;
+    SET min_parallel_table_scan_size = 0;
+    CALL paradedb.create_bm25(
+        index_name => 'parallel_build_bm25_index',
+        table_name => 'parallel_build',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );
+    "#
+    .execute(&mut conn);
+
+    let (count,) = "SELECT COUNT(*) FROM parallel_build WHERE parallel_build @@@ 'body:row'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 50000);
+
+    let (count,) = "SELECT COUNT(*) FROM parallel_build WHERE parallel_build @@@ 'body:even'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 25000);
+
+    let (num_docs,) =
+        "SELECT SUM(num_docs)::bigint FROM paradedb.index_info('parallel_build_bm25_index')"
+            .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(num_docs, 50000);
+
+    // the index is writable as usual afterwards
+    "INSERT INTO parallel_build (body) VALUES ('even newer row')".execute(&mut conn);
+    let (count,) = "SELECT COUNT(*) FROM parallel_build WHERE parallel_build @@@ 'body:even'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 25001);
+
+    "REINDEX INDEX parallel_build_bm25_index".execute(&mut conn);
+    let (count,) = "SELECT COUNT(*) FROM parallel_build WHERE parallel_build @@@ 'body:row'"
+        .fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 50001);
+}
+
+/// The build's workers show up in `pg_stat_activity` while they index their share of the table.
+#[rstest]
+#[tokio::test]
+async fn parallel_create_index_launches_workers(database: Db) -> Result<()> {
+    let mut conn = database.connection().await;
+    let mut watcher = database.connection().await;
+
+    // every row takes a little while to index, so the build lasts long enough to be watched
+    r#"
+    CREATE EXTENSION pg_search;
+    CREATE TABLE parallel_workers_build (id SERIAL PRIMARY KEY, body TEXT);
+    ALTER TABLE parallel_workers_build SET (parallel_workers = 2);
+    INSERT INTO parallel_workers_build (body) SELECT 'row ' || i FROM generate_series(1, 2000) i;
+    CREATE FUNCTION slow_body(body TEXT) RETURNS TEXT IMMUTABLE PARALLEL SAFE LANGUAGE plpgsql AS $$
+    BEGIN
+        PERFORM pg_sleep(0.002);
+        RETURN body;
+    END
+    $$;
+    "#
+    .execute(&mut conn);
+    let (leader_pid,): (i32,) = sqlx::query_as("SELECT pg_backend_pid()")
+        .fetch_one(&mut conn)
+        .await?;
+
+    let build = async move {
+        "SET max_parallel_maintenance_workers = 2;
+        SET min_parallel_table_scan_size = 0;
+        CREATE INDEX parallel_workers_build_idx ON parallel_workers_build
+        USING bm25 (id, slow_body(body))
+        WITH (key_field = 'id', text_fields = '{\"slow_body\": {}}');"
+            .execute_async(&mut conn)
+            .await;
+        conn
+    };
+
+    let watch = async move {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut most_workers = 0;
+        loop {
+            let (workers, building): (i64, bool) = sqlx::query_as(
+                "SELECT
+                    (SELECT COUNT(*) FROM pg_stat_activity WHERE leader_pid = $1 AND backend_type = 'parallel worker'),
+                    EXISTS (SELECT 1 FROM pg_stat_progress_create_index WHERE pid = $1)",
+            )
+            .bind(leader_pid)
+            .fetch_one(&mut watcher)
+            .await
+            .expect("should be able to read pg_stat_activity");
+
+            most_workers = most_workers.max(workers);
+            if most_workers == 2 || (!building && most_workers > 0) || Instant::now() > deadline {
+                return most_workers;
+            }
+            async_std::task::sleep(Duration::from_millis(20)).await;
+        }
+    };
+
+    let (mut conn, most_workers) = join!(build, watch);
+    assert!(most_workers > 0, "the build didn't launch any workers");
+
+    let (count,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM parallel_workers_build WHERE parallel_workers_build @@@ 'slow_body:row'",
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    assert_eq!(count, 2000);
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use sqlx::PgConnection;
+
+#[rstest]
+fn commit_and_rollback_prepared(mut conn: PgConnection) {
+    fn count(conn: &mut PgConnection) -> i64 {
+        "SELECT COUNT(*) FROM prepared_test WHERE prepared_test @@@ 'body:keyboard'"
+            .fetch_one::<(i64,)>(conn)
+            .0
+    }
+
+    let (max_prepared_transactions,) =
+        "SHOW max_prepared_transactions".fetch_one::<(String,)>(&mut conn);
+    assert_ne!(
+        max_prepared_transactions, "0",
+        "the test server must be configured with max_prepared_transactions > 0"
+    );
+
+    r#"
+    CREATE TABLE prepared_test (id SERIAL PRIMARY KEY, body TEXT);
+    CALL paradedb.create_bm25(
+        index_name => 'prepared_test_bm25_index',
+        table_name => 'prepared_test',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );
+    "#
+    .execute(&mut conn);
+
+    "BEGIN".execute(&mut conn);
+    "INSERT INTO prepared_test (body) VALUES ('committed keyboard')".execute(&mut conn);
+    "PREPARE TRANSACTION 'pg_search_commit'".execute(&mut conn);
+
+    "BEGIN".execute(&mut conn);
+    "INSERT INTO prepared_test (body) VALUES ('rolled back keyboard')".execute(&mut conn);
+    "PREPARE TRANSACTION 'pg_search_rollback'".execute(&mut conn);
+    assert_eq!(count(&mut conn), 0);
+
+    "COMMIT PREPARED 'pg_search_commit'".execute(&mut conn);
+    assert_eq!(count(&mut conn), 1);
+
+    // the rolled back transaction's documents are removed from the index right away, without
+    // waiting for VACUUM
+    "ROLLBACK PREPARED 'pg_search_rollback'".execute(&mut conn);
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+
+    "VACUUM prepared_test".execute(&mut conn);
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+}
+
+fn num_docs(conn: &mut PgConnection) -> i64 {
+    "SELECT SUM(num_docs)::bigint FROM paradedb.index_info('prepared_test_bm25_index')"
+        .fetch_one::<(i64,)>(conn)
+        .0
+}
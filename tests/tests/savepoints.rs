@@ -0,0 +1,182 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use sqlx::PgConnection;
+
+fn setup(conn: &mut PgConnection) {
+    r#"
+    CREATE TABLE savepoint_test (id SERIAL PRIMARY KEY, body TEXT);
+    CALL paradedb.create_bm25(
+        index_name => 'savepoint_test_bm25_index',
+        table_name => 'savepoint_test',
+        key_field => 'id',
+        text_fields => paradedb.field('body')
+    );
+    "#
+    .execute(conn);
+}
+
+fn count(conn: &mut PgConnection) -> i64 {
+    "SELECT COUNT(*) FROM savepoint_test WHERE savepoint_test @@@ 'body:keyboard'"
+        .fetch_one::<(i64,)>(conn)
+        .0
+}
+
+fn num_docs(conn: &mut PgConnection) -> i64 {
+    "SELECT SUM(num_docs)::bigint FROM paradedb.index_info('savepoint_test_bm25_index')"
+        .fetch_one::<(i64,)>(conn)
+        .0
+}
+
+#[rstest]
+fn rollback_to_savepoint(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    "BEGIN".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('kept keyboard')".execute(&mut conn);
+    "SAVEPOINT sp".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('discarded keyboard')".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) SELECT 'discarded keyboard ' || x FROM generate_series(1, 10) x"
+        .execute(&mut conn);
+    assert_eq!(count(&mut conn), 12);
+
+    "ROLLBACK TO SAVEPOINT sp".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('another kept keyboard')".execute(&mut conn);
+    assert_eq!(count(&mut conn), 2);
+    "COMMIT".execute(&mut conn);
+
+    // without a VACUUM, the rolled back documents are already gone
+    assert_eq!(count(&mut conn), 2);
+    assert_eq!(num_docs(&mut conn), 2);
+}
+
+#[rstest]
+fn search_after_rollback_to_savepoint(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    fn score(conn: &mut PgConnection) -> f32 {
+        "SELECT paradedb.score(id) FROM savepoint_test WHERE savepoint_test @@@ 'body:keyboard'"
+            .fetch_one::<(f32,)>(conn)
+            .0
+    }
+
+    "BEGIN".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('kept keyboard')".execute(&mut conn);
+    let expected_score = score(&mut conn);
+
+    "SAVEPOINT sp".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) SELECT 'discarded keyboard ' || x FROM generate_series(1, 10) x"
+        .execute(&mut conn);
+    assert_eq!(num_docs(&mut conn), 11);
+
+    // the very next search, in the same transaction, no longer sees the rolled back documents
+    "ROLLBACK TO SAVEPOINT sp".execute(&mut conn);
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+    assert_eq!(score(&mut conn), expected_score);
+    "COMMIT".execute(&mut conn);
+
+    assert_eq!(num_docs(&mut conn), 1);
+}
+
+#[rstest]
+fn search_after_exception_block(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    "BEGIN".execute(&mut conn);
+    r#"
+    DO $$
+    BEGIN
+        INSERT INTO savepoint_test (body) VALUES ('kept keyboard');
+        BEGIN
+            INSERT INTO savepoint_test (body) SELECT 'discarded keyboard ' || x FROM generate_series(1, 10) x;
+            RAISE EXCEPTION 'roll back the block';
+        EXCEPTION WHEN OTHERS THEN
+            NULL;
+        END;
+    END
+    $$;
+    "#
+    .execute(&mut conn);
+
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+    "COMMIT".execute(&mut conn);
+}
+
+#[rstest]
+fn released_savepoint_is_kept(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    "BEGIN".execute(&mut conn);
+    "SAVEPOINT outer_sp".execute(&mut conn);
+    "SAVEPOINT inner_sp".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('released keyboard')".execute(&mut conn);
+    "RELEASE SAVEPOINT inner_sp".execute(&mut conn);
+    "RELEASE SAVEPOINT outer_sp".execute(&mut conn);
+    "COMMIT".execute(&mut conn);
+
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+}
+
+#[rstest]
+fn exception_block(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    r#"
+    DO $$
+    BEGIN
+        INSERT INTO savepoint_test (body) VALUES ('kept keyboard');
+        BEGIN
+            INSERT INTO savepoint_test (body) VALUES ('discarded keyboard');
+            RAISE EXCEPTION 'roll back the block';
+        EXCEPTION WHEN OTHERS THEN
+            NULL;
+        END;
+    END
+    $$;
+    "#
+    .execute(&mut conn);
+
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+}
+
+#[rstest]
+fn failed_statement_in_savepoint(mut conn: PgConnection) {
+    setup(&mut conn);
+
+    "BEGIN".execute(&mut conn);
+    "SAVEPOINT sp".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('discarded keyboard')".execute(&mut conn);
+    let result = "INSERT INTO savepoint_test (id, body) VALUES (1, 'duplicate keyboard')"
+        .execute_result(&mut conn);
+    assert!(result.is_err());
+
+    "ROLLBACK TO SAVEPOINT sp".execute(&mut conn);
+    "INSERT INTO savepoint_test (body) VALUES ('kept keyboard')".execute(&mut conn);
+    "COMMIT".execute(&mut conn);
+
+    assert_eq!(count(&mut conn), 1);
+    assert_eq!(num_docs(&mut conn), 1);
+}
@@ -559,3 +559,54 @@ fn language_stem_filter(mut conn: PgConnection) {
         .execute(&mut conn);
     }
 }
+
+#[rstest]
+fn synonyms_tokenizer_config(mut conn: PgConnection) {
+    "CALL paradedb.create_bm25_test_table(table_name => 'tokenizer_config', schema_name => 'paradedb')"
+        .execute(&mut conn);
+
+    "SELECT paradedb.create_synonym_set('products', ARRAY['keyboard, keypad', 'earbuds => headphones'])"
+        .execute(&mut conn);
+
+    r#"CALL paradedb.create_bm25(
+    	index_name => 'tokenizer_config_idx',
+    	table_name => 'tokenizer_config',
+    	schema_name => 'paradedb',
+    	key_field => 'id',
+	    text_fields => paradedb.field('description', tokenizer => paradedb.tokenizer('default', synonyms => 'products'))
+    )"#
+    .execute(&mut conn);
+
+    let rows: Vec<(i32,)> = "
+    SELECT id FROM paradedb.tokenizer_config
+    WHERE tokenizer_config @@@ 'description:keypad' ORDER BY id"
+        .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,)]);
+
+    // an explicit mapping replaces the original term on both sides
+    let rows: Vec<(i32,)> = "
+    SELECT id FROM paradedb.tokenizer_config
+    WHERE tokenizer_config @@@ 'description:headphones' ORDER BY id"
+        .fetch(&mut conn);
+    assert_eq!(rows, vec![(12,)]);
+}
+
+#[rstest]
+fn synonyms_tokenizer_config_missing_set(mut conn: PgConnection) {
+    "CALL paradedb.create_bm25_test_table(table_name => 'tokenizer_config', schema_name => 'paradedb')"
+        .execute(&mut conn);
+
+    let res = r#"CALL paradedb.create_bm25(
+    	index_name => 'tokenizer_config_idx',
+    	table_name => 'tokenizer_config',
+    	schema_name => 'paradedb',
+    	key_field => 'id',
+	    text_fields => paradedb.field('description', tokenizer => paradedb.tokenizer('default', synonyms => 'missing'))
+    )"#
+    .execute_result(&mut conn);
+    assert!(res.is_err());
+
+    let res =
+        "SELECT paradedb.create_synonym_set('bad', ARRAY['lonely'])".execute_result(&mut conn);
+    assert!(res.is_err());
+}
@@ -119,3 +119,87 @@ fn sort_by_row_return_scores(mut conn: PgConnection) {
     assert_eq!(plan.get("   Sort Field"), None);
     assert_eq!(plan.get("Scores"), Some(&Value::Bool(true)));
 }
+
+#[rstest]
+fn sort_by_multiple_fields(mut conn: PgConnection) {
+    // ensure our custom scan wins against our small test table
+    r#"
+        SET enable_indexscan TO off;
+        CALL paradedb.create_bm25_test_table(table_name => 'bm25_search', schema_name => 'paradedb');
+
+        CALL paradedb.create_bm25(
+            index_name => 'bm25_search_idx',
+            table_name => 'bm25_search',
+            schema_name => 'paradedb',
+            key_field => 'id',
+            text_fields => paradedb.field('description') || paradedb.field('category', fast=>true, normalizer=>'raw'),
+            numeric_fields => paradedb.field('rating', fast=>true),
+            boolean_fields => paradedb.field('in_stock'),
+            json_fields => paradedb.field('metadata'),
+            datetime_fields => paradedb.field('created_at') || paradedb.field('last_updated_date') || paradedb.field('latest_available_time')
+        );
+    "#.execute(&mut conn);
+
+    let (plan, ) = "EXPLAIN (ANALYZE, FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY rating DESC, category ASC LIMIT 5".fetch_one::<(Value,)>(&mut conn);
+    let plan = plan
+        .pointer("/0/Plan/Plans/0")
+        .unwrap()
+        .as_object()
+        .unwrap();
+    eprintln!("{plan:#?}");
+    assert_eq!(
+        plan.get("   Sort Field"),
+        Some(&Value::String(String::from("rating, category")))
+    );
+    assert_eq!(
+        plan.get("   Sort Direction"),
+        Some(&Value::String(String::from("desc, asc")))
+    );
+
+    // rows that tie on both sort keys may come back in any order, so only compare the keys
+    let query = "SELECT rating, category FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY rating DESC, category ASC LIMIT 5";
+    let pushed_down: Vec<(i32, String)> = query.fetch(&mut conn);
+
+    "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+    let expected: Vec<(i32, String)> = query.fetch(&mut conn);
+    assert_eq!(pushed_down, expected);
+}
+
+#[rstest]
+fn sort_by_field_nulls_first(mut conn: PgConnection) {
+    // ensure our custom scan wins against our small test table
+    r#"
+        SET enable_indexscan TO off;
+        CALL paradedb.create_bm25_test_table(table_name => 'bm25_search', schema_name => 'paradedb');
+        UPDATE paradedb.bm25_search SET rating = NULL WHERE id IN (1, 2);
+
+        CALL paradedb.create_bm25(
+            index_name => 'bm25_search_idx',
+            table_name => 'bm25_search',
+            schema_name => 'paradedb',
+            key_field => 'id',
+            text_fields => paradedb.field('description') || paradedb.field('category', fast=>true, normalizer=>'raw'),
+            numeric_fields => paradedb.field('rating', fast=>true),
+            boolean_fields => paradedb.field('in_stock'),
+            json_fields => paradedb.field('metadata'),
+            datetime_fields => paradedb.field('created_at') || paradedb.field('last_updated_date') || paradedb.field('latest_available_time')
+        );
+    "#.execute(&mut conn);
+
+    let (plan, ) = "EXPLAIN (ANALYZE, FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE id @@@ paradedb.all() ORDER BY rating ASC NULLS FIRST LIMIT 3".fetch_one::<(Value,)>(&mut conn);
+    let plan = plan
+        .pointer("/0/Plan/Plans/0")
+        .unwrap()
+        .as_object()
+        .unwrap();
+    eprintln!("{plan:#?}");
+    assert_eq!(
+        plan.get("   Sort Direction"),
+        Some(&Value::String(String::from("asc nulls first")))
+    );
+
+    let rows: Vec<(Option<i32>,)> = "SELECT rating FROM paradedb.bm25_search WHERE id @@@ paradedb.all() ORDER BY rating ASC NULLS FIRST LIMIT 3".fetch(&mut conn);
+    assert_eq!(rows[0], (None,));
+    assert_eq!(rows[1], (None,));
+    assert!(rows[2].0.is_some());
+}
@@ -80,6 +80,165 @@ fn tokenizer_filters(mut conn: PgConnection) {
     );
 }
 
+#[rstest]
+fn tokenizer_stopwords(mut conn: PgConnection) {
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', stopwords_language => 'English', stopwords => ARRAY['Fox']),
+      'The quick fox jumps over the lazy dog'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(
+        rows,
+        vec![
+            ("quick".into(), 1),
+            ("jumps".into(), 3),
+            ("over".into(), 4),
+            ("lazy".into(), 6),
+            ("dog".into(), 7)
+        ]
+    );
+
+    let res = r#"
+    SELECT * FROM paradedb.tokenize(paradedb.tokenizer('default', stopwords_language => 'Tamil'), 'hello');
+    "#
+    .execute_result(&mut conn);
+    assert!(res.is_err());
+}
+
+#[rstest]
+fn tokenizer_synonyms(mut conn: PgConnection) {
+    "SELECT paradedb.create_synonym_set('tv', ARRAY['tv, television', 'flat screen => flatscreen'])"
+        .execute(&mut conn);
+
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', synonyms => 'tv'),
+      'Flat screen TV'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(
+        rows,
+        vec![
+            ("flatscreen".into(), 0),
+            ("tv".into(), 2),
+            ("television".into(), 2)
+        ]
+    );
+}
+
+#[rstest]
+fn tokenizer_synonyms_analyzed(mut conn: PgConnection) {
+    // synonym entries are split into words by the tokenizer, just like the text
+    "SELECT paradedb.create_synonym_set('network', ARRAY['Wi-Fi, wireless network'])"
+        .execute(&mut conn);
+
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', synonyms => 'network'),
+      'wi-fi router'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(
+        rows,
+        vec![
+            ("wi".into(), 0),
+            ("wireless".into(), 0),
+            ("fi".into(), 1),
+            ("network".into(), 1),
+            ("router".into(), 2)
+        ]
+    );
+}
+
+#[rstest]
+fn tokenizer_edge_ngram(mut conn: PgConnection) {
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('edge_ngram', min_gram => 2, max_gram => 3, token_chars => ARRAY['letter']),
+      'Wi-Fi Router'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(
+        rows,
+        vec![
+            ("wi".into(), 0),
+            ("fi".into(), 1),
+            ("ro".into(), 2),
+            ("rou".into(), 2)
+        ]
+    );
+
+    // as a filter, it follows the tokenizer's other filters
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', stemmer => 'English', edge_ngram => '{"min_gram": 3, "max_gram": 4}'),
+      'Running shoes'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(
+        rows,
+        vec![("run".into(), 0), ("sho".into(), 1), ("shoe".into(), 1)]
+    );
+
+    let res = r#"
+    SELECT * FROM paradedb.tokenize(paradedb.tokenizer('edge_ngram', min_gram => 3, max_gram => 2), 'hello');
+    "#
+    .execute_result(&mut conn);
+    assert!(res.is_err());
+}
+
+#[rstest]
+fn tokenizer_phonetic(mut conn: PgConnection) {
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', phonetic => '{"algorithm": "soundex"}'),
+      'Smith Smyth'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(rows, vec![("S530".into(), 0), ("S530".into(), 1)]);
+
+    // codes are emitted at the position of the token they encode
+    let rows: Vec<(String, i32)> = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', phonetic => '{"algorithm": "double_metaphone", "preserve_original": true}'),
+      'Smyth 42'
+    );
+    "#
+    .fetch_collect(&mut conn);
+
+    assert_eq!(
+        rows,
+        vec![
+            ("smyth".into(), 0),
+            ("SM0".into(), 0),
+            ("XMT".into(), 0),
+            ("42".into(), 1)
+        ]
+    );
+
+    let res = r#"
+    SELECT * FROM paradedb.tokenize(
+      paradedb.tokenizer('default', phonetic => '{"algorithm": "nysiis"}'),
+      'Smith'
+    );
+    "#
+    .execute_result(&mut conn);
+    assert!(res.is_err());
+}
+
 #[rstest]
 fn list_tokenizers(mut conn: PgConnection) {
     let rows: Vec<(String,)> = r#"
@@ -101,6 +260,7 @@ fn list_tokenizers(mut conn: PgConnection) {
                 ("chinese_compatible".into(),),
                 ("source_code".into(),),
                 ("ngram".into(),),
+                ("edge_ngram".into(),),
                 ("chinese_lindera".into(),),
                 ("japanese_lindera".into(),),
                 ("korean_lindera".into(),),
@@ -121,6 +281,7 @@ fn list_tokenizers(mut conn: PgConnection) {
                 ("chinese_compatible".into(),),
                 ("source_code".into(),),
                 ("ngram".into(),),
+                ("edge_ngram".into(),),
                 ("chinese_lindera".into(),),
                 ("japanese_lindera".into(),),
                 ("korean_lindera".into(),),
@@ -65,3 +65,46 @@ fn manual_vacuum(mut conn: PgConnection) {
     "update sadvac set id = id;".execute(&mut conn);
     assert_eq!(count_func(&mut conn), ROW_COUNT, "post update after vacuum");
 }
+
+#[rstest]
+fn vacuum_after_unchanged_updates(mut conn: PgConnection) {
+    fn ratings(conn: &mut PgConnection) -> Vec<(i64, i32)> {
+        "SELECT id, rating FROM unchanged WHERE unchanged @@@ 'description:keyboard' ORDER BY id"
+            .fetch(conn)
+    }
+    fn issues(conn: &mut PgConnection) -> Vec<(String,)> {
+        "SELECT kind FROM paradedb.verify_index('unchanged_bm25_index') WHERE kind <> 'dead_tuple'"
+            .fetch(conn)
+    }
+
+    // full pages, so that most updates put the new row versions on other pages
+    "CREATE TABLE unchanged (id serial8, description text, rating int)
+         WITH (fillfactor = 100, autovacuum_enabled = false);
+     INSERT INTO unchanged (description, rating)
+         SELECT 'keyboard ' || x, 0 FROM generate_series(1, 1000) x;"
+        .execute(&mut conn);
+    "CALL paradedb.create_bm25(
+        index_name => 'unchanged_bm25_index',
+        table_name => 'unchanged',
+        key_field => 'id',
+        text_fields => paradedb.field('description')
+    );"
+    .execute(&mut conn);
+
+    // none of these change an indexed column
+    for rating in 1..=3 {
+        format!("UPDATE unchanged SET rating = {rating}").execute(&mut conn);
+        let expected = (1..=1000).map(|id| (id, rating)).collect::<Vec<_>>();
+        assert_eq!(ratings(&mut conn), expected, "after update {rating}");
+        assert_eq!(issues(&mut conn), vec![], "after update {rating}");
+
+        "VACUUM unchanged".execute(&mut conn);
+        assert_eq!(ratings(&mut conn), expected, "after vacuum {rating}");
+        assert_eq!(issues(&mut conn), vec![], "after vacuum {rating}");
+    }
+
+    "UPDATE unchanged SET description = 'mouse' WHERE id <= 500".execute(&mut conn);
+    "VACUUM unchanged".execute(&mut conn);
+    let expected = (501..=1000).map(|id| (id, 3)).collect::<Vec<_>>();
+    assert_eq!(ratings(&mut conn), expected);
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use pretty_assertions::assert_eq;
+use rstest::*;
+use sqlx::PgConnection;
+
+#[rstest]
+fn verify_healthy_index(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    let rows: Vec<(String, Option<String>)> =
+        "SELECT kind, key FROM paradedb.verify_index('paradedb.bm25_search_bm25_index')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+
+    // HOT and non-HOT updates both leave the index consistent with the heap
+    "UPDATE paradedb.bm25_search SET rating = 5 WHERE id = 1".execute(&mut conn);
+    "UPDATE paradedb.bm25_search SET description = 'Plastic keypad' WHERE id = 2"
+        .execute(&mut conn);
+    let rows: Vec<(String, Option<String>)> =
+        "SELECT kind, key FROM paradedb.verify_index('paradedb.bm25_search_bm25_index') WHERE kind <> 'dead_tuple'"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+}
+
+#[rstest]
+fn verify_reports_dead_tuples(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    "DELETE FROM paradedb.bm25_search WHERE id IN (3, 4)".execute(&mut conn);
+    let rows: Vec<(String, Option<String>, bool)> =
+        "SELECT kind, key, repaired FROM paradedb.verify_index('paradedb.bm25_search_bm25_index', repair => true) ORDER BY key"
+            .fetch(&mut conn);
+    assert_eq!(
+        rows,
+        vec![
+            ("dead_tuple".into(), Some("3".into()), false),
+            ("dead_tuple".into(), Some("4".into()), false)
+        ]
+    );
+
+    // dead tuples are left for VACUUM to clean up
+    "VACUUM paradedb.bm25_search".execute(&mut conn);
+    let rows: Vec<(String, Option<String>)> =
+        "SELECT kind, key FROM paradedb.verify_index('paradedb.bm25_search_bm25_index')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+}
+
+#[rstest]
+fn verify_partial_index(mut conn: PgConnection) {
+    r#"
+    CALL paradedb.create_bm25_test_table(table_name => 'partial_verify', schema_name => 'paradedb');
+    CALL paradedb.create_bm25(
+        index_name => 'partial_verify_idx',
+        table_name => 'partial_verify',
+        schema_name => 'paradedb',
+        key_field => 'id',
+        text_fields => paradedb.field('description'),
+        predicates => 'rating > 3'
+    );
+    "#
+    .execute(&mut conn);
+
+    // rows outside the index predicate aren't reported as missing
+    let rows: Vec<(String, Option<String>)> =
+        "SELECT kind, key FROM paradedb.verify_index('paradedb.partial_verify_idx')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+}
+
+#[rstest]
+fn verify_repairs_corrupted_index(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE verify_repair (id INT, description TEXT);
+    INSERT INTO verify_repair VALUES (1, 'red keyboard'), (2, 'blue keyboard'), (3, 'green keyboard');
+    CALL paradedb.create_bm25(
+        index_name => 'verify_repair_idx',
+        table_name => 'verify_repair',
+        key_field => 'id',
+        text_fields => paradedb.field('description')
+    );
+    "#
+    .execute(&mut conn);
+
+    // While the index isn't ready for inserts, writes and VACUUM leave it alone, so it falls out
+    // of step with the heap: row 4 reuses row 1's line pointer, row 5 is never indexed, and row
+    // 3's line pointer is freed
+    "UPDATE pg_index SET indisready = false WHERE indexrelid = 'verify_repair_idx'::regclass"
+        .execute(&mut conn);
+    "DELETE FROM verify_repair WHERE id = 1".execute(&mut conn);
+    "VACUUM verify_repair".execute(&mut conn);
+    "INSERT INTO verify_repair VALUES (4, 'yellow keyboard')".execute(&mut conn);
+    "INSERT INTO verify_repair VALUES (5, 'purple keyboard')".execute(&mut conn);
+    "DELETE FROM verify_repair WHERE id = 3".execute(&mut conn);
+    "VACUUM verify_repair".execute(&mut conn);
+    "UPDATE pg_index SET indisready = true WHERE indexrelid = 'verify_repair_idx'::regclass"
+        .execute(&mut conn);
+
+    let rows: Vec<(String, Option<String>, bool)> =
+        "SELECT kind, key, repaired FROM paradedb.verify_index('verify_repair_idx', repair => true) ORDER BY kind"
+            .fetch(&mut conn);
+    assert_eq!(
+        rows,
+        vec![
+            ("dangling_ctid".into(), Some("3".into()), true),
+            ("key_mismatch".into(), Some("4".into()), true),
+            ("missing_from_index".into(), Some("5".into()), true),
+        ]
+    );
+
+    let rows: Vec<(String, Option<String>)> =
+        "SELECT kind, key FROM paradedb.verify_index('verify_repair_idx')".fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+
+    let ids: Vec<(i32,)> =
+        "SELECT id FROM verify_repair WHERE verify_repair @@@ 'description:keyboard' ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(ids, vec![(2,), (4,), (5,)]);
+}
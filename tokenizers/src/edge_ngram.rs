@@ -0,0 +1,314 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Edge n-grams, the prefixes of each word, for prefix autocomplete.
+//!
+//! Unlike the `ngram` tokenizer, which emits every n-gram of its input, only the grams that
+//! start at the beginning of a word are emitted, so `keyboard` with `min_gram` 2 and `max_gram` 4
+//! becomes `ke`, `key`, and `keyb`.  The grams of a word share its position, so phrase queries
+//! keep matching words in order.
+//!
+//! [`EdgeNgramTokenizer`] splits its input into words made of the configured [`TokenChars`],
+//! and [`EdgeNgramFilter`] splits the tokens of another tokenizer, after its other filters.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tantivy::tokenizer::{Token, TokenFilter, TokenStream, Tokenizer};
+
+/// A class of characters that words are made of.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenChars {
+    Letter,
+    Digit,
+    Whitespace,
+    Punctuation,
+    Symbol,
+}
+
+impl TokenChars {
+    fn contains(&self, c: char) -> bool {
+        match self {
+            TokenChars::Letter => c.is_alphabetic(),
+            TokenChars::Digit => c.is_numeric(),
+            TokenChars::Whitespace => c.is_whitespace(),
+            TokenChars::Punctuation => c.is_ascii_punctuation() && !is_symbol(c),
+            TokenChars::Symbol => is_symbol(c),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenChars::Letter => "letter",
+            TokenChars::Digit => "digit",
+            TokenChars::Whitespace => "whitespace",
+            TokenChars::Punctuation => "punctuation",
+            TokenChars::Symbol => "symbol",
+        }
+    }
+}
+
+/// Currency, math, and modifier symbols, which are otherwise ASCII punctuation.
+fn is_symbol(c: char) -> bool {
+    matches!(c, '$' | '+' | '<' | '=' | '>' | '^' | '`' | '|' | '~')
+}
+
+fn validate_grams(min_gram: usize, max_gram: usize) -> Result<()> {
+    if min_gram == 0 {
+        bail!("edge_ngram 'min_gram' must be at least 1");
+    }
+    if max_gram < min_gram {
+        bail!("edge_ngram 'max_gram' ({max_gram}) must not be less than 'min_gram' ({min_gram})");
+    }
+    Ok(())
+}
+
+/// Push the prefixes of `token` that are between `min_gram` and `max_gram` characters long.
+fn push_edge_ngrams(tokens: &mut Vec<Token>, token: &Token, min_gram: usize, max_gram: usize) {
+    let ends = token
+        .text
+        .char_indices()
+        .map(|(offset, c)| offset + c.len_utf8())
+        .skip(min_gram - 1)
+        .take(max_gram - min_gram + 1);
+    for end in ends {
+        tokens.push(Token {
+            text: token.text[..end].to_string(),
+            ..token.clone()
+        });
+    }
+}
+
+/// `Tokenizer` that emits the edge n-grams of each word of its input.
+#[derive(Clone, Debug)]
+pub struct EdgeNgramTokenizer {
+    min_gram: usize,
+    max_gram: usize,
+    /// the characters words are made of, or every character if empty
+    token_chars: Vec<TokenChars>,
+    tokens: Vec<Token>,
+}
+
+impl EdgeNgramTokenizer {
+    pub fn new(min_gram: usize, max_gram: usize, token_chars: Vec<TokenChars>) -> Result<Self> {
+        validate_grams(min_gram, max_gram)?;
+        Ok(Self {
+            min_gram,
+            max_gram,
+            token_chars,
+            tokens: Vec::new(),
+        })
+    }
+
+    fn is_token_char(&self, c: char) -> bool {
+        self.token_chars.is_empty() || self.token_chars.iter().any(|chars| chars.contains(c))
+    }
+}
+
+impl Tokenizer for EdgeNgramTokenizer {
+    type TokenStream<'a> = EdgeNgramTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (offset, c) in text.char_indices() {
+            match (start, self.is_token_char(c)) {
+                (None, true) => start = Some(offset),
+                (Some(from), false) => {
+                    words.push(from..offset);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(from) = start {
+            words.push(from..text.len());
+        }
+
+        self.tokens.clear();
+        for (position, word) in words.into_iter().enumerate() {
+            let token = Token {
+                offset_from: word.start,
+                offset_to: word.end,
+                position,
+                text: text[word].to_string(),
+                position_length: 1,
+            };
+            push_edge_ngrams(&mut self.tokens, &token, self.min_gram, self.max_gram);
+        }
+
+        EdgeNgramTokenStream {
+            tokens: &self.tokens,
+            cursor: 0,
+            current: Token::default(),
+        }
+    }
+}
+
+/// `TokenFilter` that replaces each token with its edge n-grams.  Tokens shorter than
+/// `min_gram` are removed.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EdgeNgramFilter {
+    pub min_gram: usize,
+    pub max_gram: usize,
+}
+
+impl EdgeNgramFilter {
+    pub fn new(min_gram: usize, max_gram: usize) -> Result<Self> {
+        validate_grams(min_gram, max_gram)?;
+        Ok(Self { min_gram, max_gram })
+    }
+}
+
+impl TokenFilter for EdgeNgramFilter {
+    type Tokenizer<T: Tokenizer> = EdgeNgramFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> EdgeNgramFilterWrapper<T> {
+        EdgeNgramFilterWrapper {
+            filter: self,
+            inner: tokenizer,
+            tokens: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EdgeNgramFilterWrapper<T> {
+    filter: EdgeNgramFilter,
+    inner: T,
+    tokens: Vec<Token>,
+}
+
+impl<T: Tokenizer> Tokenizer for EdgeNgramFilterWrapper<T> {
+    type TokenStream<'a> = EdgeNgramTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        self.tokens.clear();
+        let mut stream = self.inner.token_stream(text);
+        while stream.advance() {
+            push_edge_ngrams(
+                &mut self.tokens,
+                stream.token(),
+                self.filter.min_gram,
+                self.filter.max_gram,
+            );
+        }
+
+        EdgeNgramTokenStream {
+            tokens: &self.tokens,
+            cursor: 0,
+            current: Token::default(),
+        }
+    }
+}
+
+pub struct EdgeNgramTokenStream<'a> {
+    tokens: &'a [Token],
+    cursor: usize,
+    current: Token,
+}
+
+impl<'a> TokenStream for EdgeNgramTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        match self.tokens.get(self.cursor) {
+            Some(token) => {
+                self.current.clone_from(token);
+                self.cursor += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.current
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use tantivy::tokenizer::{LowerCaser, SimpleTokenizer, TextAnalyzer};
+
+    fn collect(analyzer: &mut TextAnalyzer, text: &str) -> Vec<(String, usize)> {
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
+        }
+        tokens
+    }
+
+    fn pairs(expected: &[(&str, usize)]) -> Vec<(String, usize)> {
+        expected
+            .iter()
+            .map(|(text, pos)| (text.to_string(), *pos))
+            .collect()
+    }
+
+    #[rstest]
+    fn test_tokenizer_token_chars() {
+        let tokenizer =
+            EdgeNgramTokenizer::new(2, 3, vec![TokenChars::Letter, TokenChars::Digit]).unwrap();
+        let mut analyzer = TextAnalyzer::builder(tokenizer).build();
+        assert_eq!(
+            collect(&mut analyzer, "Wi-Fi 6e, a router"),
+            pairs(&[("Wi", 0), ("Fi", 1), ("6e", 2), ("ro", 4), ("rou", 4)])
+        );
+    }
+
+    #[rstest]
+    fn test_tokenizer_without_token_chars() {
+        let tokenizer = EdgeNgramTokenizer::new(1, 3, vec![]).unwrap();
+        let mut analyzer = TextAnalyzer::builder(tokenizer).build();
+        assert_eq!(
+            collect(&mut analyzer, "a b"),
+            pairs(&[("a", 0), ("a ", 0), ("a b", 0)])
+        );
+    }
+
+    #[rstest]
+    fn test_filter() {
+        let mut analyzer = TextAnalyzer::builder(SimpleTokenizer::default())
+            .filter(LowerCaser)
+            .filter(EdgeNgramFilter::new(2, 4).unwrap())
+            .build();
+        assert_eq!(
+            collect(&mut analyzer, "Über keyboard"),
+            pairs(&[
+                ("üb", 0),
+                ("übe", 0),
+                ("über", 0),
+                ("ke", 1),
+                ("key", 1),
+                ("keyb", 1)
+            ])
+        );
+    }
+
+    #[rstest]
+    fn test_invalid_grams() {
+        assert!(EdgeNgramFilter::new(0, 2).is_err());
+        assert!(EdgeNgramFilter::new(3, 2).is_err());
+        assert!(EdgeNgramTokenizer::new(3, 2, vec![]).is_err());
+    }
+}
@@ -17,10 +17,13 @@
 
 pub mod cjk;
 pub mod code;
+pub mod edge_ngram;
 #[cfg(feature = "icu")]
 pub mod icu;
 pub mod lindera;
 pub mod manager;
+pub mod phonetic;
+pub mod synonyms;
 
 use tantivy::tokenizer::{
     LowerCaser, RawTokenizer, RemoveLongFilter, TextAnalyzer, TokenizerManager,
@@ -22,7 +22,10 @@ use crate::icu::ICUTokenizer;
 use crate::{
     cjk::ChineseTokenizer,
     code::CodeTokenizer,
+    edge_ngram::{EdgeNgramFilter, EdgeNgramTokenizer, TokenChars},
     lindera::{LinderaChineseTokenizer, LinderaJapaneseTokenizer, LinderaKoreanTokenizer},
+    phonetic::PhoneticFilter,
+    synonyms::{SynonymFilter, SynonymSet},
     DEFAULT_REMOVE_TOKEN_LENGTH,
 };
 use anyhow::Result;
@@ -31,7 +34,7 @@ use serde_json::json;
 use strum::AsRefStr;
 use tantivy::tokenizer::{
     AsciiFoldingFilter, Language, LowerCaser, NgramTokenizer, RawTokenizer, RegexTokenizer,
-    RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer, WhitespaceTokenizer,
+    RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter, TextAnalyzer, WhitespaceTokenizer,
 };
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
@@ -39,6 +42,11 @@ pub struct SearchTokenizerFilters {
     remove_long: Option<usize>,
     lowercase: Option<bool>,
     stemmer: Option<Language>,
+    synonyms: Option<SynonymSet>,
+    stopwords_language: Option<Language>,
+    stopwords: Option<Vec<String>>,
+    phonetic: Option<PhoneticFilter>,
+    edge_ngram: Option<EdgeNgramFilter>,
 }
 
 impl SearchTokenizerFilters {
@@ -66,6 +74,54 @@ impl SearchTokenizerFilters {
                 anyhow::anyhow!("stemmer tokenizer requires a valid 'stemmer' field")
             })?);
         }
+        if let Some(synonyms) = value.get("synonyms") {
+            let name = synonyms.as_str().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "a 'synonyms' value passed to the pg_search tokenizer configuration \
+                     must be the name of a synonym set, found: {synonyms:#?}"
+                )
+            })?;
+            filters.synonyms = Some(SynonymSet::new(name));
+        }
+        if let Some(stopwords_language) = value.get("stopwords_language") {
+            let language: Language = serde_json::from_value(stopwords_language.clone())
+                .map_err(|_| {
+                    anyhow::anyhow!("stopwords_language requires a valid language, found: {stopwords_language:#?}")
+                })?;
+            if StopWordFilter::new(language).is_none() {
+                anyhow::bail!("no built-in stop word list is available for {language:?}");
+            }
+            filters.stopwords_language = Some(language);
+        }
+        if let Some(stopwords) = value.get("stopwords") {
+            filters.stopwords = Some(serde_json::from_value(stopwords.clone()).map_err(|_| {
+                anyhow::anyhow!(
+                    "a 'stopwords' value passed to the pg_search tokenizer configuration \
+                     must be an array of strings, found: {stopwords:#?}"
+                )
+            })?);
+        }
+        if let Some(phonetic) = value.get("phonetic") {
+            filters.phonetic = Some(serde_json::from_value(phonetic.clone()).map_err(|_| {
+                anyhow::anyhow!(
+                    "a 'phonetic' value passed to the pg_search tokenizer configuration \
+                     requires an 'algorithm' field of 'soundex', 'metaphone', 'double_metaphone', \
+                     or 'beider_morse_lite', and an optional boolean 'preserve_original' field, \
+                     found: {phonetic:#?}"
+                )
+            })?);
+        }
+        if let Some(edge_ngram) = value.get("edge_ngram") {
+            let gram = |key: &str| {
+                edge_ngram[key].as_u64().map(|v| v as usize).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "an 'edge_ngram' value passed to the pg_search tokenizer configuration \
+                         requires an integer '{key}' field, found: {edge_ngram:#?}"
+                    )
+                })
+            };
+            filters.edge_ngram = Some(EdgeNgramFilter::new(gram("min_gram")?, gram("max_gram")?)?);
+        }
 
         Ok(filters)
     }
@@ -80,6 +136,26 @@ impl SearchTokenizerFilters {
             let v = serde_json::Value::Bool(value);
             enclosing.insert("lowercase".to_string(), v);
         }
+        if let Some(value) = &self.synonyms {
+            let v = serde_json::Value::String(value.name.clone());
+            enclosing.insert("synonyms".to_string(), v);
+        }
+        if let Some(value) = self.stopwords_language {
+            let v = serde_json::to_value(value).expect("Language should serialize to json");
+            enclosing.insert("stopwords_language".to_string(), v);
+        }
+        if let Some(value) = &self.stopwords {
+            let v = serde_json::Value::from(value.clone());
+            enclosing.insert("stopwords".to_string(), v);
+        }
+        if let Some(value) = self.phonetic {
+            let v = serde_json::to_value(value).expect("PhoneticFilter should serialize to json");
+            enclosing.insert("phonetic".to_string(), v);
+        }
+        if let Some(value) = self.edge_ngram {
+            let v = json!({ "min_gram": value.min_gram, "max_gram": value.max_gram });
+            enclosing.insert("edge_ngram".to_string(), v);
+        }
     }
 
     fn name_suffix(&self) -> String {
@@ -108,6 +184,47 @@ impl SearchTokenizerFilters {
             write!(buffer, "{}stemmer={value:?}", sep(is_empty)).unwrap();
             is_empty = false;
         }
+        if let Some(value) = &self.synonyms {
+            write!(buffer, "{}synonyms={}", sep(is_empty), value.name)
+                .expect("Writing to String buffer should never fail");
+            is_empty = false;
+        }
+        if let Some(value) = self.stopwords_language {
+            write!(buffer, "{}stopwords_language={value:?}", sep(is_empty))
+                .expect("Writing to String buffer should never fail");
+            is_empty = false;
+        }
+        if let Some(value) = &self.stopwords {
+            write!(buffer, "{}stopwords={value:?}", sep(is_empty))
+                .expect("Writing to String buffer should never fail");
+            is_empty = false;
+        }
+        if let Some(value) = self.phonetic {
+            write!(
+                buffer,
+                "{}phonetic={}{}",
+                sep(is_empty),
+                value.algorithm.name(),
+                if value.preserve_original {
+                    "+original"
+                } else {
+                    ""
+                }
+            )
+            .expect("Writing to String buffer should never fail");
+            is_empty = false;
+        }
+        if let Some(value) = self.edge_ngram {
+            write!(
+                buffer,
+                "{}edge_ngram={}..{}",
+                sep(is_empty),
+                value.min_gram,
+                value.max_gram
+            )
+            .expect("Writing to String buffer should never fail");
+            is_empty = false;
+        }
 
         if is_empty {
             "".into()
@@ -131,6 +248,50 @@ impl SearchTokenizerFilters {
     fn stemmer(&self) -> Option<Stemmer> {
         self.stemmer.map(Stemmer::new)
     }
+
+    /// The synonym filter of `tokenizer`, whose filters these are
+    fn synonym_filter(&self, tokenizer: &SearchTokenizer) -> Option<SynonymFilter> {
+        self.synonyms.as_ref().map(|set| {
+            // rules are analyzed like the text the filter sees, so they match its tokens
+            let mut analyzer = tokenizer.synonym_rule_analyzer();
+            SynonymFilter::new(&set.rules, |entry| {
+                let mut stream = analyzer.token_stream(entry);
+                let mut words = vec![];
+                while let Some(token) = stream.next() {
+                    words.push(token.text.clone());
+                }
+                words
+            })
+            .expect("synonym rules should have been validated by create_synonym_set")
+        })
+    }
+
+    fn stopwords_language(&self) -> Option<StopWordFilter> {
+        // languages without a built-in list are rejected by `from_json_value`
+        self.stopwords_language.and_then(StopWordFilter::new)
+    }
+
+    fn stopwords(&self) -> Option<StopWordFilter> {
+        self.stopwords.as_ref().map(|words| {
+            // custom stop words need to match the output of the `lower_caser()` filter
+            let lowercase = self.lowercase != Some(false);
+            StopWordFilter::remove(words.iter().map(|word| {
+                if lowercase {
+                    word.to_lowercase()
+                } else {
+                    word.clone()
+                }
+            }))
+        })
+    }
+
+    fn phonetic(&self) -> Option<PhoneticFilter> {
+        self.phonetic
+    }
+
+    fn edge_ngram(&self) -> Option<EdgeNgramFilter> {
+        self.edge_ngram
+    }
 }
 
 // Serde will pick a SearchTokenizer variant based on the value of the
@@ -167,6 +328,12 @@ pub enum SearchTokenizer {
         prefix_only: bool,
         filters: SearchTokenizerFilters,
     },
+    EdgeNgram {
+        min_gram: usize,
+        max_gram: usize,
+        token_chars: Vec<TokenChars>,
+        filters: SearchTokenizerFilters,
+    },
     ChineseLindera(SearchTokenizerFilters),
     JapaneseLindera(SearchTokenizerFilters),
     KoreanLindera(SearchTokenizerFilters),
@@ -212,6 +379,17 @@ impl SearchTokenizer {
                 "max_gram": max_gram,
                 "prefix_only": prefix_only,
             }),
+            SearchTokenizer::EdgeNgram {
+                min_gram,
+                max_gram,
+                token_chars,
+                filters: _,
+            } => json!({
+                "type": "edge_ngram",
+                "min_gram": min_gram,
+                "max_gram": max_gram,
+                "token_chars": token_chars,
+            }),
             SearchTokenizer::ChineseLindera(_filters) => json!({ "type": "chinese_lindera" }),
             SearchTokenizer::JapaneseLindera(_filters) => json!({ "type": "japanese_lindera" }),
             SearchTokenizer::KoreanLindera(_filters) => json!({ "type": "korean_lindera" }),
@@ -278,6 +456,34 @@ impl SearchTokenizer {
                     filters,
                 })
             }
+            "edge_ngram" => {
+                let min_gram: usize =
+                    serde_json::from_value(value["min_gram"].clone()).map_err(|_| {
+                        anyhow::anyhow!("edge_ngram tokenizer requires an integer 'min_gram' field")
+                    })?;
+                let max_gram: usize =
+                    serde_json::from_value(value["max_gram"].clone()).map_err(|_| {
+                        anyhow::anyhow!("edge_ngram tokenizer requires an integer 'max_gram' field")
+                    })?;
+                let token_chars: Vec<TokenChars> = match value.get("token_chars") {
+                    Some(token_chars) => {
+                        serde_json::from_value(token_chars.clone()).map_err(|_| {
+                            anyhow::anyhow!(
+                                "edge_ngram 'token_chars' must be an array of 'letter', 'digit', \
+                             'whitespace', 'punctuation', or 'symbol', found: {token_chars:#?}"
+                            )
+                        })?
+                    }
+                    None => vec![],
+                };
+                EdgeNgramTokenizer::new(min_gram, max_gram, token_chars.clone())?;
+                Ok(SearchTokenizer::EdgeNgram {
+                    min_gram,
+                    max_gram,
+                    token_chars,
+                    filters,
+                })
+            }
             "chinese_lindera" => Ok(SearchTokenizer::ChineseLindera(filters)),
             "japanese_lindera" => Ok(SearchTokenizer::JapaneseLindera(filters)),
             "korean_lindera" => Ok(SearchTokenizer::KoreanLindera(filters)),
@@ -296,14 +502,24 @@ impl SearchTokenizer {
                 TextAnalyzer::builder(SimpleTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::Raw(filters) => Some(
                 TextAnalyzer::builder(RawTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             // Deprecated, use `raw` with `lowercase` filter instead
@@ -311,21 +527,36 @@ impl SearchTokenizer {
                 TextAnalyzer::builder(RawTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::WhiteSpace(filters) => Some(
                 TextAnalyzer::builder(WhitespaceTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::RegexTokenizer { pattern, filters } => Some(
                 TextAnalyzer::builder(RegexTokenizer::new(pattern.as_str()).unwrap())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::Ngram {
@@ -340,14 +571,45 @@ impl SearchTokenizer {
                 )
                 .filter(filters.remove_long_filter())
                 .filter(filters.lower_caser())
+                .filter(filters.synonym_filter(self))
+                .filter(filters.stopwords_language())
+                .filter(filters.stopwords())
                 .filter(filters.stemmer())
+                .filter(filters.phonetic())
+                .filter(filters.edge_ngram())
+                .build(),
+            ),
+            SearchTokenizer::EdgeNgram {
+                min_gram,
+                max_gram,
+                token_chars,
+                filters,
+            } => Some(
+                TextAnalyzer::builder(
+                    EdgeNgramTokenizer::new(*min_gram, *max_gram, token_chars.clone()).expect(
+                        "EdgeNgram parameters should be valid parameters for EdgeNgramTokenizer",
+                    ),
+                )
+                .filter(filters.remove_long_filter())
+                .filter(filters.lower_caser())
+                .filter(filters.synonym_filter(self))
+                .filter(filters.stopwords_language())
+                .filter(filters.stopwords())
+                .filter(filters.stemmer())
+                .filter(filters.phonetic())
+                .filter(filters.edge_ngram())
                 .build(),
             ),
             SearchTokenizer::ChineseCompatible(filters) => Some(
                 TextAnalyzer::builder(ChineseTokenizer)
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::SourceCode(filters) => Some(
@@ -355,28 +617,48 @@ impl SearchTokenizer {
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
                     .filter(AsciiFoldingFilter)
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::ChineseLindera(filters) => Some(
                 TextAnalyzer::builder(LinderaChineseTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::JapaneseLindera(filters) => Some(
                 TextAnalyzer::builder(LinderaJapaneseTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             SearchTokenizer::KoreanLindera(filters) => Some(
                 TextAnalyzer::builder(LinderaKoreanTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             // Deprecated, use `stemmer` filter instead
@@ -384,7 +666,12 @@ impl SearchTokenizer {
                 TextAnalyzer::builder(SimpleTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(Stemmer::new(Language::English))
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             // Deprecated, use `stemmer` filter instead
@@ -392,7 +679,12 @@ impl SearchTokenizer {
                 TextAnalyzer::builder(SimpleTokenizer::default())
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(Stemmer::new(*language))
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
             #[cfg(feature = "icu")]
@@ -400,12 +692,37 @@ impl SearchTokenizer {
                 TextAnalyzer::builder(ICUTokenizer)
                     .filter(filters.remove_long_filter())
                     .filter(filters.lower_caser())
+                    .filter(filters.synonym_filter(self))
+                    .filter(filters.stopwords_language())
+                    .filter(filters.stopwords())
                     .filter(filters.stemmer())
+                    .filter(filters.phonetic())
+                    .filter(filters.edge_ngram())
                     .build(),
             ),
         }
     }
 
+    /// The part of this tokenizer's chain that comes before its synonym filter
+    fn synonym_rule_analyzer(&self) -> TextAnalyzer {
+        let mut prefix = self.clone();
+        let filters = prefix.filters_mut();
+        *filters = SearchTokenizerFilters {
+            remove_long: filters.remove_long,
+            lowercase: filters.lowercase,
+            ..Default::default()
+        };
+        match prefix {
+            // their built-in stemmer comes after the synonym filter
+            SearchTokenizer::EnStem(filters) | SearchTokenizer::Stem { filters, .. } => {
+                SearchTokenizer::Default(filters)
+            }
+            prefix => prefix,
+        }
+        .to_tantivy_tokenizer()
+        .expect("every tokenizer should have a tantivy tokenizer")
+    }
+
     fn filters(&self) -> &SearchTokenizerFilters {
         match self {
             SearchTokenizer::Default(filters) => filters,
@@ -418,6 +735,28 @@ impl SearchTokenizer {
             SearchTokenizer::ChineseCompatible(filters) => filters,
             SearchTokenizer::SourceCode(filters) => filters,
             SearchTokenizer::Ngram { filters, .. } => filters,
+            SearchTokenizer::EdgeNgram { filters, .. } => filters,
+            SearchTokenizer::ChineseLindera(filters) => filters,
+            SearchTokenizer::JapaneseLindera(filters) => filters,
+            SearchTokenizer::KoreanLindera(filters) => filters,
+            #[cfg(feature = "icu")]
+            SearchTokenizer::ICUTokenizer(filters) => filters,
+        }
+    }
+
+    fn filters_mut(&mut self) -> &mut SearchTokenizerFilters {
+        match self {
+            SearchTokenizer::Default(filters) => filters,
+            SearchTokenizer::Raw(filters) => filters,
+            SearchTokenizer::EnStem(filters) => filters,
+            SearchTokenizer::Stem { filters, .. } => filters,
+            SearchTokenizer::Lowercase(filters) => filters,
+            SearchTokenizer::WhiteSpace(filters) => filters,
+            SearchTokenizer::RegexTokenizer { filters, .. } => filters,
+            SearchTokenizer::ChineseCompatible(filters) => filters,
+            SearchTokenizer::SourceCode(filters) => filters,
+            SearchTokenizer::Ngram { filters, .. } => filters,
+            SearchTokenizer::EdgeNgram { filters, .. } => filters,
             SearchTokenizer::ChineseLindera(filters) => filters,
             SearchTokenizer::JapaneseLindera(filters) => filters,
             SearchTokenizer::KoreanLindera(filters) => filters,
@@ -425,6 +764,12 @@ impl SearchTokenizer {
             SearchTokenizer::ICUTokenizer(filters) => filters,
         }
     }
+
+    /// The synonym set this tokenizer is configured with, if any.  Its rules are filled in
+    /// from `paradedb.synonym_sets` when the index is created.
+    pub fn synonyms_mut(&mut self) -> Option<&mut SynonymSet> {
+        self.filters_mut().synonyms.as_mut()
+    }
 }
 
 pub fn language_to_str(lang: &Language) -> &str {
@@ -477,6 +822,19 @@ impl SearchTokenizer {
                 prefix_only,
                 filters: _,
             } => format!("ngram_mingram:{min_gram}_maxgram:{max_gram}_prefixonly:{prefix_only}{filters_suffix}"),
+            SearchTokenizer::EdgeNgram {
+                min_gram,
+                max_gram,
+                token_chars,
+                filters: _,
+            } => {
+                let token_chars = token_chars
+                    .iter()
+                    .map(TokenChars::name)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("edge_ngram_mingram:{min_gram}_maxgram:{max_gram}_tokenchars:{token_chars}{filters_suffix}")
+            }
             SearchTokenizer::ChineseLindera(_filters) => format!("chinese_lindera{filters_suffix}"),
             SearchTokenizer::JapaneseLindera(_filters) => {
                 format!("japanese_lindera{filters_suffix}")
@@ -521,6 +879,11 @@ mod tests {
             remove_long: Some(999),
             lowercase: Some(true),
             stemmer: None,
+            synonyms: None,
+            stopwords_language: None,
+            stopwords: None,
+            phonetic: None,
+            edge_ngram: None,
         });
         assert_eq!(
             tokenizer.name(),
@@ -548,7 +911,12 @@ mod tests {
                 filters: SearchTokenizerFilters {
                     remove_long: Some(123),
                     lowercase: Some(false),
-                    stemmer: None
+                    stemmer: None,
+                    synonyms: None,
+                    stopwords_language: None,
+                    stopwords: None,
+                    phonetic: None,
+                    edge_ngram: None,
                 }
             }
         );
@@ -567,6 +935,11 @@ mod tests {
                 remove_long: Some(100),
                 lowercase: None,
                 stemmer: None,
+                synonyms: None,
+                stopwords_language: None,
+                stopwords: None,
+                phonetic: None,
+                edge_ngram: None,
             },
         };
 
@@ -576,6 +949,160 @@ mod tests {
         );
     }
 
+    #[rstest]
+    fn test_synonyms_filter() {
+        let json = r#"{
+            "type": "default",
+            "synonyms": "products"
+        }"#;
+        let mut tokenizer =
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(tokenizer.name(), "default[synonyms=products]");
+        assert_eq!(
+            tokenizer.to_json_value(),
+            serde_json::json!({ "type": "default", "synonyms": "products" })
+        );
+
+        tokenizer.synonyms_mut().unwrap().rules = vec!["tv, television".to_string()];
+        let mut analyzer = tokenizer.to_tantivy_tokenizer().unwrap();
+        let mut stream = analyzer.token_stream("TV");
+        let mut texts = vec![];
+        while let Some(token) = stream.next() {
+            texts.push(token.text.clone());
+        }
+        assert_eq!(texts, vec!["tv", "television"]);
+
+        // rules are split into words the same way the text is
+        tokenizer.synonyms_mut().unwrap().rules = vec!["Wi-Fi, wireless".to_string()];
+        let mut analyzer = tokenizer.to_tantivy_tokenizer().unwrap();
+        let mut stream = analyzer.token_stream("wireless");
+        let mut texts = vec![];
+        while let Some(token) = stream.next() {
+            texts.push(token.text.clone());
+        }
+        assert_eq!(texts, vec!["wi", "wireless", "fi"]);
+
+        let json = r#"{ "type": "default", "synonyms": 5 }"#;
+        assert!(SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).is_err());
+    }
+
+    #[rstest]
+    fn test_stopwords_filter() {
+        let json = r#"{
+            "type": "default",
+            "stopwords_language": "English",
+            "stopwords": ["Quick"]
+        }"#;
+        let tokenizer =
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            tokenizer.name(),
+            r#"default[stopwords_language=English,stopwords=["Quick"]]"#
+        );
+
+        let mut analyzer = tokenizer.to_tantivy_tokenizer().unwrap();
+        let mut stream = analyzer.token_stream("The quick fox and the hound");
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
+        }
+        assert_eq!(tokens, vec![("fox".into(), 2), ("hound".into(), 5)]);
+
+        // Tamil has a stemmer, but no built-in stop word list
+        let json = r#"{ "type": "default", "stopwords_language": "Tamil" }"#;
+        assert!(SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).is_err());
+    }
+
+    #[rstest]
+    fn test_edge_ngram() {
+        let json = r#"{
+            "type": "edge_ngram",
+            "min_gram": 2,
+            "max_gram": 3,
+            "token_chars": ["letter", "digit"]
+        }"#;
+        let tokenizer =
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            tokenizer.name(),
+            "edge_ngram_mingram:2_maxgram:3_tokenchars:letter,digit"
+        );
+        assert_eq!(
+            tokenizer.to_json_value(),
+            serde_json::json!({
+                "type": "edge_ngram",
+                "min_gram": 2,
+                "max_gram": 3,
+                "token_chars": ["letter", "digit"]
+            })
+        );
+
+        let json = r#"{ "type": "default", "edge_ngram": { "min_gram": 1, "max_gram": 2 } }"#;
+        let tokenizer =
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(tokenizer.name(), "default[edge_ngram=1..2]");
+        let mut analyzer = tokenizer.to_tantivy_tokenizer().unwrap();
+        let mut stream = analyzer.token_stream("Hello World");
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
+        }
+        assert_eq!(
+            tokens,
+            vec![
+                ("h".into(), 0),
+                ("he".into(), 0),
+                ("w".into(), 1),
+                ("wo".into(), 1)
+            ]
+        );
+
+        let json = r#"{ "type": "edge_ngram", "min_gram": 3, "max_gram": 2 }"#;
+        assert!(SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).is_err());
+        let json =
+            r#"{ "type": "edge_ngram", "min_gram": 1, "max_gram": 2, "token_chars": ["emoji"] }"#;
+        assert!(SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).is_err());
+    }
+
+    #[rstest]
+    fn test_phonetic() {
+        let json = r#"{
+            "type": "whitespace",
+            "phonetic": { "algorithm": "double_metaphone", "preserve_original": true }
+        }"#;
+        let tokenizer =
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            tokenizer.name(),
+            "whitespace[phonetic=double_metaphone+original]"
+        );
+        assert_eq!(
+            tokenizer.to_json_value(),
+            serde_json::json!({
+                "type": "whitespace",
+                "phonetic": { "algorithm": "double_metaphone", "preserve_original": true }
+            })
+        );
+        let mut analyzer = tokenizer.to_tantivy_tokenizer().unwrap();
+        let mut stream = analyzer.token_stream("Smyth");
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
+        }
+        assert_eq!(
+            tokens,
+            vec![("smyth".into(), 0), ("SM0".into(), 0), ("XMT".into(), 0)]
+        );
+
+        let json = r#"{ "type": "default", "phonetic": { "algorithm": "soundex" } }"#;
+        let tokenizer =
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(tokenizer.name(), "default[phonetic=soundex]");
+
+        let json = r#"{ "type": "default", "phonetic": { "algorithm": "nysiis" } }"#;
+        assert!(SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap()).is_err());
+    }
+
     #[rstest]
     fn test_search_normalizer() {
         assert_eq!(SearchNormalizer::Lowercase.name(), "lowercase");
@@ -0,0 +1,1165 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! A token filter that encodes tokens by how they sound, so that differently spelled names
+//! like `Smith` and `Smyth` match.
+//!
+//! Codes are uppercase ASCII: letters, plus digits in Soundex codes and the `0` that Metaphone
+//! and Double Metaphone write for "th".  They're emitted at the position of the token they
+//! encode, optionally alongside the token itself.  Characters the algorithms don't know
+//! are ignored, and tokens that have no code at all, like numbers, are passed through as-is.
+
+use serde::{Deserialize, Serialize};
+use tantivy::tokenizer::{Token, TokenFilter, TokenStream, Tokenizer};
+
+/// The longest code emitted by the Metaphone and Double Metaphone algorithms.
+const MAX_METAPHONE_LEN: usize = 4;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneticAlgorithm {
+    /// American Soundex: the first letter followed by three digits, like `S530`.
+    Soundex,
+    /// Lawrence Philips' original Metaphone.
+    Metaphone,
+    /// Double Metaphone, which emits a second code for names whose spelling has an
+    /// alternative pronunciation.
+    DoubleMetaphone,
+    /// A simplified take on Beider-Morse's approximate matching.  Without its language
+    /// detection and per-language rules, letters that are commonly confused across European
+    /// spellings are merged into one code, so `Smith`, `Smyth`, and `Schmidt` all become `SMIT`.
+    BeiderMorseLite,
+}
+
+impl PhoneticAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhoneticAlgorithm::Soundex => "soundex",
+            PhoneticAlgorithm::Metaphone => "metaphone",
+            PhoneticAlgorithm::DoubleMetaphone => "double_metaphone",
+            PhoneticAlgorithm::BeiderMorseLite => "beider_morse_lite",
+        }
+    }
+
+    /// The codes of `word`, which is empty if it has none.
+    pub fn encode(&self, word: &str) -> Vec<String> {
+        let codes = match self {
+            PhoneticAlgorithm::Soundex => vec![soundex(word)],
+            PhoneticAlgorithm::Metaphone => vec![metaphone(word)],
+            PhoneticAlgorithm::DoubleMetaphone => {
+                let (primary, alternate) = double_metaphone(word);
+                vec![primary, alternate]
+            }
+            PhoneticAlgorithm::BeiderMorseLite => vec![beider_morse_lite(word)],
+        };
+
+        let mut unique = Vec::with_capacity(codes.len());
+        for code in codes {
+            if !code.is_empty() && !unique.contains(&code) {
+                unique.push(code);
+            }
+        }
+        unique
+    }
+}
+
+/// `TokenFilter` that replaces each token with its phonetic codes, or adds them after it if
+/// `preserve_original` is set.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PhoneticFilter {
+    pub algorithm: PhoneticAlgorithm,
+    #[serde(default)]
+    pub preserve_original: bool,
+}
+
+impl TokenFilter for PhoneticFilter {
+    type Tokenizer<T: Tokenizer> = PhoneticFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> PhoneticFilterWrapper<T> {
+        PhoneticFilterWrapper {
+            filter: self,
+            inner: tokenizer,
+            tokens: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PhoneticFilterWrapper<T> {
+    filter: PhoneticFilter,
+    inner: T,
+    tokens: Vec<Token>,
+}
+
+impl<T: Tokenizer> Tokenizer for PhoneticFilterWrapper<T> {
+    type TokenStream<'a> = PhoneticTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        self.tokens.clear();
+        let mut stream = self.inner.token_stream(text);
+        while stream.advance() {
+            let token = stream.token();
+            let codes = self.filter.algorithm.encode(&token.text);
+            if codes.is_empty() || self.filter.preserve_original {
+                self.tokens.push(token.clone());
+            }
+            for code in codes {
+                if code != token.text || !self.filter.preserve_original {
+                    self.tokens.push(Token {
+                        text: code,
+                        ..token.clone()
+                    });
+                }
+            }
+        }
+
+        PhoneticTokenStream {
+            tokens: &self.tokens,
+            cursor: 0,
+            current: Token::default(),
+        }
+    }
+}
+
+pub struct PhoneticTokenStream<'a> {
+    tokens: &'a [Token],
+    cursor: usize,
+    current: Token,
+}
+
+impl<'a> TokenStream for PhoneticTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        match self.tokens.get(self.cursor) {
+            Some(token) => {
+                self.current.clone_from(token);
+                self.cursor += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.current
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.current
+    }
+}
+
+/// The ASCII letters of `word`, uppercased.
+fn ascii_letters(word: &str) -> Vec<char> {
+    word.chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn soundex(word: &str) -> String {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'B' | 'F' | 'P' | 'V' => Some('1'),
+            'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+            'D' | 'T' => Some('3'),
+            'L' => Some('4'),
+            'M' | 'N' => Some('5'),
+            'R' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let letters = ascii_letters(word);
+    let Some((&first, rest)) = letters.split_first() else {
+        return String::new();
+    };
+
+    let mut code = String::from(first);
+    let mut last = digit(first);
+    for &c in rest {
+        if code.len() == 4 {
+            break;
+        }
+        match c {
+            // letters separated by H or W are coded once
+            'H' | 'W' => continue,
+            // but vowels separate them
+            'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => last = None,
+            _ => {
+                let d = digit(c);
+                if d != last {
+                    code.extend(d);
+                }
+                last = d;
+            }
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    code
+}
+
+fn metaphone(word: &str) -> String {
+    const FRONT_VOWELS: &[char] = &['E', 'I', 'Y'];
+    const VARSON: &[char] = &['C', 'S', 'P', 'T', 'G'];
+
+    let inwd = ascii_letters(word);
+    match inwd.len() {
+        0 => return String::new(),
+        1 => return inwd.iter().collect(),
+        _ => {}
+    }
+
+    // initial letters that are silent or change the sound of the next
+    let local: Vec<char> = match (inwd[0], inwd[1]) {
+        ('K' | 'G' | 'P', 'N') | ('A', 'E') | ('W', 'R') => inwd[1..].to_vec(),
+        ('W', 'H') => std::iter::once('W')
+            .chain(inwd[2..].iter().copied())
+            .collect(),
+        ('X', _) => std::iter::once('S')
+            .chain(inwd[1..].iter().copied())
+            .collect(),
+        _ => inwd,
+    };
+
+    let len = local.len();
+    let at = |i: usize| local.get(i).copied().unwrap_or('\0');
+    let is_vowel = |i: usize| matches!(at(i), 'A' | 'E' | 'I' | 'O' | 'U');
+    let is_front_vowel = |i: usize| FRONT_VOWELS.contains(&at(i));
+    let is_previous = |i: usize, c: char| i > 0 && at(i - 1) == c;
+    let is_next = |i: usize, c: char| i + 1 < len && at(i + 1) == c;
+    let is_previous_varson = |i: usize| i > 0 && VARSON.contains(&at(i - 1));
+    let is_last = |i: usize| i + 1 == len;
+    let region_matches = |i: usize, test: &str| {
+        let test: Vec<char> = test.chars().collect();
+        local.get(i..i + test.len()) == Some(&test[..])
+    };
+
+    let mut code = String::new();
+    let mut n = 0;
+    while code.len() < MAX_METAPHONE_LEN && n < len {
+        let symb = local[n];
+        // repeated letters are coded once, except for C
+        if symb != 'C' && is_previous(n, symb) {
+            n += 1;
+            continue;
+        }
+
+        match symb {
+            'A' | 'E' | 'I' | 'O' | 'U' if n == 0 => code.push(symb),
+            // silent in a trailing MB, like "dumb"
+            'B' if !(is_previous(n, 'M') && is_last(n)) => code.push('B'),
+            'C' => {
+                if is_previous(n, 'S') && !is_last(n) && is_front_vowel(n + 1) {
+                    // silent in SCI, SCE, and SCY
+                } else if region_matches(n, "CIA") {
+                    code.push('X');
+                } else if !is_last(n) && is_front_vowel(n + 1) {
+                    code.push('S');
+                } else if is_previous(n, 'S') && is_next(n, 'H') {
+                    code.push('K');
+                } else if is_next(n, 'H') {
+                    if n == 0 && len >= 3 && is_vowel(2) {
+                        code.push('K');
+                    } else {
+                        code.push('X');
+                    }
+                } else {
+                    code.push('K');
+                }
+            }
+            'D' => {
+                if !is_last(n + 1) && is_next(n, 'G') && is_front_vowel(n + 2) {
+                    // DGE, DGI, and DGY sound like J
+                    code.push('J');
+                    n += 2;
+                } else {
+                    code.push('T');
+                }
+            }
+            'G' => {
+                if (is_last(n + 1) && is_next(n, 'H'))
+                    || (!is_last(n + 1) && is_next(n, 'H') && !is_vowel(n + 2))
+                    || (n > 0 && (region_matches(n, "GN") || region_matches(n, "GNED")))
+                {
+                    // silent in GH that isn't followed by a vowel, and in GN and GNED
+                } else if !is_last(n) && is_front_vowel(n + 1) && !is_previous(n, 'G') {
+                    code.push('J');
+                } else {
+                    code.push('K');
+                }
+            }
+            'H' if !is_last(n) && !is_previous_varson(n) && is_vowel(n + 1) => code.push('H'),
+            'F' | 'J' | 'L' | 'M' | 'N' | 'R' => code.push(symb),
+            'K' if !is_previous(n, 'C') => code.push('K'),
+            'P' => code.push(if is_next(n, 'H') { 'F' } else { 'P' }),
+            'Q' => code.push('K'),
+            'S' => {
+                if region_matches(n, "SH") || region_matches(n, "SIO") || region_matches(n, "SIA") {
+                    code.push('X');
+                } else {
+                    code.push('S');
+                }
+            }
+            'T' => {
+                if region_matches(n, "TIA") || region_matches(n, "TIO") {
+                    code.push('X');
+                } else if region_matches(n, "TCH") {
+                    // silent, the CH is coded instead
+                } else if region_matches(n, "TH") {
+                    code.push('0');
+                } else {
+                    code.push('T');
+                }
+            }
+            'V' => code.push('F'),
+            'W' | 'Y' if !is_last(n) && is_vowel(n + 1) => code.push(symb),
+            'X' => code.push_str("KS"),
+            'Z' => code.push('S'),
+            _ => {}
+        }
+        n += 1;
+    }
+
+    code.truncate(MAX_METAPHONE_LEN);
+    code
+}
+
+/// The primary and alternate codes of a Double Metaphone encoding.
+#[derive(Default)]
+struct DoubleMetaphoneCodes {
+    primary: String,
+    alternate: String,
+}
+
+impl DoubleMetaphoneCodes {
+    fn is_complete(&self) -> bool {
+        self.primary.len() >= MAX_METAPHONE_LEN && self.alternate.len() >= MAX_METAPHONE_LEN
+    }
+
+    fn push_primary(&mut self, value: &str) {
+        let room = MAX_METAPHONE_LEN.saturating_sub(self.primary.len());
+        self.primary.extend(value.chars().take(room));
+    }
+
+    fn push_alternate(&mut self, value: &str) {
+        let room = MAX_METAPHONE_LEN.saturating_sub(self.alternate.len());
+        self.alternate.extend(value.chars().take(room));
+    }
+
+    fn push(&mut self, value: &str) {
+        self.push_primary(value);
+        self.push_alternate(value);
+    }
+
+    fn push_both(&mut self, primary: &str, alternate: &str) {
+        self.push_primary(primary);
+        self.push_alternate(alternate);
+    }
+}
+
+/// A word being encoded by Double Metaphone, which looks around the current letter a lot.
+struct DoubleMetaphoneWord {
+    chars: Vec<char>,
+    slavo_germanic: bool,
+}
+
+impl DoubleMetaphoneWord {
+    fn new(word: &str) -> Self {
+        let chars: Vec<char> = word
+            .chars()
+            .flat_map(char::to_uppercase)
+            .filter(|c| c.is_ascii_uppercase() || matches!(c, 'Ç' | 'Ñ'))
+            .collect();
+        let text: String = chars.iter().collect();
+        let slavo_germanic = text.contains('W')
+            || text.contains('K')
+            || text.contains("CZ")
+            || text.contains("WITZ");
+        Self {
+            chars,
+            slavo_germanic,
+        }
+    }
+
+    fn len(&self) -> isize {
+        self.chars.len() as isize
+    }
+
+    fn last(&self) -> isize {
+        self.len() - 1
+    }
+
+    /// The letter at `index`, or `'\0'` outside the word.
+    fn at(&self, index: isize) -> char {
+        if index < 0 {
+            return '\0';
+        }
+        self.chars.get(index as usize).copied().unwrap_or('\0')
+    }
+
+    /// Whether the `length` letters starting at `start` are one of `criteria`.
+    fn contains(&self, start: isize, length: isize, criteria: &[&str]) -> bool {
+        if start < 0 || start + length > self.len() {
+            return false;
+        }
+        let target: String = self.chars[start as usize..(start + length) as usize]
+            .iter()
+            .collect();
+        criteria.contains(&target.as_str())
+    }
+
+    fn is_vowel(&self, index: isize) -> bool {
+        matches!(self.at(index), 'A' | 'E' | 'I' | 'O' | 'U' | 'Y')
+    }
+
+    /// Whether the word starts with `VAN `, `VON `, or `SCH`, which are obviously Germanic.
+    fn is_germanic(&self) -> bool {
+        self.contains(0, 4, &["VAN ", "VON "]) || self.contains(0, 3, &["SCH"])
+    }
+}
+
+fn double_metaphone(word: &str) -> (String, String) {
+    let w = DoubleMetaphoneWord::new(word);
+    let mut codes = DoubleMetaphoneCodes::default();
+    if w.chars.is_empty() {
+        return (String::new(), String::new());
+    }
+
+    // initial letters that are silent
+    let mut index = if w.contains(0, 2, &["GN", "KN", "PN", "WR", "PS"]) {
+        1
+    } else {
+        0
+    };
+
+    // each letter is either coded alone, or together with the letters after it
+    let skip_double = |index: isize, c: char| {
+        if w.at(index + 1) == c {
+            index + 2
+        } else {
+            index + 1
+        }
+    };
+    while !codes.is_complete() && index <= w.last() {
+        index = match w.at(index) {
+            'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                if index == 0 {
+                    codes.push("A");
+                }
+                index + 1
+            }
+            'B' => {
+                codes.push("P");
+                skip_double(index, 'B')
+            }
+            'Ç' => {
+                codes.push("S");
+                index + 1
+            }
+            'C' => dm_c(&w, &mut codes, index),
+            'D' => dm_d(&w, &mut codes, index),
+            'F' => {
+                codes.push("F");
+                skip_double(index, 'F')
+            }
+            'G' => dm_g(&w, &mut codes, index),
+            'H' => {
+                // only kept at the start of the word, or between vowels
+                if (index == 0 || w.is_vowel(index - 1)) && w.is_vowel(index + 1) {
+                    codes.push("H");
+                    index + 2
+                } else {
+                    index + 1
+                }
+            }
+            'J' => dm_j(&w, &mut codes, index),
+            'K' => {
+                codes.push("K");
+                skip_double(index, 'K')
+            }
+            'L' => {
+                if w.at(index + 1) == 'L' {
+                    if dm_spanish_ll(&w, index) {
+                        codes.push_primary("L");
+                    } else {
+                        codes.push("L");
+                    }
+                    index + 2
+                } else {
+                    codes.push("L");
+                    index + 1
+                }
+            }
+            'M' => {
+                codes.push("M");
+                // MM, and a silent B in "dumb" and "thumbs"
+                let silent_b = w.contains(index - 1, 3, &["UMB"])
+                    && (index + 1 == w.last() || w.contains(index + 2, 2, &["ER"]));
+                if w.at(index + 1) == 'M' || silent_b {
+                    index + 2
+                } else {
+                    index + 1
+                }
+            }
+            'N' => {
+                codes.push("N");
+                skip_double(index, 'N')
+            }
+            'Ñ' => {
+                codes.push("N");
+                index + 1
+            }
+            'P' => {
+                if w.at(index + 1) == 'H' {
+                    codes.push("F");
+                    index + 2
+                } else {
+                    codes.push("P");
+                    if w.contains(index + 1, 1, &["P", "B"]) {
+                        index + 2
+                    } else {
+                        index + 1
+                    }
+                }
+            }
+            'Q' => {
+                codes.push("K");
+                skip_double(index, 'Q')
+            }
+            'R' => {
+                // French, like "Rogier", but not "hochmeier"
+                if index == w.last()
+                    && !w.slavo_germanic
+                    && w.contains(index - 2, 2, &["IE"])
+                    && !w.contains(index - 4, 2, &["ME", "MA"])
+                {
+                    codes.push_alternate("R");
+                } else {
+                    codes.push("R");
+                }
+                skip_double(index, 'R')
+            }
+            'S' => dm_s(&w, &mut codes, index),
+            'T' => dm_t(&w, &mut codes, index),
+            'V' => {
+                codes.push("F");
+                skip_double(index, 'V')
+            }
+            'W' => dm_w(&w, &mut codes, index),
+            'X' => {
+                if index == 0 {
+                    codes.push("S");
+                    index + 1
+                } else {
+                    // silent in French, like "breaux"
+                    let french = index == w.last()
+                        && (w.contains(index - 3, 3, &["IAU", "EAU"])
+                            || w.contains(index - 2, 2, &["AU", "OU"]));
+                    if !french {
+                        codes.push("KS");
+                    }
+                    if w.contains(index + 1, 1, &["C", "X"]) {
+                        index + 2
+                    } else {
+                        index + 1
+                    }
+                }
+            }
+            'Z' => {
+                if w.at(index + 1) == 'H' {
+                    // Chinese pinyin, like "Zhao"
+                    codes.push("J");
+                    index + 2
+                } else {
+                    if w.contains(index + 1, 2, &["ZO", "ZI", "ZA"])
+                        || (w.slavo_germanic && index > 0 && w.at(index - 1) != 'T')
+                    {
+                        codes.push_both("S", "TS");
+                    } else {
+                        codes.push("S");
+                    }
+                    skip_double(index, 'Z')
+                }
+            }
+            _ => index + 1,
+        };
+    }
+
+    (codes.primary, codes.alternate)
+}
+
+fn dm_c(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if dm_germanic_ach(w, index) {
+        codes.push("K");
+        index + 2
+    } else if index == 0 && w.contains(index, 6, &["CAESAR"]) {
+        codes.push("S");
+        index + 2
+    } else if w.contains(index, 2, &["CH"]) {
+        dm_ch(w, codes, index)
+    } else if w.contains(index, 2, &["CZ"]) && !w.contains(index - 2, 4, &["WICZ"]) {
+        // "Czerny"
+        codes.push_both("S", "X");
+        index + 2
+    } else if w.contains(index + 1, 3, &["CIA"]) {
+        // "focaccia"
+        codes.push("X");
+        index + 3
+    } else if w.contains(index, 2, &["CC"]) && !(index == 1 && w.at(0) == 'M') {
+        // double C, but not "McClelland"
+        if w.contains(index + 2, 1, &["I", "E", "H"]) && !w.contains(index + 2, 2, &["HU"]) {
+            if (index == 1 && w.at(index - 1) == 'A')
+                || w.contains(index - 1, 5, &["UCCEE", "UCCES"])
+            {
+                // "accident", "accede", "succeed"
+                codes.push("KS");
+            } else {
+                // "bacci", "bertucci"
+                codes.push("X");
+            }
+            index + 3
+        } else {
+            codes.push("K");
+            index + 2
+        }
+    } else if w.contains(index, 2, &["CK", "CG", "CQ"]) {
+        codes.push("K");
+        index + 2
+    } else if w.contains(index, 2, &["CI", "CE", "CY"]) {
+        // Italian or English
+        if w.contains(index, 3, &["CIO", "CIE", "CIA"]) {
+            codes.push_both("S", "X");
+        } else {
+            codes.push("S");
+        }
+        index + 2
+    } else {
+        codes.push("K");
+        if w.contains(index + 1, 2, &[" C", " Q", " G"]) {
+            // "Mac Caffrey", "Mac Gregor"
+            index + 3
+        } else if w.contains(index + 1, 1, &["C", "K", "Q"])
+            && !w.contains(index + 1, 2, &["CE", "CI"])
+        {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+/// Whether the C at `index` is the hard CH of a Germanic ACH, like "bacher" and "macher".
+fn dm_germanic_ach(w: &DoubleMetaphoneWord, index: isize) -> bool {
+    if w.contains(index, 4, &["CHIA"]) {
+        true
+    } else if index <= 1 || w.is_vowel(index - 2) || !w.contains(index - 1, 3, &["ACH"]) {
+        false
+    } else {
+        let c = w.at(index + 2);
+        (c != 'I' && c != 'E') || w.contains(index - 2, 6, &["BACHER", "MACHER"])
+    }
+}
+
+fn dm_ch(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    // Greek roots, like "chemistry" and "chorus"
+    let greek = index == 0
+        && (w.contains(index + 1, 5, &["HARAC", "HARIS"])
+            || w.contains(index + 1, 3, &["HOR", "HYM", "HIA", "HEM"]))
+        && !w.contains(0, 5, &["CHORE"]);
+    // Germanic, Greek, or otherwise a KH sound
+    let kh = w.is_germanic()
+        || w.contains(index - 2, 6, &["ORCHES", "ARCHIT", "ORCHID"])
+        || w.contains(index + 2, 1, &["T", "S"])
+        || ((w.contains(index - 1, 1, &["A", "O", "U", "E"]) || index == 0)
+            && (w.contains(
+                index + 2,
+                1,
+                &["L", "R", "N", "M", "B", "H", "F", "V", "W", " "],
+            ) || index + 1 == w.last()));
+
+    if index > 0 && w.contains(index, 4, &["CHAE"]) {
+        // "Michael"
+        codes.push_both("K", "X");
+    } else if greek || kh {
+        codes.push("K");
+    } else if index > 0 {
+        if w.contains(0, 2, &["MC"]) {
+            // "McHugh"
+            codes.push("K");
+        } else {
+            codes.push_both("X", "K");
+        }
+    } else {
+        codes.push("X");
+    }
+    index + 2
+}
+
+fn dm_d(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if w.contains(index, 2, &["DG"]) {
+        if w.contains(index + 2, 1, &["I", "E", "Y"]) {
+            // "edge"
+            codes.push("J");
+            index + 3
+        } else {
+            // "Edgar"
+            codes.push("TK");
+            index + 2
+        }
+    } else if w.contains(index, 2, &["DT", "DD"]) {
+        codes.push("T");
+        index + 2
+    } else {
+        codes.push("T");
+        index + 1
+    }
+}
+
+fn dm_g(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if w.at(index + 1) == 'H' {
+        dm_gh(w, codes, index)
+    } else if w.at(index + 1) == 'N' {
+        if index == 1 && w.is_vowel(0) && !w.slavo_germanic {
+            codes.push_both("KN", "N");
+        } else if !w.contains(index + 2, 2, &["EY"]) && w.at(index + 1) != 'Y' && !w.slavo_germanic
+        {
+            codes.push_both("N", "KN");
+        } else {
+            codes.push("KN");
+        }
+        index + 2
+    } else if w.contains(index + 1, 2, &["LI"]) && !w.slavo_germanic {
+        // "tagliaro"
+        codes.push_both("KL", "L");
+        index + 2
+    } else if index == 0
+        && (w.at(index + 1) == 'Y'
+            || w.contains(
+                index + 1,
+                2,
+                &[
+                    "ES", "EP", "EB", "EL", "EY", "IB", "IL", "IN", "IE", "EI", "ER",
+                ],
+            ))
+    {
+        // GES, GEP, GEL, and GIE at the start of the word
+        codes.push_both("K", "J");
+        index + 2
+    } else if (w.contains(index + 1, 2, &["ER"]) || w.at(index + 1) == 'Y')
+        && !w.contains(0, 6, &["DANGER", "RANGER", "MANGER"])
+        && !w.contains(index - 1, 1, &["E", "I"])
+        && !w.contains(index - 1, 3, &["RGY", "OGY"])
+    {
+        // GER and GY
+        codes.push_both("K", "J");
+        index + 2
+    } else if w.contains(index + 1, 1, &["E", "I", "Y"])
+        || w.contains(index - 1, 4, &["AGGI", "OGGI"])
+    {
+        // Italian, like "biaggi"
+        if w.is_germanic() || w.contains(index + 1, 2, &["ET"]) {
+            codes.push("K");
+        } else if w.contains(index + 1, 3, &["IER"]) {
+            codes.push("J");
+        } else {
+            codes.push_both("J", "K");
+        }
+        index + 2
+    } else if w.at(index + 1) == 'G' {
+        codes.push("K");
+        index + 2
+    } else {
+        codes.push("K");
+        index + 1
+    }
+}
+
+fn dm_gh(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if index > 0 && !w.is_vowel(index - 1) {
+        codes.push("K");
+    } else if index == 0 {
+        // "ghislane", "ghiradelli"
+        if w.at(index + 2) == 'I' {
+            codes.push("J");
+        } else {
+            codes.push("K");
+        }
+    } else if (index > 1 && w.contains(index - 2, 1, &["B", "H", "D"]))
+        || (index > 2 && w.contains(index - 3, 1, &["B", "H", "D"]))
+        || (index > 3 && w.contains(index - 4, 1, &["B", "H"]))
+    {
+        // Parker's rule, like "hugh"
+    } else if index > 2
+        && w.at(index - 1) == 'U'
+        && w.contains(index - 3, 1, &["C", "G", "L", "R", "T"])
+    {
+        // "laugh", "McLaughlin", "cough", "gough", "rough", "tough"
+        codes.push("F");
+    } else if index > 0 && w.at(index - 1) != 'I' {
+        codes.push("K");
+    }
+    index + 2
+}
+
+fn dm_j(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if w.contains(index, 4, &["JOSE"]) || w.contains(0, 4, &["SAN "]) {
+        // obviously Spanish, like "Jose" and "San Jacinto"
+        if (index == 0 && w.at(index + 4) == ' ') || w.len() == 4 || w.contains(0, 4, &["SAN "]) {
+            codes.push("H");
+        } else {
+            codes.push_both("J", "H");
+        }
+        return index + 1;
+    }
+
+    if index == 0 {
+        // "Yankelovich" and "Jankelowicz"
+        codes.push_both("J", "A");
+    } else if w.is_vowel(index - 1)
+        && !w.slavo_germanic
+        && (w.at(index + 1) == 'A' || w.at(index + 1) == 'O')
+    {
+        // Spanish pronunciation, like "bajador"
+        codes.push_both("J", "H");
+    } else if index == w.last() {
+        codes.push_primary("J");
+    } else if !w.contains(index + 1, 1, &["L", "T", "K", "S", "N", "M", "B", "Z"])
+        && !w.contains(index - 1, 1, &["S", "K", "L"])
+    {
+        codes.push("J");
+    }
+
+    if w.at(index + 1) == 'J' {
+        index + 2
+    } else {
+        index + 1
+    }
+}
+
+/// Whether the LL at `index` is Spanish, like "cabrillo" and "gallegos", where it's only
+/// coded in the alternate.
+fn dm_spanish_ll(w: &DoubleMetaphoneWord, index: isize) -> bool {
+    (index == w.len() - 3 && w.contains(index - 1, 4, &["ILLO", "ILLA", "ALLE"]))
+        || ((w.contains(w.len() - 2, 2, &["AS", "OS"]) || w.contains(w.last(), 1, &["A", "O"]))
+            && w.contains(index - 1, 4, &["ALLE"]))
+}
+
+fn dm_s(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if w.contains(index - 1, 3, &["ISL", "YSL"]) {
+        // silent in "island", "isle", "carlisle", and "carlysle"
+        index + 1
+    } else if index == 0 && w.contains(index, 5, &["SUGAR"]) {
+        codes.push_both("X", "S");
+        index + 1
+    } else if w.contains(index, 2, &["SH"]) {
+        if w.contains(index + 1, 4, &["HEIM", "HOEK", "HOLM", "HOLZ"]) {
+            // Germanic
+            codes.push("S");
+        } else {
+            codes.push("X");
+        }
+        index + 2
+    } else if w.contains(index, 3, &["SIO", "SIA"]) || w.contains(index, 4, &["SIAN"]) {
+        // Italian and Armenian
+        if w.slavo_germanic {
+            codes.push("S");
+        } else {
+            codes.push_both("S", "X");
+        }
+        index + 3
+    } else if (index == 0 && w.contains(index + 1, 1, &["M", "N", "L", "W"]))
+        || w.contains(index + 1, 1, &["Z"])
+    {
+        // German and anglicisations, like "smith" for "schmidt" and "snider" for "schneider"
+        codes.push_both("S", "X");
+        if w.contains(index + 1, 1, &["Z"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    } else if w.contains(index, 2, &["SC"]) {
+        if w.at(index + 2) == 'H' {
+            // Schlesinger's rule
+            if w.contains(index + 3, 2, &["OO", "ER", "EN", "UY", "ED", "EM"]) {
+                // Dutch, like "school" and "schooner"
+                if w.contains(index + 3, 2, &["ER", "EN"]) {
+                    // "schermerhorn", "schenker"
+                    codes.push_both("X", "SK");
+                } else {
+                    codes.push("SK");
+                }
+            } else if index == 0 && !w.is_vowel(3) && w.at(3) != 'W' {
+                codes.push_both("X", "S");
+            } else {
+                codes.push("X");
+            }
+        } else if w.contains(index + 2, 1, &["I", "E", "Y"]) {
+            codes.push("S");
+        } else {
+            codes.push("SK");
+        }
+        index + 3
+    } else {
+        if index == w.last() && w.contains(index - 2, 2, &["AI", "OI"]) {
+            // silent in French, like "resnais" and "artois"
+            codes.push_alternate("S");
+        } else {
+            codes.push("S");
+        }
+        if w.contains(index + 1, 1, &["S", "Z"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn dm_t(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if w.contains(index, 4, &["TION"]) || w.contains(index, 3, &["TIA", "TCH"]) {
+        codes.push("X");
+        index + 3
+    } else if w.contains(index, 2, &["TH"]) || w.contains(index, 3, &["TTH"]) {
+        // "thomas", "thames", or Germanic
+        if w.contains(index + 2, 2, &["OM", "AM"]) || w.is_germanic() {
+            codes.push("T");
+        } else {
+            codes.push_both("0", "T");
+        }
+        index + 2
+    } else {
+        codes.push("T");
+        if w.contains(index + 1, 1, &["T", "D"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn dm_w(w: &DoubleMetaphoneWord, codes: &mut DoubleMetaphoneCodes, index: isize) -> isize {
+    if w.contains(index, 2, &["WR"]) {
+        codes.push("R");
+        index + 2
+    } else if index == 0 && (w.is_vowel(index + 1) || w.contains(index, 2, &["WH"])) {
+        if w.is_vowel(index + 1) {
+            // "Wasserman" should match "Vasserman"
+            codes.push_both("A", "F");
+        } else {
+            // "Uomo" should match "Womo"
+            codes.push("A");
+        }
+        index + 1
+    } else if (index == w.last() && w.is_vowel(index - 1))
+        || w.contains(index - 1, 5, &["EWSKI", "EWSKY", "OWSKI", "OWSKY"])
+        || w.contains(0, 3, &["SCH"])
+    {
+        // "Arnow" should match "Arnoff"
+        codes.push_alternate("F");
+        index + 1
+    } else if w.contains(index, 4, &["WICZ", "WITZ"]) {
+        // Polish, like "filipowicz"
+        codes.push_both("TS", "FX");
+        index + 4
+    } else {
+        index + 1
+    }
+}
+
+/// Letter sequences and the codes they're merged into by [`beider_morse_lite`], tried in order
+/// at each position.  Voiced and unvoiced consonants are merged, since they're commonly swapped
+/// between languages, as are vowels that are pronounced alike.
+const BEIDER_MORSE_LITE_RULES: &[(&str, &str)] = &[
+    ("TSCH", "C"),
+    ("TCH", "C"),
+    ("SCH", "S"),
+    ("CH", "C"),
+    ("CZ", "C"),
+    ("CS", "C"),
+    ("TZ", "C"),
+    ("TS", "C"),
+    ("SH", "S"),
+    ("SZ", "S"),
+    ("ZH", "S"),
+    ("PH", "F"),
+    ("TH", "T"),
+    ("DT", "T"),
+    ("GH", "K"),
+    ("KH", "K"),
+    ("CK", "K"),
+    ("QU", "KF"),
+    ("CE", "SI"),
+    ("CI", "SI"),
+    ("CY", "SI"),
+    ("X", "KS"),
+    ("B", "P"),
+    ("P", "P"),
+    ("C", "K"),
+    ("G", "K"),
+    ("K", "K"),
+    ("Q", "K"),
+    ("D", "T"),
+    ("T", "T"),
+    ("F", "F"),
+    ("V", "F"),
+    ("W", "F"),
+    ("S", "S"),
+    ("Z", "S"),
+    ("J", "I"),
+    ("Y", "I"),
+    ("I", "I"),
+    ("E", "I"),
+    ("A", "O"),
+    ("O", "O"),
+    ("U", "U"),
+    ("L", "L"),
+    ("M", "M"),
+    ("N", "N"),
+    ("R", "R"),
+];
+
+fn beider_morse_lite(word: &str) -> String {
+    let letters: String = ascii_letters(word).into_iter().collect();
+    let mut code = String::new();
+    let mut rest = letters.as_str();
+    while let Some(first) = rest.chars().next() {
+        match BEIDER_MORSE_LITE_RULES
+            .iter()
+            .find(|(pattern, _)| rest.starts_with(pattern))
+        {
+            Some((pattern, replacement)) => {
+                for c in replacement.chars() {
+                    // sounds that repeat are coded once
+                    if !code.ends_with(c) {
+                        code.push(c);
+                    }
+                }
+                rest = &rest[pattern.len()..];
+            }
+            // H is only a sound of its own at the start of a word
+            None => {
+                if first == 'H' && code.is_empty() {
+                    code.push('H');
+                }
+                rest = &rest[first.len_utf8()..];
+            }
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use tantivy::tokenizer::{LowerCaser, SimpleTokenizer, TextAnalyzer};
+
+    #[rstest]
+    #[case("Robert", "R163")]
+    #[case("Rupert", "R163")]
+    #[case("Ashcraft", "A261")]
+    #[case("Tymczak", "T522")]
+    #[case("Pfister", "P236")]
+    #[case("Smith", "S530")]
+    #[case("Smyth", "S530")]
+    #[case("Lee", "L000")]
+    fn test_soundex(#[case] word: &str, #[case] expected: &str) {
+        assert_eq!(soundex(word), expected);
+    }
+
+    #[rstest]
+    #[case("Smith", "SM0")]
+    #[case("Smyth", "SM0")]
+    #[case("Knight", "NT")]
+    #[case("Thompson", "0MPS")]
+    #[case("Wright", "RT")]
+    #[case("Xavier", "SFR")]
+    #[case("science", "SNS")]
+    #[case("dumb", "TM")]
+    fn test_metaphone(#[case] word: &str, #[case] expected: &str) {
+        assert_eq!(metaphone(word), expected);
+    }
+
+    #[rstest]
+    #[case("Smith", "SM0", "XMT")]
+    #[case("Smyth", "SM0", "XMT")]
+    #[case("Schmidt", "XMT", "SMT")]
+    #[case("Michael", "MKL", "MXL")]
+    #[case("Thomas", "TMS", "TMS")]
+    #[case("Wasserman", "ASRM", "FSRM")]
+    #[case("Jose", "HS", "HS")]
+    #[case("Gallegos", "KLKS", "KKS")]
+    #[case("Caesar", "SSR", "SSR")]
+    fn test_double_metaphone(#[case] word: &str, #[case] primary: &str, #[case] alternate: &str) {
+        assert_eq!(
+            double_metaphone(word),
+            (primary.to_string(), alternate.to_string())
+        );
+    }
+
+    #[rstest]
+    #[case("Smith", "SMIT")]
+    #[case("Smyth", "SMIT")]
+    #[case("Schmidt", "SMIT")]
+    #[case("Schneider", "SNITIR")]
+    #[case("Snyder", "SNITIR")]
+    #[case("Hoffman", "HOFMON")]
+    fn test_beider_morse_lite(#[case] word: &str, #[case] expected: &str) {
+        assert_eq!(beider_morse_lite(word), expected);
+    }
+
+    fn collect(filter: PhoneticFilter, text: &str) -> Vec<(String, usize)> {
+        let mut analyzer = TextAnalyzer::builder(SimpleTokenizer::default())
+            .filter(LowerCaser)
+            .filter(filter)
+            .build();
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
+        }
+        tokens
+    }
+
+    #[rstest]
+    fn test_filter() {
+        let filter = PhoneticFilter {
+            algorithm: PhoneticAlgorithm::DoubleMetaphone,
+            preserve_original: false,
+        };
+        assert_eq!(
+            collect(filter, "John Smith 42"),
+            vec![
+                ("JN".into(), 0),
+                ("AN".into(), 0),
+                ("SM0".into(), 1),
+                ("XMT".into(), 1),
+                ("42".into(), 2)
+            ]
+        );
+
+        let filter = PhoneticFilter {
+            algorithm: PhoneticAlgorithm::Soundex,
+            preserve_original: true,
+        };
+        assert_eq!(
+            collect(filter, "Smyth"),
+            vec![("smyth".into(), 0), ("S530".into(), 0)]
+        );
+    }
+}
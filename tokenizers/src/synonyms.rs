@@ -0,0 +1,406 @@
+// Copyright (c) 2023-2024 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! A token filter that expands tokens using Solr-style synonym rules.
+//!
+//! Two kinds of rules are supported, one per line:
+//!
+//! - `tv, television, telly` declares an equivalence. Each entry is expanded to every
+//!   entry in the rule, including itself.
+//! - `ipod, i-pod => ipod` declares an explicit mapping. Each entry on the left-hand side
+//!   is replaced by all of the entries on the right-hand side.
+//!
+//! Each entry is analyzed by the part of the tokenizer chain that comes before the filter, so it
+//! matches however the tokenizer splits and normalizes the same text:  with the `default`
+//! tokenizer, `Wi-Fi` is the two words `wi fi`, while with `raw` it's the single token `wi-fi`.
+//! Expanded tokens are emitted at the position of the first matched token, with successive
+//! positions for multi-word entries, so that phrase queries continue to work against either form.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tantivy::tokenizer::{Token, TokenFilter, TokenStream, Tokenizer};
+
+/// A named set of synonym rules, as stored by `paradedb.create_synonym_set()`.
+///
+/// The tokenizer configuration only ever names the set.  Its rules are resolved when the
+/// index is created and serialized alongside the index schema, so changes to a set are only
+/// picked up after a `REINDEX`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct SynonymSet {
+    pub name: String,
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+impl SynonymSet {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            rules: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SynonymRule {
+    /// the entries this rule matches, as written
+    pub inputs: Vec<String>,
+    /// the entries a match expands to, as written
+    pub outputs: Vec<String>,
+}
+
+/// Parse a single synonym rule.  Returns `Ok(None)` for blank lines and `#` comments.
+pub fn parse_rule(rule: &str) -> Result<Option<SynonymRule>> {
+    let rule = rule.trim();
+    if rule.is_empty() || rule.starts_with('#') {
+        return Ok(None);
+    }
+
+    fn entries(list: &str, rule: &str) -> Result<Vec<String>> {
+        list.split(',')
+            .map(|entry| {
+                let entry = entry.trim();
+                if entry.is_empty() {
+                    bail!("synonym rule `{rule}` contains an empty entry");
+                }
+                Ok(entry.to_string())
+            })
+            .collect()
+    }
+
+    match rule.split_once("=>") {
+        Some((lhs, rhs)) => {
+            if rhs.contains("=>") {
+                bail!("synonym rule `{rule}` contains more than one `=>`");
+            }
+            Ok(Some(SynonymRule {
+                inputs: entries(lhs, rule)?,
+                outputs: entries(rhs, rule)?,
+            }))
+        }
+        None => {
+            let entries = entries(rule, rule)?;
+            if entries.len() < 2 {
+                bail!("synonym rule `{rule}` must list at least two equivalent entries");
+            }
+            Ok(Some(SynonymRule {
+                inputs: entries.clone(),
+                outputs: entries,
+            }))
+        }
+    }
+}
+
+/// Validate every rule in `rules`, returning the first parse error.
+pub fn validate_rules<S: AsRef<str>>(rules: &[S]) -> Result<()> {
+    for rule in rules {
+        parse_rule(rule.as_ref())?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct SynonymMap {
+    entries: HashMap<Vec<String>, Vec<Vec<String>>>,
+    max_input_len: usize,
+}
+
+impl SynonymMap {
+    fn build<S: AsRef<str>>(
+        rules: &[S],
+        mut analyze: impl FnMut(&str) -> Vec<String>,
+    ) -> Result<Self> {
+        let mut map = SynonymMap::default();
+        for rule in rules {
+            let Some(rule) = parse_rule(rule.as_ref())? else {
+                continue;
+            };
+
+            // entries the analyzer removes entirely, like punctuation, can't match anything
+            let mut analyze_all = |entries: Vec<String>| {
+                entries
+                    .iter()
+                    .map(|entry| analyze(entry))
+                    .filter(|words| !words.is_empty())
+                    .collect::<Vec<_>>()
+            };
+            let outputs = analyze_all(rule.outputs);
+            for input in analyze_all(rule.inputs) {
+                map.max_input_len = map.max_input_len.max(input.len());
+                let expansions = map.entries.entry(input).or_default();
+                for output in &outputs {
+                    if !expansions.contains(output) {
+                        expansions.push(output.clone());
+                    }
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    /// Find the longest rule input that matches the tokens starting at `tokens[0]`.
+    fn longest_match(&self, tokens: &[Token]) -> Option<(usize, &Vec<Vec<String>>)> {
+        let max_len = self.max_input_len.min(tokens.len());
+        (1..=max_len).rev().find_map(|len| {
+            let key = tokens[..len]
+                .iter()
+                .map(|t| t.text.clone())
+                .collect::<Vec<_>>();
+            self.entries.get(&key).map(|expansions| (len, expansions))
+        })
+    }
+}
+
+/// `TokenFilter` that expands tokens according to a set of synonym rules.
+#[derive(Clone, Debug)]
+pub struct SynonymFilter {
+    map: Arc<SynonymMap>,
+}
+
+impl SynonymFilter {
+    /// Build a filter from `rules`, whose entries are split into words by `analyze`.  That should
+    /// be the part of the tokenizer chain that comes before this filter, so the entries match the
+    /// tokens the filter sees.
+    pub fn new<S: AsRef<str>>(
+        rules: &[S],
+        analyze: impl FnMut(&str) -> Vec<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            map: Arc::new(SynonymMap::build(rules, analyze)?),
+        })
+    }
+}
+
+impl TokenFilter for SynonymFilter {
+    type Tokenizer<T: Tokenizer> = SynonymFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> SynonymFilterWrapper<T> {
+        SynonymFilterWrapper {
+            map: self.map,
+            inner: tokenizer,
+            tokens: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SynonymFilterWrapper<T> {
+    map: Arc<SynonymMap>,
+    inner: T,
+    tokens: Vec<Token>,
+}
+
+impl<T: Tokenizer> Tokenizer for SynonymFilterWrapper<T> {
+    type TokenStream<'a> = SynonymTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        // multi-word rules need to look ahead, so buffer the whole input up front
+        let mut input = Vec::new();
+        let mut stream = self.inner.token_stream(text);
+        while stream.advance() {
+            input.push(stream.token().clone());
+        }
+
+        self.tokens.clear();
+        let mut i = 0;
+        while i < input.len() {
+            match self.map.longest_match(&input[i..]) {
+                Some((len, expansions)) => {
+                    let matched = &input[i..i + len];
+                    let first = &matched[0];
+                    let last = &matched[len - 1];
+                    for expansion in expansions {
+                        if expansion.iter().eq(matched.iter().map(|t| &t.text)) {
+                            // the original tokens keep their own offsets
+                            self.tokens.extend(matched.iter().cloned());
+                            continue;
+                        }
+                        for (j, word) in expansion.iter().enumerate() {
+                            self.tokens.push(Token {
+                                offset_from: first.offset_from,
+                                offset_to: last.offset_to,
+                                position: first.position + j,
+                                text: word.clone(),
+                                position_length: 1,
+                            });
+                        }
+                    }
+                    i += len;
+                }
+                None => {
+                    self.tokens.push(input[i].clone());
+                    i += 1;
+                }
+            }
+        }
+
+        // expansions longer than their match overlap the following tokens, and positions
+        // must be non-decreasing when they're indexed
+        self.tokens.sort_by_key(|token| token.position);
+
+        SynonymTokenStream {
+            tokens: &self.tokens,
+            cursor: 0,
+            current: Token::default(),
+        }
+    }
+}
+
+pub struct SynonymTokenStream<'a> {
+    tokens: &'a [Token],
+    cursor: usize,
+    current: Token,
+}
+
+impl<'a> TokenStream for SynonymTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        match self.tokens.get(self.cursor) {
+            Some(token) => {
+                self.current.clone_from(token);
+                self.cursor += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.current
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use tantivy::tokenizer::{LowerCaser, RawTokenizer, SimpleTokenizer, TextAnalyzer};
+
+    fn words(analyzer: &mut TextAnalyzer, text: &str) -> Vec<String> {
+        let mut stream = analyzer.token_stream(text);
+        let mut words = vec![];
+        while let Some(token) = stream.next() {
+            words.push(token.text.clone());
+        }
+        words
+    }
+
+    fn tokenize_with<T: Tokenizer>(
+        tokenizer: T,
+        rules: &[&str],
+        text: &str,
+    ) -> Vec<(String, usize)> {
+        let mut rule_analyzer = TextAnalyzer::builder(tokenizer.clone())
+            .filter(LowerCaser)
+            .build();
+        let mut analyzer = TextAnalyzer::builder(tokenizer)
+            .filter(LowerCaser)
+            .filter(SynonymFilter::new(rules, |entry| words(&mut rule_analyzer, entry)).unwrap())
+            .build();
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
+        }
+        tokens
+    }
+
+    fn tokenize(rules: &[&str], text: &str) -> Vec<(String, usize)> {
+        tokenize_with(SimpleTokenizer::default(), rules, text)
+    }
+
+    fn pairs(expected: &[(&str, usize)]) -> Vec<(String, usize)> {
+        expected
+            .iter()
+            .map(|(text, pos)| (text.to_string(), *pos))
+            .collect()
+    }
+
+    #[rstest]
+    fn test_equivalence() {
+        assert_eq!(
+            tokenize(&["TV, television"], "Smart tv stand"),
+            pairs(&[("smart", 0), ("tv", 1), ("television", 1), ("stand", 2)])
+        );
+    }
+
+    #[rstest]
+    fn test_explicit_mapping() {
+        assert_eq!(
+            tokenize(&["i-pod, i pod => ipod"], "my i pod"),
+            pairs(&[("my", 0), ("ipod", 1)])
+        );
+        assert_eq!(
+            tokenize(&["i-pod, i pod => ipod"], "my I-Pod"),
+            pairs(&[("my", 0), ("ipod", 1)])
+        );
+    }
+
+    #[rstest]
+    fn test_hyphenated_entries_are_analyzed() {
+        // `Wi-Fi` is tokenized into two words, both here and in the text
+        assert_eq!(
+            tokenize(&["Wi-Fi, wireless"], "wi-fi router"),
+            pairs(&[("wi", 0), ("wireless", 0), ("fi", 1), ("router", 2)])
+        );
+        assert_eq!(
+            tokenize(&["Wi-Fi, wireless"], "Wireless router"),
+            pairs(&[("wi", 0), ("wireless", 0), ("fi", 1), ("router", 1)])
+        );
+
+        // and is a single token for a tokenizer that doesn't split it
+        assert_eq!(
+            tokenize_with(RawTokenizer::default(), &["Wi-Fi, wireless"], "WI-FI"),
+            pairs(&[("wi-fi", 0), ("wireless", 0)])
+        );
+    }
+
+    #[rstest]
+    fn test_multi_word_expansion() {
+        assert_eq!(
+            tokenize(&["usa, united states"], "usa today"),
+            pairs(&[("usa", 0), ("united", 0), ("states", 1), ("today", 1)])
+        );
+        assert_eq!(
+            tokenize(&["usa, united states"], "united states army"),
+            pairs(&[("usa", 0), ("united", 0), ("states", 1), ("army", 2)])
+        );
+    }
+
+    #[rstest]
+    fn test_parse_rule() {
+        assert_eq!(parse_rule("  # a comment").unwrap(), None);
+        assert_eq!(parse_rule("").unwrap(), None);
+        assert!(parse_rule("lonely").is_err());
+        assert!(parse_rule("a, , b").is_err());
+        assert!(parse_rule("a => b => c").is_err());
+        assert!(parse_rule("a =>").is_err());
+        assert_eq!(
+            parse_rule("a, b c => d").unwrap(),
+            Some(SynonymRule {
+                inputs: vec!["a".into(), "b c".into()],
+                outputs: vec!["d".into()],
+            })
+        );
+    }
+}